@@ -0,0 +1,126 @@
+//! Benchmarks of opening and planning synthetic repositories.
+//!
+//! Each repository is built from scratch in a temporary directory: it has some number of projects (each tagged
+//! with its own version tags), and a history of conventional commits that touch the projects in turn.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use git2::{IndexAddOption, Repository, Signature, Time};
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use tokio::runtime::Runtime;
+use versio::api::Versio;
+use versio::vcs::{VcsLevel, VcsRange};
+
+/// The shape of a synthetic repository.
+struct Shape {
+  name: &'static str,
+  projects: usize,
+  tags: usize,
+  commits: usize
+}
+
+const SHAPES: &[Shape] = &[
+  Shape { name: "many_projects", projects: 100, tags: 2, commits: 100 },
+  Shape { name: "many_tags", projects: 10, tags: 100, commits: 100 },
+  Shape { name: "long_history", projects: 5, tags: 2, commits: 2000 }
+];
+
+const KINDS: &[&str] = &["fix", "feat", "chore", "fix", "docs"];
+
+/// A synthetic repository, which is deleted when dropped.
+struct SynthRepo {
+  dir: PathBuf
+}
+
+impl SynthRepo {
+  fn build(shape: &Shape) -> SynthRepo {
+    let dir = std::env::temp_dir().join(format!("versio-bench-{}-{}", shape.name, std::process::id()));
+    if dir.exists() {
+      std::fs::remove_dir_all(&dir).unwrap();
+    }
+    let repo = Repository::init(&dir).unwrap();
+
+    let mut config = String::from("options:\n  prev_tag: \"versio-prev\"\n\nprojects:\n");
+    for p in 0 .. shape.projects {
+      writeln!(config, "  - name: proj_{}\n    id: {}\n    root: \"proj_{}\"", p, p + 1, p).unwrap();
+      writeln!(config, "    tag_prefix: \"proj{}\"\n    version:\n      file: \"VERSION\"", p).unwrap();
+      if p % 5 != 0 {
+        writeln!(config, "    depends:\n      {}:\n        size: match", p).unwrap();
+      }
+      std::fs::create_dir_all(dir.join(format!("proj_{}", p))).unwrap();
+      std::fs::write(dir.join(format!("proj_{}/VERSION", p)), format!("0.{}.0\n", shape.tags)).unwrap();
+    }
+    config.push_str("\nsizes:\n  use_angular: true\n  fail: [\"*\"]\n");
+    std::fs::write(dir.join(".versio.yaml"), config).unwrap();
+
+    // Tag the projects early on, then release, then make the commits that are to be planned.
+    commit(&repo, "chore: initial commit", 0);
+    for t in 0 .. shape.tags {
+      let oid = commit(&repo, &format!("chore: release {}", t), t + 1);
+      let target = repo.find_object(oid, None).unwrap();
+      for p in 0 .. shape.projects {
+        repo.tag_lightweight(&format!("proj{}-v0.{}.0", p, t + 1), &target, false).unwrap();
+      }
+      repo.tag_lightweight("versio-prev", &target, true).unwrap();
+    }
+
+    for c in 0 .. shape.commits {
+      let p = c % shape.projects;
+      std::fs::write(dir.join(format!("proj_{}/src.txt", p)), format!("change {}\n", c)).unwrap();
+      let kind = KINDS[c % KINDS.len()];
+      commit(&repo, &format!("{}: change {} to proj_{}", kind, c, p), shape.tags + c + 1);
+    }
+
+    SynthRepo { dir }
+  }
+
+  fn dir(&self) -> &Path { &self.dir }
+}
+
+impl Drop for SynthRepo {
+  fn drop(&mut self) { let _ = std::fs::remove_dir_all(&self.dir); }
+}
+
+/// Commit everything in the working directory, at a predictable time.
+fn commit(repo: &Repository, message: &str, time: usize) -> git2::Oid {
+  let mut index = repo.index().unwrap();
+  index.add_all(["*"].iter(), IndexAddOption::DEFAULT, None).unwrap();
+  index.write().unwrap();
+  let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+  let sig = Signature::new("Versio Bench", "bench@example.com", &Time::new(time as i64 * 60, 0)).unwrap();
+  let parent = repo.head().ok().map(|h| h.peel_to_commit().unwrap());
+  let parents: Vec<_> = parent.iter().collect();
+  repo.commit(Some("HEAD"), &sig, &sig, message, &tree, &parents).unwrap()
+}
+
+fn open(dir: &Path) -> Versio { Versio::open(dir, Some(VcsRange::exact(VcsLevel::Local))).unwrap() }
+
+fn bench_open(c: &mut Criterion) {
+  let mut group = c.benchmark_group("open");
+  group.sample_size(10);
+  for shape in SHAPES {
+    let repo = SynthRepo::build(shape);
+    group.bench_with_input(BenchmarkId::from_parameter(shape.name), repo.dir(), |b, dir| b.iter(|| open(dir)));
+  }
+  group.finish();
+}
+
+fn bench_plan(c: &mut Criterion) {
+  let runtime = Runtime::new().unwrap();
+  let mut group = c.benchmark_group("plan");
+  group.sample_size(10);
+  for shape in SHAPES {
+    let repo = SynthRepo::build(shape);
+    let versio = open(repo.dir());
+    let plan = runtime.block_on(versio.plan()).unwrap();
+    assert!(!plan.projects.is_empty(), "Synthetic repo {} should have a plan.", shape.name);
+
+    group.bench_with_input(BenchmarkId::from_parameter(shape.name), &versio, |b, versio| {
+      b.iter(|| runtime.block_on(versio.plan()).unwrap())
+    });
+  }
+  group.finish();
+}
+
+criterion_group!(benches, bench_open, bench_plan);
+criterion_main!(benches);
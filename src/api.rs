@@ -0,0 +1,313 @@
+//! A small programmatic interface to the planning engine, for embedding versio in other tools.
+//!
+//! Everything returned here is plain, serializable data, so that bindings to other languages (see the
+//! `versio-py` crate) can hand it across without knowing about versio's internal types.
+
+use crate::config::{Config, Project, ProjectId, Size};
+use crate::errors::{Context as _, Result};
+use crate::mono::{ChangelogEntry, Mono, VersionChange};
+use crate::state::StateRead;
+use crate::vcs::{VcsLevel, VcsRange, VcsState};
+use serde::Serialize;
+use std::path::Path;
+
+/// An opened monorepo, which can answer questions about its projects and release plan.
+pub struct Versio {
+  mono: Mono
+}
+
+/// The version of a single project.
+#[derive(Serialize, Clone, Debug)]
+pub struct ProjectVersion {
+  pub id: String,
+  pub name: String,
+  pub version: String
+}
+
+/// The planned increment of a single project.
+#[derive(Serialize, Clone, Debug)]
+pub struct PlannedProject {
+  pub id: String,
+  pub name: String,
+  pub size: String,
+  pub previous: Option<String>,
+  pub current: String,
+  pub target: Option<String>,
+  pub changes: Vec<PlannedChange>
+}
+
+/// A PR (or dependency) that contributed to a project's increment.
+#[derive(Serialize, Clone, Debug)]
+pub struct PlannedChange {
+  pub kind: String,
+  pub title: String,
+  pub size: Option<String>
+}
+
+/// What a release would do to a single project.
+#[derive(Serialize, Clone, Debug)]
+pub struct ReleasedProject {
+  pub id: String,
+  pub name: String,
+  pub action: ReleaseAction,
+  pub previous: Option<String>,
+  pub current: String,
+  pub version: String
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ReleaseAction {
+  /// The version would be increased.
+  Changed,
+  /// The version was already high enough, and its tag would be moved forward.
+  Forward,
+  /// The project has no previous version, and would be tagged at its current version.
+  New,
+  /// Nothing would change.
+  None
+}
+
+/// The full release plan.
+#[derive(Serialize, Clone, Debug)]
+pub struct PlanSummary {
+  pub projects: Vec<PlannedProject>,
+  pub ineffective: Vec<String>,
+  /// Suspicious states of the planned commit range, such as an unreachable previous tag.
+  pub warnings: Vec<String>
+}
+
+impl Versio {
+  /// Open the monorepo containing `dir`. If `vcs` is not given, the highest detectable level is used.
+  pub fn open<P: AsRef<Path>>(dir: P, vcs: Option<VcsRange>) -> Result<Versio> {
+    let pref = vcs.unwrap_or_else(VcsRange::full);
+    let vcs = VcsRange::detect_and_combine_at(dir.as_ref(), &pref, &VcsRange::full())?;
+    let mono = Mono::open(dir, VcsState::new(vcs.max(), false))?;
+    Ok(Versio { mono })
+  }
+
+  /// Open the monorepo containing `dir`, without consulting VCS at all.
+  pub fn open_local<P: AsRef<Path>>(dir: P) -> Result<Versio> {
+    Versio::open(dir, Some(VcsRange::exact(VcsLevel::None)))
+  }
+
+  /// The current versions of all projects.
+  pub fn versions(&self) -> Result<Vec<ProjectVersion>> {
+    let cfg = self.mono.config();
+    cfg.projects().iter().map(|p| ProjectVersion::from(p, cfg)).collect()
+  }
+
+  /// The current version of the project with the given ID.
+  pub fn get(&self, id: u32) -> Result<ProjectVersion> {
+    let cfg = self.mono.config();
+    let id = ProjectId::from_id(id);
+    let proj = cfg.get_project(&id).ok_or_else(|| bad!("No such project {}.", id))?;
+    ProjectVersion::from(proj, cfg)
+  }
+
+  /// Build the release plan: the size and target version of every project that has changed.
+  pub async fn plan(&self) -> Result<PlanSummary> {
+    let plan = self.mono.build_plan().await?;
+    let curt_config = self.mono.config();
+    let prev_config = curt_config.slice_to_prev(self.mono.repo())?;
+
+    let mut incrs: Vec<_> = plan.incrs().iter().collect();
+    incrs.sort_by_key(|(id, _)| *id);
+
+    let mut projects = Vec::new();
+    for (id, (size, changelog)) in incrs {
+      let proj = self.mono.get_project(id)?;
+      let previous = prev_config.get_value(id).with_context(|| format!("Unable to find prev {} value.", id))?;
+      let current = curt_config
+        .get_value(id)
+        .with_context(|| format!("Unable to find project {} value.", id))?
+        .ok_or_else(|| bad!("No such project {}.", id))?;
+
+      let target = match &previous {
+        Some(prev) if !size.is_failure() && size != &Size::Empty => Some(proj.apply_size(*size, prev)?),
+        _ => None
+      };
+
+      let changes = changelog
+        .entries()
+        .iter()
+        .map(|entry| match entry {
+          ChangelogEntry::Pr(pr, size) => {
+            PlannedChange { kind: "pr".into(), title: pr.title().to_string(), size: Some(size.to_string()) }
+          }
+          ChangelogEntry::Dep(_, name) => PlannedChange { kind: "dep".into(), title: name.clone(), size: None }
+        })
+        .collect();
+
+      projects.push(PlannedProject {
+        id: id.to_string(),
+        name: proj.name().to_string(),
+        size: size.to_string(),
+        previous,
+        current,
+        target,
+        changes
+      });
+    }
+
+    let ineffective = plan.ineffective().iter().map(|pr| pr.title().to_string()).collect();
+    let warnings = plan.warnings().iter().map(|w| w.to_string()).collect();
+    Ok(PlanSummary { projects, ineffective, warnings })
+  }
+
+  /// Compute what `versio release` would do, without writing anything.
+  pub async fn release_dry(&self) -> Result<Vec<ReleasedProject>> {
+    if let Err((should, is)) = self.mono.check_branch() {
+      bail!("{}", tr!("release.wrong_branch", branch = is, expected = should));
+    }
+
+    let plan = self.mono.build_plan().await?;
+    let mut nexts = self.mono.next_versions(&plan, None, false, false)?;
+    nexts.sort_by(|n1, n2| n1.id.cmp(&n2.id));
+
+    let mut released = Vec::new();
+    for next in nexts {
+      let (action, previous, current, version) = match next.change {
+        VersionChange::NoChange { prev, curt, .. } => (ReleaseAction::None, prev, curt.clone(), curt),
+        VersionChange::Released { prev, curt } => (ReleaseAction::None, Some(prev), curt.clone(), curt),
+        VersionChange::Changed { prev, curt, target } => (ReleaseAction::Changed, Some(prev), curt, target),
+        VersionChange::Forward { prev, curt, .. } => (ReleaseAction::Forward, Some(prev), curt.clone(), curt),
+        VersionChange::New { curt } => (ReleaseAction::New, None, curt.clone(), curt)
+      };
+      released.push(ReleasedProject { id: next.id.to_string(), name: next.name, action, previous, current, version });
+    }
+
+    Ok(released)
+  }
+}
+
+impl ProjectVersion {
+  fn from<R: StateRead>(proj: &Project, cfg: &Config<R>) -> Result<ProjectVersion> {
+    let version = proj.get_value(cfg.state_read())?;
+    Ok(ProjectVersion { id: proj.id().to_string(), name: proj.name().to_string(), version })
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::{ReleaseAction, Versio};
+  use crate::testing::{block_on, TestRepo};
+  use crate::vcs::{VcsLevel, VcsRange};
+
+  const CONFIG: &str = r#"
+projects:
+  - { name: a, id: 1, includes: [ "a/**" ], version: { file: a/version.txt } }
+  - { name: b, id: 2, includes: [ "b/**" ], version: { file: b/version.txt } }
+sizes:
+  use_angular: true
+  fail: [ "*" ]
+"#;
+
+  fn open(repo: &TestRepo) -> Versio { Versio::open(repo.path(), Some(VcsRange::exact(VcsLevel::Local))).unwrap() }
+
+  fn released_repo() -> TestRepo {
+    let repo = TestRepo::new(CONFIG);
+    repo.write("a/version.txt", "1.0.0");
+    repo.write("b/version.txt", "1.0.0");
+    repo.commit("chore: start");
+    repo.tag("versio-prev");
+    repo
+  }
+
+  #[test]
+  fn test_release_dry_changed() {
+    let repo = released_repo();
+    repo.write("a/code.txt", "new");
+    repo.commit("feat: add code");
+
+    let released = block_on(open(&repo).release_dry()).unwrap();
+    assert_eq!(1, released.len());
+    let a = &released[0];
+    assert_eq!((ReleaseAction::Changed, "1.0.0", "1.1.0"), (a.action, a.current.as_str(), a.version.as_str()));
+    assert_eq!(Some("1.0.0"), a.previous.as_deref());
+  }
+
+  #[test]
+  fn test_release_dry_forward() {
+    let repo = released_repo();
+    repo.write("b/version.txt", "2.0.0");
+    repo.commit("fix: jump ahead");
+
+    let released = block_on(open(&repo).release_dry()).unwrap();
+    let b = released.iter().find(|p| p.name == "b").unwrap();
+    assert_eq!(ReleaseAction::Forward, b.action);
+    assert_eq!("2.0.0", b.current);
+    assert_eq!("2.0.0", b.version);
+  }
+
+  #[test]
+  fn test_release_dry_fail() {
+    let repo = released_repo();
+    repo.write("a/code.txt", "new");
+    repo.commit("unconventional");
+    assert!(block_on(open(&repo).release_dry()).is_err());
+  }
+
+  #[test]
+  fn test_release_dry_fail_new() {
+    let repo = TestRepo::new(CONFIG);
+    repo.write("a/version.txt", "0.1.0");
+    repo.write("b/version.txt", "0.2.0");
+    repo.commit("unconventional");
+
+    let released = block_on(open(&repo).release_dry()).unwrap();
+    let actions: Vec<_> = released.iter().map(|p| (p.action, p.version.as_str())).collect();
+    assert_eq!(vec![(ReleaseAction::New, "0.1.0"), (ReleaseAction::New, "0.2.0")], actions);
+  }
+
+  /// Projects with IDs 2 and 10, which sort the other way as strings.
+  fn wide_ids_repo() -> TestRepo {
+    let repo = TestRepo::new(&CONFIG.replace("id: 1,", "id: 10,"));
+    repo.write("a/version.txt", "1.0.0");
+    repo.write("b/version.txt", "1.0.0");
+    repo.commit("chore: start");
+    repo.tag("versio-prev");
+    repo.write("a/code.txt", "new");
+    repo.write("b/code.txt", "new");
+    repo.commit("fix: add code");
+    repo
+  }
+
+  #[test]
+  fn test_versions() {
+    let repo = released_repo();
+    let versio = open(&repo);
+    let versions: Vec<_> = versio.versions().unwrap().into_iter().map(|v| (v.id, v.name, v.version)).collect();
+    assert_eq!(vec![("1".into(), "a".into(), "1.0.0".into()), ("2".into(), "b".into(), "1.0.0".into())], versions);
+    assert_eq!("b", versio.get(2).unwrap().name);
+    assert!(versio.get(3).is_err());
+  }
+
+  #[test]
+  fn test_plan() {
+    let repo = released_repo();
+    repo.write("a/code.txt", "new");
+    repo.commit("feat: add code");
+
+    let plan = block_on(open(&repo).plan()).unwrap();
+    assert_eq!(1, plan.projects.len());
+    let a = &plan.projects[0];
+    assert_eq!(("a", "minor", Some("1.1.0")), (a.name.as_str(), a.size.as_str(), a.target.as_deref()));
+    let changes: Vec<_> = a.changes.iter().map(|c| (c.kind.as_str(), c.size.as_deref())).collect();
+    assert_eq!(vec![("pr", Some("minor"))], changes);
+  }
+
+  #[test]
+  fn test_plan_sorts_ids_numerically() {
+    let repo = wide_ids_repo();
+    let plan = block_on(open(&repo).plan()).unwrap();
+    assert_eq!(vec!["2", "10"], plan.projects.iter().map(|p| p.id.as_str()).collect::<Vec<_>>());
+  }
+
+  #[test]
+  fn test_release_dry_sorts_ids_numerically() {
+    let repo = wide_ids_repo();
+    let released = block_on(open(&repo).release_dry()).unwrap();
+    assert_eq!(vec!["2", "10"], released.iter().map(|p| p.id.as_str()).collect::<Vec<_>>());
+  }
+}
@@ -0,0 +1,285 @@
+//! The object access that dominates planning on a large repo: listing tags, walking history, and reading files at
+//! a commit.
+//!
+//! libgit2 does all of this by default. Built with the `gix` feature, versio can instead use gitoxide, whose object
+//! access is faster, by setting `VERSIO_GIT_BACKEND=gix`. Everything else that `Repo` does (fetching, pushing,
+//! committing, and tagging) is still done by libgit2, which is kept as the default until gitoxide is proven to read
+//! the same things.
+
+use crate::errors::Result;
+use git2::{ErrorCode, Oid, Repository, Sort};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::env::var;
+use std::hash::Hash;
+use std::path::Path;
+
+/// The environment variable that picks the backend: `libgit2` (the default) or `gix`.
+const BACKEND_VAR: &str = "VERSIO_GIT_BACKEND";
+
+/// Read-only access to the objects of a repository.
+pub trait Backend {
+  /// The names of the tags that match the glob `pattern`, or of all tags.
+  fn tags(&self, pattern: Option<&str>) -> Result<Vec<String>>;
+
+  /// The commits of `git rev-list ^hide... to`, with each commit before its parents. libgit2 walks them as they're
+  /// taken; gitoxide has to walk them all first, to put them in that order.
+  fn walk<'a>(&'a self, hide: &[Oid], to: Oid) -> Result<Box<dyn Iterator<Item = Result<Oid>> + 'a>>;
+
+  /// The contents of the file at `path` in the tree of `rev`, or `None` if there's no such file.
+  fn read_blob(&self, rev: &str, path: &str) -> Result<Option<Vec<u8>>>;
+
+  /// Whether there's anything (a file or a directory) at `path` in the tree of `rev`.
+  fn has_entry(&self, rev: &str, path: &str) -> Result<bool>;
+
+  /// The names of the entries in the directory at `path` (the top of the tree, if empty) in the tree of `rev`.
+  fn tree_names(&self, rev: &str, path: &str) -> Result<Vec<String>>;
+}
+
+/// Open the backend that `VERSIO_GIT_BACKEND` asks for, for the repository at `path`. This is `None` for libgit2,
+/// since the repository that `Repo` already has open is used for that.
+pub fn open_preferred(path: &Path) -> Result<Option<Box<dyn Backend + Send>>> {
+  match var(BACKEND_VAR).ok().as_deref() {
+    None | Some("") | Some("libgit2") => Ok(None),
+    Some("gix") => open_gix(path),
+    Some(other) => bail!("Unknown {} \"{}\": use \"libgit2\" or \"gix\".", BACKEND_VAR, other)
+  }
+}
+
+#[cfg(feature = "gix")]
+fn open_gix(path: &Path) -> Result<Option<Box<dyn Backend + Send>>> {
+  Ok(Some(Box::new(gix_backend::Gix::open(path)?)))
+}
+
+#[cfg(not(feature = "gix"))]
+fn open_gix(_path: &Path) -> Result<Option<Box<dyn Backend + Send>>> {
+  bail!("This versio wasn't built with the \"gix\" feature, so it can't use {}=gix.", BACKEND_VAR)
+}
+
+impl Backend for Repository {
+  fn tags(&self, pattern: Option<&str>) -> Result<Vec<String>> {
+    Ok(self.tag_names(pattern)?.iter().flatten().map(|n| n.to_string()).collect())
+  }
+
+  fn walk<'a>(&'a self, hide: &[Oid], to: Oid) -> Result<Box<dyn Iterator<Item = Result<Oid>> + 'a>> {
+    let mut revwalk = self.revwalk()?;
+    revwalk.set_sorting(Sort::TOPOLOGICAL)?;
+    for oid in hide {
+      revwalk.hide(*oid)?;
+    }
+    revwalk.push(to)?;
+    Ok(Box::new(revwalk.map(|oid| Ok(oid?))))
+  }
+
+  fn read_blob(&self, rev: &str, path: &str) -> Result<Option<Vec<u8>>> {
+    let tree = self.revparse_single(rev)?.peel_to_tree()?;
+    match tree.get_path(Path::new(path)) {
+      Ok(entry) => Ok(entry.to_object(self)?.into_blob().ok().map(|blob| blob.content().to_vec())),
+      Err(e) if e.code() == ErrorCode::NotFound => Ok(None),
+      Err(e) => Err(e.into())
+    }
+  }
+
+  fn has_entry(&self, rev: &str, path: &str) -> Result<bool> {
+    match self.revparse_single(rev)?.peel_to_tree()?.get_path(Path::new(path)) {
+      Ok(_) => Ok(true),
+      Err(e) if e.code() == ErrorCode::NotFound => Ok(false),
+      Err(e) => Err(e.into())
+    }
+  }
+
+  fn tree_names(&self, rev: &str, path: &str) -> Result<Vec<String>> {
+    let tree = self.revparse_single(rev)?.peel_to_tree()?;
+    let tree = match path {
+      "" => tree,
+      path => tree.get_path(Path::new(path))?.to_object(self)?.into_tree().map_err(|_| bad!("Not a tree: {}", path))?
+    };
+    Ok(tree.iter().map(|entry| String::from_utf8_lossy(entry.name_bytes()).into_owned()).collect())
+  }
+}
+
+/// Order the `walked` commits (each with its parents) so that every commit comes before its parents, as libgit2's
+/// topological sort does. Otherwise, commits keep the order that they were walked in.
+pub fn topo_order<T: Copy + Eq + Hash>(walked: Vec<(T, Vec<T>)>) -> Vec<T> {
+  let index: HashMap<T, usize> = walked.iter().enumerate().map(|(i, (id, _))| (*id, i)).collect();
+  let mut children = vec![0; walked.len()];
+  for (_, parents) in &walked {
+    for i in parents.iter().filter_map(|p| index.get(p)) {
+      children[*i] += 1;
+    }
+  }
+
+  let mut ready: BinaryHeap<Reverse<usize>> = (0 .. walked.len()).filter(|i| children[*i] == 0).map(Reverse).collect();
+  let mut order = Vec::with_capacity(walked.len());
+  while let Some(Reverse(i)) = ready.pop() {
+    let (id, parents) = &walked[i];
+    order.push(*id);
+    for p in parents.iter().filter_map(|p| index.get(p)) {
+      children[*p] -= 1;
+      if children[*p] == 0 {
+        ready.push(Reverse(*p));
+      }
+    }
+  }
+  order
+}
+
+#[cfg(feature = "gix")]
+mod gix_backend {
+  use super::{topo_order, Backend};
+  use crate::errors::Result;
+  use git2::Oid;
+  use std::path::Path;
+
+  /// The gitoxide backend.
+  pub struct Gix {
+    repo: gix::Repository
+  }
+
+  impl Gix {
+    pub fn open(path: &Path) -> Result<Gix> { Ok(Gix { repo: gix::discover(path)? }) }
+
+    fn tree_at(&self, rev: &str) -> Result<gix::Tree<'_>> {
+      Ok(self.repo.rev_parse_single(rev)?.object()?.peel_to_tree()?)
+    }
+  }
+
+  impl Backend for Gix {
+    fn tags(&self, pattern: Option<&str>) -> Result<Vec<String>> {
+      let pattern = pattern.map(glob::Pattern::new).transpose()?;
+      let mut names = Vec::new();
+      for tag in self.repo.references()?.tags()? {
+        let tag = tag.map_err(|e| bad!("Can't read tag: {}", e))?;
+        let name = tag.name().shorten().to_string();
+        if pattern.as_ref().map(|p| p.matches(&name)).unwrap_or(true) {
+          names.push(name);
+        }
+      }
+      Ok(names)
+    }
+
+    fn walk<'a>(&'a self, hide: &[Oid], to: Oid) -> Result<Box<dyn Iterator<Item = Result<Oid>> + 'a>> {
+      let hide = hide.iter().map(|oid| to_gix(*oid)).collect::<Result<Vec<_>>>()?;
+      let mut walked = Vec::new();
+      for info in self.repo.rev_walk([to_gix(to)?]).with_hidden(hide).all()? {
+        let info = info?;
+        let parents = info.parent_ids().map(|p| to_git2(p.detach())).collect::<Result<_>>()?;
+        walked.push((to_git2(info.id)?, parents));
+      }
+      Ok(Box::new(topo_order(walked).into_iter().map(Ok)))
+    }
+
+    fn read_blob(&self, rev: &str, path: &str) -> Result<Option<Vec<u8>>> {
+      match self.tree_at(rev)?.lookup_entry_by_path(path)? {
+        Some(entry) if entry.mode().is_blob() => Ok(Some(entry.object()?.detach().data)),
+        _ => Ok(None)
+      }
+    }
+
+    fn has_entry(&self, rev: &str, path: &str) -> Result<bool> {
+      Ok(self.tree_at(rev)?.lookup_entry_by_path(path)?.is_some())
+    }
+
+    fn tree_names(&self, rev: &str, path: &str) -> Result<Vec<String>> {
+      let tree = match path {
+        "" => self.tree_at(rev)?,
+        path => match self.tree_at(rev)?.lookup_entry_by_path(path)? {
+          Some(entry) if entry.mode().is_tree() => entry.object()?.into_tree(),
+          _ => bail!("Not a tree: {}", path)
+        }
+      };
+      Ok(tree.decode()?.entries.iter().map(|entry| entry.filename.to_string()).collect())
+    }
+  }
+
+  fn to_gix(oid: Oid) -> Result<gix::ObjectId> { Ok(gix::ObjectId::from_hex(oid.to_string().as_bytes())?) }
+  fn to_git2(id: gix::ObjectId) -> Result<Oid> { Ok(Oid::from_bytes(id.as_bytes())?) }
+}
+
+#[cfg(test)]
+mod test {
+  use super::topo_order;
+
+  #[test]
+  fn test_topo_order_keeps_walk() {
+    let walked = vec![(4, vec![3]), (3, vec![2]), (2, vec![1]), (1, vec![])];
+    assert_eq!(vec![4, 3, 2, 1], topo_order(walked));
+  }
+
+  #[test]
+  fn test_topo_order_parents_last() {
+    // A merge (5) of 4 and 2, where the walk reached 2 before 4 and 3, which are between them.
+    let walked = vec![(5, vec![4, 2]), (2, vec![1]), (4, vec![3]), (1, vec![]), (3, vec![2])];
+    assert_eq!(vec![5, 4, 3, 2, 1], topo_order(walked));
+  }
+
+  #[test]
+  fn test_topo_order_hidden_parents() {
+    let walked = vec![(3, vec![2, 9]), (2, vec![8])];
+    assert_eq!(vec![3, 2], topo_order(walked));
+  }
+
+  #[cfg(feature = "gix")]
+  #[test]
+  fn test_backends_agree() {
+    use super::gix_backend::Gix;
+    use super::Backend;
+    use crate::testing::TestRepo;
+    use git2::{Oid, Signature};
+
+    let repo = TestRepo::new("projects: []\n");
+    repo.write("proj/a.txt", "a");
+    let base = repo.commit("base");
+    repo.tag("v1.0.0");
+    repo.tag("other");
+
+    // A side branch off `base`, merged back into `main` after another commit there.
+    let git = repo.repo();
+    let sig = Signature::now("Test", "test@example.com").unwrap();
+    let base_commit = git.find_commit(base).unwrap();
+    let side = git.commit(Some("refs/heads/side"), &sig, &sig, "side", &base_commit.tree().unwrap(), &[&base_commit]);
+    let side = git.find_commit(side.unwrap()).unwrap();
+    repo.write("proj/b.txt", "b");
+    let main = git.find_commit(repo.commit("main")).unwrap();
+    let tree = main.tree().unwrap();
+    let merge = git.commit(Some("HEAD"), &sig, &sig, "merge", &tree, &[&main, &side]).unwrap();
+    repo.tag("v1.1.0");
+
+    let gix = Gix::open(repo.path()).unwrap();
+    let backends: [&dyn Backend; 2] = [git, &gix];
+    let walks: Vec<Vec<Oid>> =
+      backends.iter().map(|b| b.walk(&[], merge).unwrap().collect::<crate::errors::Result<_>>().unwrap()).collect();
+
+    let mut sorted = walks.clone();
+    sorted.iter_mut().for_each(|w| w.sort());
+    assert_eq!(sorted[0], sorted[1]);
+    assert_eq!(4, sorted[0].len());
+    for walk in &walks {
+      let at = |oid: Oid| walk.iter().position(|o| *o == oid).unwrap();
+      for oid in walk {
+        for parent in git.find_commit(*oid).unwrap().parent_ids() {
+          assert!(at(*oid) < at(parent), "{} came after its parent {}", oid, parent);
+        }
+      }
+    }
+
+    let (l, g) = (backends[0], backends[1]);
+    assert_eq!(l.tags(None).unwrap(), g.tags(None).unwrap());
+    assert_eq!(l.tags(Some("v*")).unwrap(), g.tags(Some("v*")).unwrap());
+    let hidden_walk = |b: &dyn Backend| {
+      let mut walk = b.walk(&[base], merge).unwrap().collect::<crate::errors::Result<Vec<_>>>().unwrap();
+      walk.sort();
+      walk
+    };
+    assert_eq!(3, hidden_walk(l).len());
+    assert_eq!(hidden_walk(l), hidden_walk(g));
+    for rev in ["v1.0.0", "v1.1.0"] {
+      for path in ["proj/a.txt", "proj/b.txt", "proj", "missing"] {
+        assert_eq!(l.read_blob(rev, path).unwrap(), g.read_blob(rev, path).unwrap(), "{}:{}", rev, path);
+        assert_eq!(l.has_entry(rev, path).unwrap(), g.has_entry(rev, path).unwrap(), "{}:{}", rev, path);
+      }
+      assert_eq!(l.tree_names(rev, "").unwrap(), g.tree_names(rev, "").unwrap());
+      assert_eq!(l.tree_names(rev, "proj").unwrap(), g.tree_names(rev, "proj").unwrap());
+    }
+  }
+}
@@ -0,0 +1,165 @@
+//! Interactions with the Bitbucket Cloud API 2.0.
+
+use crate::errors::Result;
+use crate::git::{GithubInfo, Repo, Span};
+use crate::github::{ApiCommit, PrEdgeNode};
+use crate::gitea::{get_json_at, RestPr};
+use chrono::{DateTime, FixedOffset};
+use hyper::Uri;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use tracing::instrument;
+
+/// How many PRs to ask for in each page of the list of merged PRs, which is the most that Bitbucket allows.
+const PAGE_LENGTH: u32 = 50;
+
+/// Find the commits of a PR's span, each with the PR that merged it (if any).
+///
+/// Bitbucket can't tell which PR a commit belongs to. Instead, the commits are found locally, and each is matched
+/// to the merged PR whose merge commit it is. Those PRs are listed from the most recently updated, until they were
+/// last updated before the span's oldest commit, since such a PR can't have been merged by one of its commits.
+#[instrument(skip_all)]
+pub async fn commits_from_api(repo: &Repo, bitbucket_info: &GithubInfo, span: &Span) -> Result<Vec<ApiCommit>> {
+  let (commits, oldest) = match repo.commits_between_buf(span.begin(), span.end())? {
+    Some(found) => found,
+    None => return Ok(Vec::new())
+  };
+
+  let mut merged = merged_prs_since(bitbucket_info, oldest.seconds()).await?;
+  Ok(
+    commits
+      .into_iter()
+      .map(|commit| {
+        let id = commit.id().to_string();
+        let prs = match merged.iter().position(|pr| pr.is_merged_by(&id)) {
+          Some(i) => merged.swap_remove(i).into_node().into_iter().collect(),
+          None => Vec::new()
+        };
+        ApiCommit::new(id, commit.summary().to_string(), prs)
+      })
+      .collect()
+  )
+}
+
+/// The merged PRs that were last updated at or after `since` (in epoch seconds), following the list's pages.
+async fn merged_prs_since(bitbucket_info: &GithubInfo, since: i64) -> Result<Vec<BitbucketPr>> {
+  let mut next = Some(format!(
+    "{}/pullrequests?state=MERGED&sort=-updated_on&pagelen={}&fields=%2Bvalues.description",
+    bitbucket_info.bitbucket_api_url(),
+    PAGE_LENGTH
+  ));
+
+  let mut prs = Vec::new();
+  while let Some(url) = next.take() {
+    let page: Page = match get_json(bitbucket_info, &url).await? {
+      Some(page) => page,
+      None => break
+    };
+    let done = page.values.iter().any(|pr| pr.updated_on.timestamp() < since);
+    prs.extend(page.values.into_iter().filter(|pr| pr.updated_on.timestamp() >= since));
+    if !done {
+      next = page.next;
+    }
+  }
+  Ok(prs)
+}
+
+/// Get a URL of the API, with the app password of the user preferences (if any).
+async fn get_json<T: DeserializeOwned>(bitbucket_info: &GithubInfo, url: &str) -> Result<Option<T>> {
+  let authorization = bitbucket_info.token().await?.map(BitbucketPr::authorization);
+  get_json_at(url.parse::<Uri>()?, authorization).await
+}
+
+/// A page of a list, with the URL of the next page if there is one.
+#[derive(Deserialize)]
+struct Page {
+  values: Vec<BitbucketPr>,
+  next: Option<String>
+}
+
+#[derive(Deserialize)]
+pub struct BitbucketPr {
+  id: u32,
+  title: String,
+  #[serde(default)]
+  description: Option<String>,
+  state: String,
+  source: Endpoint,
+  destination: Endpoint,
+  merge_commit: Option<CommitRef>,
+  updated_on: DateTime<FixedOffset>
+}
+
+/// The branch and commit of either side of a PR.
+#[derive(Deserialize)]
+struct Endpoint {
+  branch: BranchRef,
+  commit: Option<CommitRef>
+}
+
+#[derive(Deserialize)]
+struct BranchRef {
+  name: String
+}
+
+/// A commit, whose hash Bitbucket usually abbreviates.
+#[derive(Deserialize)]
+struct CommitRef {
+  hash: String
+}
+
+impl BitbucketPr {
+  /// Whether the full commit ID `id` is this PR's merge commit.
+  fn is_merged_by(&self, id: &str) -> bool {
+    self.merge_commit.as_ref().map(|c| !c.hash.is_empty() && id.starts_with(&c.hash)).unwrap_or(false)
+  }
+}
+
+impl RestPr for BitbucketPr {
+  fn url(info: &GithubInfo, number: u32) -> String { format!("{}/pullrequests/{}", info.bitbucket_api_url(), number) }
+  fn authorization(credentials: String) -> String { format!("Basic {}", credentials) }
+
+  /// Bitbucket's PR is declined or superseded, rather than closed. It has no labels, and no time of merging: a
+  /// merged PR's last update is used instead.
+  fn into_node(self) -> Option<PrEdgeNode> {
+    let merged_at = match self.state.as_str() {
+      "MERGED" => Some(self.updated_on),
+      "OPEN" => None,
+      _ => return None
+    };
+    let base = self.destination.commit.map(|c| c.hash).unwrap_or_default();
+    Some(PrEdgeNode::new(self.id, self.title, self.description, Vec::new(), self.source.branch.name, base, merged_at))
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::BitbucketPr;
+  use crate::gitea::RestPr;
+
+  fn pr(state: &str, merge_commit: &str) -> BitbucketPr {
+    let json = format!(
+      r#"{{"id": 7, "title": "feat: widgets", "description": "Adds widgets.", "state": "{}",
+          "source": {{"branch": {{"name": "widgets"}}, "commit": {{"hash": "0a1b2c3d4e5f"}}}},
+          "destination": {{"branch": {{"name": "main"}}, "commit": {{"hash": "9f8e7d6c5b4a"}}}},
+          "merge_commit": {}, "updated_on": "2024-03-01T12:00:00.123456+00:00"}}"#,
+      state, merge_commit
+    );
+    serde_json::from_str(&json).unwrap()
+  }
+
+  #[test]
+  fn test_into_node() {
+    let merged = pr("MERGED", r#"{"hash": "abcdef012345"}"#);
+    assert!(merged.is_merged_by("abcdef0123456789abcdef0123456789abcdef01"));
+    assert!(!merged.is_merged_by("0a1b2c3d4e5f6789abcdef0123456789abcdef01"));
+    let merged = merged.into_node().unwrap();
+    assert_eq!(7, merged.number());
+    assert_eq!("MERGED", merged.state());
+
+    let open = pr("OPEN", "null");
+    assert!(!open.is_merged_by("abcdef0123456789abcdef0123456789abcdef01"));
+    assert_eq!("OPEN", open.into_node().unwrap().state());
+    assert!(pr("DECLINED", "null").into_node().is_none());
+  }
+}
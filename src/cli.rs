@@ -2,9 +2,12 @@
 
 use clap::error::ErrorKind;
 use clap::{ArgGroup, CommandFactory, Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
+use clap_mangen::Man;
+use std::path::PathBuf;
 use versio::commands::*;
 use versio::errors::Result;
-use versio::init::init;
+use versio::init::{init, Stack};
 use versio::vcs::{VcsLevel, VcsRange};
 
 #[derive(Parser, Debug)]
@@ -26,6 +29,22 @@ struct Cli {
   #[arg(short = 'c', long)]
   no_current: bool,
 
+  /// Write a Chrome trace of where the time went to this file
+  #[arg(long, value_name = "FILE", global = true)]
+  profile: Option<PathBuf>,
+
+  /// Print how long each step took, instead of showing progress
+  #[arg(long, global = true)]
+  verbose: bool,
+
+  /// Wait for another versio command that's changing the repo to finish, instead of failing
+  #[arg(long, global = true)]
+  wait_lock: bool,
+
+  /// Take over the repo lock of another versio command, if it's stale
+  #[arg(long, global = true, conflicts_with = "wait_lock")]
+  force_lock: bool,
+
   #[command(subcommand)]
   command: Commands
 }
@@ -75,7 +94,15 @@ impl VcsLevelBound {
 #[derive(Debug, Subcommand)]
 enum Commands {
   /// Check current config
-  Check {},
+  Check {
+    /// Also check that the repo agrees with the config, and report every problem found
+    #[arg(long)]
+    strict: bool,
+
+    /// Fail if a project's version or changelog was changed since branching from this base (outside a release)
+    #[arg(long, value_name = "BASE")]
+    release_only: Option<String>
+  },
 
   /// Show all versions
   Show {
@@ -99,6 +126,10 @@ enum Commands {
     #[arg(short, long)]
     version_only: bool,
 
+    /// Render the project with a Liquid template, like '{{name}}={{version}}'
+    #[arg(short, long, conflicts_with = "version_only")]
+    template: Option<String>,
+
     /// Wide output shows IDs
     #[arg(short, long)]
     wide: bool,
@@ -133,14 +164,94 @@ enum Commands {
 
     /// The new value
     #[arg(short, long)]
-    value: String
+    value: String,
+
+    /// Also update the version in the projects that depend on this one
+    #[arg(long)]
+    cascade: bool
+  },
+
+  /// Bump a version by a size, and cascade to dependents.
+  #[command(group(ArgGroup::new("ident").args(["name", "id", "exact"]),))]
+  Bump {
+    /// The name to bump.
+    #[arg(short, long)]
+    name: Option<String>,
+
+    /// The ID to bump.
+    #[arg(short, long)]
+    id: Option<u32>,
+
+    /// The exact name to bump.
+    #[arg(short, long)]
+    exact: Option<String>,
+
+    /// The size of the bump
+    #[arg(short, long, value_parser = ["major", "minor", "patch"])]
+    size: String
+  },
+
+  /// Pin the next version of a project, for the next release
+  Pin {
+    /// The project name or ID
+    project: String,
+
+    /// The next version
+    #[arg(required_unless_present = "clear")]
+    version: Option<String>,
+
+    /// Unpin the project's next version instead
+    #[arg(long, conflicts_with = "version")]
+    clear: bool
   },
 
   /// View changes from previous
-  Diff {},
+  Diff {
+    /// Compare from this commit, tag, or branch, instead of the previous tag
+    #[arg(long, value_name = "REF")]
+    from: Option<String>,
+
+    /// Compare to this commit, tag, or branch, instead of the current files
+    #[arg(long, value_name = "REF")]
+    to: Option<String>
+  },
 
   /// Stream changed files
-  Files {},
+  Files {
+    /// Only files of labeled projects
+    #[arg(short, long)]
+    label: Vec<String>,
+
+    /// Start after this revision, instead of the previous release
+    #[arg(long, value_name = "REF")]
+    since: Option<String>,
+
+    /// End at this revision, instead of HEAD
+    #[arg(long, value_name = "REF")]
+    until: Option<String>
+  },
+
+  /// Report which projects cover each file, and flag files covered by none or by several
+  Owners {
+    /// The files to check; if none (and no range) are given, paths or a unified diff are read from stdin
+    paths: Vec<String>,
+
+    /// Check the files changed after this revision
+    #[arg(long, value_name = "REF", conflicts_with = "paths")]
+    since: Option<String>,
+
+    /// Check the files changed up to this revision
+    #[arg(long, value_name = "REF", conflicts_with = "paths")]
+    until: Option<String>,
+
+    /// The output format
+    #[arg(long, value_parser = ["text", "json"], default_value = "text")]
+    format: String,
+
+    /// Also fail if a file is covered by more than one project
+    #[arg(long)]
+    strict: bool
+  },
 
   /// Find versions that need to change
   Plan {
@@ -148,6 +259,56 @@ enum Commands {
     #[arg(short, long)]
     template: Option<String>,
 
+    /// Plan only a single project
+    #[arg(short, long)]
+    id: Option<u32>,
+
+    /// Don't use the forge API: plan from local commits only
+    #[arg(long)]
+    offline: bool,
+
+    /// Plan only labeled projects
+    #[arg(short, long)]
+    label: Vec<String>,
+
+    /// The output format
+    #[arg(long, value_parser = ["text", "json"], default_value = "text", conflicts_with = "template")]
+    format: String,
+
+    /// Start after this revision, instead of the previous release
+    #[arg(long, value_name = "REF")]
+    since: Option<String>,
+
+    /// End at this revision, instead of HEAD
+    #[arg(long, value_name = "REF")]
+    until: Option<String>,
+
+    /// List the commits that didn't apply to any project, with their files
+    #[arg(long, conflicts_with = "template")]
+    show_ineffective: bool,
+
+    /// Fail if any commit didn't apply to any project
+    #[arg(long)]
+    fail_on_uncovered: bool
+  },
+
+  /// Show the next version of each project, without changing anything
+  Preview {
+    /// Don't use the forge API: plan from local commits only
+    #[arg(long)]
+    offline: bool
+  },
+
+  /// Plan hypothetical commits from a file
+  Simulate {
+    /// The YAML file of simulated commits
+    #[arg(short, long)]
+    file: String,
+
+    /// The changelog template to format with
+    #[arg(short, long)]
+    template: Option<String>,
+
     /// Plan only a single project
     #[arg(short, long)]
     id: Option<u32>
@@ -175,23 +336,212 @@ enum Commands {
     #[arg(short, long)]
     dry_run: bool,
 
+    /// With a dry run, show a diff of each file that would be written
+    #[arg(long, requires = "dry_run")]
+    show_diff: bool,
+
+    /// Release for real, even if the config sets `dry_run_default`
+    #[arg(long, visible_alias = "yes", conflicts_with = "dry_run")]
+    confirm: bool,
+
     #[arg(short, long)]
     changelog_only: bool,
 
     #[arg(short, long)]
-    lock_tags: bool
+    lock_tags: bool,
+
+    /// Don't use the forge API: plan from local commits only
+    #[arg(long)]
+    offline: bool,
+
+    /// Release from a detached HEAD, creating tags only
+    #[arg(long)]
+    force_detached: bool,
+
+    /// Release even at versions that break a project's constraints
+    #[arg(long)]
+    force: bool,
+
+    /// Build a fresh plan, even if `plan` cached one for this state
+    #[arg(long)]
+    no_cache: bool,
+
+    /// Don't post to the `notifications` webhooks, or publish to the `notes_publishers`
+    #[arg(long)]
+    no_notify: bool,
+
+    /// Don't run the `post_release` hooks
+    #[arg(long)]
+    skip_publish: bool,
+
+    /// Release only this project ID
+    #[arg(short, long)]
+    id: Vec<u32>,
+
+    /// Release only projects with this name
+    #[arg(short, long)]
+    name: Vec<String>,
+
+    /// Release only projects with this label
+    #[arg(long)]
+    label: Vec<String>,
+
+    /// Also release the projects that depend on a released project
+    #[arg(long)]
+    with_dependents: bool,
+
+    /// Only write the changelog of this project ID, deferring its release to a later run
+    #[arg(long, value_name = "ID")]
+    only_changelog_for: Vec<u32>,
+
+    /// Print a canonical receipt of the release, and its SHA-256
+    #[arg(long)]
+    receipt: bool,
+
+    /// Post the release receipt to this URL
+    #[arg(long, value_name = "URL")]
+    receipt_url: Option<String>,
+
+    /// Stop before changing anything unless the release receipt has this SHA-256
+    #[arg(long, value_name = "SHA256")]
+    verify_receipt: Option<String>,
+
+    /// Plan again, and report how that differs from this plan saved by `plan --format json`
+    #[arg(long, value_name = "FILE")]
+    saved_plan: Option<PathBuf>,
+
+    /// What to do when the release has drifted from the saved plan
+    #[arg(long, value_enum, default_value = "warn", requires = "saved_plan")]
+    on_drift: DriftArg,
+
+    /// Only create tags, at this commit (like a merge queue's merge commit) instead of HEAD
+    #[arg(long, value_name = "REV")]
+    target: Option<String>,
+
+    /// Write the release notes of the whole release to this file
+    #[arg(long, value_name = "FILE")]
+    release_notes: Option<PathBuf>,
+
+    /// The release notes template to format with
+    #[arg(long, value_name = "URL", requires = "release_notes")]
+    notes_template: Option<String>,
+
+    /// Write a manifest of the release to this file, or to stdout for "-"
+    #[arg(long, value_name = "FILE")]
+    manifest: Option<String>,
+
+    /// The format of the release manifest
+    #[arg(long, value_enum, default_value = "json", requires = "manifest")]
+    manifest_format: ManifestFormatArg,
+
+    /// Review each project's release before anything is written: accept, skip, or change it
+    #[arg(long)]
+    interactive: bool
+  },
+
+  /// Output the release notes of the pending release, across all projects
+  ReleaseNotes {
+    /// The release notes template to format with
+    #[arg(short, long, default_value = "builtin:release-notes")]
+    template: String,
+
+    /// Don't use the forge API: plan from local commits only
+    #[arg(long)]
+    offline: bool,
+
+    /// Write the release notes to this file, instead of printing them
+    #[arg(short, long, value_name = "FILE")]
+    output: Option<PathBuf>
+  },
+
+  /// Check that a release tag is what versio would have released
+  VerifyRelease {
+    /// The version tag of the release
+    tag: String,
+
+    /// Don't use the forge API: plan from local commits only
+    #[arg(long)]
+    offline: bool
+  },
+
+  /// Check the tag history of every project for versions that go backwards, skip, or don't match the version file
+  Audit {
+    /// The output format
+    #[arg(long, value_parser = ["text", "json"], default_value = "text")]
+    format: String
+  },
+
+  /// Undo the latest release
+  Rollback {
+    /// Only show what would be undone
+    #[arg(short, long)]
+    dry_run: bool
+  },
+
+  /// Start using versio in a repo with existing version tags
+  Adopt {
+    /// Also write the changelog entries of past releases
+    #[arg(long)]
+    changelogs: bool,
+
+    /// Don't use the forge API: read past releases from local commits only
+    #[arg(long)]
+    offline: bool,
+
+    /// Milliseconds to wait between past releases, to stay under API rate limits
+    #[arg(long, default_value_t = 0)]
+    delay: u64,
+
+    /// Only show what would be adopted
+    #[arg(short, long)]
+    dry_run: bool
   },
 
   /// Print true changes
-  Changes {},
+  Changes {
+    /// Only changes to labeled projects
+    #[arg(short, long)]
+    label: Vec<String>,
+
+    /// Start after this revision, instead of the previous release
+    #[arg(long, value_name = "REF")]
+    since: Option<String>,
+
+    /// End at this revision, instead of HEAD
+    #[arg(long, value_name = "REF")]
+    until: Option<String>
+  },
 
   /// Search for projects and write a config
   Init {
     /// Max descent to search
     #[arg(short = 'd', long, default_value_t = 5)]
-    max_depth: u16
+    max_depth: u16,
+
+    /// Only find the projects of this stack, and configure them with its conventions
+    #[arg(long, value_enum, value_name = "STACK")]
+    template: Option<StackArg>,
+
+    /// Increase each Maven module's version whenever its parent's is increased
+    #[arg(long)]
+    propagate_parents: bool
   },
 
+  /// Print detected repository info as JSON
+  Env {},
+
+  /// Print the JSON Schema of the config file
+  Schema {},
+
+  /// Print the completions script for a shell
+  Completions {
+    #[arg(value_enum)]
+    shell: Shell
+  },
+
+  /// Print the man page
+  Man {},
+
   /// Print info about projects
   Info {
     /// Info on a project ID
@@ -240,7 +590,11 @@ enum Commands {
 
     /// Show the project(s) tag prefix
     #[arg(short = 'T', long)]
-    show_tag_prefix: bool
+    show_tag_prefix: bool,
+
+    /// Render each project with a Liquid template, like '{{name}}={{version}}', instead of JSON
+    #[arg(short, long)]
+    template: Option<String>
   },
 
   /// Output a changelog template
@@ -248,6 +602,40 @@ enum Commands {
     /// The changelog template to output
     #[arg(short, long)]
     template: String
+  },
+
+  /// Maintain the config file
+  Config {
+    #[command(subcommand)]
+    command: ConfigCommands
+  },
+
+  /// Maintain changelogs
+  Changelog {
+    #[command(subcommand)]
+    command: ChangelogCommands
+  }
+}
+
+#[derive(Debug, Subcommand)]
+enum ConfigCommands {
+  /// Rename deprecated keys in the config file
+  Fix {
+    /// Only show what would be renamed
+    #[arg(short, long)]
+    dry_run: bool
+  }
+}
+
+#[derive(Debug, Subcommand)]
+enum ChangelogCommands {
+  /// Rebuild the changelog section of a released version with the current template
+  Regenerate {
+    /// The project name or ID
+    project: String,
+
+    /// The released version
+    version: String
   }
 }
 
@@ -255,9 +643,19 @@ impl Commands {
   fn requires_sanity(&self) -> bool {
     match self {
       Self::Release { abort, resume, .. } => !*abort && !*resume,
+      Self::Env {} | Self::Schema {} | Self::Completions { .. } | Self::Man {} => false,
       _ => true
     }
   }
+
+  /// Whether the command writes JSON to stdout, which progress and hook output shouldn't be mixed with.
+  fn json(&self) -> bool {
+    match self {
+      Self::Owners { format, .. } | Self::Plan { format, .. } | Self::Audit { format } => format == "json",
+      Self::Release { manifest, .. } => manifest.as_deref() == Some("-"),
+      _ => false
+    }
+  }
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Ord, PartialOrd, ValueEnum)]
@@ -265,6 +663,82 @@ enum PauseStage {
   Commit
 }
 
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Ord, PartialOrd, ValueEnum)]
+enum DriftArg {
+  Warn,
+  Fail
+}
+
+impl DriftArg {
+  fn to_tolerance(self) -> DriftTolerance {
+    match self {
+      Self::Warn => DriftTolerance::Warn,
+      Self::Fail => DriftTolerance::Fail
+    }
+  }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Ord, PartialOrd, ValueEnum)]
+enum ManifestFormatArg {
+  Json,
+  Cyclonedx
+}
+
+impl ManifestFormatArg {
+  fn to_format(self) -> ManifestFormat {
+    match self {
+      Self::Json => ManifestFormat::Json,
+      Self::Cyclonedx => ManifestFormat::CycloneDx
+    }
+  }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Ord, PartialOrd, ValueEnum)]
+enum StackArg {
+  RustWorkspace,
+  NpmMonorepo,
+  PythonPoetry
+}
+
+impl StackArg {
+  fn to_stack(self) -> Stack {
+    match self {
+      Self::RustWorkspace => Stack::RustWorkspace,
+      Self::NpmMonorepo => Stack::NpmMonorepo,
+      Self::PythonPoetry => Stack::PythonPoetry
+    }
+  }
+}
+
+/// The `--profile` trace file, if any. This is needed before the rest of the options are checked, so that all of
+/// the work can be traced.
+pub fn profile_file() -> Option<PathBuf> { Cli::try_parse().ok().and_then(|cli| cli.profile) }
+
+/// Whether `--verbose` was given, which is also needed before the work starts, to time its steps.
+pub fn verbose() -> bool { Cli::try_parse().map(|cli| cli.verbose).unwrap_or(false) }
+
+/// Print the shell completions or the man page, if that's the command. These only need the command-line options,
+/// not a repo, so they're printed before anything else: returns whether one was.
+pub fn generate() -> Result<bool> {
+  let cli = match Cli::try_parse() {
+    Ok(cli) => cli,
+    Err(_) => return Ok(false)
+  };
+  let mut cmd = Cli::command();
+  match cli.command {
+    Commands::Completions { shell } => {
+      let name = cmd.get_name().to_string();
+      clap_complete::generate(shell, &mut cmd, name, &mut std::io::stdout());
+      Ok(true)
+    }
+    Commands::Man {} => {
+      Man::new(cmd).render(&mut std::io::stdout())?;
+      Ok(true)
+    }
+    _ => Ok(false)
+  }
+}
+
 pub async fn execute(early_info: &EarlyInfo) -> Result<()> {
   let id_required = early_info.project_count() != 1;
   let cli = Cli::parse();
@@ -274,27 +748,91 @@ pub async fn execute(early_info: &EarlyInfo) -> Result<()> {
     sanity_check()?;
   }
 
-  let pref_vcs = parse_vcs(&cli);
+  let defaults = early_info.defaults();
+  let pref_vcs = match parse_vcs(&cli) {
+    Some(vcs) => Some(vcs),
+    None => defaults.vcs_range()?
+  };
   let no_current = cli.no_current;
+  set_lock_policy(cli.wait_lock, cli.force_lock);
+  set_progress(!cli.verbose && !cli.command.json());
+  set_json_stdout(cli.command.json());
 
   match &cli.command {
-    Commands::Check {} => check(pref_vcs, no_current)?,
-    Commands::Get { prev, version_only, wide, name, exact, id } => {
+    Commands::Check { strict, release_only } => check(pref_vcs, *strict, release_only.as_deref(), no_current)?,
+    Commands::Get { prev, version_only, template, wide, name, exact, id } => {
+      let name_match = NameMatch::from(name, exact);
+      let template = if *version_only { Some("{{ version }}") } else { template.as_deref() };
+      get(pref_vcs, *wide || defaults.wide(), template, *prev, id.as_ref(), &name_match, no_current)?
+    }
+    Commands::Show { prev, wide } => show(pref_vcs, *wide || defaults.wide(), *prev, no_current)?,
+    Commands::Set { name, exact, id, value, cascade } => {
       let name_match = NameMatch::from(name, exact);
-      get(pref_vcs, *wide, *version_only, *prev, id.as_ref(), &name_match, no_current)?
+      set(pref_vcs, id.as_ref(), &name_match, value, *cascade)?
     }
-    Commands::Show { prev, wide } => show(pref_vcs, *wide, *prev, no_current)?,
-    Commands::Set { name, exact, id, value } => {
+    Commands::Bump { name, exact, id, size } => {
       let name_match = NameMatch::from(name, exact);
-      set(pref_vcs, id.as_ref(), &name_match, value)?
+      bump(pref_vcs, id.as_ref(), &name_match, size)?
     }
-    Commands::Diff {} => diff(pref_vcs, no_current)?,
-    Commands::Files {} => files(pref_vcs, no_current).await?,
-    Commands::Changes {} => changes(pref_vcs, no_current).await?,
-    Commands::Plan { template, id } => plan(early_info, pref_vcs, id.as_ref(), template.as_deref(), no_current).await?,
-    Commands::Release { abort: a, .. } if *a => abort()?,
+    Commands::Pin { project, version, .. } => pin(pref_vcs, project, version.as_deref())?,
+    Commands::Diff { from, to } => diff(pref_vcs, from.as_deref(), to.as_deref(), no_current)?,
+    Commands::Files { label, since, until } => {
+      files(pref_vcs, label, since.as_deref(), until.as_deref(), no_current).await?
+    }
+    Commands::Changes { label, since, until } => {
+      changes(pref_vcs, label, since.as_deref(), until.as_deref(), no_current).await?
+    }
+    Commands::Owners { paths, since, until, format, strict } => {
+      owners(pref_vcs, paths, since.as_deref(), until.as_deref(), format == "json", *strict).await?
+    }
+    Commands::Plan { template, id, offline, label, format, since, until, show_ineffective, fail_on_uncovered } => {
+      let opts = PlanOptions::new()
+        .offline(*offline || defaults.offline())
+        .labels(label)
+        .json(format == "json")
+        .range(since.as_deref(), until.as_deref())
+        .show_ineffective(*show_ineffective)
+        .fail_on_uncovered(*fail_on_uncovered);
+      plan(early_info, pref_vcs, id.as_ref(), template.as_deref(), no_current, &opts).await?
+    }
+    Commands::Preview { offline } => preview(pref_vcs, *offline || defaults.offline(), no_current).await?,
+    Commands::Simulate { file, template, id } => {
+      simulate(early_info, pref_vcs, file, id.as_ref(), template.as_deref(), no_current).await?
+    }
+    Commands::Release { abort: a, .. } if *a => abort(pref_vcs)?,
     Commands::Release { resume: r, .. } if *r => resume(pref_vcs)?,
-    Commands::Release { show_all, pause, dry_run, changelog_only, lock_tags, .. } => {
+    Commands::Release {
+      show_all,
+      pause,
+      dry_run,
+      show_diff,
+      confirm,
+      changelog_only,
+      lock_tags,
+      offline,
+      force_detached,
+      force,
+      no_cache,
+      no_notify,
+      skip_publish,
+      id,
+      name,
+      label,
+      with_dependents,
+      only_changelog_for,
+      receipt,
+      receipt_url,
+      verify_receipt,
+      saved_plan,
+      on_drift,
+      target,
+      release_notes,
+      notes_template,
+      manifest,
+      manifest_format,
+      interactive,
+      ..
+    } => {
       let dry = if *dry_run {
         Engagement::Dry
       } else if *changelog_only {
@@ -303,9 +841,54 @@ pub async fn execute(early_info: &EarlyInfo) -> Result<()> {
         Engagement::Full
       };
 
-      release(pref_vcs, *show_all, &dry, *lock_tags, pause.is_some()).await?
+      let opts = ReleaseOptions::new()
+        .show_all(*show_all)
+        .lock_tags(*lock_tags)
+        .pause(pause.is_some())
+        .offline(*offline || defaults.offline())
+        .force_detached(*force_detached)
+        .force(*force)
+        .no_cache(*no_cache)
+        .no_notify(*no_notify)
+        .skip_publish(*skip_publish)
+        .pick(id, name, label)
+        .with_dependents(*with_dependents)
+        .changelog_only_for(only_changelog_for)
+        .receipt(*receipt)
+        .receipt_url(receipt_url.clone())
+        .verify_receipt(verify_receipt.clone())
+        .saved_plan(saved_plan.clone())
+        .on_drift(on_drift.to_tolerance())
+        .target(target.clone())
+        .release_notes(release_notes.clone(), notes_template.clone())
+        .manifest(manifest.clone(), manifest_format.to_format())
+        .show_diff(*show_diff)
+        .confirm(*confirm)
+        .interactive(*interactive);
+      release(pref_vcs, &dry, &opts).await?
+    }
+    Commands::ReleaseNotes { template, offline, output } => {
+      let offline = *offline || defaults.offline();
+      release_notes(early_info, pref_vcs, template, offline, output.as_deref(), no_current).await?
+    }
+    Commands::VerifyRelease { tag, offline } => verify_release(pref_vcs, tag, *offline || defaults.offline()).await?,
+    Commands::Audit { format } => audit(pref_vcs, format == "json")?,
+    Commands::Rollback { dry_run } => rollback(pref_vcs, *dry_run)?,
+    Commands::Adopt { changelogs, offline, delay, dry_run } => {
+      let opts = AdoptOptions::new()
+        .changelogs(*changelogs)
+        .offline(*offline || defaults.offline())
+        .delay(*delay)
+        .dry_run(*dry_run);
+      adopt(pref_vcs, &opts).await?
+    }
+    Commands::Init { max_depth, template, propagate_parents } => {
+      init(*max_depth, template.map(StackArg::to_stack), *propagate_parents)?
     }
-    Commands::Init { max_depth } => init(*max_depth)?,
+    Commands::Env {} => env(early_info)?,
+    Commands::Schema {} => schema()?,
+    // These were already printed by `generate`.
+    Commands::Completions { .. } | Commands::Man {} => (),
     Commands::Info {
       id,
       name,
@@ -318,7 +901,8 @@ pub async fn execute(early_info: &EarlyInfo) -> Result<()> {
       show_id,
       show_full_version,
       show_version,
-      show_tag_prefix
+      show_tag_prefix,
+      template
     } => {
       let show = InfoShow::new()
         .pick_all(*all)
@@ -327,11 +911,16 @@ pub async fn execute(early_info: &EarlyInfo) -> Result<()> {
         .show_id(*show_id || *show_all)
         .show_full_version(*show_full_version || *show_all)
         .show_version(*show_version || *show_all)
-        .show_tag_prefix(*show_tag_prefix || *show_all);
+        .show_tag_prefix(*show_tag_prefix || *show_all)
+        .with_template(template.clone());
 
       info(pref_vcs, id, name, exact, label, show, no_current)?
     }
-    Commands::Template { template: t } => template(early_info, t).await?
+    Commands::Template { template: t } => template(early_info, t).await?,
+    Commands::Config { command: ConfigCommands::Fix { dry_run } } => config_fix(early_info, *dry_run)?,
+    Commands::Changelog { command: ChangelogCommands::Regenerate { project, version } } => {
+      changelog_regenerate(pref_vcs, project, version).await?
+    }
   }
 
   Ok(())
@@ -361,7 +950,7 @@ fn verify_cli(cli: &Cli, id_required: bool) -> Result<()> {
     }
   }
 
-  if let Commands::Set { name, id, exact, .. } = &cli.command {
+  if let Commands::Set { name, id, exact, .. } | Commands::Bump { name, id, exact, .. } = &cli.command {
     let is_idented = name.is_some() || id.is_some() || exact.is_some();
     if !is_idented && id_required {
       let mut cmd = Cli::command();
@@ -369,13 +958,20 @@ fn verify_cli(cli: &Cli, id_required: bool) -> Result<()> {
     }
   }
 
-  if let Commands::Plan { id, template } = &cli.command {
+  if let Commands::Plan { id, template, .. } = &cli.command {
     if template.is_some() && id.is_none() && id_required {
       let mut cmd = Cli::command();
       cmd.error(ErrorKind::ValueValidation, "Choose an ID for template plan.").exit();
     }
   }
 
+  if let Commands::Simulate { id, template, .. } = &cli.command {
+    if template.is_some() && id.is_none() && id_required {
+      let mut cmd = Cli::command();
+      cmd.error(ErrorKind::ValueValidation, "Choose an ID for template simulation.").exit();
+    }
+  }
+
   if let Commands::Release { dry_run, changelog_only, lock_tags, pause, resume, abort, .. } = &cli.command {
     if *dry_run && (pause.is_some() || *resume || *abort || *changelog_only) {
       let mut cmd = Cli::command();
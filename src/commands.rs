@@ -1,28 +1,44 @@
 //! The command-line options for the executable.
 
 use crate::bail;
-use crate::config::{Config, ConfigFile, ProjectId, Size};
+use crate::config::{CommitConfig, Config, ConfigFile, Project, ProjectId, Size, CONFIG_FILENAME};
+use crate::deprecate::fix;
+use crate::drift::SavedPlan;
 use crate::errors::{Context as _, Result};
 use crate::git::Repo;
-use crate::mono::{Mono, Plan};
-use crate::output::{Output, ProjLine};
+pub use crate::lock::set_lock_policy;
+pub use crate::progress::{set_progress, StepTimer};
+use crate::mono::{read_defaults, AdoptProgress, Defaults, Mono, NextVersion, Plan, VersionChange};
+pub use crate::mono::ManifestFormat;
+pub use crate::output::{eprintln_pending_warnings, set_json_stdout};
+use crate::output::{eprintln_mirror_failures, warn_later, Output, ProjLine};
+use crate::review::{Decision, Reviewer};
+use crate::schema::config_schema;
+use crate::simulate::Simulation;
 use crate::state::{CommitState, StateRead};
-use crate::template::read_template;
+use crate::template::{read_template, DEFAULT_NOTES_TEMPLATE};
+use crate::unidiff::{diff_paths, is_diff};
 use crate::vcs::{VcsLevel, VcsRange, VcsState};
-use std::collections::HashMap;
-use std::fs::{remove_file, File};
-use std::io::BufReader;
+use chrono::Utc;
+use std::collections::{HashMap, HashSet};
+use std::io::Read as _;
 use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 
 pub fn early_info() -> Result<EarlyInfo> {
-  let vcs = VcsRange::detect()?.max();
-  let root = Repo::find_working_dir(".", vcs, true)?;
+  let vcs = VcsRange::detect()?;
+  let root = Repo::find_working_dir(".", vcs.max(), true)?;
   let file = ConfigFile::from_dir(&root)?;
   let project_count = file.projects().len();
   let orig_dir = std::env::current_dir()?;
   assert_ok!(orig_dir.is_absolute(), "Couldn't find current working directory.");
+  let defaults = read_defaults(&root)?;
+  crate::i18n::init(&root, defaults.locale())?;
+  for deprecation in file.deprecations() {
+    warn_later(tr!("deprecated.warning", key = deprecation));
+  }
 
-  Ok(EarlyInfo::new(project_count, root, orig_dir))
+  Ok(EarlyInfo::new(project_count, root, orig_dir, vcs, defaults))
 }
 
 pub enum Engagement {
@@ -31,52 +47,93 @@ pub enum Engagement {
   Full
 }
 
+/// What to do when a release has drifted from the plan that was saved for it.
+#[derive(Clone, Copy)]
+pub enum DriftTolerance {
+  /// Warn about the drift, and release the current plan anyway.
+  Warn,
+  /// Stop before changing anything.
+  Fail
+}
+
 /// Environment information gathered even before we set the CLI options.
 pub struct EarlyInfo {
   project_count: usize,
   working_dir: PathBuf,
-  orig_dir: PathBuf
+  orig_dir: PathBuf,
+  vcs: VcsRange,
+  defaults: Defaults
 }
 
 impl EarlyInfo {
-  pub fn new(project_count: usize, working_dir: PathBuf, orig_dir: PathBuf) -> EarlyInfo {
-    EarlyInfo { project_count, working_dir, orig_dir }
+  pub fn new(
+    project_count: usize, working_dir: PathBuf, orig_dir: PathBuf, vcs: VcsRange, defaults: Defaults
+  ) -> EarlyInfo {
+    EarlyInfo { project_count, working_dir, orig_dir, vcs, defaults }
   }
 
   pub fn project_count(&self) -> usize { self.project_count }
   pub fn working_dir(&self) -> &Path { &self.working_dir }
   pub fn orig_dir(&self) -> &Path { &self.orig_dir }
+  pub fn vcs(&self) -> &VcsRange { &self.vcs }
+  pub fn defaults(&self) -> &Defaults { &self.defaults }
 }
 
-pub fn check(pref_vcs: Option<VcsRange>, ignore_current: bool) -> Result<()> {
-  let mono = with_opts(pref_vcs, VcsLevel::None, VcsLevel::Local, VcsLevel::None, VcsLevel::Smart, ignore_current)?;
+pub fn check(pref_vcs: Option<VcsRange>, strict: bool, release_only: Option<&str>, ignore_current: bool) -> Result<()> {
+  // A strict or release-only check compares the config against the repo's history, so it needs a local repo.
+  let reqd_lo = if strict || release_only.is_some() { VcsLevel::Local } else { VcsLevel::None };
+  let mono = with_opts(pref_vcs, VcsLevel::None, VcsLevel::Local, reqd_lo, VcsLevel::Smart, ignore_current)?;
   let output = Output::new();
   let mut output = output.check();
 
-  mono.check()?;
+  let violations = mono.config().schema_violations()?;
+  if !violations.is_empty() {
+    output.write_violations(&violations);
+    bail!("{}", tr!("check.invalid", count = violations.len()));
+  }
+
+  if strict {
+    let violations = mono.check_strict()?;
+    if !violations.is_empty() {
+      output.write_violations(&violations);
+      bail!("{}", tr!("check.failed", count = violations.len()));
+    }
+  } else {
+    mono.check()?;
+  }
+
+  if let Some(base) = release_only {
+    let violations = mono.check_release_only(base)?;
+    if !violations.is_empty() {
+      output.write_violations(&violations);
+      bail!("{}", tr!("check.manual", count = violations.len()));
+    }
+  }
+  output.write_ambiguous_tags(&mono.ambiguous_tags()?);
   output.write_done()?;
 
   output.commit()
 }
 
+/// Print a project's version; or, with a `template`, render the project with it instead.
 pub fn get(
-  pref_vcs: Option<VcsRange>, wide: bool, versonly: bool, prev: bool, id: Option<&u32>, name: &NameMatch,
+  pref_vcs: Option<VcsRange>, wide: bool, template: Option<&str>, prev: bool, id: Option<&u32>, name: &NameMatch,
   ignore_current: bool
 ) -> Result<()> {
   let mono = with_opts(pref_vcs, VcsLevel::None, VcsLevel::Local, VcsLevel::None, VcsLevel::Smart, ignore_current)?;
 
   if prev {
-    get_using_cfg(&mono.config().slice_to_prev(mono.repo())?, wide, versonly, id, name)
+    get_using_cfg(&mono.config().slice_to_prev(mono.repo())?, wide, template, id, name)
   } else {
-    get_using_cfg(mono.config(), wide, versonly, id, name)
+    get_using_cfg(mono.config(), wide, template, id, name)
   }
 }
 
 fn get_using_cfg<R: StateRead>(
-  cfg: &Config<R>, wide: bool, versonly: bool, id: Option<&u32>, name: &NameMatch
+  cfg: &Config<R>, wide: bool, template: Option<&str>, id: Option<&u32>, name: &NameMatch
 ) -> Result<()> {
   let output = Output::new();
-  let mut output = output.projects(wide, versonly);
+  let mut output = output.projects(wide).with_template(template)?;
 
   let ensure = || bad!("No such project.");
 
@@ -113,42 +170,107 @@ pub fn show(pref_vcs: Option<VcsRange>, wide: bool, prev: bool, ignore_current:
 
 fn show_using_cfg<R: StateRead>(cfg: &Config<R>, wide: bool) -> Result<()> {
   let output = Output::new();
-  let mut output = output.projects(wide, false);
+  let mut output = output.projects(wide);
   let reader = cfg.state_read();
   output.write_projects(cfg.projects().iter().map(|p| ProjLine::from(p, reader)))?;
   output.commit()
 }
 
-pub fn set(pref_vcs: Option<VcsRange>, id: Option<&u32>, name: &NameMatch, value: &str) -> Result<()> {
-  let mut mono = build(pref_vcs, VcsLevel::None, VcsLevel::None, VcsLevel::None, VcsLevel::Smart)?;
+pub fn set(pref_vcs: Option<VcsRange>, id: Option<&u32>, name: &NameMatch, value: &str, cascade: bool) -> Result<()> {
+  let mut mono = build_locked(pref_vcs, VcsLevel::None, VcsLevel::None, VcsLevel::None, VcsLevel::Smart, false)?;
+  let id = pick_project(mono.config(), id, name)?;
 
+  mono.set_by_id(&id, value)?;
+  if cascade {
+    mono.write_dependents(&id, value)?;
+  }
+
+  eprintln_mirror_failures(mono.commit(false, false)?);
+  Ok(())
+}
+
+/// Find the project picked by the `--id`, `--name`, or `--exact` option, or the only project if none was given.
+fn pick_project<S: StateRead>(cfg: &Config<S>, id: Option<&u32>, name: &NameMatch) -> Result<ProjectId> {
   if let Some(id) = id {
-    let id = ProjectId::from_id(*id);
-    mono.set_by_id(&id, value)?;
+    Ok(ProjectId::from_id(*id))
   } else if let NameMatch::Partial(name) = name {
-    mono.set_by_name(name, value)?;
+    Ok(cfg.find_unique(name)?.clone())
   } else if let NameMatch::Exact(name) = name {
-    mono.set_by_exact_name(name, value)?;
+    Ok(cfg.find_exact(name)?.clone())
+  } else if cfg.projects().len() == 1 {
+    Ok(cfg.projects()[0].id().clone())
   } else {
-    mono.set_by_only(value)?;
+    bail!("No solo project.");
   }
+}
 
-  mono.commit(false, false)
+/// Pin the next version of a project (or unpin it, if there's no `version`), which the next release uses instead of
+/// the project's planned size.
+pub fn pin(pref_vcs: Option<VcsRange>, project: &str, version: Option<&str>) -> Result<()> {
+  let mut mono = build_locked(pref_vcs, VcsLevel::None, VcsLevel::None, VcsLevel::None, VcsLevel::Smart, false)?;
+  let id = match project.parse::<u32>() {
+    Ok(id) => ProjectId::from_id(id),
+    Err(_) => mono.config().find_exact(project)?.clone()
+  };
+  let name = mono.get_project(&id)?.name().to_string();
+
+  mono.pin(&id, version)?;
+  eprintln_mirror_failures(mono.commit(false, false)?);
+
+  let output = Output::new();
+  let mut output = output.pin();
+  output.write_pinned(&name, version);
+  output.commit();
+  Ok(())
 }
 
-pub fn diff(pref_vcs: Option<VcsRange>, ignore_current: bool) -> Result<()> {
+/// Increase a project's version by `size`, cascading the increase to dependents as a release would.
+pub fn bump(pref_vcs: Option<VcsRange>, id: Option<&u32>, name: &NameMatch, size: &str) -> Result<()> {
+  let size = Size::from_str(size)?;
+  let mut mono = build_locked(pref_vcs, VcsLevel::None, VcsLevel::None, VcsLevel::None, VcsLevel::Smart, false)?;
+  let id = pick_project(mono.config(), id, name)?;
+
+  let plan = mono.bump_plan(&id, size)?;
+  let output = Output::new();
+  let mut output = output.bump();
+  let mut versions = HashMap::new();
+  for (id, (size, _)) in plan.incrs() {
+    let proj = mono.get_project(id)?;
+    let name = proj.name().to_string();
+    let curt_vers = mono.config().get_value(id)?.ok_or_else(|| bad!("Unable to find project {} value.", id))?;
+    let target = proj.apply_size(*size, &curt_vers)?;
+    proj.verify_restrictions(&target)?;
+    mono.set_by_id(id, &target)?;
+    output.write_bumped(name, curt_vers, target.clone());
+    versions.insert(id.clone(), target);
+  }
+
+  let chains: Vec<_> = plan.chain_writes().iter().filter(|(id, _)| versions.contains_key(id)).cloned().collect();
+  mono.write_chains(&chains, &versions)?;
+  eprintln_mirror_failures(mono.commit(false, false)?);
+  output.commit();
+  Ok(())
+}
+
+pub fn diff(pref_vcs: Option<VcsRange>, from: Option<&str>, to: Option<&str>, ignore_current: bool) -> Result<()> {
   let mono = with_opts(pref_vcs, VcsLevel::None, VcsLevel::Local, VcsLevel::Local, VcsLevel::Smart, ignore_current)?;
   let output = Output::new();
   let mut output = output.diff();
 
-  let analysis = mono.diff()?;
+  let analysis = mono.diff(from, to)?;
 
   output.write_analysis(analysis)?;
   output.commit()
 }
 
-pub async fn files(pref_vcs: Option<VcsRange>, ignore_current: bool) -> Result<()> {
-  let mono = with_opts(pref_vcs, VcsLevel::None, VcsLevel::Smart, VcsLevel::Local, VcsLevel::Smart, ignore_current)?;
+pub async fn files(
+  pref_vcs: Option<VcsRange>, labels: &[String], since: Option<&str>, until: Option<&str>, ignore_current: bool
+) -> Result<()> {
+  let mut mono =
+    with_opts(pref_vcs, VcsLevel::None, VcsLevel::Smart, VcsLevel::Local, VcsLevel::Smart, ignore_current)?;
+  mono.set_fallback(true);
+  mono.set_labels(labels);
+  mono.set_range(since, until)?;
   let output = Output::new();
   let mut output = output.files();
 
@@ -156,8 +278,14 @@ pub async fn files(pref_vcs: Option<VcsRange>, ignore_current: bool) -> Result<(
   output.commit()
 }
 
-pub async fn changes(pref_vcs: Option<VcsRange>, ignore_current: bool) -> Result<()> {
-  let mono = with_opts(pref_vcs, VcsLevel::None, VcsLevel::Smart, VcsLevel::Local, VcsLevel::Smart, ignore_current)?;
+pub async fn changes(
+  pref_vcs: Option<VcsRange>, labels: &[String], since: Option<&str>, until: Option<&str>, ignore_current: bool
+) -> Result<()> {
+  let mut mono =
+    with_opts(pref_vcs, VcsLevel::None, VcsLevel::Smart, VcsLevel::Local, VcsLevel::Smart, ignore_current)?;
+  mono.set_fallback(true);
+  mono.set_labels(labels);
+  mono.set_range(since, until)?;
   let output = Output::new();
   let mut output = output.changes();
 
@@ -167,17 +295,130 @@ pub async fn changes(pref_vcs: Option<VcsRange>, ignore_current: bool) -> Result
 }
 
 pub async fn plan(
-  early_info: &EarlyInfo, pref_vcs: Option<VcsRange>, id: Option<&u32>, template: Option<&str>, ignore_current: bool
+  early_info: &EarlyInfo, pref_vcs: Option<VcsRange>, id: Option<&u32>, template: Option<&str>, ignore_current: bool,
+  opts: &PlanOptions
 ) -> Result<()> {
-  let mono = with_opts(pref_vcs, VcsLevel::None, VcsLevel::Smart, VcsLevel::Local, VcsLevel::Smart, ignore_current)?;
+  let mut mono =
+    with_opts(pref_vcs, VcsLevel::None, VcsLevel::Smart, VcsLevel::Local, VcsLevel::Smart, ignore_current)?;
+  mono.set_offline(opts.offline);
+  mono.set_fallback(true);
+  mono.set_labels(&opts.labels);
+  mono.set_range(opts.since.as_deref(), opts.until.as_deref())?;
   let output = Output::new();
-  let mut output = output.plan();
-  let plan = mono.build_plan().await?;
+  let mut output = output.plan().with_json(opts.json).with_ineffective(opts.show_ineffective);
+  let plan = mono.build_and_cache_plan().await?;
+  let uncovered = plan.uncovered().len();
   let id = id.map(|i| ProjectId::from_id(*i));
   let orig_dir = early_info.orig_dir();
 
   output.write_plan(plan, id, template, orig_dir)?;
-  output.commit(&mono).await
+  output.commit(&mono).await?;
+  if opts.fail_on_uncovered && uncovered > 0 {
+    bail!("{}", tr!("plan.uncovered_failed", count = uncovered));
+  }
+  Ok(())
+}
+
+#[derive(Default)]
+pub struct PlanOptions {
+  offline: bool,
+  labels: Vec<String>,
+  json: bool,
+  since: Option<String>,
+  until: Option<String>,
+  show_ineffective: bool,
+  fail_on_uncovered: bool
+}
+
+impl PlanOptions {
+  pub fn new() -> PlanOptions { PlanOptions::default() }
+
+  pub fn offline(mut self, v: bool) -> PlanOptions {
+    self.offline = v;
+    self
+  }
+
+  pub fn labels(mut self, v: &[String]) -> PlanOptions {
+    self.labels = v.to_vec();
+    self
+  }
+
+  pub fn json(mut self, v: bool) -> PlanOptions {
+    self.json = v;
+    self
+  }
+
+  /// Plan the changes after `since` up to `until`, instead of the usual range.
+  pub fn range(mut self, since: Option<&str>, until: Option<&str>) -> PlanOptions {
+    self.since = since.map(|s| s.to_string());
+    self.until = until.map(|u| u.to_string());
+    self
+  }
+
+  /// List the commits that didn't apply to any project, with the files they changed.
+  pub fn show_ineffective(mut self, v: bool) -> PlanOptions {
+    self.show_ineffective = v;
+    self
+  }
+
+  /// Fail if any commit didn't apply to any project.
+  pub fn fail_on_uncovered(mut self, v: bool) -> PlanOptions {
+    self.fail_on_uncovered = v;
+    self
+  }
+}
+
+/// Print the release notes of the projects that a release would release now, or write them to `output`.
+pub async fn release_notes(
+  early_info: &EarlyInfo, pref_vcs: Option<VcsRange>, template: &str, offline: bool, output_file: Option<&Path>,
+  ignore_current: bool
+) -> Result<()> {
+  let mut mono =
+    with_opts(pref_vcs, VcsLevel::None, VcsLevel::Smart, VcsLevel::Local, VcsLevel::Smart, ignore_current)?;
+  mono.set_offline(offline);
+  let plan = mono.build_and_cache_plan().await?;
+  let orig_dir = early_info.orig_dir();
+  let template = read_template(template, Some(orig_dir), false).await?;
+
+  let output = Output::new();
+  let mut output = output.notes();
+  let released = mono.planned_releases(&plan)?;
+  if released.is_empty() {
+    output.write_empty();
+  } else {
+    let notes = mono.release_notes(&plan, &released, template).await?;
+    match output_file {
+      Some(file) => {
+        let path = orig_dir.join(file);
+        std::fs::write(&path, notes).with_context(|| format!("Unable to write release notes {}.", path.display()))?;
+        output.write_wrote(path);
+      }
+      None => output.write_notes(notes)
+    }
+  }
+  output.commit();
+  Ok(())
+}
+
+pub async fn simulate(
+  early_info: &EarlyInfo, pref_vcs: Option<VcsRange>, file: &str, id: Option<&u32>, template: Option<&str>,
+  ignore_current: bool
+) -> Result<()> {
+  let mono = with_opts(pref_vcs, VcsLevel::None, VcsLevel::Local, VcsLevel::None, VcsLevel::Smart, ignore_current)?;
+  let sim = Simulation::read(early_info.orig_dir().join(file))?;
+  let plan = mono.simulate_plan(&sim.prs())?;
+  let mismatches = sim.mismatches(&plan, mono.config())?;
+
+  let output = Output::new();
+  let mut output = output.plan();
+  let id = id.map(|i| ProjectId::from_id(*i));
+  output.write_plan(plan, id, template, early_info.orig_dir())?;
+  output.commit(&mono).await?;
+
+  if !mismatches.is_empty() {
+    bail!("Simulation didn't match expectations:\n  {}", mismatches.join("\n  "));
+  }
+  Ok(())
 }
 
 pub async fn template(early_info: &EarlyInfo, template: &str) -> Result<()> {
@@ -187,6 +428,48 @@ pub async fn template(early_info: &EarlyInfo, template: &str) -> Result<()> {
   Ok(())
 }
 
+/// Rename the deprecated keys in the config file.
+pub fn config_fix(early_info: &EarlyInfo, dry: bool) -> Result<()> {
+  let path = early_info.working_dir().join(CONFIG_FILENAME);
+  let data = std::fs::read_to_string(&path).with_context(|| format!("Unable to read {}.", path.display()))?;
+  let fixed = fix(&data)?;
+  let output = Output::new();
+  let mut output = output.config_fix();
+  output.write_fixed(&fixed);
+
+  if dry {
+    output.write_dry();
+  } else if !fixed.fixed().is_empty() {
+    std::fs::write(&path, fixed.text())?;
+  }
+
+  output.commit();
+  Ok(())
+}
+
+/// Print the JSON Schema of the config file.
+pub fn schema() -> Result<()> {
+  println!("{}", serde_json::to_string_pretty(&config_schema())?);
+  Ok(())
+}
+
+/// Print what was detected about the repository, without planning or reading any project versions.
+pub fn env(early_info: &EarlyInfo) -> Result<()> {
+  let vcs = early_info.vcs();
+  let repo = match vcs.max() {
+    VcsLevel::None => None,
+    level => Repo::open(early_info.working_dir(), VcsState::new(level, true), CommitConfig::default()).ok()
+  };
+  let branch = repo.as_ref().and_then(|r| r.branch_name().ok().cloned().flatten());
+  let github = repo.as_ref().and_then(|r| r.github_info(&Default::default()).ok());
+
+  let output = Output::new();
+  let mut output = output.env();
+  output.write_env(early_info, branch.as_deref(), github.as_ref())?;
+  output.commit()?;
+  Ok(())
+}
+
 pub fn info(
   pref_vcs: Option<VcsRange>, ids: &[u32], names: &[String], exacts: &[String], labels: &[String], show: InfoShow,
   ignore_current: bool
@@ -195,7 +478,8 @@ pub fn info(
   let mono = with_opts(pref_vcs, VcsLevel::None, VcsLevel::Smart, VcsLevel::None, VcsLevel::Smart, ignore_current)?;
   let output = Output::new();
   let all = show.all();
-  let mut output = output.info(show);
+  let template = show.template().map(|t| t.to_string());
+  let mut output = output.info(show).with_template(template.as_deref())?;
 
   let cfg = mono.config();
   let reader = cfg.state_read();
@@ -228,7 +512,8 @@ pub struct InfoShow {
   show_name: bool,
   show_tag_prefix: bool,
   show_full_version: bool,
-  show_version: bool
+  show_version: bool,
+  template: Option<String>
 }
 
 impl Default for InfoShow {
@@ -244,7 +529,8 @@ impl InfoShow {
       show_name: false,
       show_version: false,
       show_tag_prefix: false,
-      show_full_version: false
+      show_full_version: false,
+      template: None
     }
   }
 
@@ -255,6 +541,7 @@ impl InfoShow {
   pub fn tag_prefix(&self) -> bool { self.show_tag_prefix }
   pub fn full_version(&self) -> bool { self.show_full_version }
   pub fn version(&self) -> bool { self.show_version }
+  pub fn template(&self) -> Option<&str> { self.template.as_deref() }
 
   pub fn pick_all(mut self, v: bool) -> InfoShow {
     self.pick_all = v;
@@ -290,88 +577,479 @@ impl InfoShow {
     self.show_version = v;
     self
   }
+
+  /// Render each project with this Liquid template, instead of choosing the fields of the JSON output.
+  pub fn with_template(mut self, v: Option<String>) -> InfoShow {
+    self.template = v;
+    self
+  }
 }
 
-pub async fn release(
-  pref_vcs: Option<VcsRange>, all: bool, dry: &Engagement, locktags: bool, pause: bool
-) -> Result<()> {
-  let mut mono = build(pref_vcs, VcsLevel::None, VcsLevel::Smart, VcsLevel::Local, VcsLevel::Smart)?;
+pub struct ReleaseOptions {
+  show_all: bool,
+  lock_tags: bool,
+  pause: bool,
+  offline: bool,
+  force_detached: bool,
+  no_cache: bool,
+  no_notify: bool,
+  skip_publish: bool,
+  ids: Vec<u32>,
+  names: Vec<String>,
+  labels: Vec<String>,
+  with_dependents: bool,
+  receipt: bool,
+  receipt_url: Option<String>,
+  verify_receipt: Option<String>,
+  changelog_only_ids: Vec<u32>,
+  saved_plan: Option<PathBuf>,
+  on_drift: DriftTolerance,
+  target: Option<String>,
+  release_notes: Option<(PathBuf, String)>,
+  manifest: Option<(String, ManifestFormat)>,
+  show_diff: bool,
+  confirm: bool,
+  force: bool,
+  interactive: bool
+}
+
+impl Default for ReleaseOptions {
+  fn default() -> ReleaseOptions { ReleaseOptions::new() }
+}
+
+impl ReleaseOptions {
+  pub fn new() -> ReleaseOptions {
+    ReleaseOptions {
+      show_all: false,
+      lock_tags: false,
+      pause: false,
+      offline: false,
+      force_detached: false,
+      no_cache: false,
+      no_notify: false,
+      skip_publish: false,
+      ids: Vec::new(),
+      names: Vec::new(),
+      labels: Vec::new(),
+      with_dependents: false,
+      receipt: false,
+      receipt_url: None,
+      verify_receipt: None,
+      changelog_only_ids: Vec::new(),
+      saved_plan: None,
+      on_drift: DriftTolerance::Warn,
+      target: None,
+      release_notes: None,
+      manifest: None,
+      show_diff: false,
+      confirm: false,
+      force: false,
+      interactive: false
+    }
+  }
+
+  pub fn show_all(mut self, v: bool) -> ReleaseOptions {
+    self.show_all = v;
+    self
+  }
+
+  pub fn lock_tags(mut self, v: bool) -> ReleaseOptions {
+    self.lock_tags = v;
+    self
+  }
+
+  pub fn pause(mut self, v: bool) -> ReleaseOptions {
+    self.pause = v;
+    self
+  }
+
+  pub fn offline(mut self, v: bool) -> ReleaseOptions {
+    self.offline = v;
+    self
+  }
+
+  pub fn force_detached(mut self, v: bool) -> ReleaseOptions {
+    self.force_detached = v;
+    self
+  }
+
+  pub fn no_cache(mut self, v: bool) -> ReleaseOptions {
+    self.no_cache = v;
+    self
+  }
+
+  pub fn no_notify(mut self, v: bool) -> ReleaseOptions {
+    self.no_notify = v;
+    self
+  }
+
+  pub fn skip_publish(mut self, v: bool) -> ReleaseOptions {
+    self.skip_publish = v;
+    self
+  }
+
+  /// Release only the projects with these IDs, names, or labels; if none are given, release every project.
+  pub fn pick(mut self, ids: &[u32], names: &[String], labels: &[String]) -> ReleaseOptions {
+    self.ids = ids.to_vec();
+    self.names = names.to_vec();
+    self.labels = labels.to_vec();
+    self
+  }
+
+  pub fn with_dependents(mut self, v: bool) -> ReleaseOptions {
+    self.with_dependents = v;
+    self
+  }
+
+  /// Print the release receipt and its hash.
+  pub fn receipt(mut self, v: bool) -> ReleaseOptions {
+    self.receipt = v;
+    self
+  }
+
+  /// Also post the release receipt to this URL.
+  pub fn receipt_url(mut self, v: Option<String>) -> ReleaseOptions {
+    self.receipt_url = v;
+    self
+  }
+
+  /// Stop before changing anything unless the release receipt has this hash.
+  pub fn verify_receipt(mut self, v: Option<String>) -> ReleaseOptions {
+    self.verify_receipt = v;
+    self
+  }
+
+  /// Only write the changelogs of the projects with these IDs: their releases are deferred to a later run.
+  pub fn changelog_only_for(mut self, ids: &[u32]) -> ReleaseOptions {
+    self.changelog_only_ids = ids.to_vec();
+    self
+  }
+
+  /// Plan again, and compare it to this plan, saved by `plan --format json`.
+  pub fn saved_plan(mut self, v: Option<PathBuf>) -> ReleaseOptions {
+    self.saved_plan = v;
+    self
+  }
+
+  pub fn on_drift(mut self, v: DriftTolerance) -> ReleaseOptions {
+    self.on_drift = v;
+    self
+  }
+
+  /// Only create tags, at this commit instead of HEAD.
+  pub fn target(mut self, v: Option<String>) -> ReleaseOptions {
+    self.target = v;
+    self
+  }
+
+  /// Write the release notes to this file, from this template (or else the built-in one).
+  pub fn release_notes(mut self, path: Option<PathBuf>, template: Option<String>) -> ReleaseOptions {
+    self.release_notes = path.map(|p| (p, template.unwrap_or_else(|| DEFAULT_NOTES_TEMPLATE.to_string())));
+    self
+  }
+
+  /// Write a manifest of the release to this file (or to stdout, for `-`), in this format.
+  pub fn manifest(mut self, path: Option<String>, format: ManifestFormat) -> ReleaseOptions {
+    self.manifest = path.map(|p| (p, format));
+    self
+  }
+
+  /// With a dry run, show a diff of each file that would be written.
+  pub fn show_diff(mut self, v: bool) -> ReleaseOptions {
+    self.show_diff = v;
+    self
+  }
+
+  /// Release for real, even the projects that are set to `dry_run_default`.
+  pub fn confirm(mut self, v: bool) -> ReleaseOptions {
+    self.confirm = v;
+    self
+  }
+
+  /// Release a project even at a version that breaks its `constraints`, with a warning.
+  pub fn force(mut self, v: bool) -> ReleaseOptions {
+    self.force = v;
+    self
+  }
+
+  /// Show each project's release before anything is written, and let the user accept, skip, or change it.
+  pub fn interactive(mut self, v: bool) -> ReleaseOptions {
+    self.interactive = v;
+    self
+  }
+
+  fn wants_receipt(&self) -> bool { self.receipt || self.receipt_url.is_some() || self.verify_receipt.is_some() }
+
+  fn picks_all(&self) -> bool { self.ids.is_empty() && self.names.is_empty() && self.labels.is_empty() }
+
+  fn picks(&self, proj: &Project) -> bool {
+    self.ids.iter().any(|i| proj.id().same_project(&ProjectId::from_id(*i)))
+      || self.names.iter().any(|n| proj.name().contains(n))
+      || proj.labels().iter().any(|l| self.labels.iter().any(|ll| ll == l))
+  }
+
+  fn defers(&self, id: &ProjectId) -> bool {
+    self.changelog_only_ids.iter().any(|i| id.same_project(&ProjectId::from_id(*i)))
+  }
+}
+
+/// The projects to release, or `None` if all of them are. Dependents of a picked project that are also bumped by
+/// the plan are picked too if `with_dependents`; otherwise, they're returned separately so they can be warned about.
+fn pick_releases<S: StateRead>(
+  cfg: &Config<S>, plan: &Plan, opts: &ReleaseOptions
+) -> Result<Option<(HashSet<ProjectId>, Vec<ProjectId>)>> {
+  if opts.picks_all() {
+    return Ok(None);
+  }
+
+  let mut picked: HashSet<ProjectId> =
+    cfg.projects().iter().filter(|p| opts.picks(p)).map(|p| p.id().clone()).collect();
+  if picked.is_empty() {
+    bail!("{}", tr!("release.none_picked"));
+  }
+
+  let bumped = |id: &ProjectId| plan.incrs().get(id).map(|(size, _)| size != &Size::Empty).unwrap_or(false);
+  let mut dependents = Vec::new();
+  let mut queue: Vec<ProjectId> = picked.iter().cloned().collect();
+  while let Some(id) = queue.pop() {
+    for (dep, depd) in plan.chain_writes() {
+      if dep == &id && bumped(depd) && !picked.contains(depd) && !dependents.contains(depd) {
+        dependents.push(depd.clone());
+        queue.push(depd.clone());
+      }
+    }
+  }
+
+  if opts.with_dependents {
+    picked.extend(dependents.drain(..));
+  }
+  Ok(Some((picked, dependents)))
+}
+
+/// The names of the planned projects that are set to `dry_run_default`, whose releases are dry runs unless they're
+/// confirmed.
+fn dry_by_default(mono: &Mono, plan: &Plan) -> Vec<String> {
+  let file = mono.config().file();
+  let mut names: Vec<_> = plan
+    .incrs()
+    .iter()
+    .filter(|(_, (size, _))| size != &Size::Empty)
+    .filter_map(|(id, _)| file.get_project(id))
+    .filter(|p| p.dry_run_default(file.dry_run_default()))
+    .map(|p| p.name().to_string())
+    .collect();
+  names.sort();
+  names
+}
+
+/// Stop if the saved plan is older than the config's `plan_max_hours`, or further than `plan_max_commits` behind
+/// the commit at `released`.
+fn verify_fresh(mono: &Mono, saved: &SavedPlan, released: &str) -> Result<()> {
+  let (max_hours, max_commits) = (mono.config().plan_max_hours(), mono.config().plan_max_commits());
+  let behind = |commit: &str| mono.repo().commits_ahead_of(released, commit);
+  if let Some(reason) = saved.staleness(max_hours, max_commits, Utc::now(), behind)? {
+    bail!("{}", tr!("release.stale_plan", reason = reason));
+  }
+  Ok(())
+}
+
+pub async fn release(pref_vcs: Option<VcsRange>, dry: &Engagement, opts: &ReleaseOptions) -> Result<()> {
+  let began = Instant::now();
+  let (all, locktags, pause) = (opts.show_all, opts.lock_tags, opts.pause);
+  let vcs = combine_vcs(pref_vcs, VcsLevel::None, VcsLevel::Smart, VcsLevel::Local, VcsLevel::Smart)?;
+  let saved = opts.saved_plan.as_ref().map(SavedPlan::read).transpose()?;
+  let mut mono = Mono::here(VcsState::new(vcs.max(), false).with_lock(!matches!(dry, Engagement::Dry)))?;
+  mono.set_offline(opts.offline);
+  if let Some(saved) = &saved {
+    verify_fresh(&mono, saved, opts.target.as_deref().unwrap_or("HEAD"))?;
+  }
+  if let Some(target) = &opts.target {
+    mono.tag_at(target)?;
+  }
+
   let output = Output::new();
   let mut output = output.release();
-  let plan = mono.build_plan().await?;
-
-  if let Err((should, is)) = mono.check_branch() {
-    bail!("Branch name \"{}\"\" doesn't match \"{}\".", is, should);
+  // A saved plan was made on another commit (like a PR's head), so it's always compared to a fresh plan.
+  let fresh = opts.no_cache || saved.is_some();
+  let mut plan = if fresh { mono.build_plan().await? } else { mono.cached_plan().await? };
+  output.write_warnings(plan.warnings());
+
+  let mut dry = dry;
+  if matches!(dry, Engagement::Full) && !opts.confirm {
+    let names = dry_by_default(&mono, &plan);
+    if !names.is_empty() {
+      output.write_dry_by_default(names);
+      dry = &Engagement::Dry;
+    }
+  }
+  if matches!(dry, Engagement::Full) && !vcs.max().is_none() {
+    Repo::verify_releasable(".", opts.force_detached || opts.target.is_some())?;
+  }
+  // On a (forced) detached HEAD, or when tagging another commit, there's no branch to commit to: only create tags.
+  let tag_only = matches!(dry, Engagement::Full) && (opts.target.is_some() || mono.repo().is_detached()?);
+
+  let branch = mono
+    .check_branch()
+    .map_err(|(should, is)| bad!("{}", tr!("release.wrong_branch", branch = is, expected = should)))?;
+  plan.set_branch(branch.as_deref());
+
+  if let Some(saved) = &saved {
+    let drift = saved.drift(&mono.planned_projects(&plan)?);
+    output.write_drift(&drift);
+    if !drift.is_empty() && matches!(opts.on_drift, DriftTolerance::Fail) {
+      bail!("{}", tr!("release.drift_failed"));
+    }
   }
 
   if plan.incrs().is_empty() {
     output.write_empty()?;
+    if matches!(dry, Engagement::Full) {
+      output.write_metrics(mono.emit_metrics(began.elapsed(), 0, &plan).await);
+    }
     output.commit();
     return Ok(());
   }
 
+  let picked = pick_releases(mono.config(), &plan, opts)?;
+  if let Some((_, unpicked)) = &picked {
+    let names = unpicked.iter().map(|id| mono.get_project(id).map(|p| p.name().to_string())).collect::<Result<_>>()?;
+    output.write_unpicked(names);
+  }
+  let picked = picked.map(|(picked, _)| picked);
+  for id in &opts.changelog_only_ids {
+    if !mono.config().projects().iter().any(|p| p.id().same_project(&ProjectId::from_id(*id))) {
+      bail!("{}", tr!("release.unknown_deferred", id = id));
+    }
+  }
+
   let mut final_sizes = HashMap::new();
-  for (id, (size, changelog)) in plan.incrs() {
-    let proj = mono.get_project(id)?;
-    let name = proj.name().to_string();
-    let curt_config = mono.config();
-    let prev_config = curt_config.slice_to_prev(mono.repo())?;
-
-    let curt_vers = curt_config
-      .get_value(id)
-      .with_context(|| format!("Unable to find project {} value.", id))?
-      .unwrap_or_else(|| panic!("No such project {}.", id));
-    let prev_vers = prev_config.get_value(id).with_context(|| format!("Unable to find prev {} value.", id))?;
-    let new_vers = if size == &Size::Empty {
-      output.write_no_change(all, false, name.clone(), prev_vers.clone(), curt_vers.clone());
-      curt_vers
-    } else if let Some(prev_vers) = prev_vers {
-      if size.is_failure() {
-        bail!("Couldn't parse conventional commit(s): {}", failed_hashes(&plan));
+  let mut logs = Vec::new();
+  let mut released = Vec::new();
+  let mut pinned = Vec::new();
+  let mut deferred = false;
+  let mut nexts = mono.next_versions(&plan, picked.as_ref(), locktags, opts.force)?;
+  if opts.interactive {
+    let (reviewed, skipped) = review(&mono, nexts, opts.force)?;
+    nexts = reviewed;
+    // A skipped project's changes are still planned next time, like those of a project that isn't picked.
+    deferred = skipped;
+  }
+  for next in nexts {
+    let NextVersion { id, name, size, applied, changelog, version, change, pin, forced } = next;
+    if let Some(reason) = forced {
+      output.write_forced(&name, &version, &reason);
+    }
+    if applied != size && matches!(change, VersionChange::Changed { .. } | VersionChange::Forward { .. }) {
+      output.write_zero_major(&name, size, applied);
+    }
+    let changes = !matches!(change, VersionChange::NoChange { .. } | VersionChange::Released { .. });
+    if changes && opts.defers(&id) {
+      // Write the changelog, but leave the version and tags alone until the project is released by a later run.
+      output.write_deferred(name, version.clone());
+      logs.push((id, changelog, version));
+      deferred = true;
+      continue;
+    }
+    match change {
+      VersionChange::NoChange { prev, curt, locked } => output.write_no_change(all, locked, name, prev, curt),
+      VersionChange::Released { prev, curt } => {
+        output.write_no_change(all, false, name, Some(prev), curt);
+        final_sizes.insert(id, version);
+        continue;
       }
-      let target = size.apply(&prev_vers)?;
-
-      if Size::less_than(&curt_vers, &target)? {
-        proj.verify_restrictions(&target)?;
-        mono.set_by_id(id, &target)?;
-        output.write_changed(name.clone(), prev_vers.clone(), curt_vers.clone(), target.clone());
-      } else {
-        proj.verify_restrictions(&curt_vers)?;
-        if locktags {
-          output.write_no_change(all, true, name.clone(), Some(prev_vers.clone()), curt_vers.clone());
+      VersionChange::Changed { prev, curt, target } => {
+        if tag_only {
+          mono.forward_by_id(&id, &target)?;
         } else {
-          mono.forward_by_id(id, &curt_vers)?;
-          output.write_forward(all, name.clone(), prev_vers.clone(), curt_vers.clone(), target.clone());
+          mono.set_by_id(&id, &target)?;
+        }
+        mono.annotate_tag(&id, &target, &changelog)?;
+        output.write_changed(name, prev.clone(), curt, target.clone());
+        released.push((id.clone(), Some(prev), target));
+        if pin {
+          pinned.push(id.clone());
         }
       }
-      target
-    } else {
-      proj.verify_restrictions(&curt_vers)?;
-      if locktags {
-        output.write_no_change(all, true, name.clone(), prev_vers.clone(), curt_vers.clone());
-      } else {
-        mono.forward_by_id(id, &curt_vers)?;
-        output.write_new(all, name.clone(), curt_vers.clone());
+      VersionChange::Forward { prev, curt, target } => {
+        mono.forward_by_id(&id, &curt)?;
+        mono.annotate_tag(&id, &curt, &changelog)?;
+        output.write_forward(all, name, prev.clone(), curt.clone(), target);
+        released.push((id.clone(), Some(prev), curt));
       }
-      curt_vers
-    };
+      VersionChange::New { curt } => {
+        mono.forward_by_id(&id, &curt)?;
+        mono.annotate_tag(&id, &curt, &changelog)?;
+        output.write_new(all, name, curt.clone());
+        released.push((id.clone(), None, curt));
+      }
+    }
 
-    if let Some(wrote) = mono.write_changelog(id, changelog, &new_vers).await? {
-      output.write_logged(wrote);
+    logs.push((id.clone(), changelog, version.clone()));
+    final_sizes.insert(id, version);
+  }
+
+  if opts.wants_receipt() {
+    let receipt = mono.receipt(&released, &plan)?;
+    if let Some(expected) = &opts.verify_receipt {
+      receipt.verify(expected)?;
+    }
+    output.write_receipt(receipt.canonical()?, receipt.hash()?);
+    if let Some(url) = &opts.receipt_url {
+      receipt.upload(url).await.with_context(|| tr!("release.receipt_upload_failed", url = url))?;
     }
+  }
 
-    final_sizes.insert(id.clone(), new_vers);
+  if tag_only {
+    output.write_tag_only(opts.target.clone());
+  } else {
+    let start = Instant::now();
+    let logs: Vec<_> = logs.iter().map(|(id, log, vers)| (id.clone(), &**log, vers.clone())).collect();
+    let wrote = mono.write_changelogs_for(&logs).await?;
+    let count = wrote.len();
+    for wrote in wrote {
+      output.write_logged(wrote);
+    }
+    output.write_logged_timing(count, start.elapsed());
+
+    let chains: Vec<_> = plan.chain_writes().iter().filter(|(id, _)| final_sizes.contains_key(id)).cloned().collect();
+    mono.write_chains(&chains, &final_sizes)?;
+    mono.clear_pins(&pinned)?;
+    if !matches!(dry, Engagement::Changelog) {
+      if let Some(manifest) = mono.write_manifest(&released)? {
+        output.write_manifest(manifest);
+      }
+    }
   }
 
-  mono.write_chains(plan.chain_writes(), &final_sizes)?;
+  let disallowed = mono.disallowed_writes();
+  if !disallowed.is_empty() && !matches!(dry, Engagement::Dry) {
+    let paths = disallowed.iter().map(|p| p.to_string_lossy()).collect::<Vec<_>>().join(", ");
+    bail!("{}", tr!("release.disallowed_writes", paths = paths));
+  }
 
+  let mut publish_failed = None;
   match dry {
     Engagement::Full => {
-      mono.commit(true, pause)?;
+      mono.pre_release(plan.dep_order(), &released)?;
+      // Leave the prev tag for a partial release, so the other projects' changes are still planned next time.
+      let mirror_failed = mono.commit(picked.is_none() && !deferred, pause)?;
+      output.write_mirrored(mirror_failed);
       if pause {
         output.write_pause();
       } else {
-        output.write_commit();
+        if !tag_only {
+          output.write_commit();
+        }
+        if !opts.skip_publish {
+          let published = mono.publish(plan.dep_order(), &released)?;
+          output.write_published(&published);
+          publish_failed = published.into_failed();
+        }
+        if !opts.no_notify {
+          output.write_notified(mono.notify(&released).await?);
+          output.write_notes_published(mono.publish_notes(&plan, &released, false).await?, false);
+        }
+        output.write_metrics(mono.emit_metrics(began.elapsed(), released.len(), &plan).await);
         output.write_done();
       }
     }
@@ -380,45 +1058,333 @@ pub async fn release(
       output.write_wrote_changelogs();
     }
     Engagement::Dry => {
+      if opts.show_diff {
+        output.write_diffs(mono.pending_diffs()?);
+      }
+      output.write_dry_run_hooks(&mono.dry_run_hooks(plan.dep_order(), &released, !opts.skip_publish)?);
+      if !opts.no_notify {
+        output.write_notes_published(mono.publish_notes(&plan, &released, true).await?, true);
+      }
       output.write_dry();
     }
   }
 
+  if let Some((path, template)) = &opts.release_notes {
+    let notes = mono.release_notes(&plan, &released, read_template(template, None, false).await?).await?;
+    std::fs::write(path, notes).with_context(|| format!("Unable to write release notes {}.", path.display()))?;
+    output.write_notes(path.clone());
+  }
+
+  if let Some((path, format)) = &opts.manifest {
+    let rev = opts.target.as_deref().unwrap_or("HEAD");
+    let manifest = mono.release_manifest(&released, rev, *format)?;
+    if path == "-" {
+      output.write_manifest_json(manifest);
+    } else {
+      std::fs::write(path, manifest).with_context(|| format!("Unable to write release manifest {}.", path))?;
+      output.write_manifest(PathBuf::from(path));
+    }
+  }
+
+  output.commit();
+  match publish_failed {
+    Some((name, e)) => Err(e.context(tr!("release.publish_failed", name = name))),
+    None => Ok(())
+  }
+}
+
+/// Print the version that each project of the pending plan would be released at, without writing anything.
+pub async fn preview(pref_vcs: Option<VcsRange>, offline: bool, ignore_current: bool) -> Result<()> {
+  let mut mono =
+    with_opts(pref_vcs, VcsLevel::None, VcsLevel::Smart, VcsLevel::Local, VcsLevel::Smart, ignore_current)?;
+  mono.set_offline(offline);
+  mono.set_fallback(true);
+  let output = Output::new();
+  let mut output = output.preview();
+
+  let plan = mono.cached_plan().await?;
+  output.write_warnings(plan.warnings());
+  for next in mono.next_versions(&plan, None, false, false)? {
+    output.write_next(&next);
+  }
+
+  output.commit();
+  Ok(())
+}
+
+/// Show each project that would be released, and let the user accept, skip, or change its release: its size, its
+/// version, or the text of its changelog entries. Returns the releases to make, and whether any were skipped.
+fn review<'p>(mono: &Mono, nexts: Vec<NextVersion<'p>>, force: bool) -> Result<(Vec<NextVersion<'p>>, bool)> {
+  let mut reviewer = Reviewer::new()?;
+  let mut reviewed = Vec::new();
+  let mut skipped = false;
+  for mut next in nexts {
+    if matches!(next.change, VersionChange::NoChange { .. } | VersionChange::Released { .. }) {
+      reviewed.push(next);
+      continue;
+    }
+
+    let proj = mono.get_project(&next.id)?;
+    loop {
+      reviewer.show(&next.name, next.prev(), &next.version, next.size, &next.changelog.summary());
+      let changed = match reviewer.decide()? {
+        Decision::Accept => {
+          reviewed.push(next);
+          break;
+        }
+        Decision::Skip => {
+          skipped = true;
+          break;
+        }
+        Decision::Edit => {
+          for (index, text) in next.changelog.pr_texts() {
+            if let Some(text) = reviewer.edit(&text)? {
+              next.changelog.to_mut().set_pr_text(index, text);
+            }
+          }
+          Ok(())
+        }
+        Decision::Size(size) => next.resize(proj, size, force),
+        Decision::Version(version) => next.retarget(proj, version, force)
+      };
+      if let Err(e) = changed {
+        reviewer.reject(&e);
+      }
+    }
+  }
+  Ok((reviewed, skipped))
+}
+
+pub fn rollback(pref_vcs: Option<VcsRange>, dry: bool) -> Result<()> {
+  let mono = build_locked(pref_vcs, VcsLevel::None, VcsLevel::Smart, VcsLevel::Local, VcsLevel::Smart, dry)?;
+  let output = Output::new();
+  let mut output = output.rollback();
+
+  let rollback = mono.plan_rollback()?;
+  output.write_rollback(&rollback);
+  if dry {
+    output.write_dry();
+  } else {
+    mono.rollback(&rollback)?;
+  }
+
+  output.commit();
+  Ok(())
+}
+
+pub async fn adopt(pref_vcs: Option<VcsRange>, opts: &AdoptOptions) -> Result<()> {
+  let mut mono =
+    build_locked(pref_vcs, VcsLevel::Local, VcsLevel::Smart, VcsLevel::Local, VcsLevel::Smart, opts.dry_run)?;
+  mono.set_offline(opts.offline);
+  let output = Output::new();
+  let mut output = output.adopt();
+
+  let mut progress = match mono.adopt_progress()? {
+    Some(progress) => {
+      output.write_resuming();
+      progress
+    }
+    None if mono.has_released() => bail!("{}", tr!("adopt.already", tag = mono.config().prev_tag())),
+    None => AdoptProgress::new()
+  };
+
+  let ids: Vec<_> = mono.config().projects().iter().map(|p| p.id().clone()).collect();
+  let mut rendered_any = false;
+  for id in &ids {
+    let proj = mono.get_project(id)?;
+    let name = proj.name().to_string();
+    let releases = mono.past_releases(id)?;
+    output.write_project(&name, &releases);
+    if let Some(latest) = releases.last() {
+      if mono.changed_since(id, latest)? {
+        output.write_unreleased(&name, latest.tag());
+      }
+    }
+    if !opts.changelogs || opts.dry_run || proj.changelog().is_none() {
+      continue;
+    }
+
+    let done = progress.rendered(id).and_then(|v| releases.iter().position(|r| r.version() == v));
+    for i in done.map(|i| i + 1).unwrap_or(0) .. releases.len() {
+      if rendered_any && !opts.offline && opts.delay > 0 {
+        tokio::time::sleep(Duration::from_millis(opts.delay)).await;
+      }
+      let since = if i == 0 { None } else { Some(&releases[i - 1]) };
+      let log = mono.render_past_changelog(id, since, &releases[i], progress.log(id)).await?;
+      if log.is_some() {
+        output.write_logged(&name, releases[i].version());
+      }
+      progress.record(id, releases[i].version(), log);
+      mono.save_adopt_progress(&progress)?;
+      rendered_any = true;
+    }
+  }
+
+  if opts.dry_run {
+    output.write_dry();
+  } else {
+    eprintln_mirror_failures(mono.finish_adopt(progress)?);
+    output.write_done(mono.config().prev_tag());
+  }
+
+  output.commit();
+  Ok(())
+}
+
+/// Render the changelog section of a past version of a project again, with the current template.
+pub async fn changelog_regenerate(pref_vcs: Option<VcsRange>, project: &str, version: &str) -> Result<()> {
+  let mut mono = build_locked(pref_vcs, VcsLevel::Local, VcsLevel::Smart, VcsLevel::Local, VcsLevel::Smart, false)?;
+  let id = match project.parse::<u32>() {
+    Ok(id) => ProjectId::from_id(id),
+    Err(_) => mono.config().find_exact(project)?.clone()
+  };
+  let name = mono.get_project(&id)?.name().to_string();
+
+  let path = mono.regenerate_changelog(&id, version).await?;
+  eprintln_mirror_failures(mono.commit(false, false)?);
+
+  let output = Output::new();
+  let mut output = output.changelog();
+  output.write_regenerated(&name, version, &path);
+  output.commit();
+  Ok(())
+}
+
+/// Check that a release `tag` is what versio would have released at its commit, so that an audit can tell that no
+/// tag was made by hand. Each discrepancy is printed, and fails the command.
+pub async fn verify_release(pref_vcs: Option<VcsRange>, tag: &str, offline: bool) -> Result<()> {
+  let mut mono = build(pref_vcs, VcsLevel::Local, VcsLevel::Smart, VcsLevel::Local, VcsLevel::Smart)?;
+  mono.set_offline(offline);
+  let output = Output::new();
+  let mut output = output.verify();
+
+  let (name, discrepancies) = mono.verify_release(tag).await?;
+  if !discrepancies.is_empty() {
+    output.write_discrepancies(&discrepancies);
+    bail!("{}", tr!("verify.failed", count = discrepancies.len(), tag = tag));
+  }
+  output.write_verified(&name, tag);
   output.commit();
   Ok(())
 }
 
+/// Report which projects cover each of `paths`; or, with no paths, each file changed in the range from `since` to
+/// `until` if either is given, or else each path (or each file of the unified diff) read from stdin. A file that no
+/// project covers fails the command, as does (if `strict`) a file that more than one project covers.
+pub async fn owners(
+  pref_vcs: Option<VcsRange>, paths: &[String], since: Option<&str>, until: Option<&str>, json: bool, strict: bool
+) -> Result<()> {
+  let mut mono = build(pref_vcs, VcsLevel::None, VcsLevel::Smart, VcsLevel::Local, VcsLevel::Smart)?;
+  let output = Output::new();
+  let mut output = output.owners(json);
+
+  let paths = if !paths.is_empty() {
+    paths.to_vec()
+  } else if since.is_some() || until.is_some() {
+    mono.set_range(since, until)?;
+    mono.changed_files().await?
+  } else {
+    let mut input = String::new();
+    std::io::stdin().read_to_string(&mut input)?;
+    if is_diff(&input) {
+      diff_paths(&input)
+    } else {
+      input.lines().map(|l| l.trim()).filter(|l| !l.is_empty()).map(|l| l.to_string()).collect()
+    }
+  };
+
+  let owners = mono.owners(&paths)?;
+  let uncovered = owners.iter().filter(|o| o.owners().is_empty()).count();
+  let overlaps = owners.iter().filter(|o| o.owners().len() > 1).count();
+  output.write_owners(owners);
+  output.commit()?;
+  if uncovered > 0 || (strict && overlaps > 0) {
+    bail!("{}", tr!("owners.failed", uncovered = uncovered, overlaps = overlaps));
+  }
+  Ok(())
+}
+
+/// Check the tag history of every project, and report what's wrong with it. Any problem fails the command, after
+/// the report is printed.
+pub fn audit(pref_vcs: Option<VcsRange>, json: bool) -> Result<()> {
+  let mono = build(pref_vcs, VcsLevel::Local, VcsLevel::Smart, VcsLevel::Local, VcsLevel::Smart)?;
+  let output = Output::new();
+  let mut output = output.audit(json);
+
+  let reports = mono.audit()?;
+  let count: usize = reports.iter().map(|r| r.problems().len()).sum();
+  output.write_reports(reports);
+  output.commit()?;
+  if count > 0 {
+    bail!("{}", tr!("audit.failed", count = count));
+  }
+  Ok(())
+}
+
+#[derive(Default)]
+pub struct AdoptOptions {
+  changelogs: bool,
+  offline: bool,
+  delay: u64,
+  dry_run: bool
+}
+
+impl AdoptOptions {
+  pub fn new() -> AdoptOptions { AdoptOptions::default() }
+
+  pub fn changelogs(mut self, v: bool) -> AdoptOptions {
+    self.changelogs = v;
+    self
+  }
+
+  pub fn offline(mut self, v: bool) -> AdoptOptions {
+    self.offline = v;
+    self
+  }
+
+  /// Wait this many milliseconds before planning each past release, to stay under the forge's rate limits.
+  pub fn delay(mut self, v: u64) -> AdoptOptions {
+    self.delay = v;
+    self
+  }
+
+  pub fn dry_run(mut self, v: bool) -> AdoptOptions {
+    self.dry_run = v;
+    self
+  }
+}
+
 pub fn resume(user_pref_vcs: Option<VcsRange>) -> Result<()> {
   let vcs = combine_vcs(user_pref_vcs, VcsLevel::None, VcsLevel::Smart, VcsLevel::Local, VcsLevel::Smart)?;
   let output = Output::new();
   let mut output = output.resume();
 
-  let mut commit: CommitState = {
-    let file = File::open(".versio-paused")?;
-    let reader = BufReader::new(file);
-    let commit: CommitState = serde_json::from_reader(reader)?;
-
-    // We must remove the pausefile before resuming, or else it will be committed.
-    remove_file(".versio-paused")?;
-    commit
+  let paused = Repo::open(".", VcsState::new(vcs.max(), false), CommitConfig::default())?.find_paused()?;
+  let mut commit: CommitState = match paused {
+    Some(paused) => serde_json::from_str(&paused)?,
+    None => bail!("{}", tr!("release.not_paused"))
   };
   let repo = Repo::open(".", VcsState::new(vcs.max(), false), commit.commit_config().clone())?;
-  commit.resume(&repo)?;
+  let mirror_failed = commit.resume(&repo)?;
+  // Only forget the paused release once it's resumed, so that a failed resume can be tried again.
+  repo.clear_paused()?;
 
+  output.write_mirrored(mirror_failed);
   output.write_done()?;
   output.commit()?;
 
   Ok(())
 }
 
-pub fn abort() -> Result<()> {
-  remove_file(".versio-paused")?;
-  println!("Release aborted. You may need to rollback your VCS \n(i.e `git checkout -- .`)");
+pub fn abort(user_pref_vcs: Option<VcsRange>) -> Result<()> {
+  let vcs = combine_vcs(user_pref_vcs, VcsLevel::None, VcsLevel::Smart, VcsLevel::Local, VcsLevel::Smart)?;
+  Repo::open(".", VcsState::new(vcs.max(), false), CommitConfig::default())?.clear_paused()?;
+  println!("{}", tr!("release.aborted"));
   Ok(())
 }
 
 pub fn sanity_check() -> Result<()> {
-  if Path::new(".versio-paused").exists() {
+  if Repo::is_paused(".") {
     bail!("versio is paused: use `release --resume` or `--abort`.")
   } else {
     Ok(())
@@ -440,6 +1406,15 @@ fn build(
   with_opts(user_pref_vcs, my_pref_lo, my_pref_hi, my_reqd_lo, my_reqd_hi, false)
 }
 
+/// Like `build`, but lock the repo (unless it's a `dry` run), since the command changes it.
+fn build_locked(
+  user_pref_vcs: Option<VcsRange>, my_pref_lo: VcsLevel, my_pref_hi: VcsLevel, my_reqd_lo: VcsLevel,
+  my_reqd_hi: VcsLevel, dry: bool
+) -> Result<Mono> {
+  let vcs = combine_vcs(user_pref_vcs, my_pref_lo, my_pref_hi, my_reqd_lo, my_reqd_hi)?;
+  Mono::here(VcsState::new(vcs.max(), false).with_lock(!dry))
+}
+
 fn combine_vcs(
   user_pref_vcs: Option<VcsRange>, my_pref_lo: VcsLevel, my_pref_hi: VcsLevel, my_reqd_lo: VcsLevel,
   my_reqd_hi: VcsLevel
@@ -449,20 +1424,6 @@ fn combine_vcs(
   VcsRange::detect_and_combine(&pref_vcs, &reqd_vcs)
 }
 
-pub fn failed_hashes(plan: &Plan) -> String {
-  let mut commits =
-    plan.info().failed_commits().iter().rev().take(5).map(|c| c.id()[.. 7].to_string()).collect::<Vec<_>>().join(",");
-  if plan.info().failed_commits().len() > 5 {
-    commits.push_str(",...");
-  }
-  if commits.is_empty() {
-    // This shouldn't happen.
-    commits.push_str("<unfound>");
-  }
-
-  commits
-}
-
 pub enum NameMatch {
   Partial(String),
   Exact(String),
@@ -480,3 +1441,64 @@ impl NameMatch {
     }
   }
 }
+
+#[cfg(test)]
+mod test {
+  use super::{pick_releases, ReleaseOptions};
+  use crate::config::ProjectId;
+  use crate::mono::Mono;
+  use crate::testing::{block_on, TestRepo};
+  use crate::vcs::{VcsLevel, VcsState};
+  use std::collections::HashSet;
+
+  /// A repo where `lib` has a new feature, which `app` depends on; `alpha` has no changes.
+  fn dependent_repo() -> TestRepo {
+    let repo = TestRepo::new(
+      r#"
+projects:
+  - { name: alpha, id: 1, labels: [ ui ], includes: [ "alpha/**" ], version: { file: alpha/version.txt } }
+  - { name: lib, id: 2, includes: [ "lib/**" ], version: { file: lib/version.txt } }
+  - name: app
+    id: 3
+    includes: [ "app/**" ]
+    version: { file: app/version.txt }
+    depends: { 2: { size: patch } }
+sizes: { use_angular: true }
+"#
+    );
+    for proj in ["alpha", "lib", "app"] {
+      repo.write(&format!("{}/version.txt", proj), "1.0.0");
+    }
+    repo.commit("chore: start");
+    repo.tag("versio-prev");
+    repo.write("lib/code.txt", "new");
+    repo.commit("feat: add code");
+    repo
+  }
+
+  fn ids(ids: &[u32]) -> HashSet<ProjectId> { ids.iter().map(|id| ProjectId::from_id(*id)).collect() }
+
+  #[test]
+  fn test_pick_releases() {
+    let repo = dependent_repo();
+    let mut mono = Mono::open(repo.path(), VcsState::new(VcsLevel::Local, false)).unwrap();
+    mono.set_offline(true);
+    let plan = block_on(mono.build_plan()).unwrap();
+    let pick = |opts: ReleaseOptions| pick_releases(mono.config(), &plan, &opts);
+
+    assert!(pick(ReleaseOptions::new()).unwrap().is_none());
+
+    let (picked, unpicked) = pick(ReleaseOptions::new().pick(&[2], &[], &[])).unwrap().unwrap();
+    assert_eq!(ids(&[2]), picked);
+    assert_eq!(vec![ProjectId::from_id(3)], unpicked);
+
+    let (picked, unpicked) = pick(ReleaseOptions::new().pick(&[2], &[], &[]).with_dependents(true)).unwrap().unwrap();
+    assert_eq!(ids(&[2, 3]), picked);
+    assert!(unpicked.is_empty());
+
+    let (picked, _) = pick(ReleaseOptions::new().pick(&[], &["pp".into()], &["ui".into()])).unwrap().unwrap();
+    assert_eq!(ids(&[1, 3]), picked);
+
+    assert!(pick(ReleaseOptions::new().pick(&[9], &[], &[])).is_err());
+  }
+}
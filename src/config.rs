@@ -2,16 +2,23 @@
 
 use crate::analyze::AnnotatedMark;
 use crate::bail;
+use crate::deprecate::{upgrade, Deprecation};
 use crate::either::IterEither2 as E2;
 use crate::errors::{Context as _, Result};
-use crate::git::{FromTagBuf, Repo, Slice};
+use crate::git::{FromTagBuf, Repo, BREAKING_KIND};
+use crate::interpolate::interpolate;
+use crate::links::LinkConfig;
 use crate::mark::{FilePicker, LinePicker, Picker, ScanningPicker};
 use crate::mono::Changelog;
-use crate::output::ProjLine;
+use crate::notify::{NotesPublisher, Notification};
+use crate::output::{println_hook_output, ProjLine};
 use crate::scan::parts::{deserialize_parts, Part};
-use crate::state::{CurrentFiles, CurrentState, FilesRead, OldTags, PickPath, PrevFiles, PrevState, StateRead,
-                   StateWrite};
-use crate::template::{construct_changelog_html, extract_old_content, read_template};
+use crate::schema::{explain, validate, FRAGMENT};
+use crate::state::{run_version_command, shell, CurrentFiles, CurrentState, FilesRead, OldTags, PickPath, PrevState,
+                   StateRead, StateWrite};
+use crate::template::{construct_changelog_html, extract_content, extract_old_content, read_template, replace_section,
+                      splice_keep_a_changelog, ChangelogBlocks, RenderedLog};
+use chrono::{DateTime, Utc};
 use glob::{glob_with, MatchOptions, Pattern};
 use liquid::ParserBuilder;
 use path_slash::PathBufExt as _;
@@ -23,14 +30,32 @@ use std::borrow::Cow;
 use std::cmp::{Ord, Ordering};
 use std::collections::{HashMap, HashSet};
 use std::fmt;
+use std::io::{BufRead as _, BufReader, Read};
 use std::iter::once;
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
+use std::process::{Child, ExitStatus, Stdio};
 use std::str::FromStr;
-use tracing::trace;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use tracing::{instrument, trace};
 
 pub const CONFIG_FILENAME: &str = ".versio.yaml";
 
-#[derive(Hash, Debug, Eq, PartialEq, Clone)]
+/// The file of versions pinned by `versio pin`, which the next release uses instead of the planned sizes.
+pub const PINS_FILENAME: &str = ".versio-pins.yaml";
+/// A PR with this label is left out of the plan, as if it had never been merged.
+pub const SKIP_LABEL: &str = "versio:skip";
+/// The message of an annotated version tag, if the project has no `tag_message`.
+const DEFAULT_TAG_MESSAGE: &str = "{{ name }} {{ version }}\n\n{{ summary }}";
+
+/// The environment variables that hold the number of a CI run, for `build_metadata`: of GitHub Actions, GitLab,
+/// Jenkins, CircleCI, and Azure Pipelines.
+const CI_RUN_VARS: &[&str] =
+  &["GITHUB_RUN_NUMBER", "CI_PIPELINE_IID", "BUILD_NUMBER", "CIRCLE_BUILD_NUM", "BUILD_BUILDNUMBER"];
+/// How often to check whether a hook with a `timeout` has finished.
+const HOOK_POLL: Duration = Duration::from_millis(50);
+
+#[derive(Hash, Debug, Eq, PartialEq, Ord, PartialOrd, Clone)]
 pub struct ProjectId {
   id: u32,
   majors: Vec<u32>
@@ -40,6 +65,9 @@ impl ProjectId {
   pub fn new(id: u32, majors: Vec<u32>) -> ProjectId { ProjectId { id, majors } }
   pub fn from_id(id: u32) -> ProjectId { ProjectId { id, majors: Vec::new() } }
 
+  /// Whether both IDs are of the same configured project, which may have been expanded into several majors.
+  pub fn same_project(&self, other: &ProjectId) -> bool { self.id == other.id }
+
   fn expand(&self, sub: &SubExtent) -> ProjectId {
     assert!(self.majors.is_empty(), "ProjectId {} expanding.", self);
     ProjectId { id: self.id, majors: sub.majors().to_vec() }
@@ -125,16 +153,49 @@ pub struct Config<S: StateRead> {
 impl Config<CurrentState> {
   pub fn prev_tag(&self) -> &str { self.file.prev_tag() }
 
+  #[instrument(skip_all)]
   pub fn slice_to_prev<'r>(&self, repo: &'r Repo) -> Result<Config<PrevState<'r>>> {
     let spec = FromTagBuf::new(self.prev_tag().to_string(), true);
     let old_tags = self.state.old_tags().slice_to_prev()?;
     let prev_state = PrevState::new(repo.slice(spec), old_tags);
-    Config::from_state(prev_state)
+    let mut prev = Config::from_state(prev_state)?;
+    prev.file.adopt_aliases(&self.file);
+    Ok(prev)
+  }
+
+  /// The config and files as of the `base` commit. The tags aren't sliced, since they're only used by projects
+  /// that are versioned by tags, whose versions aren't in the files.
+  pub fn slice_to_commit<'r>(&self, repo: &'r Repo, base: &str) -> Result<Config<PrevState<'r>>> {
+    self.slice_to_ref(repo, base, self.old_tags().current().clone())
+  }
+
+  /// The config and files as of `rev`, which may be any commit, tag, or branch; where the projects that are
+  /// versioned by tags have the `tag_versions`.
+  pub fn slice_to_ref<'r>(
+    &self, repo: &'r Repo, rev: &str, tag_versions: HashMap<ProjectId, String>
+  ) -> Result<Config<PrevState<'r>>> {
+    let spec = FromTagBuf::new(rev.to_string(), false);
+    let old_tags = OldTags::new(tag_versions, HashMap::new());
+    let mut then = Config::from_state(PrevState::new(repo.slice(spec), old_tags))?;
+    then.file.adopt_aliases(&self.file);
+    Ok(then)
   }
 
   pub fn old_tags(&self) -> &OldTags { self.state.old_tags() }
 
-  pub fn hooks(&self) -> HashMap<ProjectId, (Option<&String>, &HookSet)> { self.file.hooks() }
+  /// Check the config file and its `include` files against the schema, returning every violation found.
+  pub fn schema_violations(&self) -> Result<Vec<String>> {
+    if !self.state.has_file(CONFIG_FILENAME.as_ref())? {
+      return Ok(Vec::new());
+    }
+    let data = self.state.read_file(CONFIG_FILENAME.as_ref())?;
+    let mut found: Vec<_> = validate(&data, None)?.iter().map(|v| format!("{}:{}", CONFIG_FILENAME, v)).collect();
+    for path in self.file.included() {
+      let data = self.state.read_file(path.as_ref())?;
+      found.extend(validate(&data, Some(FRAGMENT))?.iter().map(|v| format!("{}:{}", path, v)));
+    }
+    Ok(found)
+  }
 }
 
 impl<S: StateRead> Config<S> {
@@ -149,7 +210,6 @@ impl<S: StateRead> Config<S> {
   pub fn state_read(&self) -> &S { &self.state }
   pub fn projects(&self) -> &[Project] { self.file.projects() }
   pub fn get_project(&self, id: &ProjectId) -> Option<&Project> { self.file.get_project(id) }
-  pub fn branch(&self) -> &Option<String> { self.file.branch() }
 
   pub fn find_unique(&self, name: &str) -> Result<&ProjectId> {
     let mut iter = self.file.projects.iter().filter(|p| p.name.contains(name)).map(|p| p.id());
@@ -183,30 +243,155 @@ impl<S: StateRead> Config<S> {
   }
 }
 
-pub struct FsConfig<F: FilesRead> {
-  files: F,
-  file: ConfigFile
+/// A file of more projects, from the `include` list of the config.
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+enum Include {
+  File(String),
+  Spec {
+    file: String,
+    #[serde(default)]
+    id_base: u32
+  }
 }
 
-impl<'r> FsConfig<PrevFiles<'r>> {
-  pub fn slice_to(&self, spec: FromTagBuf) -> Result<FsConfig<PrevFiles<'r>>> {
-    FsConfig::from_read(self.files.slice_to(spec)?)
+impl Include {
+  fn id_base(&self) -> u32 {
+    match self {
+      Include::File(_) => 0,
+      Include::Spec { id_base, .. } => *id_base
+    }
+  }
+
+  /// The included files, relative to the repo root. Each `*` directory in the path matches every directory there
+  /// that has the file; otherwise, the file must exist.
+  fn paths<R: FilesRead>(&self, read: &R) -> Result<Vec<String>> {
+    let path = match self {
+      Include::File(file) | Include::Spec { file, .. } => file.trim_start_matches("./")
+    };
+    let (dirs, name) = path.rsplit_once('/').unwrap_or(("", path));
+
+    let mut paths = Vec::new();
+    for file in matching_dirs(read, dirs)?.iter().map(|base| join_path(base, name)) {
+      if read.has_file(file.as_ref())? {
+        paths.push(file);
+      } else if !dirs.split('/').any(is_glob) {
+        bail!("Included config {} doesn't exist.", file);
+      }
+    }
+    Ok(paths)
   }
+}
 
-  pub fn from_slice(slice: Slice<'r>) -> Result<FsConfig<PrevFiles<'r>>> {
-    FsConfig::from_read(PrevFiles::from_slice(slice)?)
+/// The directories that match a slash-separated `pattern`, relative to the repo root. Each part of the pattern is
+/// either a directory name, or a glob (like `*` or `crate-*`) that matches the names of non-hidden directories.
+fn matching_dirs<R: FilesRead>(read: &R, pattern: &str) -> Result<Vec<String>> {
+  let opts = MatchOptions { require_literal_leading_dot: true, ..Default::default() };
+  let mut found = vec![String::new()];
+  for part in pattern.split('/').filter(|d| !d.is_empty() && *d != ".") {
+    let mut next = Vec::new();
+    for base in found {
+      if is_glob(part) {
+        let glob = Pattern::new(part)?;
+        let root = (!base.is_empty()).then(|| base.clone());
+        let mut subs = read.subdirs(root.as_ref(), "^[^.]")?;
+        subs.sort();
+        subs.dedup();
+        next.extend(subs.into_iter().filter(|sub| glob.matches_with(sub, opts)).map(|sub| join_path(&base, &sub)));
+      } else {
+        next.push(join_path(&base, part));
+      }
+    }
+    found = next;
   }
+  Ok(found)
 }
 
-impl<F: FilesRead> FsConfig<F> {
-  pub fn new(files: F, file: ConfigFile) -> FsConfig<F> { FsConfig { files, file } }
+fn is_glob(part: &str) -> bool { part.contains(|c| matches!(c, '*' | '?' | '[')) }
+
+/// The directories of the packages in the repo's Cargo and npm workspaces, by package name. A package at the top
+/// of the repo has the directory `"."`.
+fn workspace_members<R: FilesRead>(read: &R) -> Result<HashMap<String, String>> {
+  let mut members = HashMap::new();
+
+  if read.has_file("Cargo.toml".as_ref())? {
+    let manifest: toml::Value = toml::from_str(&read.read_file("Cargo.toml".as_ref())?)?;
+    let workspace = manifest.get("workspace");
+    let globs = toml_strings(workspace.and_then(|w| w.get("members")));
+    let excludes = toml_strings(workspace.and_then(|w| w.get("exclude")));
+    let name_of = |data: &str| -> Result<Option<String>> {
+      let package: toml::Value = toml::from_str(data)?;
+      Ok(package.get("package").and_then(|p| p.get("name")).and_then(|n| n.as_str()).map(|n| n.to_string()))
+    };
+    add_members(read, &globs, &excludes, "Cargo.toml", name_of, &mut members)?;
+  }
 
-  pub fn from_read(files: F) -> Result<FsConfig<F>> {
-    let file = ConfigFile::from_read(&files)?;
-    Ok(FsConfig::new(files, file))
+  if read.has_file("package.json".as_ref())? {
+    let manifest: serde_json::Value = serde_json::from_str(&read.read_file("package.json".as_ref())?)?;
+    let workspaces = match &manifest["workspaces"] {
+      serde_json::Value::Object(obj) => obj.get("packages").cloned().unwrap_or_default(),
+      other => other.clone()
+    };
+    let patterns: Vec<_> = workspaces.as_array().into_iter().flatten().filter_map(|w| w.as_str()).collect();
+    let globs: Vec<_> = patterns.iter().filter(|p| !p.starts_with('!')).map(|p| p.to_string()).collect();
+    let excludes: Vec<_> = patterns.iter().filter_map(|p| p.strip_prefix('!')).map(|p| p.to_string()).collect();
+    let name_of = |data: &str| -> Result<Option<String>> {
+      let package: serde_json::Value = serde_json::from_str(data)?;
+      Ok(package["name"].as_str().map(|n| n.to_string()))
+    };
+    add_members(read, &globs, &excludes, "package.json", name_of, &mut members)?;
   }
 
-  pub fn file(&self) -> &ConfigFile { &self.file }
+  Ok(members)
+}
+
+fn toml_strings(value: Option<&toml::Value>) -> Vec<String> {
+  let list = value.and_then(|v| v.as_array()).map(|a| a.as_slice()).unwrap_or_default();
+  list.iter().filter_map(|v| v.as_str()).map(|v| v.to_string()).collect()
+}
+
+/// Add the packages of a workspace: those with a `manifest` file in the directories of `globs` (but not of
+/// `excludes`), and the workspace's own package.
+fn add_members<R: FilesRead, F: Fn(&str) -> Result<Option<String>>>(
+  read: &R, globs: &[String], excludes: &[String], manifest: &str, name_of: F, members: &mut HashMap<String, String>
+) -> Result<()> {
+  let mut excluded = HashSet::new();
+  for exclude in excludes {
+    excluded.extend(matching_dirs(read, exclude.trim_start_matches("./"))?);
+  }
+
+  let mut dirs = vec![String::new()];
+  for glob in globs {
+    dirs.extend(matching_dirs(read, glob.trim_start_matches("./"))?);
+  }
+
+  for dir in dirs.into_iter().filter(|d| !excluded.contains(d)) {
+    let file = join_path(&dir, manifest);
+    if !read.has_file(file.as_ref())? {
+      continue;
+    }
+    let data = read.read_file(file.as_ref())?;
+    if let Some(name) = name_of(&data).with_context(|| format!("Unable to read package {}.", file))? {
+      members.entry(name).or_insert_with(|| if dir.is_empty() { ".".to_string() } else { dir });
+    }
+  }
+  Ok(())
+}
+
+fn join_path(base: &str, name: &str) -> String {
+  if base.is_empty() {
+    name.to_string()
+  } else {
+    format!("{}/{}", base, name)
+  }
+}
+
+/// The contents of an included file: only projects can be defined there.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Fragment {
+  #[serde(default)]
+  projects: Vec<Project>
 }
 
 #[derive(Deserialize, Debug)]
@@ -218,16 +403,42 @@ pub struct ConfigFile {
   #[serde(default)]
   commit: CommitConfig,
   #[serde(deserialize_with = "deser_sizes", default)]
-  sizes: HashMap<String, Size>
+  sizes: Sizes,
+  #[serde(default)]
+  notifications: Vec<Notification>,
+  #[serde(default)]
+  notes_publishers: Vec<NotesPublisher>,
+  #[serde(default)]
+  links: Vec<LinkConfig>,
+  #[serde(default)]
+  include: Vec<Include>,
+  #[serde(skip)]
+  included: Vec<String>,
+  #[serde(skip)]
+  env: Vec<String>,
+  #[serde(skip)]
+  deprecations: Vec<Deprecation>
 }
 
 impl Default for ConfigFile {
   fn default() -> ConfigFile {
-    let mut sizes = HashMap::new();
-    insert_angular(&mut sizes);
+    let mut sizes = Sizes::default();
+    insert_angular(&mut sizes.kinds);
     sizes.insert("*".into(), Size::Fail);
 
-    ConfigFile { options: Default::default(), projects: Default::default(), commit: Default::default(), sizes }
+    ConfigFile {
+      options: Default::default(),
+      projects: Default::default(),
+      commit: Default::default(),
+      sizes,
+      notifications: Vec::new(),
+      notes_publishers: Vec::new(),
+      links: Vec::new(),
+      include: Vec::new(),
+      included: Vec::new(),
+      env: Vec::new(),
+      deprecations: Vec::new()
+    }
   }
 }
 
@@ -236,7 +447,7 @@ impl ConfigFile {
     if !read.has_file(CONFIG_FILENAME.as_ref())? {
       return Ok(Default::default());
     }
-    ConfigFile::read(&read.read_file(CONFIG_FILENAME.as_ref())?)?.expand(read)
+    ConfigFile::read(&read.read_file(CONFIG_FILENAME.as_ref())?)?.include_from(read)?.expand(read)
   }
 
   pub fn from_dir<P: AsRef<Path>>(p: P) -> Result<ConfigFile> {
@@ -244,30 +455,147 @@ impl ConfigFile {
     ConfigFile::from_read(&files)
   }
 
-  fn read(data: &str) -> Result<ConfigFile> {
-    let file: ConfigFile = serde_yaml::from_str(data)?;
+  fn read(data: &str) -> Result<ConfigFile> { ConfigFile::read_unexplained(data).map_err(|e| explain(data, None, e)) }
+
+  fn read_unexplained(data: &str) -> Result<ConfigFile> {
+    let mut value: serde_yaml::Value = serde_yaml::from_str(data)?;
+    let deprecations = upgrade(&mut value)?;
+    let env = interpolate(&mut value)?;
+    let mut file: ConfigFile = if deprecations.is_empty() && !data.contains("${") {
+      // Parse the text directly when possible, since its errors have line numbers.
+      serde_yaml::from_str(data)?
+    } else {
+      ConfigFile { deprecations, env, ..serde_yaml::from_value(value)? }
+    };
+    file.projects.iter_mut().for_each(Project::normalize_paths);
+    file.projects.iter_mut().for_each(Project::default_go_tags);
     file.validate()?;
     Ok(file)
   }
 
+  /// Add the projects of the `include` files.
+  fn include_from<R: FilesRead>(mut self, read: &R) -> Result<ConfigFile> {
+    for include in std::mem::take(&mut self.include) {
+      for path in include.paths(read)? {
+        let data = read.read_file(path.as_ref()).with_context(|| format!("Unable to read included {}.", path))?;
+        let mut value: serde_yaml::Value = serde_yaml::from_str(&data)?;
+        self.env.extend(interpolate(&mut value)?);
+        let fragment: Fragment = serde_yaml::from_value(value)
+          .map_err(|e| explain(&data, Some(FRAGMENT), e.into()))
+          .with_context(|| format!("Unable to parse included {}.", path))?;
+        let dir = path.rsplit_once('/').map(|(dir, _)| dir).unwrap_or("");
+        let local: HashSet<u32> = fragment.projects.iter().map(|p| p.id.id).collect();
+        for proj in fragment.projects {
+          let mut proj = proj.rebase(dir, include.id_base(), &local);
+          proj.normalize_paths();
+          proj.default_go_tags();
+          self.projects.push(proj);
+        }
+        self.validate().with_context(|| format!("Conflict including {}.", path))?;
+        self.included.push(path);
+      }
+    }
+    Ok(self)
+  }
+
+  /// The `include` files that were read, in order.
+  pub fn included(&self) -> &[String] { &self.included }
+
+  /// The environment variables used in the config values, as "NAME=value".
+  pub fn env(&self) -> &[String] { &self.env }
+
   fn expand<R: FilesRead>(self, read: &R) -> Result<ConfigFile> {
-    let iters: Vec<_> = self.projects.into_iter().map(move |p| p.expand(read)).collect::<Result<_>>()?;
+    let members =
+      if self.projects.iter().any(|p| p.workspace.is_some()) { workspace_members(read)? } else { HashMap::new() };
+    let iters: Vec<_> = self
+      .projects
+      .into_iter()
+      .map(move |p| p.resolve_workspace(&members)?.expand(read))
+      .collect::<Result<_>>()?;
     let projects = iters.into_iter().flatten().collect();
 
     Ok(ConfigFile { projects, ..self })
   }
 
+  /// Bind the config to the `branch` that's checked out: on a `maintenance` branch, the projects only consider and
+  /// make tags of its majors (unless they already have their own), and its `prev_tag` is used instead.
+  pub fn for_branch(mut self, branch: Option<&str>) -> ConfigFile {
+    let maintenance = match branch.and_then(|b| self.options.maintenance_of(b)) {
+      Some(maintenance) => maintenance.clone(),
+      None => return self
+    };
+    if let Some(prev_tag) = maintenance.prev_tag {
+      self.options.prev_tag = prev_tag;
+    }
+    for proj in self.projects.iter_mut().filter(|p| p.version.tag_majors().is_none()) {
+      proj.branch_majors = Some(maintenance.majors.clone());
+    }
+    self
+  }
+
   pub fn prev_tag(&self) -> &str { self.options.prev_tag() }
   pub fn projects(&self) -> &[Project] { &self.projects }
   pub fn get_project(&self, id: &ProjectId) -> Option<&Project> { self.projects.iter().find(|p| p.id() == id) }
-  pub fn sizes(&self) -> &HashMap<String, Size> { &self.sizes }
-  pub fn branch(&self) -> &Option<String> { self.options.branch() }
 
-  pub fn hooks(&self) -> HashMap<ProjectId, (Option<&String>, &HookSet)> {
-    self.projects.iter().map(|p| (p.id().clone(), (p.root(), p.hooks()))).collect()
+  /// Give the projects of this earlier config the IDs that they have in the `current` config, if they've since been
+  /// renumbered: see the projects' `aliases`.
+  pub fn adopt_aliases(&mut self, current: &ConfigFile) {
+    for i in 0 .. self.projects.len() {
+      let id = &self.projects[i].id;
+      if current.get_project(id).is_some() {
+        continue;
+      }
+      let now = match current.projects.iter().find(|p| p.is_aliased(id)) {
+        Some(now) => now.id.clone(),
+        None => continue
+      };
+      if self.get_project(&now).is_none() {
+        self.projects[i].id = now;
+      }
+    }
+  }
+  pub fn sizes(&self) -> &Sizes { &self.sizes }
+  pub fn branches(&self) -> &[String] { self.options.branches() }
+  pub fn allows_branch(&self, name: &str) -> bool { self.options.allows_branch(name) }
+  pub fn tag_remote(&self) -> &Option<String> { self.options.tag_remote() }
+  pub fn mirrors(&self) -> &[String] { self.options.mirrors() }
+  pub fn parallel_push(&self) -> bool { self.options.parallel_push() }
+  pub fn allows_write(&self, path: &Path) -> bool { self.options.allows_write(path) }
+  pub fn max_size(&self) -> Option<Size> { self.options.max_size() }
+  pub fn dry_run_default(&self) -> bool { self.options.dry_run_default() }
+  pub fn stale_months(&self) -> u32 { self.options.stale_months() }
+  pub fn plan_max_hours(&self) -> Option<u32> { self.options.plan_max_hours() }
+  pub fn plan_max_commits(&self) -> Option<u32> { self.options.plan_max_commits() }
+  pub fn releases_dir(&self) -> Option<&str> { self.options.releases_dir() }
+  pub fn size_trailer(&self) -> &str { self.options.size_trailer() }
+  pub fn skip_trailer(&self) -> &str { self.options.skip_trailer() }
+
+  /// Whether the PR should be left out of the plan: it's listed in `skip_prs`, or has the `versio:skip` label.
+  pub fn skips_pr(&self, number: u32, labels: &[String]) -> bool {
+    self.options.skip_prs.contains(&number) || labels.iter().any(|l| l == SKIP_LABEL)
   }
 
   pub fn commit_config(&self) -> &CommitConfig { &self.commit }
+  pub fn notifications(&self) -> &[Notification] { &self.notifications }
+  pub fn notes_publishers(&self) -> &[NotesPublisher] { &self.notes_publishers }
+
+  /// The issue trackers that commit messages and PR descriptions can refer to by key.
+  pub fn links(&self) -> &[LinkConfig] { &self.links }
+
+  /// Check that no project depends on itself, through the `depends` of any other projects.
+  pub fn verify_acyclic(&self) -> Result<()> {
+    let mut done = HashSet::new();
+    for p in &self.projects {
+      if let Some(cycle) = find_cycle(&self.projects, p, &mut Vec::new(), &mut done) {
+        let names: Vec<_> = cycle.iter().chain(cycle.first()).map(|p| format!("{} ({})", p.name, p.id)).collect();
+        bail!("depends has a cycle: {}", names.join(" -> "));
+      }
+    }
+    Ok(())
+  }
+
+  /// The deprecated keys that were used in the config file.
+  pub fn deprecations(&self) -> &[Deprecation] { &self.deprecations }
 
   /// Check that IDs are unique, etc.
   fn validate(&self) -> Result<()> {
@@ -295,27 +623,296 @@ impl ConfigFile {
         }
         prefs.insert(pref.clone());
       }
+
+      p.version_format.validate().with_context(|| format!("illegal version_format of {}", p.id))?;
+      if let Some(tmpl) = &p.tag_template {
+        let pref = match &p.tag_prefix {
+          Some(pref) => pref,
+          None => bail!("project {} has tag_template without tag_prefix", p.id)
+        };
+        let filled = p.tag_template_for(pref);
+        let (sample, parts) = (p.version_format.sample(), p.version_format.parts);
+        if version_from_tag(&filled, &fill_tag_template(&filled, pref, &sample), parts) != Some(sample) {
+          bail!("illegal tag_template \"{}\"", tmpl);
+        }
+      }
+
+      if let Some(msg) = &p.tag_message {
+        if p.tag_style != TagStyle::Annotated {
+          bail!("project {} has tag_message without tag_style: annotated", p.id);
+        }
+        ParserBuilder::with_stdlib().build()?.parse(msg).with_context(|| format!("illegal tag_message of {}", p.id))?;
+      }
+
+      if !p.floating_tags.is_empty() {
+        match &p.tag_prefix {
+          Some(pref) if p.tag_template_for(pref).contains("{version}") => (),
+          Some(_) => bail!("project {} has floating_tags, but its tag_template has no {{version}}", p.id),
+          None => bail!("project {} has floating_tags without tag_prefix", p.id)
+        }
+      }
+
+      if let Some(diff_sizes) = &p.diff_sizes {
+        diff_sizes.validate().with_context(|| format!("illegal diff_sizes of {}", p.id))?;
+      }
+      p.constraints.validate().with_context(|| format!("illegal constraints of {}", p.id))?;
+
+      for alias in &p.tag_prefix_aliases {
+        if p.tag_prefix.is_none() {
+          bail!("project {} has tag_prefix_aliases without tag_prefix", p.id);
+        }
+        if !legal_tag(alias) {
+          bail!("illegal tag_prefix_aliases \"{}\"", alias);
+        }
+      }
+    }
+
+    let mut logs: HashMap<PathBuf, Vec<&ChangelogConfig>> = HashMap::new();
+    for p in &self.projects {
+      for target in &p.changelog {
+        if target.is_templated() {
+          ParserBuilder::with_stdlib()
+            .build()?
+            .parse(target.file())
+            .with_context(|| format!("illegal changelog file \"{}\" of {}", target.file(), p.id))?;
+        }
+        let file = without_dot_dirs(&PathBuf::from_slash(p.rooted_pattern(target.file())));
+        logs.entry(file).or_default().push(target);
+      }
+    }
+    for (file, targets) in &logs {
+      if targets.len() > 1 && targets.iter().any(|t| t.format() != ChangelogFormat::KeepAChangelog) {
+        bail!("changelog {} is written more than once, so it needs format: keep-a-changelog", file.display());
+      }
+    }
+
+    let aliases = self.projects.iter().flat_map(|p| p.tag_prefix_aliases.iter().map(move |a| (p, a)));
+    for (p, alias) in aliases {
+      if self.projects.iter().any(|other| other.tag_prefix.as_ref() == Some(alias) && other.id != p.id) {
+        bail!("tag_prefix_aliases \"{}\" of {} is another project's tag_prefix", alias, p.id);
+      }
+    }
+
+    let id_aliases = self.projects.iter().flat_map(|p| p.aliases.iter().map(move |a| (p, a)));
+    for (p, alias) in id_aliases {
+      if self.projects.iter().any(|other| other.id.id == *alias) {
+        bail!("alias {} of {} is a current project id", alias, p.id);
+      }
+      if self.projects.iter().any(|other| other.aliases.contains(alias) && other.id != p.id) {
+        bail!("alias {} of {} is another project's alias", alias, p.id);
+      }
+    }
+
+    for b in self.options.branches() {
+      Pattern::new(b).map_err(|e| bad!("illegal branch pattern \"{}\": {}", b, e))?;
+    }
+
+    for m in &self.options.maintenance {
+      Pattern::new(&m.branch).map_err(|e| bad!("illegal maintenance branch \"{}\": {}", m.branch, e))?;
+      if m.majors.is_empty() {
+        bail!("maintenance branch \"{}\" has no majors", m.branch);
+      }
+    }
+
+    for n in &self.notifications {
+      n.validate()?;
+    }
+
+    for p in &self.notes_publishers {
+      p.validate()?;
+    }
+
+    for link in &self.links {
+      link.validate()?;
+    }
+
+    self.verify_acyclic()?;
+
+    for p in self.projects.iter().filter(|p| p.is_umbrella()) {
+      if !p.version.is_tag() {
+        bail!("umbrella {} must have a tag version", p.id);
+      }
+      for member in &p.members {
+        match self.projects.iter().find(|m| &m.id == member) {
+          None => bail!("umbrella {} has unknown member {}", p.id, member),
+          Some(m) if m.is_umbrella() => bail!("umbrella {} has umbrella member {}", p.id, member),
+          Some(_) => ()
+        }
+      }
     }
 
     Ok(())
   }
 }
 
+/// A cycle of `depends` that `proj` is part of or leads to, found depth-first from the projects on the `path` to
+/// it. Each project of the cycle depends on the next, and the last on the first. Projects in `done` are already
+/// known not to lead to a cycle.
+fn find_cycle<'a>(
+  projects: &'a [Project], proj: &'a Project, path: &mut Vec<&'a Project>, done: &mut HashSet<&'a ProjectId>
+) -> Option<Vec<&'a Project>> {
+  if let Some(start) = path.iter().position(|p| p.id == proj.id) {
+    return Some(path[start ..].to_vec());
+  }
+  if done.contains(&proj.id) {
+    return None;
+  }
+
+  path.push(proj);
+  let mut deps: Vec<_> = proj.depends.keys().filter_map(|id| projects.iter().find(|p| &p.id == id)).collect();
+  deps.sort_by_key(|p| p.id.id);
+  for dep in deps {
+    if let Some(cycle) = find_cycle(projects, dep, path, done) {
+      return Some(cycle);
+    }
+  }
+  path.pop();
+  done.insert(&proj.id);
+  None
+}
+
 #[derive(Deserialize, Debug)]
 struct Options {
   #[serde(default = "default_prev_tag")]
   prev_tag: String,
-  #[serde(default = "default_branch")]
-  branch: Option<String>
+  #[serde(default, deserialize_with = "deser_labels")]
+  branch: Vec<String>,
+  #[serde(default)]
+  maintenance: Vec<MaintenanceBranch>,
+  #[serde(default)]
+  tag_remote: Option<String>,
+  #[serde(default)]
+  mirrors: Vec<String>,
+  #[serde(default)]
+  parallel_push: bool,
+  #[serde(default)]
+  allowed_paths: Vec<String>,
+  #[serde(default)]
+  max_size: Option<Size>,
+  #[serde(default = "default_stale_months")]
+  stale_months: u32,
+  #[serde(default)]
+  plan_max_hours: Option<u32>,
+  #[serde(default)]
+  plan_max_commits: Option<u32>,
+  #[serde(default)]
+  releases_dir: Option<String>,
+  #[serde(default)]
+  skip_prs: Vec<u32>,
+  #[serde(default = "default_size_trailer")]
+  size_trailer: String,
+  #[serde(default = "default_skip_trailer")]
+  skip_trailer: String,
+  #[serde(default)]
+  dry_run_default: bool
 }
 
 impl Default for Options {
-  fn default() -> Options { Options { prev_tag: default_prev_tag(), branch: default_branch() } }
+  fn default() -> Options {
+    Options {
+      prev_tag: default_prev_tag(),
+      branch: Vec::new(),
+      maintenance: Vec::new(),
+      tag_remote: None,
+      mirrors: Vec::new(),
+      parallel_push: false,
+      allowed_paths: Vec::new(),
+      max_size: None,
+      stale_months: default_stale_months(),
+      plan_max_hours: None,
+      plan_max_commits: None,
+      releases_dir: None,
+      skip_prs: Vec::new(),
+      size_trailer: default_size_trailer(),
+      skip_trailer: default_skip_trailer(),
+      dry_run_default: false
+    }
+  }
 }
 
 impl Options {
   pub fn prev_tag(&self) -> &str { &self.prev_tag }
-  pub fn branch(&self) -> &Option<String> { &self.branch }
+  pub fn branches(&self) -> &[String] { &self.branch }
+
+  /// Whether a release may be made from the `name` branch: it's listed in `branch`, or matches one of its glob
+  /// patterns, or it's a `maintenance` branch. Any branch is allowed if `branch` isn't set.
+  pub fn allows_branch(&self, name: &str) -> bool {
+    self.branch.is_empty() || self.branch.iter().any(|b| matches_branch(b, name)) || self.maintenance_of(name).is_some()
+  }
+
+  /// The first `maintenance` entry whose pattern matches the `name` branch.
+  fn maintenance_of(&self, name: &str) -> Option<&MaintenanceBranch> {
+    self.maintenance.iter().find(|m| matches_branch(&m.branch, name))
+  }
+  pub fn tag_remote(&self) -> &Option<String> { &self.tag_remote }
+  pub fn mirrors(&self) -> &[String] { &self.mirrors }
+  pub fn parallel_push(&self) -> bool { self.parallel_push }
+
+  /// Whether a release may write the file at `path` (relative to the repo root): it's under one of the
+  /// `allowed_paths`. Any path is allowed if `allowed_paths` isn't set.
+  pub fn allows_write(&self, path: &Path) -> bool {
+    let path = without_cur_dirs(path);
+    self.allowed_paths.is_empty()
+      || self.allowed_paths.iter().any(|p| path.starts_with(without_cur_dirs(&PathBuf::from_slash(p))))
+  }
+  pub fn max_size(&self) -> Option<Size> { self.max_size }
+  pub fn dry_run_default(&self) -> bool { self.dry_run_default }
+  pub fn stale_months(&self) -> u32 { self.stale_months }
+  pub fn plan_max_hours(&self) -> Option<u32> { self.plan_max_hours }
+  pub fn plan_max_commits(&self) -> Option<u32> { self.plan_max_commits }
+  pub fn releases_dir(&self) -> Option<&str> { self.releases_dir.as_deref() }
+  pub fn size_trailer(&self) -> &str { &self.size_trailer }
+  pub fn skip_trailer(&self) -> &str { &self.skip_trailer }
+}
+
+/// Whether the `name` branch is the `pattern` branch, or matches it as a glob pattern.
+fn matches_branch(pattern: &str, name: &str) -> bool {
+  pattern == name || Pattern::new(pattern).map(|p| p.matches_with(name, match_opts())).unwrap_or(false)
+}
+
+/// A branch (or glob pattern of branches) on which older major versions are maintained: only the tags of its
+/// `majors` are considered or made on it, and it can have its own `prev_tag`.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+struct MaintenanceBranch {
+  branch: String,
+  majors: Vec<u32>,
+  prev_tag: Option<String>
+}
+
+/// The path without any `.` components, so that `./a/b` and `a/b` are the same.
+fn without_cur_dirs(path: &Path) -> PathBuf { path.components().filter(|c| !matches!(c, Component::CurDir)).collect() }
+
+/// The path without any `.` components, and with each `..` resolved against the component before it, so that
+/// `a/../b` and `b` are the same.
+fn without_dot_dirs(path: &Path) -> PathBuf {
+  let mut resolved = PathBuf::new();
+  for c in path.components() {
+    match c {
+      Component::CurDir => (),
+      Component::ParentDir if matches!(resolved.components().next_back(), Some(Component::Normal(_))) => {
+        resolved.pop();
+      }
+      c => resolved.push(c)
+    }
+  }
+  resolved
+}
+
+/// The module path of a `go.mod` file.
+const GO_MODULE_PATTERN: &str = r"(?m)^module\s+(\S+)";
+
+/// A Go module `path` for the `major` version: from v2 on, it ends with a `/v2` (or later) suffix.
+fn go_module_path(path: &str, major: u32) -> String {
+  let base = match path.rsplit_once("/v") {
+    Some((base, n)) if n.parse::<u32>().map(|n| n >= 2).unwrap_or(false) => base,
+    _ => path
+  };
+  if major >= 2 {
+    format!("{}/v{}", base, major)
+  } else {
+    base.to_string()
+  }
 }
 
 fn legal_tag(prefix: &str) -> bool {
@@ -324,29 +921,109 @@ fn legal_tag(prefix: &str) -> bool {
       && (prefix.chars().all(|c| c.is_ascii() && (c == '/' || c == '_' || c == '-' || c.is_alphanumeric()))))
 }
 
+/// Fill in a tag template with a tag prefix and version. Each of `{major}`, `{minor}`, and `{patch}` is a part of the
+/// version.
+pub fn fill_tag_template(tmpl: &str, prefix: &str, vers: &str) -> String {
+  let parts: Vec<_> = vers.splitn(3, '.').collect();
+  let part = |i: usize| parts.get(i).copied().unwrap_or("");
+  tmpl
+    .replace("{prefix}", prefix)
+    .replace("{version}", vers)
+    .replace("{major}", part(0))
+    .replace("{minor}", part(1))
+    .replace("{patch}", part(2))
+}
+
+/// Match a tag against a tag template (with its prefix already filled in), returning the tag's version, which has
+/// `parts` numbers.
+fn version_from_tag(tmpl: &str, tag: &str, parts: usize) -> Option<String> {
+  let version = format!(r"(?P<version>\d+(?:\.\d+){{{}}})", parts.saturating_sub(1));
+  let mut pattern = String::from("^");
+  let mut rest = tmpl;
+  while let Some(start) = rest.find('{') {
+    let end = start + rest[start ..].find('}')?;
+    pattern.push_str(&escape(&rest[.. start]));
+    pattern.push_str(match &rest[start + 1 .. end] {
+      "version" => version.as_str(),
+      "major" => r"(?P<major>\d+)",
+      "minor" => r"(?P<minor>\d+)",
+      "patch" => r"(?P<patch>\d+)",
+      _ => return None
+    });
+    rest = &rest[end + 1 ..];
+  }
+  pattern.push_str(&escape(rest));
+  pattern.push('$');
+
+  let caps = Regex::new(&pattern).ok()?.captures(tag)?;
+  if let Some(version) = caps.name("version") {
+    return Some(version.as_str().to_string());
+  }
+  let part = |name: &str| caps.name(name).map(|m| m.as_str());
+  Some(format!("{}.{}.{}", part("major")?, part("minor")?, part("patch")?))
+}
+
 #[derive(Deserialize, Debug)]
 pub struct Project {
   name: String,
   id: ProjectId,
   root: Option<String>,
+  workspace: Option<String>,
   #[serde(default = "default_includes")]
   includes: Vec<String>,
   #[serde(default)]
   excludes: Vec<String>,
   #[serde(default)]
+  case_insensitive: bool,
+  #[serde(default)]
+  allow_overlap: bool,
+  #[serde(default)]
   depends: HashMap<ProjectId, Depends>,
-  changelog: Option<ChangelogConfig>,
+  #[serde(default, deserialize_with = "deser_changelog")]
+  changelog: Vec<ChangelogConfig>,
   version: Location,
   #[serde(default)]
   also: Vec<Location>,
   #[serde(default, deserialize_with = "deser_labels")]
   labels: Vec<String>,
+  #[serde(default, deserialize_with = "deser_labels")]
+  scopes: Vec<String>,
   tag_prefix: Option<String>,
   tag_prefix_separator: Option<String>,
   #[serde(default)]
+  tag_prefix_aliases: Vec<String>,
+  tag_template: Option<String>,
+  #[serde(default)]
+  tag_style: TagStyle,
+  tag_message: Option<String>,
+  #[serde(default)]
+  floating_tags: Vec<FloatingTag>,
+  prev_tag: Option<String>,
+  #[serde(default)]
   subs: Option<Subs>,
   #[serde(default)]
-  hooks: HookSet
+  hooks: HookSet,
+  cargo_lock: Option<String>,
+  max_size: Option<Size>,
+  diff_sizes: Option<DiffSizes>,
+  #[serde(default)]
+  zero_major_policy: ZeroMajorPolicy,
+  #[serde(default)]
+  version_format: VersionFormat,
+  build_metadata: Option<String>,
+  #[serde(default)]
+  constraints: VersionConstraints,
+  #[serde(default)]
+  members: Vec<ProjectId>,
+  dry_run_default: Option<bool>,
+  #[serde(default)]
+  go_module: bool,
+  #[serde(default)]
+  aliases: Vec<u32>,
+  #[serde(default)]
+  previous_roots: Vec<String>,
+  #[serde(skip)]
+  branch_majors: Option<Vec<u32>>
 }
 
 impl Project {
@@ -356,13 +1033,35 @@ impl Project {
   pub fn root(&self) -> Option<&String> { self.root.as_ref().and_then(|r| if r == "." { None } else { Some(r) }) }
   pub fn hooks(&self) -> &HookSet { &self.hooks }
   pub fn labels(&self) -> &[String] { &self.labels }
+  pub fn members(&self) -> &[ProjectId] { &self.members }
+
+  /// Whether this project had the ID `prev` in an earlier config, before it was renumbered.
+  pub fn is_aliased(&self, prev: &ProjectId) -> bool {
+    self.aliases.contains(&prev.id) && prev.majors == self.id.majors
+  }
+
+  /// An umbrella project has no files of its own: it aggregates the sizes and changes of its members.
+  pub fn is_umbrella(&self) -> bool { !self.members.is_empty() }
+
+  /// Whether the project is meant to share files with other projects, so that `check --strict` allows it.
+  pub fn allows_overlap(&self) -> bool { self.allow_overlap }
 
   fn annotate<S: StateRead>(&self, state: &S) -> Result<AnnotatedMark> {
     Ok(AnnotatedMark::new(self.id.clone(), self.name.clone(), self.get_value(state)?))
   }
 
+  /// Check that the project can be released at `vers`: it's one of the project's majors (if it's restricted to some),
+  /// and doesn't break its `constraints`.
   pub fn verify_restrictions(&self, vers: &str) -> Result<()> {
-    let major = Size::parts(vers)?[0];
+    self.verify_majors(vers)?;
+    if let Some(why) = self.constraint_violation(vers)? {
+      bail!("Illegal version {} for project \"{}\": {}.", vers, self.id, why);
+    }
+    Ok(())
+  }
+
+  pub fn verify_majors(&self, vers: &str) -> Result<()> {
+    let major = Size::all_parts(vers)?[0];
     if let Some(tag_majors) = self.tag_majors() {
       if !tag_majors.contains(&major) {
         bail!("Illegal version {} for restricted project \"{}\" with majors {:?}.", vers, self.id, tag_majors);
@@ -371,8 +1070,13 @@ impl Project {
     Ok(())
   }
 
+  /// Why `vers` breaks the project's `constraints`, if it does.
+  pub fn constraint_violation(&self, vers: &str) -> Result<Option<String>> { self.constraints.violation(vers) }
+
+  /// The file and template of the project's primary changelog: the first of its changelog targets. The file may be
+  /// templated, and is only a path once it's filled in by `changelog_path`.
   pub fn changelog(&self) -> Option<(Cow<str>, &str)> {
-    self.changelog.as_ref().map(|changelog| {
+    self.changelog.first().map(|changelog| {
       if let Some(root) = self.root() {
         (
           Cow::Owned(
@@ -386,90 +1090,299 @@ impl Project {
     })
   }
 
+  /// All of the project's changelog targets, from its primary changelog on.
+  pub fn changelogs(&self) -> &[ChangelogConfig] { &self.changelog }
+
+  /// The path of one of the project's changelog `target`s for its release of `vers`. A templated file is rendered
+  /// with the project's `name` and the `version`.
+  pub fn changelog_path(&self, target: &ChangelogConfig, vers: &str) -> Result<PathBuf> {
+    let file = if target.is_templated() {
+      let tmpl = ParserBuilder::with_stdlib().build()?.parse(target.file())?;
+      tmpl.render(&liquid::object!({ "name": self.name, "version": vers }))?
+    } else {
+      target.file().to_string()
+    };
+    Ok(PathBuf::from_slash(self.rooted_pattern(&file)))
+  }
+
   pub fn tag_prefix(&self) -> &Option<String> { &self.tag_prefix }
   pub fn tag_prefix_separator(&self) -> &str { self.tag_prefix_separator.as_deref().unwrap_or("-") }
-  pub fn tag_majors(&self) -> Option<&[u32]> { self.version.tag_majors() }
 
-  pub async fn write_changelog(
-    &self, write: &mut StateWrite, cl: &Changelog, new_vers: &str
-  ) -> Result<Option<PathBuf>> {
-    if cl.is_empty() {
-      return Ok(None);
+  /// Earlier tag prefixes of the project, whose tags also count as its versions.
+  pub fn tag_prefix_aliases(&self) -> &[String] { &self.tag_prefix_aliases }
+
+  /// The template of the project's version tags with a tag `prefix` filled in, such as `proj-v{version}`.
+  pub fn tag_template_for(&self, prefix: &str) -> String {
+    match &self.tag_template {
+      Some(tmpl) => tmpl.replace("{prefix}", prefix),
+      None if prefix.is_empty() => "v{version}".to_string(),
+      None => format!("{}{}v{{version}}", prefix, self.tag_prefix_separator())
     }
+  }
 
-    if let Some((log_path, template)) = self.changelog().as_ref() {
-      let log_path = PathBuf::from_slash(log_path.as_ref());
-      let old_content = extract_old_content(&log_path)?;
-      let tmpl = read_template(template, self.root().map(PathBuf::from_slash).as_deref(), true).await?;
-      write.write_file(
-        log_path.clone(),
-        construct_changelog_html(cl, ProjLine::from_version(self, new_vers.to_string())?, new_vers, old_content, tmpl)?,
-        self.id(),
-        true
-      )?;
-      Ok(Some(log_path))
-    } else {
-      Ok(None)
+  /// The templates of the project's version tags: for its `tag_prefix`, and then for each of its aliases.
+  pub fn tag_templates(&self) -> impl Iterator<Item = String> + '_ {
+    self.tag_prefix.iter().chain(&self.tag_prefix_aliases).map(move |pref| self.tag_template_for(pref))
+  }
+
+  /// The version of one of the project's version tags, or `None` if the tag doesn't fit any of its templates.
+  pub fn tag_version(&self, tag: &str) -> Option<String> {
+    self.tag_templates().find_map(|tmpl| version_from_tag(&tmpl, tag, self.version_format.parts))
+  }
+
+  /// The tag or commit that this project's changes are counted from, instead of the repo-wide `prev_tag`.
+  pub fn prev_tag(&self) -> Option<&str> { self.prev_tag.as_deref() }
+  /// The only majors of the project's versions: from its `subs`, or else from a `maintenance` branch.
+  pub fn tag_majors(&self) -> Option<&[u32]> { self.version.tag_majors().or(self.branch_majors.as_deref()) }
+  pub fn is_tag_version(&self) -> bool { self.version.is_tag() }
+
+  /// The configured preamble and footer of this project's primary changelog, and whether it shows release
+  /// statistics.
+  pub fn changelog_blocks(&self) -> Result<ChangelogBlocks> {
+    match self.changelog.first() {
+      Some(changelog) => changelog.blocks(self.root()),
+      None => Ok(ChangelogBlocks::default())
     }
   }
 
-  pub fn size(&self, parent_sizes: &HashMap<String, Size>, kind: &str) -> Result<Size> {
-    let kind = kind.trim();
-    parent_sizes
-      .get(kind)
-      .copied()
-      .map(Ok)
-      .unwrap_or_else(|| parent_sizes.get("*").copied().map(Ok).unwrap_or_else(|| err!("Unknown kind \"{}\".", kind)))
+  /// The sections by which this project's primary changelog is grouped, or an empty list if it isn't grouped.
+  pub fn changelog_sections(&self) -> Vec<ChangelogSection> {
+    self.changelog.first().map(|changelog| changelog.sections()).unwrap_or_default()
   }
 
-  pub fn does_cover(&self, path: &str) -> Result<bool> {
-    let excludes = self.excludes.iter().try_fold::<_, _, Result<_>>(false, |val, cov| {
-      Ok(
-        val || {
-          let rooted = self.rooted_pattern(cov);
-          let result = Pattern::new(&rooted)?.matches_with(path, match_opts());
-          trace!("exclude {} match {} vs {}: {}", self.id(), rooted, path, result);
-          result
-        }
-      )
-    })?;
+  pub fn changelog_format(&self) -> ChangelogFormat {
+    self.changelog.first().map(|changelog| changelog.format()).unwrap_or_default()
+  }
 
-    if excludes {
-      return Ok(false);
+  /// Render each of this project's changelog targets, returning the path each should be written to and either its
+  /// new content, or the release's section to splice into it.
+  pub async fn render_changelog(&self, cl: &Changelog, new_vers: &str) -> Result<Vec<(PathBuf, RenderedLog)>> {
+    if cl.is_empty() {
+      return Ok(Vec::new());
     }
 
-    self.includes.iter().try_fold(false, |val, cov| {
-      Ok(
-        val || {
-          let rooted = self.rooted_pattern(cov);
-          let result = Pattern::new(&rooted)?.matches_with(path, match_opts());
-          trace!("include {} match {} vs {}: {}", self.id(), rooted, path, result);
-          result
+    let mut rendered = Vec::new();
+    for target in &self.changelog {
+      let log_path = self.changelog_path(target, new_vers)?;
+      let log = match target.format() {
+        ChangelogFormat::Template => {
+          let old_content = extract_old_content(&log_path)?;
+          RenderedLog::Full(self.render_release(cl, new_vers, target, old_content).await?)
         }
-      )
-    })
+        ChangelogFormat::KeepAChangelog => {
+          let section = self.render_release(cl, new_vers, target, "".into()).await?;
+          RenderedLog::Section { section, version: new_vers.to_string(), tag: self.full_version(new_vers) }
+        }
+      };
+      rendered.push((log_path, log));
+    }
+    Ok(rendered)
   }
 
-  pub fn check<S: StateRead>(&self, state: &S) -> Result<()> {
-    // Check that we can find the given mark.
-    self.get_value(state)?;
+  /// Render the primary changelog on top of a `previous` rendering of it, if given, instead of the changelog file.
+  /// A templated changelog has a file for each version, so it's never rendered on top of an earlier version's.
+  pub async fn render_changelog_onto(
+    &self, cl: &Changelog, new_vers: &str, previous: Option<&str>
+  ) -> Result<Option<(PathBuf, String)>> {
+    if cl.is_empty() {
+      return Ok(None);
+    }
 
-    self.check_excludes()?;
+    if let Some(target) = self.changelog.first() {
+      let log_path = self.changelog_path(target, new_vers)?;
+      let previous = previous.filter(|_| !target.is_templated());
+      if target.format() == ChangelogFormat::KeepAChangelog {
+        let old = match previous {
+          Some(previous) => previous.to_string(),
+          None if log_path.exists() => std::fs::read_to_string(&log_path)
+            .with_context(|| format!("Unable to read changelog {}.", log_path.display()))?,
+          None => String::new()
+        };
+        let section = self.render_release(cl, new_vers, target, "".into()).await?;
+        let tag = self.full_version(new_vers);
+        return Ok(Some((log_path, splice_keep_a_changelog(&old, &section, new_vers, tag.as_deref()))));
+      }
 
-    self.check_prefix()?;
+      let old_content = match previous {
+        Some(previous) => extract_content(previous),
+        None => extract_old_content(&log_path)?
+      };
+      let content = self.render_release(cl, new_vers, target, old_content).await?;
+      Ok(Some((log_path, content)))
+    } else {
+      Ok(None)
+    }
+  }
 
-    // Check that each pattern includes at least one file.
-    for cov in &self.includes {
-      let pattern = self.rooted_pattern(cov);
-      if !glob_with(&pattern, match_opts())?.any(|_| true) {
-        return err!("No files in proj. {} covered by \"{}\".", self.id, pattern);
-      }
+  /// Render just the section of a release in markdown, for the release notes. It's grouped like the primary
+  /// changelog, and uses its template if it's a keep-a-changelog changelog.
+  pub async fn render_section(&self, cl: &Changelog, new_vers: &str) -> Result<String> {
+    let mut target = self.changelog.first().cloned().unwrap_or_else(|| ChangelogConfig::from_file(String::new()));
+    if target.format() != ChangelogFormat::KeepAChangelog {
+      target.template = ChangelogFormat::KeepAChangelog.default_template();
     }
+    self.render_release(cl, new_vers, &target, "".into()).await
+  }
 
-    Ok(())
+  async fn render_release(
+    &self, cl: &Changelog, new_vers: &str, target: &ChangelogConfig, old_content: String
+  ) -> Result<String> {
+    let tmpl = read_template(target.template(), self.root().map(PathBuf::from_slash).as_deref(), true).await?;
+    let proj_line = ProjLine::from_version(self, new_vers.to_string())?;
+    let blocks = target.blocks(self.root())?;
+    let sections = target.sections();
+    construct_changelog_html(cl, proj_line, new_vers, old_content, blocks, &sections, tmpl)
   }
 
-  /// Ensure that we don't have excludes without includes.
+  /// Render the changelog of a past `version` by itself, and patch it over that version's section of the changelog
+  /// file. Returns nothing if the project doesn't have a changelog.
+  pub async fn regenerate_changelog_section(
+    &self, cl: &Changelog, version: &str
+  ) -> Result<Option<(PathBuf, String)>> {
+    if self.changelog_format() == ChangelogFormat::KeepAChangelog {
+      bail!("Can't regenerate a version of project \"{}\"'s keep-a-changelog changelog.", self.id);
+    }
+
+    if let Some(target) = self.changelog.first() {
+      let log_path = self.changelog_path(target, version)?;
+      let full_content = std::fs::read_to_string(&log_path)
+        .with_context(|| format!("Unable to read changelog {}.", log_path.display()))?;
+      let rendered = self.render_release(cl, version, target, "".into()).await?;
+      let date = cl.date().unwrap_or_default();
+      let content = replace_section(&full_content, version, date, &extract_content(&rendered))?;
+      Ok(Some((log_path, content)))
+    } else {
+      Ok(None)
+    }
+  }
+
+  /// The size of a commit with this summary and conventional kind: from the first of the `sizes` rules that
+  /// matches the summary, or else from the kind.
+  pub fn size(&self, parent_sizes: &Sizes, summary: &str, kind: &str) -> Result<Size> {
+    if let Some(size) = parent_sizes.rule_for(summary) {
+      return Ok(size);
+    }
+
+    let kind = kind.trim();
+    if kind == BREAKING_KIND && !parent_sizes.contains_key(kind) {
+      // Breaking changes are major, even if the sizes don't mention them.
+      return Ok(Size::Major);
+    }
+
+    parent_sizes
+      .get(kind)
+      .copied()
+      .map(Ok)
+      .unwrap_or_else(|| parent_sizes.get("*").copied().map(Ok).unwrap_or_else(|| err!("Unknown kind \"{}\".", kind)))
+  }
+
+  /// Whether a release of this project is a dry run unless it's confirmed: the project's own `dry_run_default`, or
+  /// else the global one.
+  pub fn dry_run_default(&self, global: bool) -> bool { self.dry_run_default.unwrap_or(global) }
+
+  /// Limit an automatically-derived size to this project's `max_size` (or the global `max_size`, if the
+  /// project has none). A commit's `Versio-Size` trailer isn't capped: it replaces the derived size entirely.
+  pub fn cap_size(&self, size: Size, global_max: Option<Size>) -> Size {
+    let max = match self.max_size.or(global_max) {
+      Some(max) => max,
+      None => return size
+    };
+    if size.is_failure() || size <= max {
+      size
+    } else {
+      max
+    }
+  }
+
+  /// The size of the increment that's applied to this project's `version`, in place of `size`: see
+  /// `zero_major_policy`.
+  pub fn applied_size(&self, size: Size, version: &str) -> Result<Size> {
+    size.under_zero_major(version, self.zero_major_policy)
+  }
+
+  /// The version that an increment of `size` makes of `version`, after `applied_size`.
+  pub fn apply_size(&self, size: Size, version: &str) -> Result<String> {
+    self.bump(self.applied_size(size, version)?, version)
+  }
+
+  /// The version that an increment of `applied` (a size that's already been through `applied_size`) makes of
+  /// `version`, in the project's `version_format`.
+  pub fn bump(&self, applied: Size, version: &str) -> Result<String> {
+    self.version_format.apply(applied, version, Utc::now())
+  }
+
+  /// How to size a commit whose message has no conventional kind, from its diff, if the project asks for it.
+  pub fn diff_sizes(&self) -> Option<&DiffSizes> { self.diff_sizes.as_ref() }
+
+  /// Whether a conventional commit scope (such as "frontend" in `fix(frontend): ...`) routes to this project.
+  pub fn does_cover_scope(&self, scope: &str) -> bool { self.scopes.iter().any(|s| s.eq_ignore_ascii_case(scope)) }
+
+  pub fn does_cover(&self, path: &str) -> Result<bool> { self.does_cover_at(self.root().map(|r| r.as_str()), path) }
+
+  /// Whether the file at `path` was covered by this project before it moved from one of its `previous_roots`.
+  pub fn did_cover(&self, path: &str) -> Result<bool> {
+    self.previous_roots.iter().try_fold(false, |val, root| Ok(val || self.does_cover_at(Some(root), path)?))
+  }
+
+  /// Whether this project covers the file at `path` when its root is `root`. A `path` with Windows separators is
+  /// matched as if it had forward slashes, like the patterns.
+  fn does_cover_at(&self, root: Option<&str>, path: &str) -> Result<bool> {
+    if self.is_umbrella() {
+      return Ok(false);
+    }
+
+    let path = &slashed(path);
+    let opts = self.cover_opts();
+
+    let excludes = self.excludes.iter().try_fold::<_, _, Result<_>>(false, |val, cov| {
+      Ok(
+        val || {
+          let rooted = rooted_at(root, cov);
+          let result = Pattern::new(&rooted)?.matches_with(path, opts);
+          trace!("exclude {} match {} vs {}: {}", self.id(), rooted, path, result);
+          result
+        }
+      )
+    })?;
+
+    if excludes {
+      return Ok(false);
+    }
+
+    self.includes.iter().try_fold(false, |val, cov| {
+      Ok(
+        val || {
+          let rooted = rooted_at(root, cov);
+          let result = Pattern::new(&rooted)?.matches_with(path, opts);
+          trace!("include {} match {} vs {}: {}", self.id(), rooted, path, result);
+          result
+        }
+      )
+    })
+  }
+
+  pub fn check<S: StateRead>(&self, state: &S) -> Result<()> {
+    // Check that we can find the given mark.
+    self.get_value(state)?;
+
+    self.check_excludes()?;
+
+    self.check_prefix()?;
+
+    self.check_go_module(state)?;
+
+    // Check that each pattern includes at least one file.
+    for cov in self.includes.iter().filter(|_| !self.is_umbrella()) {
+      let pattern = self.rooted_pattern(cov);
+      if !glob_with(&pattern, self.cover_opts())?.any(|_| true) {
+        return err!("No files in proj. {} covered by \"{}\".", self.id, pattern);
+      }
+    }
+
+    Ok(())
+  }
+
+  /// Ensure that we don't have excludes without includes.
   fn check_excludes(&self) -> Result<()> {
     if !self.excludes.is_empty() && self.includes.is_empty() {
       bail!("Proj {} has excludes, but no includes.", self.id);
@@ -485,16 +1398,126 @@ impl Project {
     Ok(())
   }
 
+  /// Ensure that a Go module's path has the suffix of its major version, and that its tags are the ones that Go
+  /// looks for: `v1.2.3` at the top of the repo, or `dir/v1.2.3` for a module in `dir`.
+  fn check_go_module<S: StateRead>(&self, state: &S) -> Result<()> {
+    if !self.go_module {
+      return Ok(());
+    }
+
+    let path = self.go_module_path(state.read_file(self.rooted_pattern("go.mod").as_ref())?)?;
+    let vers = self.get_value(state)?;
+    let major = Size::parts(&vers)?[0];
+    let expected = go_module_path(&path, major);
+    if path != expected {
+      bail!("Proj {} is at version {}, so its module path should be {}, not {}.", self.id, vers, expected, path);
+    }
+
+    // A module in a major subdirectory, like `dir/v2`, is tagged like `dir/v2.0.0`.
+    let root = self.root.as_deref().filter(|r| *r != ".").unwrap_or("");
+    let sub = format!("v{}", major);
+    let root = if root == sub { "" } else { root.strip_suffix(&format!("/{}", sub)).unwrap_or(root) };
+    let tag = self.full_version(&vers).unwrap_or_default();
+    let expected = if root.is_empty() { format!("v{}", vers) } else { format!("{}/v{}", root, vers) };
+    if tag != expected {
+      bail!("Proj {} is a Go module, so its tags should be like \"{}\", not \"{}\".", self.id, expected, tag);
+    }
+    Ok(())
+  }
+
+  fn go_module_path(&self, go_mod: String) -> Result<String> {
+    let found = LinePicker::new(GO_MODULE_PATTERN.into()).find(&go_mod);
+    Ok(found.with_context(|| format!("No module directive in go.mod of proj {}.", self.id))?.value().to_string())
+  }
+
+  /// Use forward slashes in the root and coverage patterns, which may have been written with Windows separators:
+  /// git, and the globs, always separate with `/`.
+  fn normalize_paths(&mut self) {
+    if let Some(root) = self.root.as_mut() {
+      *root = slashed(root);
+    }
+    for path in self.includes.iter_mut().chain(&mut self.excludes).chain(&mut self.previous_roots) {
+      *path = slashed(path);
+    }
+  }
+
+  /// How the coverage patterns match paths: case-insensitively if the project asks for it.
+  fn cover_opts(&self) -> MatchOptions { MatchOptions { case_sensitive: !self.case_insensitive, ..match_opts() } }
+
+  /// A Go module is tagged by its root, unless it has its own `tag_prefix`.
+  fn default_go_tags(&mut self) {
+    if self.go_module && self.tag_prefix.is_none() {
+      self.tag_prefix = Some(self.root.clone().filter(|r| r != ".").unwrap_or_default());
+      self.tag_prefix_separator.get_or_insert_with(|| "/".into());
+    }
+  }
+
+  /// The project's version, without any build metadata that `build_metadata` added to it.
   pub fn get_value<S: StateRead>(&self, read: &S) -> Result<String> {
-    self.version.read_value(read, self.root(), self.id())
+    Ok(strip_build_metadata(&self.version.read_value(read, self.root(), self.id())?).to_string())
   }
 
-  pub fn set_value(&self, write: &mut StateWrite, vers: &str) -> Result<()> {
-    self.version.write_value(write, self.root(), vers, &self.id)?;
-    self.set_also(write, vers)?;
+  /// Write `vers` as the project's version. The files get the build metadata of `build_metadata`, if the project
+  /// has it; tags never do.
+  pub fn set_value(&self, write: &mut StateWrite, vers: &str, build: &BuildInfo) -> Result<()> {
+    let written = self.with_build_metadata(vers, build)?;
+    self.version.write_value(write, self.root(), &written, &self.id)?;
+    self.set_also(write, &written)?;
+    self.set_cargo_lock(write, &written)?;
+    self.set_go_module(write, vers)?;
     self.forward_tag(write, vers)
   }
 
+  /// The version `vers` with the build metadata that the project's `build_metadata` template renders, if any. A
+  /// character that semver doesn't allow in build metadata is replaced by `-`.
+  fn with_build_metadata(&self, vers: &str, build: &BuildInfo) -> Result<String> {
+    let tmpl = match &self.build_metadata {
+      Some(tmpl) => ParserBuilder::with_stdlib().build()?.parse(tmpl)?,
+      None => return Ok(vers.to_string())
+    };
+    let globals = liquid::object!({
+      "version": vers,
+      "sha": build.sha,
+      "sha7": build.sha.chars().take(7).collect::<String>(),
+      "date": build.date,
+      "run": build.run
+    });
+    let rendered = tmpl.render(&globals)?;
+    let meta: String = rendered
+      .trim()
+      .trim_start_matches('+')
+      .chars()
+      .map(|c| if c.is_ascii_alphanumeric() || c == '.' || c == '-' { c } else { '-' })
+      .collect();
+    Ok(if meta.is_empty() { vers.to_string() } else { format!("{}+{}", vers, meta) })
+  }
+
+  /// Give a Go module's path the suffix of its new major version, if that's changed.
+  fn set_go_module(&self, write: &mut StateWrite, vers: &str) -> Result<()> {
+    if !self.go_module {
+      return Ok(());
+    }
+    let file = PathBuf::from_slash(self.rooted_pattern("go.mod"));
+    let go_mod = std::fs::read_to_string(&file).with_context(|| format!("Can't read {}.", file.display()))?;
+    let path = self.go_module_path(go_mod)?;
+    let next = go_module_path(&path, Size::parts(vers)?[0]);
+    if next != path {
+      write.update_mark(PickPath::new(file, Picker::Line(LinePicker::new(GO_MODULE_PATTERN.into()))), next, &self.id)?;
+    }
+    Ok(())
+  }
+
+  fn set_cargo_lock(&self, write: &mut StateWrite, vers: &str) -> Result<()> {
+    if let Some(lock) = &self.cargo_lock {
+      let manifest = self
+        .version
+        .file_path(self.root())
+        .ok_or_else(|| bad!("Project {} needs a file version to update \"{}\".", self.id, lock))?;
+      write.update_cargo_lock(PathBuf::from_slash(self.rooted_pattern(lock)), manifest, vers, &self.id)?;
+    }
+    Ok(())
+  }
+
   fn set_also(&self, write: &mut StateWrite, vers: &str) -> Result<()> {
     for loc in &self.also {
       loc.write_value(write, self.root(), vers, &self.id)?;
@@ -504,58 +1527,140 @@ impl Project {
 
   pub fn forward_tag(&self, write: &mut StateWrite, vers: &str) -> Result<()> {
     if let Some(full_tag) = self.full_version(vers) {
+      if let Some(msg) = self.tag_annotation(vers, "")? {
+        write.annotate_tag(full_tag.clone(), msg);
+      }
       write.tag_head_or_last(vers, full_tag, &self.id)?;
+      for tag in self.floating_tags_for(vers)? {
+        write.float_tag(tag, &self.id);
+      }
     }
     Ok(())
   }
 
-  pub fn full_version(&self, vers: &str) -> Option<String> {
-    let tag_prefix_separator = self.tag_prefix_separator();
+  /// The floating tags that are moved to each new version `vers`, such as `v1` and `v1.4` for `v1.4.2`.
+  pub fn floating_tags_for(&self, vers: &str) -> Result<Vec<String>> {
+    let prefix = match &self.tag_prefix {
+      Some(prefix) => prefix,
+      None => return Ok(Vec::new())
+    };
+    let [major, minor, _] = Size::parts(vers)?;
+    let tmpl = self.tag_template_for(prefix);
+    let short = |floating: &FloatingTag| match floating {
+      FloatingTag::Major => major.to_string(),
+      FloatingTag::Minor => format!("{}.{}", major, minor)
+    };
+    Ok(self.floating_tags.iter().map(|floating| fill_tag_template(&tmpl, prefix, &short(floating))).collect())
+  }
 
-    self.tag_prefix.as_ref().map(|tag_prefix| {
-      if tag_prefix.is_empty() {
-        format!("v{}", vers)
-      } else {
-        format!("{}{}v{}", tag_prefix, tag_prefix_separator, vers)
-      }
-    })
+  pub fn full_version(&self, vers: &str) -> Option<String> {
+    self.tag_prefix.as_ref().map(|tag_prefix| fill_tag_template(&self.tag_template_for(tag_prefix), tag_prefix, vers))
   }
 
-  fn rooted_pattern(&self, pat: &str) -> String {
-    if let Some(root) = self.root() {
-      if root == "." {
-        pat.to_string()
-      } else {
-        PathBuf::from_slash(root).join(PathBuf::from_slash(pat)).to_slash_lossy().into_owned()
-      }
-    } else {
-      pat.to_string()
+  /// The message of the project's version tag for `vers`, rendered from its `tag_message` with the changelog
+  /// `summary`; or `None` if its tags are lightweight.
+  pub fn tag_annotation(&self, vers: &str, summary: &str) -> Result<Option<String>> {
+    if self.tag_style != TagStyle::Annotated {
+      return Ok(None);
     }
+    let tmpl = self.tag_message.as_deref().unwrap_or(DEFAULT_TAG_MESSAGE);
+    let tmpl = ParserBuilder::with_stdlib().build()?.parse(tmpl)?;
+    let globals = liquid::object!({
+      "name": self.name,
+      "version": vers,
+      "tag": self.full_version(vers).unwrap_or_default(),
+      "summary": summary
+    });
+    Ok(Some(tmpl.render(&globals)?.trim().to_string()))
   }
 
+  /// The pattern `pat`, relative to the project's root, made relative to the repo instead.
+  pub fn rooted_pattern(&self, pat: &str) -> String { rooted_at(self.root().map(|r| r.as_str()), pat) }
+
   fn expand<R: FilesRead>(self, read: &R) -> Result<impl Iterator<Item = Project>> {
     if let Some(subs) = self.read_subs(read)? {
       Ok(E2::A(subs.into_iter().map(move |sub| Project {
         name: expand_name(&self.name, &sub),
         id: self.id.expand(&sub),
         root: expand_root(self.root(), &sub),
+        workspace: self.workspace.clone(),
         includes: self.includes.clone(),
         excludes: expand_excludes(&self.excludes, &sub),
+        case_insensitive: self.case_insensitive,
+        allow_overlap: self.allow_overlap,
         depends: expand_depends(&self.depends, &sub),
         changelog: self.changelog.clone(),
         version: expand_version(&self.version, &sub),
         also: expand_also(&self.also),
         labels: Default::default(),
+        scopes: self.scopes.clone(),
         tag_prefix: self.tag_prefix.clone(),
         tag_prefix_separator: self.tag_prefix_separator.clone(),
+        tag_prefix_aliases: self.tag_prefix_aliases.clone(),
+        tag_template: self.tag_template.clone(),
+        tag_style: self.tag_style,
+        tag_message: self.tag_message.clone(),
+        floating_tags: self.floating_tags.clone(),
+        prev_tag: self.prev_tag.clone(),
         subs: None,
-        hooks: self.hooks.clone()
+        hooks: self.hooks.clone(),
+        cargo_lock: self.cargo_lock.clone(),
+        max_size: self.max_size,
+        diff_sizes: self.diff_sizes.clone(),
+        zero_major_policy: self.zero_major_policy,
+        version_format: self.version_format,
+        build_metadata: self.build_metadata.clone(),
+        constraints: self.constraints.clone(),
+        members: self.members.clone(),
+        dry_run_default: self.dry_run_default,
+        go_module: self.go_module,
+        aliases: self.aliases.clone(),
+        previous_roots: self.previous_roots.iter().filter_map(|r| expand_root(Some(r), &sub)).collect(),
+        branch_majors: self.branch_majors.clone()
       })))
     } else {
       Ok(E2::B(once(self)))
     }
   }
 
+  /// Move a project from an included file in `dir`: its root is relative to that directory, and its ID is offset by
+  /// `id_base`, as are its `depends` and `members` on the `local` projects of the same file.
+  fn rebase(mut self, dir: &str, id_base: u32, local: &HashSet<u32>) -> Project {
+    // A workspace package's root is always found from the top of the repo.
+    if !dir.is_empty() && self.workspace.is_none() {
+      self.root = Some(match self.root() {
+        Some(root) => join_path(dir, root),
+        None => dir.to_string()
+      });
+    }
+
+    let shift = |id: &ProjectId| {
+      if local.contains(&id.id) {
+        ProjectId { id: id.id + id_base, majors: id.majors.clone() }
+      } else {
+        id.clone()
+      }
+    };
+    self.id = shift(&self.id);
+    self.depends = self.depends.iter().map(|(id, dep)| (shift(id), dep.clone())).collect();
+    self.members = self.members.iter().map(shift).collect();
+    self
+  }
+
+  /// Use the directory of the project's `workspace` package, among the workspace `members`, as its root.
+  fn resolve_workspace(mut self, members: &HashMap<String, String>) -> Result<Project> {
+    if let Some(package) = &self.workspace {
+      if self.root.is_some() {
+        bail!("Proj {} has both a root and a workspace package.", self.id);
+      }
+      let dir = members
+        .get(package)
+        .ok_or_else(|| bad!("Proj {} package \"{}\" isn't in a Cargo or npm workspace.", self.id, package))?;
+      self.root = Some(dir.clone());
+    }
+    Ok(self)
+  }
+
   fn read_subs<R: FilesRead>(&self, read: &R) -> Result<Option<Vec<SubExtent>>> {
     if let Some(subs) = &self.subs {
       let pattern = format!("^{}$", escape(subs.dirs()).replace("<>", "([0-9]+)"));
@@ -593,18 +1698,79 @@ impl Project {
 #[derive(Clone, Debug)]
 pub struct ChangelogConfig {
   file: String,
-  template: String
+  template: String,
+  format: ChangelogFormat,
+  preamble: Option<TextBlock>,
+  footer: Option<TextBlock>,
+  group_by_type: bool,
+  sections: Option<Vec<ChangelogSection>>,
+  stats: bool
 }
 
 impl ChangelogConfig {
-  pub fn from_file(file: String) -> ChangelogConfig { ChangelogConfig { file, template: default_changelog_template() } }
+  pub fn from_file(file: String) -> ChangelogConfig {
+    ChangelogConfig {
+      file,
+      template: default_changelog_template(),
+      format: ChangelogFormat::Template,
+      preamble: None,
+      footer: None,
+      group_by_type: false,
+      sections: None,
+      stats: false
+    }
+  }
 
   pub fn file(&self) -> &str { &self.file }
   pub fn template(&self) -> &str { &self.template }
+  pub fn format(&self) -> ChangelogFormat { self.format }
+
+  /// Whether the file is a template, such as `docs/releases/{{version}}.md`, instead of a single path.
+  pub fn is_templated(&self) -> bool { self.file.contains("{{") || self.file.contains("{%") }
+
+  /// The configured preamble and footer, read relative to the project's `root`, and whether the changelog shows
+  /// release statistics.
+  fn blocks(&self, root: Option<&String>) -> Result<ChangelogBlocks> {
+    let read = |block: &Option<TextBlock>| block.as_ref().map(|b| b.read(root)).transpose();
+    Ok(ChangelogBlocks {
+      preamble: read(&self.preamble)?.unwrap_or_default(),
+      footer: read(&self.footer)?.unwrap_or_default(),
+      stats: self.stats
+    })
+  }
+
+  /// The sections by which the changelog is grouped, or an empty list if it isn't grouped.
+  fn sections(&self) -> Vec<ChangelogSection> {
+    if self.group_by_type {
+      self.sections.clone().unwrap_or_else(default_sections)
+    } else {
+      Vec::new()
+    }
+  }
 }
 
 fn default_changelog_template() -> String { "builtin:html".to_string() }
 
+/// How a new release is written to a changelog.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum ChangelogFormat {
+  /// The whole changelog is rendered from its template.
+  #[default]
+  Template,
+  /// Only the release is rendered, and is spliced into an existing Keep-a-Changelog file.
+  KeepAChangelog
+}
+
+impl ChangelogFormat {
+  fn default_template(&self) -> String {
+    match self {
+      ChangelogFormat::Template => default_changelog_template(),
+      ChangelogFormat::KeepAChangelog => "builtin:keep-a-changelog".to_string()
+    }
+  }
+}
+
 impl<'de> Deserialize<'de> for ChangelogConfig {
   fn deserialize<D: Deserializer<'de>>(desr: D) -> std::result::Result<ChangelogConfig, D::Error> {
     struct TheVisitor;
@@ -623,13 +1789,34 @@ impl<'de> Deserialize<'de> for ChangelogConfig {
         #[derive(Deserialize)]
         struct InnerConfig {
           file: String,
-          #[serde(default = "default_changelog_template")]
-          template: String
+          #[serde(default)]
+          template: Option<String>,
+          #[serde(default)]
+          format: ChangelogFormat,
+          #[serde(default)]
+          preamble: Option<TextBlock>,
+          #[serde(default)]
+          footer: Option<TextBlock>,
+          #[serde(default)]
+          group_by_type: bool,
+          #[serde(default)]
+          sections: Option<Vec<ChangelogSection>>,
+          #[serde(default)]
+          stats: bool
         }
 
         impl InnerConfig {
           pub fn into_changelog(self) -> ChangelogConfig {
-            ChangelogConfig { file: self.file, template: self.template }
+            ChangelogConfig {
+              template: self.template.unwrap_or_else(|| self.format.default_template()),
+              file: self.file,
+              format: self.format,
+              preamble: self.preamble,
+              footer: self.footer,
+              group_by_type: self.group_by_type,
+              sections: self.sections,
+              stats: self.stats
+            }
           }
         }
 
@@ -642,27 +1829,265 @@ impl<'de> Deserialize<'de> for ChangelogConfig {
   }
 }
 
+/// A project's `changelog`, which is a file, a map, a list of either (the first being the primary changelog), or
+/// `false` for no changelog at all. A project without a changelog is still planned and released, and its changes
+/// are still in the plan: they just aren't written anywhere.
+fn deser_changelog<'de, D: Deserializer<'de>>(desr: D) -> std::result::Result<Vec<ChangelogConfig>, D::Error> {
+  struct ChangelogVisitor;
+  type T = Vec<ChangelogConfig>;
+  type R<T, E> = std::result::Result<T, E>;
+
+  impl<'de> Visitor<'de> for ChangelogVisitor {
+    type Value = T;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result { write!(f, "a changelog config, or false") }
+
+    fn visit_bool<E: de::Error>(self, v: bool) -> R<T, E> {
+      if v {
+        Err(E::custom("a changelog can be turned off with `false`, but needs a file to be on"))
+      } else {
+        Ok(Vec::new())
+      }
+    }
+
+    fn visit_unit<E: de::Error>(self) -> R<T, E> { Ok(Vec::new()) }
+    fn visit_str<E: de::Error>(self, v: &str) -> R<T, E> { Ok(vec![ChangelogConfig::from_file(v.to_string())]) }
+    fn visit_string<E: de::Error>(self, v: String) -> R<T, E> { Ok(vec![ChangelogConfig::from_file(v)]) }
+
+    fn visit_map<M: MapAccess<'de>>(self, map: M) -> R<T, M::Error> {
+      Deserialize::deserialize(de::value::MapAccessDeserializer::new(map)).map(|c| vec![c])
+    }
+
+    fn visit_seq<S: SeqAccess<'de>>(self, seq: S) -> R<T, S::Error> {
+      let targets: Vec<ChangelogConfig> = Deserialize::deserialize(de::value::SeqAccessDeserializer::new(seq))?;
+      if targets.is_empty() {
+        return Err(de::Error::custom("a changelog list needs a target: use `false` for no changelog"));
+      }
+      Ok(targets)
+    }
+  }
+
+  desr.deserialize_any(ChangelogVisitor)
+}
+
+/// A heading in a changelog that is grouped by commit type.
+#[derive(Deserialize, Clone, Debug)]
+pub struct ChangelogSection {
+  title: String,
+  #[serde(default)]
+  types: Vec<String>,
+  #[serde(default)]
+  sizes: Vec<Size>
+}
+
+impl ChangelogSection {
+  fn new(title: &str, types: &[&str], sizes: &[Size]) -> ChangelogSection {
+    ChangelogSection {
+      title: title.to_string(),
+      types: types.iter().map(|t| t.to_string()).collect(),
+      sizes: sizes.to_vec()
+    }
+  }
+
+  pub fn title(&self) -> &str { &self.title }
+
+  /// Find the section for a commit: the first section that lists its type, or else the first section that
+  /// lists its size. Returns `None` if no section matches.
+  pub fn find(sections: &[ChangelogSection], kind: &str, size: Size) -> Option<usize> {
+    let kind = kind.trim();
+    sections
+      .iter()
+      .position(|s| s.types.iter().any(|t| t.eq_ignore_ascii_case(kind)))
+      .or_else(|| sections.iter().position(|s| s.sizes.contains(&size)))
+  }
+}
+
+fn default_sections() -> Vec<ChangelogSection> {
+  vec![
+    ChangelogSection::new("Breaking Changes", &[BREAKING_KIND], &[Size::Major]),
+    ChangelogSection::new("Features", &["feat"], &[Size::Minor]),
+    ChangelogSection::new("Fixes", &["fix"], &[Size::Patch]),
+  ]
+}
+
+/// Some text for a changelog: either inline, or read from a file relative to the project root.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(untagged)]
+pub enum TextBlock {
+  Inline(String),
+  File { file: String }
+}
+
+impl TextBlock {
+  fn read(&self, root: Option<&String>) -> Result<String> {
+    match self {
+      TextBlock::Inline(text) => Ok(text.clone()),
+      TextBlock::File { file } => {
+        let path = match root {
+          Some(root) => PathBuf::from_slash(root).join(PathBuf::from_slash(file)),
+          None => PathBuf::from_slash(file)
+        };
+        std::fs::read_to_string(&path).with_context(|| format!("Can't read changelog text {}.", path.display()))
+      }
+    }
+  }
+}
+
 #[derive(Deserialize, Debug, Clone)]
 pub struct Depends {
   #[serde(default)]
   files: Vec<Location>,
   #[serde(default = "default_relative_size")]
-  size: RelativeSize
+  size: RelativeSize,
+  #[serde(default)]
+  style: DependsStyle,
+  npm: Option<NpmDepends>
 }
 
 impl Depends {
-  pub fn write_values(
-    &self, write: &mut StateWrite, root: Option<&String>, val: &str, proj_id: &ProjectId
+  pub fn write_values<S: StateRead>(
+    &self, write: &mut StateWrite, read: &S, root: Option<&String>, val: &str, proj_id: &ProjectId
   ) -> Result<()> {
     for file in &self.files {
-      file.write_value(write, root, val, proj_id)?;
+      file.write_value(write, root, &self.style.apply(val, file.is_json())?, proj_id)?;
+    }
+
+    if let Some(npm) = &self.npm {
+      let manifest = PathBuf::from_slash(rooted_at(root.map(|r| r.as_str()), "package.json"));
+      let mut wrote = false;
+      for (section, before) in npm.requirements(read, &manifest)? {
+        if let Some(after) = npm_requirement(&before, val) {
+          let parts = vec![Part::Map(section.into()), Part::Map(npm.package.clone())];
+          write.update_mark(PickPath::new(manifest.clone(), Picker::Json(ScanningPicker::new(parts))), after, proj_id)?;
+          wrote = true;
+        }
+      }
+      if let Some(lockfile) = npm.lockfile.filter(|_| wrote) {
+        write.update_lockfile(lockfile.command().into(), PathBuf::from("."), proj_id);
+      }
     }
     Ok(())
   }
 
+  /// Each requirement on the depended-on project that would be rewritten for its version `val`, as it is now and as
+  /// it would be written.
+  pub fn rewrites<S: StateRead>(
+    &self, read: &S, root: Option<&String>, val: &str, proj_id: &ProjectId
+  ) -> Result<Vec<(String, String)>> {
+    let mut rewrites = Vec::new();
+    for file in &self.files {
+      rewrites.push((file.read_value(read, root, proj_id)?, self.style.apply(val, file.is_json())?));
+    }
+    if let Some(npm) = &self.npm {
+      let manifest = PathBuf::from_slash(rooted_at(root.map(|r| r.as_str()), "package.json"));
+      for (_, before) in npm.requirements(read, &manifest)? {
+        if let Some(after) = npm_requirement(&before, val) {
+          rewrites.push((before, after));
+        }
+      }
+    }
+    Ok(rewrites)
+  }
+
   pub fn size(&self) -> &RelativeSize { &self.size }
 }
 
+/// A dependency on a package of the same npm, pnpm, or yarn workspace, whose requirements are found in each of the
+/// dependency sections of the dependent's `package.json`.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct NpmDepends {
+  package: String,
+  lockfile: Option<NpmLockfile>
+}
+
+const NPM_SECTIONS: &[&str] = &["dependencies", "devDependencies", "peerDependencies", "optionalDependencies"];
+
+impl NpmDepends {
+  /// The sections of the `manifest` that require the package, and what they require.
+  fn requirements<S: StateRead>(&self, read: &S, manifest: &Path) -> Result<Vec<(&'static str, String)>> {
+    let data: serde_json::Value = serde_json::from_str(&read.read_file(manifest)?)
+      .with_context(|| format!("Can't parse {}.", manifest.display()))?;
+    let found = NPM_SECTIONS.iter().filter_map(|section| {
+      let req = data.get(section)?.get(&self.package)?.as_str()?;
+      Some((*section, req.to_string()))
+    });
+    Ok(found.collect())
+  }
+}
+
+/// The package manager whose lockfile is updated after a workspace dependency is rewritten.
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum NpmLockfile {
+  Npm,
+  Pnpm,
+  Yarn
+}
+
+impl NpmLockfile {
+  fn command(self) -> &'static str {
+    match self {
+      NpmLockfile::Npm => "npm install --package-lock-only --ignore-scripts",
+      NpmLockfile::Pnpm => "pnpm install --lockfile-only --ignore-scripts",
+      NpmLockfile::Yarn => "yarn install --mode update-lockfile"
+    }
+  }
+}
+
+/// An npm requirement like `before`, but on the version `val`: its `workspace:` protocol and range operator (like
+/// `^` or `~`) are kept. A requirement that isn't on a single version (like `workspace:^`, which the package manager
+/// fills in when it publishes, or `1.x`) is left as it is.
+fn npm_requirement(before: &str, val: &str) -> Option<String> {
+  let (protocol, range) = match before.strip_prefix("workspace:") {
+    Some(range) => ("workspace:", range),
+    None => ("", before)
+  };
+  let split = range.find(|c: char| c.is_ascii_digit())?;
+  let (operator, version) = range.split_at(split);
+  if !["", "^", "~", "=", ">="].contains(&operator) || Size::parts(version).is_err() {
+    return None;
+  }
+  Some(format!("{}{}{}", protocol, operator, val))
+}
+
+/// How a depended-on version is written into a dependent's files.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum DependsStyle {
+  /// `1.4.0`
+  #[default]
+  Exact,
+  /// `^1.4.0`
+  Caret,
+  /// `~1.4.0`
+  Tilde,
+  /// `>=1.4.0, <2.0.0`: the same versions as `caret`, spelled out.
+  Range
+}
+
+impl DependsStyle {
+  /// Style the version `vers`. A `range` is written in npm's syntax (`>=1.4.0 <2.0.0`) into a `json` file such as
+  /// `package.json`, and in Cargo's syntax elsewhere.
+  pub fn apply(self, vers: &str, json: bool) -> Result<String> {
+    match self {
+      DependsStyle::Exact => Ok(vers.to_string()),
+      DependsStyle::Caret => Ok(format!("^{}", vers)),
+      DependsStyle::Tilde => Ok(format!("~{}", vers)),
+      DependsStyle::Range => {
+        let [major, minor, patch] = Size::parts(vers)?;
+        let upper = match (major, minor) {
+          (0, 0) => format!("0.0.{}", patch + 1),
+          (0, _) => format!("0.{}.0", minor + 1),
+          _ => format!("{}.0.0", major + 1)
+        };
+        let separator = if json { " " } else { ", " };
+        Ok(format!(">={}{}<{}", vers, separator, upper))
+      }
+    }
+  }
+}
+
 fn default_relative_size() -> RelativeSize { RelativeSize::Match }
 
 #[derive(Debug, Clone)]
@@ -717,15 +2142,50 @@ pub struct HookSet {
 }
 
 impl HookSet {
-  pub fn execute(&self, which: &str, root: &Option<&String>) -> Result<()> {
+  /// Run the `which` hook of the `name` project, if it has one.
+  pub fn execute(&self, which: &str, name: &str, root: &Option<&String>, env: &[(&str, &str)]) -> Result<()> {
     if let Some(hook) = self.hooks.get(which) {
-      hook.execute(root)?;
+      hook.execute(&format!("{} {}", name, which), root, env)?;
     }
 
     Ok(())
   }
 
-  pub fn execute_post_write(&self, root: &Option<&String>) -> Result<()> { self.execute("post_write", root) }
+  pub fn has(&self, which: &str) -> bool { self.hooks.contains_key(which) }
+
+  /// Whether the hook is declared safe to run in a dry run.
+  pub fn is_safe(&self, which: &str) -> bool {
+    self.hooks.get(which).map(|hook| hook.safety == HookSafety::Safe).unwrap_or(false)
+  }
+}
+
+/// A project's hooks, with what they need to run: the project's name and root, and their environment.
+pub struct ProjectHooks<'a> {
+  name: &'a str,
+  root: Option<&'a String>,
+  hooks: &'a HookSet,
+  env: Vec<(&'static str, String)>
+}
+
+impl<'a> ProjectHooks<'a> {
+  pub fn new(proj: &'a Project, env: Vec<(&'static str, String)>) -> ProjectHooks<'a> {
+    ProjectHooks { name: proj.name(), root: proj.root(), hooks: proj.hooks(), env }
+  }
+
+  pub fn name(&self) -> &str { self.name }
+  pub fn has(&self, which: &str) -> bool { self.hooks.has(which) }
+  pub fn is_safe(&self, which: &str) -> bool { self.hooks.is_safe(which) }
+
+  /// Also set `key` in the environment of the hooks.
+  pub fn with_env(mut self, key: &'static str, val: &str) -> ProjectHooks<'a> {
+    self.env.push((key, val.to_string()));
+    self
+  }
+
+  pub fn execute(&self, which: &str) -> Result<()> {
+    let env: Vec<_> = self.env.iter().map(|(k, v)| (*k, v.as_str())).collect();
+    self.hooks.execute(which, self.name, &self.root, &env)
+  }
 }
 
 impl<'de> Deserialize<'de> for HookSet {
@@ -740,34 +2200,151 @@ impl Serialize for HookSet {
 
 #[derive(Clone, Debug)]
 pub struct Hook {
-  cmd: String
+  cmd: String,
+  safety: HookSafety,
+  timeout: Option<u64>
 }
 
-impl Hook {
-  pub fn execute(&self, root: &Option<&String>) -> Result<()> {
-    use std::process::Command;
+/// Whether a project's version tags are lightweight (the default), or annotated with a `tag_message`.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum TagStyle {
+  #[default]
+  Lightweight,
+  Annotated
+}
 
-    let mut command = Command::new("bash");
-    if let Some(root) = root {
-      command.current_dir(root);
-    }
-    let status = command.args(["-e", "-c", &self.cmd]).status()?;
-    if !status.success() {
-      bail!("Unable to run hook {}.", self.cmd);
-    } else {
-      Ok(())
-    }
+/// A floating tag of a project, which is moved to each new version: `major` (like `v1`) or `minor` (like `v1.4`).
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum FloatingTag {
+  Major,
+  Minor
+}
+
+/// How a project's increments are applied while its major version is 0, where semver allows any change to break
+/// compatibility.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ZeroMajorPolicy {
+  /// Increments are applied as they are, so a breaking change releases 1.0.0.
+  #[default]
+  Keep,
+  /// A major increment is applied as a minor one.
+  Minor,
+  /// Every increment is applied one size smaller: major as minor, and minor as patch.
+  Shift
+}
+
+/// Whether a hook may be run by a dry run: a safe hook (such as a validation script) has no side effects, like
+/// publishing or pushing.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum HookSafety {
+  Safe,
+  #[default]
+  Unsafe
+}
+
+impl Hook {
+  /// Run the hook, streaming each line of its output with the `prefix`. If it has a `timeout` and doesn't finish
+  /// in time, it's killed.
+  pub fn execute(&self, prefix: &str, root: &Option<&String>, env: &[(&str, &str)]) -> Result<()> {
+    let mut command = shell(&self.cmd, &[]);
+    if let Some(root) = root {
+      command.current_dir(root);
+    }
+    command.envs(env.iter().copied());
+    let mut child = command.stdout(Stdio::piped()).stderr(Stdio::piped()).spawn()?;
+
+    let streams = [
+      child.stdout.take().map(|out| stream_lines(out, prefix, false)),
+      child.stderr.take().map(|err| stream_lines(err, prefix, true))
+    ];
+
+    let status = match self.timeout {
+      Some(secs) => match wait_timeout(&mut child, Duration::from_secs(secs))? {
+        Some(status) => status,
+        // Don't wait for the output: anything the hook started in the background may still have it open.
+        None => bail!("Hook {} didn't finish in {} seconds.", self.cmd, secs)
+      },
+      None => child.wait()?
+    };
+    for stream in streams.into_iter().flatten() {
+      let _ = stream.join();
+    }
+
+    if !status.success() {
+      bail!("Unable to run hook {}.", self.cmd);
+    } else {
+      Ok(())
+    }
+  }
+}
+
+/// Print each line read from a hook's output as it arrives, with the `prefix`.
+fn stream_lines<R: Read + Send + 'static>(read: R, prefix: &str, err: bool) -> JoinHandle<()> {
+  let prefix = prefix.to_string();
+  std::thread::spawn(move || {
+    for line in BufReader::new(read).lines().map_while(Result::ok) {
+      println_hook_output(&prefix, &line, err);
+    }
+  })
+}
+
+/// Wait for the child to exit, killing it if it takes longer than `timeout`. Returns `None` if it was killed.
+fn wait_timeout(child: &mut Child, timeout: Duration) -> Result<Option<ExitStatus>> {
+  let deadline = Instant::now() + timeout;
+  loop {
+    if let Some(status) = child.try_wait()? {
+      return Ok(Some(status));
+    }
+    if Instant::now() >= deadline {
+      child.kill()?;
+      child.wait()?;
+      return Ok(None);
+    }
+    std::thread::sleep(HOOK_POLL);
   }
 }
 
 impl<'de> Deserialize<'de> for Hook {
   fn deserialize<D: Deserializer<'de>>(desr: D) -> std::result::Result<Hook, D::Error> {
-    Ok(Hook { cmd: Deserialize::deserialize(desr)? })
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum HookDef {
+      Cmd(String),
+      Full {
+        cmd: String,
+        #[serde(default)]
+        safety: HookSafety,
+        #[serde(default)]
+        timeout: Option<u64>
+      }
+    }
+
+    match Deserialize::deserialize(desr)? {
+      HookDef::Cmd(cmd) => Ok(Hook { cmd, safety: HookSafety::Unsafe, timeout: None }),
+      HookDef::Full { cmd, safety, timeout } => Ok(Hook { cmd, safety, timeout })
+    }
   }
 }
 
 impl Serialize for Hook {
-  fn serialize<S: Serializer>(&self, srlr: S) -> std::result::Result<S::Ok, S::Error> { self.cmd.serialize(srlr) }
+  fn serialize<S: Serializer>(&self, srlr: S) -> std::result::Result<S::Ok, S::Error> {
+    #[derive(Serialize)]
+    struct FullHook<'a> {
+      cmd: &'a str,
+      safety: HookSafety,
+      #[serde(skip_serializing_if = "Option::is_none")]
+      timeout: Option<u64>
+    }
+
+    match (self.safety, self.timeout) {
+      (HookSafety::Unsafe, None) => self.cmd.serialize(srlr),
+      (safety, timeout) => FullHook { cmd: &self.cmd, safety, timeout }.serialize(srlr)
+    }
+  }
 }
 
 fn expand_name(name: &str, sub: &SubExtent) -> String {
@@ -777,6 +2354,17 @@ fn expand_name(name: &str, sub: &SubExtent) -> String {
   }
 }
 
+/// The `path` with forward slashes, if it was written with Windows separators.
+fn slashed(path: &str) -> String { path.replace('\\', "/") }
+
+/// The pattern `pat`, relative to `root`, made relative to the repo instead.
+fn rooted_at(root: Option<&str>, pat: &str) -> String {
+  match root {
+    Some(root) if root != "." => PathBuf::from_slash(root).join(PathBuf::from_slash(pat)).to_slash_lossy().into_owned(),
+    _ => pat.to_string()
+  }
+}
+
 fn expand_root(root: Option<&String>, sub: &SubExtent) -> Option<String> {
   match root {
     Some(root) => match sub.dir() {
@@ -830,15 +2418,17 @@ impl SubExtent {
 // #[serde(untagged)]
 enum Location {
   File(FileLocation),
-  Tag(TagLocation)
+  Tag(TagLocation),
+  Command(CommandLocation)
 }
 
 impl Location {
   pub fn is_tags(&self) -> bool { matches!(self, Location::Tag(_)) }
+  pub fn is_json(&self) -> bool { matches!(self, Location::File(l) if matches!(l.picker, Picker::Json(_))) }
 
   pub fn tag_majors(&self) -> Option<&[u32]> {
     match self {
-      Location::File(_) => None,
+      Location::File(_) | Location::Command(_) => None,
       Location::Tag(tagl) => tagl.majors()
     }
   }
@@ -846,19 +2436,31 @@ impl Location {
   pub fn write_value(&self, write: &mut StateWrite, root: Option<&String>, vers: &str, id: &ProjectId) -> Result<()> {
     match self {
       Location::File(l) => l.write_value(write, root, vers, id),
-      Location::Tag(_) => Ok(())
+      Location::Tag(_) => Ok(()),
+      Location::Command(l) => {
+        l.write_value(write, root, vers, id);
+        Ok(())
+      }
     }
   }
 
   pub fn read_value<S: StateRead>(&self, read: &S, root: Option<&String>, proj: &ProjectId) -> Result<String> {
     match self {
       Location::File(l) => l.read_value(read, root),
-      Location::Tag(l) => Ok(l.read_value(read, proj))
+      Location::Tag(l) => Ok(l.read_value(read, proj)),
+      Location::Command(l) => l.read_value(read, root, proj)
     }
   }
 
   pub fn is_tag(&self) -> bool { matches!(self, Location::Tag(..)) }
 
+  pub fn file_path(&self, root: Option<&String>) -> Option<PathBuf> {
+    match self {
+      Location::File(l) => Some(l.rooted(root)),
+      Location::Tag(_) | Location::Command(_) => None
+    }
+  }
+
   #[cfg(test)]
   pub fn picker(&self) -> &Picker {
     match self {
@@ -899,6 +2501,7 @@ impl<'de> Deserialize<'de> for Location {
         let mut tags: Option<TagSpec> = None;
         let mut code: Option<String> = None;
         let mut format: Option<String> = None;
+        let mut command: Option<CommandLocation> = None;
 
         while let Some(key) = map.next_key::<String>()? {
           match key.as_str() {
@@ -918,11 +2521,20 @@ impl<'de> Deserialize<'de> for Location {
             "format" => {
               format = Some(map.next_value()?);
             }
+            "command" => {
+              command = Some(map.next_value()?);
+            }
             other => return Err(de::Error::invalid_value(Unexpected::Str(other), &"a location key"))
           }
         }
 
-        if let Some(file) = file {
+        if let Some(command) = command {
+          if file.is_some() || tags.is_some() || pattern.is_some() || parts.is_some() || format.is_some() {
+            Err(de::Error::custom("can't have other fields with 'command' for location"))
+          } else {
+            Ok(Location::Command(command))
+          }
+        } else if let Some(file) = file {
           if tags.is_some() {
             Err(de::Error::custom("cant have both 'file' and 'tags' for location"))
           } else if pattern.is_none() && parts.is_none() {
@@ -951,7 +2563,7 @@ impl<'de> Deserialize<'de> for Location {
             Ok(Location::Tag(TagLocation { tags }))
           }
         } else {
-          Err(de::Error::custom("must have 'file' or 'tags' for location"))
+          Err(de::Error::custom("must have 'file', 'tags', or 'command' for location"))
         }
       }
     }
@@ -973,11 +2585,12 @@ impl TagLocation {
   }
 }
 
+// `MajorTag` is tried first, since any map (even an empty one) is a `DefaultTag`.
 #[derive(Clone, Deserialize, Debug)]
 #[serde(untagged)]
 enum TagSpec {
-  DefaultTag(DefaultTagSpec),
-  MajorTag(MajorTagSpec)
+  MajorTag(MajorTagSpec),
+  DefaultTag(DefaultTagSpec)
 }
 
 impl TagSpec {
@@ -1001,9 +2614,12 @@ impl TagSpec {
 
 #[derive(Clone, Deserialize, Debug)]
 struct DefaultTagSpec {
+  #[serde(default = "default_tag_version")]
   default: String
 }
 
+fn default_tag_version() -> String { "0.0.0".to_string() }
+
 #[derive(Clone, Deserialize, Debug)]
 struct MajorTagSpec {
   majors: Vec<u32>
@@ -1013,6 +2629,38 @@ impl MajorTagSpec {
   pub fn majors(&self) -> &[u32] { &self.majors }
 }
 
+/// A version that's read and written by running commands, in the project root: `read` prints the version, and
+/// `write` is given the new version as both its first argument and `$VERSIO_VERSION`.
+#[derive(Clone, Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct CommandLocation {
+  read: String,
+  write: String
+}
+
+impl CommandLocation {
+  fn write_value(&self, write: &mut StateWrite, root: Option<&String>, vers: &str, id: &ProjectId) {
+    write.write_by_command(self.write.clone(), command_dir(root), vers, id);
+  }
+
+  /// Commands can only be run on the current files: an earlier version is taken from the project's latest tag, or
+  /// from the current files if the project has never been tagged.
+  fn read_value<S: StateRead>(&self, read: &S, root: Option<&String>, proj: &ProjectId) -> Result<String> {
+    match read.command_version(&self.read, root)? {
+      Some(vers) => Ok(vers),
+      None => match read.latest_tag(proj) {
+        Some(tag) => Ok(tag.clone()),
+        None => run_version_command(&self.read, &command_dir(root))
+      }
+    }
+  }
+}
+
+/// The directory to run a project's commands in, relative to the repo root.
+fn command_dir(root: Option<&String>) -> PathBuf {
+  root.map(PathBuf::from_slash).unwrap_or_else(|| PathBuf::from("."))
+}
+
 #[derive(Clone, Deserialize, Debug)]
 struct FileLocation {
   file: String,
@@ -1059,13 +2707,46 @@ pub struct CommitConfig {
   #[serde(default = "CommitConfig::default_author")]
   author: String,
   #[serde(default = "CommitConfig::default_email")]
-  email: String
+  email: String,
+  #[serde(default)]
+  skip_ci: Option<String>,
+  #[serde(default)]
+  tag_skip_ci: Option<String>,
+  #[serde(default)]
+  sign: SigningConfig,
+  #[serde(default)]
+  state_storage: StateStorage
 }
 
 impl CommitConfig {
   pub fn message(&self) -> &str { &self.message }
   pub fn author(&self) -> &str { &self.author }
   pub fn email(&self) -> &str { &self.email }
+  pub fn skip_ci(&self) -> Option<&str> { self.skip_ci.as_deref().and_then(skip_ci_marker) }
+  pub fn tag_skip_ci(&self) -> Option<&str> { self.tag_skip_ci.as_deref().and_then(skip_ci_marker) }
+  pub fn sign(&self) -> &SigningConfig { &self.sign }
+  pub fn state_storage(&self) -> StateStorage { self.state_storage }
+
+  /// Apply the user's own signing preferences, which take precedence over the config file.
+  pub fn with_signing(&self, prefs: &SigningConfig) -> CommitConfig {
+    CommitConfig { sign: self.sign.overlay(prefs), ..self.clone() }
+  }
+
+  /// The full release commit message, including any CI-skip marker.
+  pub fn commit_message(&self) -> String {
+    match self.skip_ci() {
+      Some(marker) => format!("{} {}", self.message, marker),
+      None => self.message.clone()
+    }
+  }
+
+  /// The full annotation of the prev tag, including any CI-skip marker on its own line.
+  pub fn tag_message(&self, msg: &str) -> String {
+    match self.tag_skip_ci() {
+      Some(marker) => format!("{}\n{}", msg, marker),
+      None => msg.to_string()
+    }
+  }
 
   pub fn default_message() -> String { "build(deploy): Versio update versions".into() }
   pub fn default_author() -> String { "Versio".into() }
@@ -1077,11 +2758,72 @@ impl Default for CommitConfig {
     Self {
       message: CommitConfig::default_message(),
       author: CommitConfig::default_author(),
-      email: CommitConfig::default_email()
+      email: CommitConfig::default_email(),
+      skip_ci: None,
+      tag_skip_ci: None,
+      sign: SigningConfig::default(),
+      state_storage: StateStorage::default()
+    }
+  }
+}
+
+/// How to sign release commits and annotated tags. Anything left unset falls back to the git config
+/// (`commit.gpgSign`, `tag.gpgSign`, `user.signingKey`, `gpg.format`).
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct SigningConfig {
+  #[serde(default)]
+  commits: Option<bool>,
+  #[serde(default)]
+  tags: Option<bool>,
+  #[serde(default)]
+  key: Option<String>,
+  #[serde(default)]
+  format: Option<SigningFormat>
+}
+
+impl SigningConfig {
+  pub fn commits(&self) -> Option<bool> { self.commits }
+  pub fn tags(&self) -> Option<bool> { self.tags }
+  pub fn key(&self) -> Option<&str> { self.key.as_deref() }
+  pub fn format(&self) -> Option<SigningFormat> { self.format }
+
+  fn overlay(&self, other: &SigningConfig) -> SigningConfig {
+    SigningConfig {
+      commits: other.commits.or(self.commits),
+      tags: other.tags.or(self.tags),
+      key: other.key.clone().or_else(|| self.key.clone()),
+      format: other.format.or(self.format)
     }
   }
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SigningFormat {
+  #[serde(alias = "gpg")]
+  Openpgp,
+  Ssh
+}
+
+/// Where the prev tag's release state is kept: in the tag's own annotation, or in a git note on the tagged commit.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum StateStorage {
+  #[default]
+  Annotation,
+  Notes
+}
+
+/// Resolve a CI-skip setting: either a known provider (or "none"), or a literal marker string.
+fn skip_ci_marker(name: &str) -> Option<&str> {
+  match name {
+    "none" | "" => None,
+    "github" | "gitlab" | "bitbucket" | "circleci" | "travis" | "jenkins" | "default" => Some("[skip ci]"),
+    "azure" => Some("***NO_CI***"),
+    other => Some(other)
+  }
+}
+
 #[derive(Deserialize, Debug)]
 struct Subs {
   #[serde(default)]
@@ -1112,7 +2854,7 @@ impl Subs {
 /// drop-in replacement. The "major" part of the version number will be incremented, and other parts reset.
 /// - **Fail**: A change occured to the project that could not be understood. No changes will be made to any
 /// version numbers; in fact, the entire process is prematurely halted.
-#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum Size {
   Fail,
@@ -1127,7 +2869,7 @@ impl Size {
   pub fn is_size(v: &str) -> bool { Size::from_str(v).is_ok() }
   pub fn is_failure(&self) -> bool { matches!(self, Size::Fail) }
 
-  fn from_str(v: &str) -> Result<Size> {
+  pub(crate) fn from_str(v: &str) -> Result<Size> {
     match v {
       "major" => Ok(Size::Major),
       "minor" => Ok(Size::Minor),
@@ -1140,37 +2882,146 @@ impl Size {
   }
 
   pub fn parts(v: &str) -> Result<[u32; 3]> {
-    let parts: Vec<_> = v
-      .split('.')
-      .map(|p| p.parse())
-      .collect::<std::result::Result<_, _>>()
-      .with_context(|| format!("Couldn't split {} into parts", v))?;
+    let parts = Size::all_parts(v)?;
     if parts.len() != 3 {
       return err!("Not a 3-part version: {}", v);
     }
     Ok([parts[0], parts[1], parts[2]])
   }
 
+  /// The numbers of a version of any number of parts, such as `2.1` or `1.4.2.318`. Build metadata is ignored.
+  pub fn all_parts(v: &str) -> Result<Vec<u32>> {
+    strip_build_metadata(v)
+      .split('.')
+      .map(|p| p.parse())
+      .collect::<std::result::Result<_, _>>()
+      .with_context(|| format!("Couldn't split {} into parts", v))
+  }
+
+  /// Whether version `v1` is lower than `v2`. Versions of different lengths are compared as if the shorter one ended
+  /// in zeros, so `1.2` is the same as `1.2.0`.
   pub fn less_than(v1: &str, v2: &str) -> Result<bool> {
-    let p1 = Size::parts(v1)?;
-    let p2 = Size::parts(v2)?;
+    Ok(compare_parts(&Size::all_parts(v1)?, &Size::all_parts(v2)?) == Ordering::Less)
+  }
+
+  /// The size that's applied to `v` under a project's `policy`: while `v` is below 1.0.0, the policy may make it
+  /// smaller than this one.
+  pub fn under_zero_major(self, v: &str, policy: ZeroMajorPolicy) -> Result<Size> {
+    if Size::all_parts(v)?[0] != 0 {
+      return Ok(self);
+    }
+    Ok(match (policy, self) {
+      (ZeroMajorPolicy::Minor, Size::Major) | (ZeroMajorPolicy::Shift, Size::Major) => Size::Minor,
+      (ZeroMajorPolicy::Shift, Size::Minor) => Size::Patch,
+      _ => self
+    })
+  }
+}
+
+/// The version `v` without its build metadata: everything from a `+` on, which doesn't count in comparisons.
+pub fn strip_build_metadata(v: &str) -> &str { v.split('+').next().unwrap_or(v) }
 
-    Ok(p1[0] < p2[0] || (p1[0] == p2[0] && (p1[1] < p2[1] || (p1[1] == p2[1] && p1[2] < p2[2]))))
+/// What a project's `build_metadata` template can use: the commit that's released, the date, and the number of the
+/// CI run, if there is one.
+pub struct BuildInfo {
+  sha: String,
+  date: String,
+  run: String
+}
+
+impl BuildInfo {
+  pub fn new(sha: Option<String>, now: DateTime<Utc>) -> BuildInfo {
+    let run = CI_RUN_VARS.iter().find_map(|name| std::env::var(name).ok().filter(|v| !v.is_empty()));
+    BuildInfo {
+      sha: sha.unwrap_or_default(),
+      date: now.format("%Y%m%d").to_string(),
+      run: run.unwrap_or_default()
+    }
+  }
+}
+
+/// Compare the numbers of two versions, as if the shorter one ended in zeros.
+pub fn compare_parts(p1: &[u32], p2: &[u32]) -> Ordering {
+  let len = p1.len().max(p2.len());
+  let at = |parts: &[u32], i: usize| parts.get(i).copied().unwrap_or(0);
+  (0 .. len).map(|i| at(p1, i).cmp(&at(p2, i))).find(|o| o != &Ordering::Equal).unwrap_or(Ordering::Equal)
+}
+
+/// The shape of a project's versions: how many numbers they have, and where the build number of a four-part
+/// version comes from.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct VersionFormat {
+  #[serde(default = "VersionFormat::default_parts")]
+  parts: usize,
+  #[serde(default)]
+  build: BuildNumber
+}
+
+impl Default for VersionFormat {
+  fn default() -> VersionFormat { VersionFormat { parts: VersionFormat::default_parts(), build: Default::default() } }
+}
+
+impl VersionFormat {
+  fn default_parts() -> usize { 3 }
+
+  /// An example version of this format, for checking tag templates.
+  fn sample(&self) -> String { ["1", "2", "3", "4"][.. self.parts].join(".") }
+
+  fn validate(&self) -> Result<()> {
+    if !(2 ..= 4).contains(&self.parts) {
+      bail!("parts must be 2, 3, or 4, not {}", self.parts);
+    }
+    Ok(())
   }
 
-  pub fn apply(self, v: &str) -> Result<String> {
-    let parts = Size::parts(v)?;
+  /// The version that an increment of `size` makes of `v`, as of `now`. A two-part version (`major.minor`) has no
+  /// patch number, so a patch increment raises its minor number. The build number of a four-part version
+  /// (`major.minor.patch.build`) is raised by every increment, and never reset.
+  pub fn apply(&self, size: Size, v: &str, now: DateTime<Utc>) -> Result<String> {
+    let mut parts = Size::all_parts(v)?;
+    if parts.len() != self.parts {
+      bail!("Not a {}-part version: {}", self.parts, v);
+    }
 
-    let newv = match self {
-      Size::Major => format!("{}.{}.{}", parts[0] + 1, 0, 0),
-      Size::Minor => format!("{}.{}.{}", parts[0], parts[1] + 1, 0),
-      Size::Patch => format!("{}.{}.{}", parts[0], parts[1], parts[2] + 1),
-      Size::None => format!("{}.{}.{}", parts[0], parts[1], parts[2]),
-      Size::Empty => format!("{}.{}.{}", parts[0], parts[1], parts[2]),
+    // The build number isn't one of the numbers that an increment raises and resets.
+    let numbered = self.parts.min(3);
+    let index = match size {
+      Size::Major => 0,
+      Size::Minor => 1,
+      Size::Patch => numbered - 1,
+      Size::None | Size::Empty => return Ok(v.to_string()),
       Size::Fail => bail!("Unparseable conventional commit encountered.")
     };
+    parts[index] += 1;
+    for part in &mut parts[index + 1 .. numbered] {
+      *part = 0;
+    }
+    if self.parts == 4 {
+      parts[3] = self.build.next(parts[3], now);
+    }
+    Ok(parts.iter().map(|p| p.to_string()).collect::<Vec<_>>().join("."))
+  }
+}
+
+/// Where the build number of a four-part version comes from.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum BuildNumber {
+  /// One more than the previous build number.
+  #[default]
+  Counter,
+  /// The date of the release as `yyDDD` (the year, and the day of the year), which fits in the 16 bits that some
+  /// installers allow; or one more than the previous build number, if that's already as large.
+  Timestamp
+}
 
-    Ok(newv)
+impl BuildNumber {
+  fn next(self, prev: u32, now: DateTime<Utc>) -> u32 {
+    match self {
+      BuildNumber::Counter => prev + 1,
+      BuildNumber::Timestamp => now.format("%y%j").to_string().parse::<u32>().unwrap_or(0).max(prev + 1)
+    }
   }
 }
 
@@ -1228,7 +3079,9 @@ impl Ord for Size {
 
 fn default_includes() -> Vec<String> { vec!["**/*".into()] }
 fn default_prev_tag() -> String { "versio-prev".into() }
-fn default_branch() -> Option<String> { None }
+fn default_stale_months() -> u32 { 6 }
+fn default_size_trailer() -> String { "Versio-Size".into() }
+fn default_skip_trailer() -> String { "Versio-Skip".into() }
 
 fn deser_labels<'de, D: Deserializer<'de>>(desr: D) -> std::result::Result<Vec<String>, D::Error> {
   struct StringsVisitor;
@@ -1251,11 +3104,171 @@ fn deser_labels<'de, D: Deserializer<'de>>(desr: D) -> std::result::Result<Vec<S
   desr.deserialize_any(StringsVisitor)
 }
 
-fn deser_sizes<'de, D: Deserializer<'de>>(desr: D) -> std::result::Result<HashMap<String, Size>, D::Error> {
+/// The `sizes` of the config: the size of each conventional commit kind, and the `rules` that size a commit by its
+/// summary instead.
+#[derive(Clone, Debug, Default)]
+pub struct Sizes {
+  kinds: HashMap<String, Size>,
+  rules: Vec<(Regex, Size)>
+}
+
+impl Sizes {
+  pub fn get(&self, kind: &str) -> Option<&Size> { self.kinds.get(kind) }
+  pub fn contains_key(&self, kind: &str) -> bool { self.kinds.contains_key(kind) }
+  pub fn insert(&mut self, kind: String, size: Size) { self.kinds.insert(kind, size); }
+
+  /// The size of the first rule that matches the commit summary, if any.
+  pub fn rule_for(&self, summary: &str) -> Option<Size> {
+    self.rules.iter().find(|(regex, _)| regex.is_match(summary)).map(|(_, size)| *size)
+  }
+}
+
+/// The thresholds of a commit's diff, at which a commit whose message has no conventional kind is a `minor` or
+/// `major` change, instead of a failure. A commit below both thresholds is a `patch`.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct DiffSizes {
+  minor: Option<DiffThreshold>,
+  major: Option<DiffThreshold>
+}
+
+impl DiffSizes {
+  fn validate(&self) -> Result<()> {
+    if self.minor.is_none() && self.major.is_none() {
+      bail!("no minor or major threshold");
+    }
+    for threshold in self.minor.iter().chain(self.major.iter()) {
+      if threshold.files.is_none() && threshold.lines.is_none() {
+        bail!("threshold has no files or lines");
+      }
+    }
+    Ok(())
+  }
+
+  /// The size of a commit that touches `files` files and changes `lines` lines, if the lines are known.
+  pub fn size(&self, files: usize, lines: Option<usize>) -> Size {
+    let reaches = |t: &Option<DiffThreshold>| t.map(|t| t.reached_by(files, lines)).unwrap_or(false);
+    if reaches(&self.major) {
+      Size::Major
+    } else if reaches(&self.minor) {
+      Size::Minor
+    } else {
+      Size::Patch
+    }
+  }
+}
+
+/// The versions that a project may not be released at, from its `constraints`: those below its `minimum`, in one of
+/// its `skip`ped (yanked) ranges, or in one of its `frozen` lines.
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct VersionConstraints {
+  minimum: Option<String>,
+  #[serde(default)]
+  skip: Vec<String>,
+  #[serde(default)]
+  frozen: Vec<String>
+}
+
+impl VersionConstraints {
+  fn validate(&self) -> Result<()> {
+    if let Some(minimum) = &self.minimum {
+      Size::parts(minimum).with_context(|| format!("illegal minimum \"{}\"", minimum))?;
+    }
+    for range in self.skip.iter().chain(self.frozen.iter()) {
+      VersionRange::parse(range)?;
+    }
+    Ok(())
+  }
+
+  fn violation(&self, vers: &str) -> Result<Option<String>> {
+    if self.minimum.is_none() && self.skip.is_empty() && self.frozen.is_empty() {
+      return Ok(None);
+    }
+    let parts = Size::parts(vers)?;
+    if let Some(minimum) = &self.minimum {
+      if parts < Size::parts(minimum)? {
+        return Ok(Some(format!("it's below the minimum version {}", minimum)));
+      }
+    }
+    for range in &self.skip {
+      if VersionRange::parse(range)?.contains(parts) {
+        return Ok(Some(format!("it's in the skipped range {}", range)));
+      }
+    }
+    for range in &self.frozen {
+      if VersionRange::parse(range)?.contains(parts) {
+        return Ok(Some(format!("the {} line is frozen", range)));
+      }
+    }
+    Ok(None)
+  }
+}
+
+/// A range of versions like `1.3.x` or `1.x`: its leading parts are fixed, and the rest (which may be left off, or
+/// be `x` or `*`) can be anything. A full version like `1.3.2` is a range of just that version.
+struct VersionRange {
+  fixed: Vec<u32>
+}
+
+impl VersionRange {
+  fn parse(range: &str) -> Result<VersionRange> {
+    let illegal = || bad!("illegal version range \"{}\"", range);
+    let pieces: Vec<_> = range.split('.').collect();
+    if pieces.len() > 3 {
+      return Err(illegal());
+    }
+
+    let mut fixed = Vec::new();
+    let mut wild = false;
+    for piece in pieces {
+      match piece {
+        "x" | "X" | "*" => wild = true,
+        _ if wild => return Err(illegal()),
+        _ => fixed.push(piece.parse().map_err(|_| illegal())?)
+      }
+    }
+    Ok(VersionRange { fixed })
+  }
+
+  fn contains(&self, parts: [u32; 3]) -> bool { parts.starts_with(&self.fixed) }
+}
+
+/// A commit reaches the threshold if it touches at least `files` files, or changes (adds or deletes) at least
+/// `lines` lines.
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(deny_unknown_fields)]
+struct DiffThreshold {
+  files: Option<usize>,
+  lines: Option<usize>
+}
+
+impl DiffThreshold {
+  fn reached_by(&self, files: usize, lines: Option<usize>) -> bool {
+    self.files.map(|t| files >= t).unwrap_or(false) || matches!((self.lines, lines), (Some(t), Some(l)) if l >= t)
+  }
+}
+
+/// Read the `rules` of the sizes: a list of single-entry maps, from a regex to a size.
+fn read_rules(rules: Vec<HashMap<String, String>>) -> Result<Vec<(Regex, Size)>> {
+  rules
+    .into_iter()
+    .map(|rule| {
+      if rule.len() != 1 {
+        bail!("A sizes rule must have exactly one pattern, not {}.", rule.len());
+      }
+      let (pattern, size) = rule.into_iter().next().unwrap();
+      let regex = Regex::new(&pattern).with_context(|| format!("Bad sizes rule \"{}\".", pattern))?;
+      Ok((regex, Size::from_str(&size)?))
+    })
+    .collect()
+}
+
+fn deser_sizes<'de, D: Deserializer<'de>>(desr: D) -> std::result::Result<Sizes, D::Error> {
   struct MapVisitor;
 
   impl<'de> Visitor<'de> for MapVisitor {
-    type Value = HashMap<String, Size>;
+    type Value = Sizes;
 
     fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result { formatter.write_str("a list of sizes") }
 
@@ -1264,6 +3277,7 @@ fn deser_sizes<'de, D: Deserializer<'de>>(desr: D) -> std::result::Result<HashMa
       M: MapAccess<'de>
     {
       let mut result = HashMap::new();
+      let mut rules = Vec::new();
       let mut using_angular = false;
 
       while let Some(val) = map.next_key::<String>()? {
@@ -1282,6 +3296,9 @@ fn deser_sizes<'de, D: Deserializer<'de>>(desr: D) -> std::result::Result<HashMa
           "use_angular" => {
             using_angular = map.next_value()?;
           }
+          "rules" => {
+            rules = read_rules(map.next_value()?).map_err(|e| de::Error::custom(format!("{:#}", e)))?;
+          }
           _ => return Err(de::Error::custom(format!("Unrecognized sizes key \"{}\".", val)))
         }
       }
@@ -1292,7 +3309,7 @@ fn deser_sizes<'de, D: Deserializer<'de>>(desr: D) -> std::result::Result<HashMa
         insert_angular(&mut result);
       }
 
-      Ok(result)
+      Ok(Sizes { kinds: result, rules })
     }
   }
 
@@ -1300,7 +3317,7 @@ fn deser_sizes<'de, D: Deserializer<'de>>(desr: D) -> std::result::Result<HashMa
 }
 
 fn insert_angular(result: &mut HashMap<String, Size>) {
-  insert_if_missing(result, "!", Size::Major);
+  insert_if_missing(result, BREAKING_KIND, Size::Major);
   insert_if_missing(result, "feat", Size::Minor);
   insert_if_missing(result, "fix", Size::Patch);
   insert_if_missing(result, "docs", Size::None);
@@ -1323,8 +3340,32 @@ fn match_opts() -> MatchOptions { MatchOptions { require_literal_separator: true
 
 #[cfg(test)]
 mod test {
-  use super::{ConfigFile, FileLocation, HashMap, Location, Picker, Project, ProjectId, ScanningPicker, Size};
+  use super::{go_module_path, npm_requirement, strip_build_metadata, version_from_tag, BuildInfo, ChangelogFormat,
+              ChangelogSection, ConfigFile, DependsStyle, FileLocation, HashMap, Hook, HookSafety, Location, Picker,
+              Project, ProjectId, ScanningPicker, SigningConfig, SigningFormat, Size, StateStorage, VersionFormat,
+              CONFIG_FILENAME};
+  use crate::errors::Result;
   use crate::scan::parts::Part;
+  use crate::state::{CurrentState, FilesRead, OldTags};
+  use chrono::{DateTime, Utc};
+  use path_slash::PathBufExt as _;
+  use std::path::{Path, PathBuf};
+
+  /// Files in memory, by their slash-separated path.
+  struct MemFiles<'a>(HashMap<&'a str, &'a str>);
+
+  impl<'a> FilesRead for MemFiles<'a> {
+    fn has_file(&self, path: &Path) -> Result<bool> { Ok(self.0.contains_key(path.to_str().unwrap())) }
+    fn read_file(&self, path: &Path) -> Result<String> { Ok(self.0[path.to_str().unwrap()].to_string()) }
+
+    fn subdirs(&self, root: Option<&String>, _regex: &str) -> Result<Vec<String>> {
+      let prefix = root.map(|r| format!("{}/", r)).unwrap_or_default();
+      let mut dirs: Vec<_> =
+        self.0.keys().filter_map(|k| k.strip_prefix(&prefix)?.split_once('/').map(|(d, _)| d.to_string())).collect();
+      dirs.dedup();
+      Ok(dirs)
+    }
+  }
 
   #[test]
   fn test_both_file_and_tags() {
@@ -1393,6 +3434,29 @@ projects:
     assert!(ConfigFile::read(config).is_err());
   }
 
+  #[test]
+  fn test_validate_cycle() {
+    let config = r#"
+projects:
+  - name: p1
+    id: 1
+    version: { file: f1 }
+    depends: { 2: { size: match } }
+  - name: p2
+    id: 2
+    version: { file: f2 }
+    depends: { 3: { size: match } }
+  - name: p3
+    id: 3
+    version: { file: f3 }
+    depends: { 2: { size: match } }
+    "#;
+
+    let err = ConfigFile::read(config).unwrap_err();
+    assert!(format!("{:#}", err).contains("p2 (2) -> p3 (3) -> p2 (2)"));
+    assert!(ConfigFile::read(&config.replace("{ 3: { size: match } }", "{}")).is_ok());
+  }
+
   #[test]
   fn test_validate_names() {
     let config = r#"
@@ -1440,59 +3504,332 @@ projects:
   }
 
   #[test]
-  fn test_validate_unascii_prefix() {
+  fn test_tag_template() {
     let config = r#"
 projects:
   - name: p1
     id: 1
-    tag_prefix: "ixthïo"
+    tag_prefix: "foo"
+    tag_template: "{prefix}_{major}_{minor}_{patch}"
+    tag_prefix_aliases: [ "" ]
     version: { file: f1 }
+  - name: p2
+    id: 2
+    tag_prefix: "bar"
+    tag_template: "release-{prefix}/{version}"
+    version: { file: f2 }
     "#;
 
-    assert!(ConfigFile::read(config).is_err());
+    let config = ConfigFile::read(config).unwrap();
+    let (p1, p2) = (&config.projects[0], &config.projects[1]);
+
+    assert_eq!(p1.full_version("1.2.3"), Some("foo_1_2_3".to_string()));
+    assert_eq!(p1.tag_version("foo_1_2_3"), Some("1.2.3".to_string()));
+    assert_eq!(p1.tag_version("_1_2_3"), Some("1.2.3".to_string()));
+    assert_eq!(p1.tag_version("foo-v1.2.3"), None);
+    assert_eq!(p2.full_version("1.2.3"), Some("release-bar/1.2.3".to_string()));
+    assert_eq!(p2.tag_version("release-bar/1.2.3"), Some("1.2.3".to_string()));
+    assert_eq!(p2.tag_version("release-bar/1.2"), None);
   }
 
   #[test]
-  fn test_validate_prefix() {
+  fn test_floating_tags() {
     let config = r#"
 projects:
   - name: p1
     id: 1
-    tag_prefix: proj
+    tag_prefix: ""
+    floating_tags: [major, minor]
     version: { file: f1 }
-
   - name: p2
     id: 2
-    tag_prefix: proj
+    tag_prefix: "bar"
+    floating_tags: [major]
     version: { file: f2 }
     "#;
 
+    let config = ConfigFile::read(config).unwrap();
+    let (p1, p2) = (&config.projects[0], &config.projects[1]);
+
+    assert_eq!(vec!["v1", "v1.4"], p1.floating_tags_for("1.4.2").unwrap());
+    assert_eq!(vec!["bar-v2"], p2.floating_tags_for("2.0.1").unwrap());
+    assert_eq!(p1.tag_version("v1.4"), None);
+
+    let config = r#"
+projects:
+  - name: p1
+    id: 1
+    tag_prefix: "foo"
+    tag_template: "{prefix}_{major}_{minor}_{patch}"
+    floating_tags: [major]
+    version: { file: f1 }
+    "#;
     assert!(ConfigFile::read(config).is_err());
   }
 
   #[test]
-  fn test_validate_ok() {
+  fn test_tag_annotation() {
     let config = r#"
 projects:
   - name: p1
     id: 1
-    tag_prefix: "_proj1-abc"
+    tag_prefix: "foo"
+    tag_style: annotated
     version: { file: f1 }
-
   - name: p2
     id: 2
-    tag_prefix: proj2
+    tag_prefix: "bar"
+    tag_style: annotated
+    tag_message: "Release {{ tag }}:\n{{ summary }}"
     version: { file: f2 }
+  - name: p3
+    id: 3
+    tag_prefix: "baz"
+    version: { file: f3 }
     "#;
 
-    assert!(ConfigFile::read(config).is_ok());
+    let config = ConfigFile::read(config).unwrap();
+    let (p1, p2, p3) = (&config.projects[0], &config.projects[1], &config.projects[2]);
+    let summary = "- Add the frobber (#12)";
+
+    assert_eq!(Some("p1 1.2.3\n\n- Add the frobber (#12)".to_string()), p1.tag_annotation("1.2.3", summary).unwrap());
+    assert_eq!(Some("p1 1.2.3".to_string()), p1.tag_annotation("1.2.3", "").unwrap());
+    let release = "Release bar-v1.2.3:\n- Add the frobber (#12)".to_string();
+    assert_eq!(Some(release), p2.tag_annotation("1.2.3", summary).unwrap());
+    assert_eq!(None, p3.tag_annotation("1.2.3", summary).unwrap());
+
+    let lightweight = r#"
+projects:
+  - name: p1
+    id: 1
+    tag_prefix: "foo"
+    tag_message: "{{ name }}"
+    version: { file: f1 }
+    "#;
+    assert!(ConfigFile::read(lightweight).is_err());
   }
 
   #[test]
-  fn test_sizes() {
+  fn test_hook_safety() {
     let config = r#"
-projects: []
-sizes:
+projects:
+  - name: p1
+    id: 1
+    version: { file: f1 }
+    hooks:
+      post_write: { cmd: "./check.sh", safety: safe }
+      post_release: "cargo publish"
+    "#;
+
+    let config = ConfigFile::read(config).unwrap();
+    let hooks = config.projects[0].hooks();
+    assert!(hooks.is_safe("post_write"));
+    assert!(!hooks.is_safe("post_release"));
+    assert!(!hooks.is_safe("pre_write"));
+  }
+
+  #[test]
+  fn test_hook_timeout() {
+    let hook: Hook = serde_yaml::from_str("{ cmd: \"cargo test\", timeout: 600 }").unwrap();
+    assert_eq!(Some(600), hook.timeout);
+    assert_eq!(HookSafety::Unsafe, hook.safety);
+    assert_eq!("cmd: cargo test\nsafety: unsafe\ntimeout: 600\n", serde_yaml::to_string(&hook).unwrap());
+
+    let hook: Hook = serde_yaml::from_str("cargo publish").unwrap();
+    assert_eq!(None, hook.timeout);
+    assert_eq!("cargo publish\n", serde_yaml::to_string(&hook).unwrap());
+  }
+
+  #[cfg(not(windows))]
+  #[test]
+  fn test_hook_timeout_kills() {
+    use std::time::{Duration, Instant};
+
+    let hook: Hook = serde_yaml::from_str("{ cmd: \"sleep 5\", timeout: 1 }").unwrap();
+    let start = Instant::now();
+    assert!(hook.execute("p1 pre_release", &None, &[]).is_err());
+    assert!(start.elapsed() < Duration::from_secs(4));
+  }
+
+  #[test]
+  fn test_validate_tag_template() {
+    let config = r#"
+projects:
+  - name: p1
+    id: 1
+    tag_prefix: "foo"
+    tag_template: "{prefix}-{major}.{minor}"
+    version: { file: f1 }
+    "#;
+
+    assert!(ConfigFile::read(config).is_err());
+  }
+
+  #[test]
+  fn test_validate_unascii_prefix() {
+    let config = r#"
+projects:
+  - name: p1
+    id: 1
+    tag_prefix: "ixthïo"
+    version: { file: f1 }
+    "#;
+
+    assert!(ConfigFile::read(config).is_err());
+  }
+
+  #[test]
+  fn test_validate_prefix() {
+    let config = r#"
+projects:
+  - name: p1
+    id: 1
+    tag_prefix: proj
+    version: { file: f1 }
+
+  - name: p2
+    id: 2
+    tag_prefix: proj
+    version: { file: f2 }
+    "#;
+
+    assert!(ConfigFile::read(config).is_err());
+  }
+
+  #[test]
+  fn test_validate_prefix_aliases() {
+    let config = r#"
+projects:
+  - name: p1
+    id: 1
+    tag_prefix: proj1
+    tag_prefix_aliases: [ "", old ]
+    version: { file: f1 }
+
+  - name: p2
+    id: 2
+    tag_prefix: proj2
+    version: { file: f2 }
+    "#;
+    assert!(ConfigFile::read(config).is_ok());
+    assert!(ConfigFile::read(&config.replace("old ]", "proj2 ]")).is_err());
+    assert!(ConfigFile::read(&config.replace("tag_prefix: proj1\n", "")).is_err());
+  }
+
+  #[test]
+  fn test_include() {
+    let root = r#"
+include:
+  - "services/*/.versio.yaml"
+  - file: "libs/.versio.yaml"
+    id_base: 100
+projects:
+  - name: root
+    id: 1
+    version: { file: VERSION }
+"#;
+    let libs = r#"
+projects:
+  - name: util
+    id: 1
+    root: util
+    version: { file: VERSION }
+  - name: net
+    id: 2
+    root: net
+    version: { file: VERSION }
+    depends: { 1: { size: match }, 10: { size: match } }
+"#;
+    let auth = "projects:\n  - name: auth\n    id: 10\n    version: { file: VERSION }\n";
+    let files = MemFiles(HashMap::from([
+      (CONFIG_FILENAME, root),
+      ("libs/.versio.yaml", libs),
+      ("services/auth/.versio.yaml", auth),
+      ("services/auth/VERSION", "1.0.0")
+    ]));
+
+    let config = ConfigFile::from_read(&files).unwrap();
+    let names: Vec<_> = config.projects().iter().map(|p| (p.id().to_string(), p.root().cloned())).collect();
+    assert_eq!(
+      vec![
+        ("1".to_string(), None),
+        ("10".to_string(), Some("services/auth".to_string())),
+        ("101".to_string(), Some("libs/util".to_string())),
+        ("102".to_string(), Some("libs/net".to_string()))
+      ],
+      names
+    );
+    let net = config.get_project(&ProjectId::from_id(102)).unwrap();
+    assert!(net.depends().contains_key(&ProjectId::from_id(101)));
+    assert!(net.depends().contains_key(&ProjectId::from_id(10)));
+    assert_eq!(&["services/auth/.versio.yaml", "libs/.versio.yaml"], config.included());
+
+    let conflict = root.replace("id_base: 100", "id_base: 9");
+    let files = MemFiles(HashMap::from([
+      (CONFIG_FILENAME, conflict.as_str()),
+      ("libs/.versio.yaml", libs),
+      ("services/auth/.versio.yaml", auth)
+    ]));
+    assert!(ConfigFile::from_read(&files).is_err());
+  }
+
+  #[test]
+  fn test_workspace() {
+    let root = r#"
+projects:
+  - name: core
+    id: 1
+    workspace: my-core
+    version: { file: Cargo.toml, toml: package.version }
+  - name: web
+    id: 2
+    workspace: "@me/web"
+    version: { file: package.json, json: version }
+"#;
+    let cargo = "[workspace]\nmembers = [\"crates/*\"]\nexclude = [\"crates/old\"]\n";
+    let npm = r#"{ "private": true, "workspaces": ["packages/*"] }"#;
+    let files = MemFiles(HashMap::from([
+      (CONFIG_FILENAME, root),
+      ("Cargo.toml", cargo),
+      ("crates/core/Cargo.toml", "[package]\nname = \"my-core\"\nversion = \"1.0.0\"\n"),
+      ("crates/old/Cargo.toml", "[package]\nname = \"my-core\"\nversion = \"0.1.0\"\n"),
+      ("package.json", npm),
+      ("packages/web/package.json", r#"{ "name": "@me/web", "version": "2.0.0" }"#)
+    ]));
+
+    let config = ConfigFile::from_read(&files).unwrap();
+    let roots: Vec<_> = config.projects().iter().map(|p| p.root().cloned()).collect();
+    assert_eq!(vec![Some("crates/core".to_string()), Some("packages/web".to_string())], roots);
+    assert!(config.projects()[0].does_cover("crates/core/src/lib.rs").unwrap());
+
+    let missing = root.replace("my-core", "other");
+    let files = MemFiles(HashMap::from([(CONFIG_FILENAME, missing.as_str()), ("Cargo.toml", cargo)]));
+    assert!(ConfigFile::from_read(&files).is_err());
+  }
+
+  #[test]
+  fn test_validate_ok() {
+    let config = r#"
+projects:
+  - name: p1
+    id: 1
+    tag_prefix: "_proj1-abc"
+    version: { file: f1 }
+
+  - name: p2
+    id: 2
+    tag_prefix: proj2
+    version: { file: f2 }
+    "#;
+
+    assert!(ConfigFile::read(config).is_ok());
+  }
+
+  #[test]
+  fn test_sizes() {
+    let config = r#"
+projects: []
+sizes:
   major: [ break, "!" ]
   minor: [ feat ]
   patch: [ fix, "-" ]
@@ -1528,10 +3865,13 @@ sizes:
       name: "test".into(),
       id: ProjectId::from_id(1),
       root: Some("base".into()),
+      workspace: None,
       includes: vec!["**/*".into()],
       excludes: Vec::new(),
+      case_insensitive: false,
+      allow_overlap: false,
       depends: HashMap::new(),
-      changelog: None,
+      changelog: Vec::new(),
       version: Location::File(FileLocation {
         file: "package.json".into(),
         picker: Picker::Json(ScanningPicker::new(vec![Part::Map("version".into())])),
@@ -1540,9 +3880,29 @@ sizes:
       also: Vec::new(),
       tag_prefix: None,
       tag_prefix_separator: None,
+      tag_prefix_aliases: Vec::new(),
+      tag_template: None,
+      tag_style: Default::default(),
+      tag_message: None,
+      floating_tags: Vec::new(),
+      prev_tag: None,
       labels: Default::default(),
+      scopes: Default::default(),
       hooks: Default::default(),
-      subs: None
+      subs: None,
+      cargo_lock: None,
+      max_size: None,
+      diff_sizes: None,
+      zero_major_policy: Default::default(),
+      version_format: Default::default(),
+      build_metadata: None,
+      constraints: Default::default(),
+      members: Vec::new(),
+      dry_run_default: None,
+      go_module: false,
+      aliases: Vec::new(),
+      previous_roots: Vec::new(),
+      branch_majors: None
     };
 
     assert!(proj.does_cover("base/somefile.txt").unwrap());
@@ -1555,10 +3915,13 @@ sizes:
       name: "test".into(),
       id: ProjectId::from_id(1),
       root: Some("base".into()),
+      workspace: None,
       includes: vec!["**/*".into()],
       excludes: vec!["internal/**/*".into()],
+      case_insensitive: false,
+      allow_overlap: false,
       depends: HashMap::new(),
-      changelog: None,
+      changelog: Vec::new(),
       version: Location::File(FileLocation {
         file: "package.json".into(),
         picker: Picker::Json(ScanningPicker::new(vec![Part::Map("version".into())])),
@@ -1567,24 +3930,72 @@ sizes:
       also: Vec::new(),
       tag_prefix: None,
       tag_prefix_separator: None,
+      tag_prefix_aliases: Vec::new(),
+      tag_template: None,
+      tag_style: Default::default(),
+      tag_message: None,
+      floating_tags: Vec::new(),
+      prev_tag: None,
       labels: Default::default(),
+      scopes: Default::default(),
       hooks: Default::default(),
-      subs: None
+      subs: None,
+      cargo_lock: None,
+      max_size: None,
+      diff_sizes: None,
+      zero_major_policy: Default::default(),
+      version_format: Default::default(),
+      build_metadata: None,
+      constraints: Default::default(),
+      members: Vec::new(),
+      dry_run_default: None,
+      go_module: false,
+      aliases: Vec::new(),
+      previous_roots: Vec::new(),
+      branch_majors: None
     };
 
     assert!(!proj.does_cover("base/internal/infile.txt").unwrap());
   }
 
+  #[test]
+  fn test_windows_paths() {
+    let config = r#"
+projects:
+  - name: proj
+    id: 1
+    root: "base\\sub"
+    includes: ["src\\**\\*.rs"]
+    excludes: ["src\\gen\\*"]
+    case_insensitive: true
+    version:
+      file: "Cargo.toml"
+      toml: "version"
+"#;
+
+    let file = ConfigFile::read(config).unwrap();
+    let proj = &file.projects[0];
+    assert_eq!(Some(&"base/sub".to_string()), proj.root());
+    assert!(proj.does_cover("base/sub/src/main.rs").unwrap());
+    assert!(proj.does_cover("base\\sub\\src\\lib.rs").unwrap());
+    assert!(proj.does_cover("Base/Sub/SRC/Main.RS").unwrap());
+    assert!(!proj.does_cover("base/sub/src/gen/out.rs").unwrap());
+    assert!(!proj.does_cover("base/sub/README.md").unwrap());
+  }
+
   #[test]
   fn test_excludes_check() {
     let proj = Project {
       name: "test".into(),
       id: ProjectId::from_id(1),
       root: Some("base".into()),
+      workspace: None,
       includes: vec![],
       excludes: vec!["internal/**/*".into()],
+      case_insensitive: false,
+      allow_overlap: false,
       depends: HashMap::new(),
-      changelog: None,
+      changelog: Vec::new(),
       version: Location::File(FileLocation {
         file: "package.json".into(),
         picker: Picker::Json(ScanningPicker::new(vec![Part::Map("version".into())])),
@@ -1593,9 +4004,29 @@ sizes:
       also: Vec::new(),
       tag_prefix: None,
       tag_prefix_separator: None,
+      tag_prefix_aliases: Vec::new(),
+      tag_template: None,
+      tag_style: Default::default(),
+      tag_message: None,
+      floating_tags: Vec::new(),
+      prev_tag: None,
       labels: Default::default(),
+      scopes: Default::default(),
       hooks: Default::default(),
-      subs: None
+      subs: None,
+      cargo_lock: None,
+      max_size: None,
+      diff_sizes: None,
+      zero_major_policy: Default::default(),
+      version_format: Default::default(),
+      build_metadata: None,
+      constraints: Default::default(),
+      members: Vec::new(),
+      dry_run_default: None,
+      go_module: false,
+      aliases: Vec::new(),
+      previous_roots: Vec::new(),
+      branch_majors: None
     };
 
     assert!(proj.check_excludes().is_err());
@@ -1623,4 +4054,673 @@ sizes:
     assert_eq!(&Size::None, config.sizes.get("style").unwrap());
     assert_eq!(&Size::None, config.sizes.get("test").unwrap());
   }
+
+  #[test]
+  fn test_breaking_size() {
+    let config = r#"
+projects:
+  - name: p1
+    id: 1
+    version: { file: f1 }
+sizes:
+  minor: [ feat ]
+  none: [ "*" ]
+"#;
+
+    let config = ConfigFile::read(config).unwrap();
+    let proj = &config.projects[0];
+    assert_eq!(Size::Major, proj.size(config.sizes(), "", "!").unwrap());
+    assert_eq!(Size::Minor, proj.size(config.sizes(), "", "feat").unwrap());
+
+    let mut sizes = config.sizes().clone();
+    sizes.insert("!".into(), Size::Fail);
+    assert_eq!(Size::Fail, proj.size(&sizes, "", "!").unwrap());
+  }
+
+  #[test]
+  fn test_size_rules() {
+    let config = r#"
+projects:
+  - name: p1
+    id: 1
+    version: { file: f1 }
+sizes:
+  use_angular: true
+  rules:
+    - '^JIRA-\d+ (fix|bug)': patch
+    - '^JIRA-\d+ ': minor
+  fail: [ "*" ]
+"#;
+
+    let config = ConfigFile::read(config).unwrap();
+    let proj = &config.projects[0];
+    let size = |summary: &str, kind: &str| proj.size(config.sizes(), summary, kind).unwrap();
+    assert_eq!(Size::Patch, size("JIRA-12 fix the login", "-"));
+    assert_eq!(Size::Minor, size("JIRA-12 add a login", "-"));
+    assert_eq!(Size::Minor, size("feat: add a login", "feat"));
+    assert_eq!(Size::Fail, size("add a login", "-"));
+
+    assert!(ConfigFile::read("projects: []\nsizes:\n  rules:\n    - '(': patch\n").is_err());
+    assert!(ConfigFile::read("projects: []\nsizes:\n  rules:\n    - 'a': huge\n").is_err());
+  }
+
+  #[test]
+  fn test_aliases() {
+    let current = r#"
+projects:
+  - name: api
+    id: 3
+    aliases: [1]
+    root: apps/api
+    previous_roots: [services/api]
+    version: { file: package.json, json: version }
+  - name: web
+    id: 2
+    version: { file: web/package.json, json: version }
+"#;
+    let mut prev = ConfigFile::read(
+      r#"
+projects:
+  - name: api
+    id: 1
+    root: services/api
+    version: { file: package.json, json: version }
+  - name: web
+    id: 2
+    version: { file: web/package.json, json: version }
+"#
+    )
+    .unwrap();
+
+    let current = ConfigFile::read(current).unwrap();
+    prev.adopt_aliases(&current);
+    let ids: Vec<_> = prev.projects().iter().map(|p| p.id().to_string()).collect();
+    assert_eq!(vec!["3", "2"], ids);
+
+    let api = &current.projects()[0];
+    assert!(!api.does_cover("services/api/src/main.ts").unwrap());
+    assert!(api.did_cover("services/api/src/main.ts").unwrap());
+    assert!(!api.did_cover("apps/api/src/main.ts").unwrap());
+
+    let clash = r#"
+projects:
+  - { name: a, id: 1, aliases: [2], version: { file: f1 } }
+  - { name: b, id: 2, version: { file: f2 } }
+"#;
+    assert!(ConfigFile::read(clash).is_err());
+  }
+
+  #[test]
+  fn test_max_size() {
+    let config = r#"
+options:
+  max_size: minor
+projects:
+  - name: p1
+    id: 1
+    version: { file: f1 }
+  - name: p2
+    id: 2
+    version: { file: f2 }
+    max_size: patch
+"#;
+
+    let config = ConfigFile::read(config).unwrap();
+    let global = config.max_size();
+    let (p1, p2) = (&config.projects[0], &config.projects[1]);
+
+    assert_eq!(Some(Size::Minor), global);
+    assert_eq!(Size::Minor, p1.cap_size(Size::Major, global));
+    assert_eq!(Size::Fail, p1.cap_size(Size::Fail, global));
+    assert_eq!(Size::Patch, p2.cap_size(Size::Major, global));
+    assert_eq!(Size::None, p2.cap_size(Size::None, global));
+  }
+
+  #[test]
+  fn test_dry_run_default() {
+    let config = r#"
+options:
+  dry_run_default: true
+projects:
+  - name: p1
+    id: 1
+    version: { file: f1 }
+  - name: p2
+    id: 2
+    version: { file: f2 }
+    dry_run_default: false
+"#;
+
+    let config = ConfigFile::read(config).unwrap();
+    let global = config.dry_run_default();
+    assert!(global);
+    assert!(config.projects[0].dry_run_default(global));
+    assert!(!config.projects[1].dry_run_default(global));
+    assert!(!config.projects[0].dry_run_default(false));
+  }
+
+  #[test]
+  fn test_zero_major_policy() {
+    let config = r#"
+projects:
+  - name: p1
+    id: 1
+    version: { file: f1 }
+  - name: p2
+    id: 2
+    version: { file: f2 }
+    zero_major_policy: minor
+  - name: p3
+    id: 3
+    version: { file: f3 }
+    zero_major_policy: shift
+"#;
+
+    let config = ConfigFile::read(config).unwrap();
+    let (p1, p2, p3) = (&config.projects[0], &config.projects[1], &config.projects[2]);
+
+    assert_eq!("1.0.0", p1.apply_size(Size::Major, "0.3.1").unwrap());
+    assert_eq!("0.4.0", p2.apply_size(Size::Major, "0.3.1").unwrap());
+    assert_eq!("0.4.0", p2.apply_size(Size::Minor, "0.3.1").unwrap());
+    assert_eq!("0.4.0", p3.apply_size(Size::Major, "0.3.1").unwrap());
+    assert_eq!("0.3.2", p3.apply_size(Size::Minor, "0.3.1").unwrap());
+    assert_eq!("0.3.2", p3.apply_size(Size::Patch, "0.3.1").unwrap());
+    assert_eq!("2.0.0", p3.apply_size(Size::Major, "1.3.1").unwrap());
+    assert_eq!(Size::Fail, p3.applied_size(Size::Fail, "0.3.1").unwrap());
+  }
+
+  #[test]
+  fn test_branches() {
+    let single = ConfigFile::read("options:\n  branch: main\n").unwrap();
+    assert!(single.allows_branch("main"));
+    assert!(!single.allows_branch("release/1.x"));
+
+    let multi = ConfigFile::read("options:\n  branch: [main, \"release/*\"]\n").unwrap();
+    assert!(multi.allows_branch("main"));
+    assert!(multi.allows_branch("release/1.x"));
+    assert!(!multi.allows_branch("release/1.x/hotfix"));
+    assert!(!multi.allows_branch("develop"));
+
+    assert!(ConfigFile::read("options:\n  prev_tag: prev\n").unwrap().allows_branch("develop"));
+    assert!(ConfigFile::read("options:\n  branch: \"release/[\"\n").is_err());
+  }
+
+  #[test]
+  fn test_maintenance_branch() {
+    let yaml = r#"
+options:
+  branch: main
+  maintenance:
+    - branch: "maint/*"
+      majors: [1]
+      prev_tag: prev-1
+projects:
+  - name: proj
+    id: 1
+    tag_prefix: ""
+    version: { file: f1 }
+    "#;
+
+    let config = ConfigFile::read(yaml).unwrap();
+    assert!(config.allows_branch("maint/1.x"));
+    assert!(!config.allows_branch("develop"));
+
+    let maint = ConfigFile::read(yaml).unwrap().for_branch(Some("maint/1.x"));
+    assert_eq!("prev-1", maint.prev_tag());
+    assert_eq!(Some(&[1][..]), maint.projects()[0].tag_majors());
+
+    let main = ConfigFile::read(yaml).unwrap().for_branch(Some("main"));
+    assert_eq!("versio-prev", main.prev_tag());
+    assert_eq!(None, main.projects()[0].tag_majors());
+
+    assert!(ConfigFile::read("options:\n  maintenance:\n    - branch: maint\n      majors: []\n").is_err());
+  }
+
+  #[test]
+  fn test_mirrors() {
+    let config = ConfigFile::read("options:\n  mirrors: [gitlab, backup]\n  parallel_push: true\n").unwrap();
+    assert_eq!(&["gitlab".to_string(), "backup".to_string()], config.mirrors());
+    assert!(config.parallel_push());
+    assert!(ConfigFile::read("options:\n  prev_tag: prev\n").unwrap().mirrors().is_empty());
+  }
+
+  #[test]
+  fn test_allows_write() {
+    let config = ConfigFile::read("options:\n  allowed_paths: [\"packages/\", \"./CHANGELOG.md\"]\n").unwrap();
+    assert!(config.allows_write(Path::new("packages/a/package.json")));
+    assert!(config.allows_write(Path::new("./CHANGELOG.md")));
+    assert!(!config.allows_write(Path::new("packages-old/package.json")));
+    assert!(!config.allows_write(Path::new("generated/pins.yaml")));
+    assert!(ConfigFile::read("options:\n  prev_tag: prev\n").unwrap().allows_write(Path::new("any/where")));
+  }
+
+  #[test]
+  fn test_skips_pr() {
+    let config = ConfigFile::read("options:\n  skip_prs: [12, 40]\n").unwrap();
+    assert!(config.skips_pr(12, &[]));
+    assert!(!config.skips_pr(13, &["bug".into()]));
+    assert!(config.skips_pr(13, &["bug".into(), "versio:skip".into()]));
+  }
+
+  #[test]
+  fn test_scopes() {
+    let config = r#"
+projects:
+  - name: p1
+    id: 1
+    version: { file: f1 }
+    scopes: frontend
+  - name: p2
+    id: 2
+    version: { file: f2 }
+    scopes: [ api, Server ]
+"#;
+
+    let config = ConfigFile::read(config).unwrap();
+    let (p1, p2) = (&config.projects[0], &config.projects[1]);
+
+    assert!(p1.does_cover_scope("frontend"));
+    assert!(!p1.does_cover_scope("api"));
+    assert!(p2.does_cover_scope("api"));
+    assert!(p2.does_cover_scope("server"));
+  }
+
+  #[test]
+  fn test_umbrella() {
+    let config = r#"
+projects:
+  - name: p1
+    id: 1
+    version: { file: f1 }
+  - name: all
+    id: 2
+    tag_prefix: release
+    version: { tags: { default: "1.0.0" } }
+    members: [ 1 ]
+"#;
+
+    let config = ConfigFile::read(config).unwrap();
+    let (p1, all) = (&config.projects[0], &config.projects[1]);
+    assert!(!p1.is_umbrella());
+    assert!(all.is_umbrella());
+    assert!(!all.does_cover("f1").unwrap());
+
+    let unknown = r#"
+projects:
+  - name: all
+    id: 2
+    tag_prefix: release
+    version: { tags: { default: "1.0.0" } }
+    members: [ 1 ]
+"#;
+    assert!(ConfigFile::read(unknown).is_err());
+
+    let untagged = r#"
+projects:
+  - name: p1
+    id: 1
+    version: { file: f1 }
+  - name: all
+    id: 2
+    version: { file: f2 }
+    members: [ 1 ]
+"#;
+    assert!(ConfigFile::read(untagged).is_err());
+  }
+
+  #[test]
+  fn test_tags_only() {
+    let config = r#"
+projects:
+  - name: image
+    id: 1
+    tag_prefix: image
+    version: { tags: {} }
+  - name: legacy
+    id: 2
+    tag_prefix: legacy
+    version: { tags: { majors: [ 2, 3 ] } }
+"#;
+
+    let config = ConfigFile::read(config).unwrap();
+    let (image, legacy) = (&config.projects[0], &config.projects[1]);
+    assert!(image.is_tag_version());
+    assert_eq!(None, image.tag_majors());
+    assert!(image.version.file_path(None).is_none());
+    match &image.version {
+      Location::Tag(l) => assert_eq!("0.0.0", l.tags.default_value()),
+      _ => panic!("Not a tag location")
+    }
+    assert_eq!(Some(&[2, 3][..]), legacy.tag_majors());
+  }
+
+  #[test]
+  fn test_command_version() {
+    let config = r#"
+projects:
+  - name: proj
+    id: 1
+    version:
+      command:
+        read: "echo ' 1.2.3 '"
+        write: "echo $1"
+"#;
+
+    let config = ConfigFile::read(config).unwrap();
+    let proj = &config.projects[0];
+    assert!(!proj.is_tag_version());
+    assert!(proj.version.file_path(None).is_none());
+    let state = CurrentState::new(PathBuf::from("."), OldTags::new(HashMap::new(), HashMap::new()));
+    assert_eq!("1.2.3", proj.get_value(&state).unwrap());
+
+    let mixed = r#"
+projects:
+  - name: proj
+    id: 1
+    version: { file: f1, command: { read: "echo 1.2.3", write: "echo $1" } }
+"#;
+    assert!(ConfigFile::read(mixed).is_err());
+  }
+
+  #[test]
+  fn test_changelog_sections() {
+    let config = r#"
+projects:
+  - name: p1
+    id: 1
+    version: { file: f1 }
+    changelog: { file: CHANGELOG.html, group_by_type: true }
+  - name: p2
+    id: 2
+    version: { file: f2 }
+    changelog:
+      file: CHANGELOG.html
+      group_by_type: true
+      sections:
+        - title: Documentation
+          types: [ docs ]
+        - title: Everything else
+          sizes: [ major, minor, patch ]
+  - name: p3
+    id: 3
+    version: { file: f3 }
+    changelog: CHANGELOG.html
+"#;
+
+    let config = ConfigFile::read(config).unwrap();
+    let defaults = config.projects[0].changelog_sections();
+    let custom = config.projects[1].changelog_sections();
+
+    assert_eq!(Some(0), ChangelogSection::find(&defaults, "!", Size::Major));
+    assert_eq!(Some(1), ChangelogSection::find(&defaults, "FEAT", Size::Minor));
+    assert_eq!(Some(2), ChangelogSection::find(&defaults, "perf", Size::Patch));
+    assert_eq!(None, ChangelogSection::find(&defaults, "chore", Size::None));
+    assert_eq!(Some(0), ChangelogSection::find(&custom, "docs", Size::Minor));
+    assert_eq!("Everything else", custom[ChangelogSection::find(&custom, "feat", Size::Minor).unwrap()].title());
+    assert!(config.projects[2].changelog_sections().is_empty());
+  }
+
+  #[test]
+  fn test_changelog_format() {
+    let config = r#"
+projects:
+  - name: p1
+    id: 1
+    version: { file: f1 }
+    changelog: { file: CHANGELOG.md, format: keep-a-changelog }
+  - name: p2
+    id: 2
+    version: { file: f2 }
+    changelog: CHANGELOG.html
+"#;
+
+    let config = ConfigFile::read(config).unwrap();
+    assert_eq!(ChangelogFormat::KeepAChangelog, config.projects[0].changelog_format());
+    assert_eq!(Some("builtin:keep-a-changelog"), config.projects[0].changelog().map(|(_, t)| t));
+    assert_eq!(ChangelogFormat::Template, config.projects[1].changelog_format());
+    assert_eq!(Some("builtin:html"), config.projects[1].changelog().map(|(_, t)| t));
+  }
+
+  #[test]
+  fn test_changelog_off() {
+    let text = r#"
+projects:
+  - name: p1
+    id: 1
+    version: { file: f1 }
+    changelog: false
+  - name: p2
+    id: 2
+    version: { file: f2 }
+"#;
+
+    let config = ConfigFile::read(text).unwrap();
+    assert!(config.projects[0].changelog().is_none());
+    assert!(config.projects[1].changelog().is_none());
+    assert!(ConfigFile::read(&text.replace("changelog: false", "changelog: true")).is_err());
+  }
+
+  #[test]
+  fn test_changelog_targets() {
+    let text = r#"
+projects:
+  - name: p1
+    id: 1
+    root: p1
+    version: { file: f1 }
+    changelog:
+      - docs/releases/{{name}}-{{version}}.md
+      - { file: ../RELEASE_NOTES.md, format: keep-a-changelog }
+  - name: p2
+    id: 2
+    version: { file: f2 }
+    changelog: { file: RELEASE_NOTES.md, format: keep-a-changelog }
+"#;
+
+    let config = ConfigFile::read(text).unwrap();
+    let p1 = &config.projects[0];
+    assert_eq!(2, p1.changelogs().len());
+    assert!(p1.changelogs()[0].is_templated());
+    assert_eq!(
+      PathBuf::from_slash("p1/docs/releases/p1-1.2.3.md"),
+      p1.changelog_path(&p1.changelogs()[0], "1.2.3").unwrap()
+    );
+    assert_eq!(PathBuf::from_slash("p1/../RELEASE_NOTES.md"), p1.changelog_path(&p1.changelogs()[1], "1.2.3").unwrap());
+    assert_eq!(1, config.projects[1].changelogs().len());
+
+    let p2_log = "changelog: { file: RELEASE_NOTES.md, format: keep-a-changelog }";
+    assert!(ConfigFile::read(&text.replace(p2_log, "changelog: RELEASE_NOTES.md")).is_err());
+    assert!(ConfigFile::read(&text.replace(p2_log, "changelog: []")).is_err());
+    assert!(ConfigFile::read(&text.replace("{{version}}", "{{version")).is_err());
+  }
+
+  #[test]
+  fn test_npm_requirement() {
+    assert_eq!(Some("1.5.0".to_string()), npm_requirement("1.4.2", "1.5.0"));
+    assert_eq!(Some("^1.5.0".to_string()), npm_requirement("^1.4.2", "1.5.0"));
+    assert_eq!(Some("workspace:~1.5.0".to_string()), npm_requirement("workspace:~1.4.2", "1.5.0"));
+    assert_eq!(Some(">=1.5.0".to_string()), npm_requirement(">=1.4.2", "1.5.0"));
+    assert_eq!(None, npm_requirement("workspace:^", "1.5.0"));
+    assert_eq!(None, npm_requirement("workspace:*", "1.5.0"));
+    assert_eq!(None, npm_requirement("1.x", "1.5.0"));
+    assert_eq!(None, npm_requirement(">=1.4.2 <2.0.0", "1.5.0"));
+  }
+
+  #[test]
+  fn test_go_module() {
+    assert_eq!("example.com/widgets", go_module_path("example.com/widgets", 1));
+    assert_eq!("example.com/widgets/v2", go_module_path("example.com/widgets", 2));
+    assert_eq!("example.com/widgets/v3", go_module_path("example.com/widgets/v2", 3));
+    assert_eq!("example.com/widgets", go_module_path("example.com/widgets/v2", 0));
+    assert_eq!("example.com/v1beta/v2", go_module_path("example.com/v1beta", 2));
+
+    let text = r#"
+projects:
+  - name: widgets
+    id: 1
+    version: { tags: { default: "0.0.0" } }
+    go_module: true
+  - name: gadgets
+    id: 2
+    root: tools/gadgets
+    version: { tags: { default: "0.0.0" } }
+    go_module: true
+"#;
+
+    let config = ConfigFile::read(text).unwrap();
+    assert_eq!(Some("v2.0.0".to_string()), config.projects[0].full_version("2.0.0"));
+    assert_eq!(Some("tools/gadgets/v1.4.0".to_string()), config.projects[1].full_version("1.4.0"));
+  }
+
+  #[test]
+  fn test_constraints() {
+    let text = r#"
+projects:
+  - name: p1
+    id: 1
+    version: { file: f1 }
+    constraints:
+      minimum: 1.2.0
+      skip: [ 1.3.x, 2.0.1 ]
+      frozen: [ "1.*" ]
+"#;
+
+    let config = ConfigFile::read(text).unwrap();
+    let proj = &config.projects[0];
+    assert!(proj.verify_restrictions("1.1.9").is_err());
+    assert!(proj.verify_restrictions("1.3.4").is_err());
+    assert!(proj.verify_restrictions("1.4.0").is_err());
+    assert!(proj.verify_restrictions("2.0.1").is_err());
+    assert!(proj.verify_restrictions("2.0.2").is_ok());
+    assert_eq!(Some("it's in the skipped range 1.3.x".to_string()), proj.constraint_violation("1.3.0").unwrap());
+
+    assert!(ConfigFile::read(&text.replace("1.3.x", "1.x.3")).is_err());
+    assert!(ConfigFile::read(&text.replace("1.2.0", "1.2")).is_err());
+  }
+
+  #[test]
+  fn test_depends_style() {
+    let config = r#"
+projects:
+  - name: core
+    id: 1
+    version:
+      file: "core.json"
+      json: "version"
+  - name: app
+    id: 2
+    depends:
+      1:
+        style: tilde
+        files:
+          - file: "package.json"
+            json: "dependencies.core"
+    version:
+      file: "package.json"
+      json: "version"
+"#;
+
+    let config = ConfigFile::read(config).unwrap();
+    let app = config.get_project(&ProjectId::from_id(2)).unwrap();
+    assert_eq!(DependsStyle::Tilde, app.depends().get(&ProjectId::from_id(1)).unwrap().style);
+
+    assert_eq!("1.4.2", DependsStyle::Exact.apply("1.4.2", false).unwrap());
+    assert_eq!("^1.4.2", DependsStyle::Caret.apply("1.4.2", false).unwrap());
+    assert_eq!("~1.4.2", DependsStyle::Tilde.apply("1.4.2", false).unwrap());
+    assert_eq!(">=1.4.2, <2.0.0", DependsStyle::Range.apply("1.4.2", false).unwrap());
+    assert_eq!(">=0.4.2, <0.5.0", DependsStyle::Range.apply("0.4.2", false).unwrap());
+    assert_eq!(">=0.0.2, <0.0.3", DependsStyle::Range.apply("0.0.2", false).unwrap());
+    assert_eq!(">=1.4.2 <2.0.0", DependsStyle::Range.apply("1.4.2", true).unwrap());
+    assert!(app.depends().get(&ProjectId::from_id(1)).unwrap().files[0].is_json());
+  }
+
+  #[test]
+  fn test_skip_ci() {
+    let config = r#"
+projects: []
+commit:
+  skip_ci: github
+  tag_skip_ci: "[ci deploy]"
+"#;
+
+    let config = ConfigFile::read(config).unwrap();
+    let commit = config.commit_config();
+    assert_eq!("build(deploy): Versio update versions [skip ci]", commit.commit_message());
+    assert_eq!("{}\n[ci deploy]", commit.tag_message("{}"));
+
+    let commit = ConfigFile::read("projects: []").unwrap().commit_config().clone();
+    assert_eq!("build(deploy): Versio update versions", commit.commit_message());
+    assert_eq!("{}", commit.tag_message("{}"));
+  }
+
+  #[test]
+  fn test_signing_overlay() {
+    let config = r#"
+projects: []
+commit:
+  sign:
+    commits: true
+    key: "ABCD1234"
+"#;
+
+    let config = ConfigFile::read(config).unwrap();
+    let prefs: SigningConfig = toml::from_str("tags = true\nkey = \"~/.ssh/id.pub\"\nformat = \"ssh\"").unwrap();
+    let commit = config.commit_config().with_signing(&prefs);
+    assert_eq!(Some(true), commit.sign().commits());
+    assert_eq!(Some(true), commit.sign().tags());
+    assert_eq!(Some("~/.ssh/id.pub"), commit.sign().key());
+    assert_eq!(Some(SigningFormat::Ssh), commit.sign().format());
+  }
+
+  #[test]
+  fn test_state_storage() {
+    let config = ConfigFile::read("projects: []\ncommit:\n  state_storage: notes\n").unwrap();
+    assert_eq!(StateStorage::Notes, config.commit_config().state_storage());
+
+    let config = ConfigFile::read("projects: []").unwrap();
+    assert_eq!(StateStorage::Annotation, config.commit_config().state_storage());
+    assert!(ConfigFile::read("projects: []\ncommit:\n  state_storage: tags\n").is_err());
+  }
+
+  #[test]
+  fn test_version_format() {
+    let now = DateTime::parse_from_rfc3339("2024-03-01T12:00:00Z").unwrap().with_timezone(&Utc);
+    let two: VersionFormat = serde_yaml::from_str("parts: 2").unwrap();
+    assert_eq!("2.0", two.apply(Size::Major, "1.4", now).unwrap());
+    assert_eq!("1.5", two.apply(Size::Patch, "1.4", now).unwrap());
+    assert!(two.apply(Size::Minor, "1.4.2", now).is_err());
+
+    let four: VersionFormat = serde_yaml::from_str("parts: 4").unwrap();
+    assert_eq!("1.5.0.319", four.apply(Size::Minor, "1.4.2.318", now).unwrap());
+    assert_eq!("1.4.2.318", four.apply(Size::None, "1.4.2.318", now).unwrap());
+    let stamped: VersionFormat = serde_yaml::from_str("{ parts: 4, build: timestamp }").unwrap();
+    assert_eq!("1.4.3.24061", stamped.apply(Size::Patch, "1.4.2.318", now).unwrap());
+    assert_eq!("1.4.4.24062", stamped.apply(Size::Patch, "1.4.3.24061", now).unwrap());
+
+    assert!(Size::less_than("1.4", "1.4.1").unwrap());
+    assert!(!Size::less_than("1.4.0.0", "1.4").unwrap());
+    assert!(Size::less_than("1.4.2.318", "1.4.2.319").unwrap());
+    assert_eq!(Some("1.4.2.318".to_string()), version_from_tag("v{version}", "v1.4.2.318", 4));
+    assert_eq!(None, version_from_tag("v{version}", "v1.4.2", 4));
+  }
+
+  #[test]
+  fn test_build_metadata() {
+    let data = r#"
+projects:
+  - name: proj
+    id: 1
+    version: { file: "VERSION", pattern: "(.*)" }
+    build_metadata: "+{{ sha7 }}.{{ date }}{% if run != '' %}.ci{{ run }}{% endif %}"
+"#;
+    let config = ConfigFile::read(data).unwrap();
+    let build = BuildInfo { sha: "0123456789abcdef".into(), date: "20240301".into(), run: String::new() };
+    assert_eq!("1.2.3+0123456.20240301", config.projects[0].with_build_metadata("1.2.3", &build).unwrap());
+    let build = BuildInfo { run: "42".into(), ..build };
+    assert_eq!("1.2.3+0123456.20240301.ci42", config.projects[0].with_build_metadata("1.2.3", &build).unwrap());
+
+    assert_eq!("1.2.3", strip_build_metadata("1.2.3+0123456"));
+    assert!(Size::less_than("1.2.3+0123456", "1.2.4").unwrap());
+    assert!(!Size::less_than("1.2.3+0123456", "1.2.3").unwrap());
+  }
 }
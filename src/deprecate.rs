@@ -0,0 +1,278 @@
+//! Deprecated config keys.
+//!
+//! A deprecated key still works: when the config is read, it's renamed to its replacement before the config is
+//! parsed, and a warning is shown. `versio config fix` rewrites the keys in the config file itself, keeping its
+//! comments and layout.
+
+use crate::errors::Result;
+use serde_yaml::Value;
+use std::fmt;
+
+/// Keys that have been renamed. A `*` in a path matches every item of a list.
+const RENAMED: &[Renamed] = &[];
+
+struct Renamed {
+  path: &'static str,
+  replacement: &'static str
+}
+
+/// A deprecated key found in a config, with where it was found.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Deprecation {
+  location: String,
+  replacement: &'static str,
+  line: Option<usize>
+}
+
+impl Deprecation {
+  /// The full path of the deprecated key, like "projects.2.subs.old".
+  pub fn location(&self) -> &str { &self.location }
+
+  /// The key to use instead.
+  pub fn replacement(&self) -> &str { self.replacement }
+
+  /// The (1-based) line of the config file, if known.
+  pub fn line(&self) -> Option<usize> { self.line }
+}
+
+impl fmt::Display for Deprecation {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let key = tr!("deprecated.key", location = self.location, replacement = self.replacement);
+    match self.line {
+      Some(line) => write!(f, "{}", tr!("deprecated.at_line", key = key, line = line)),
+      None => write!(f, "{}", key)
+    }
+  }
+}
+
+/// Rename the deprecated keys in a parsed config to their replacements, returning what was renamed.
+pub fn upgrade(value: &mut Value) -> Result<Vec<Deprecation>> { upgrade_keys(value, RENAMED) }
+
+fn upgrade_keys(value: &mut Value, renames: &[Renamed]) -> Result<Vec<Deprecation>> {
+  let mut found = Vec::new();
+  for renamed in renames {
+    let parts: Vec<_> = renamed.path.split('.').collect();
+    upgrade_at(value, &parts, "", renamed, &mut found)?;
+  }
+  Ok(found)
+}
+
+fn upgrade_at(
+  value: &mut Value, parts: &[&str], location: &str, renamed: &Renamed, found: &mut Vec<Deprecation>
+) -> Result<()> {
+  match (parts, value) {
+    ([key], Value::Mapping(map)) if map.contains_key(*key) => {
+      let location = join(location, key);
+      if map.contains_key(renamed.replacement) {
+        bail!("Both {} and its replacement \"{}\" are set.", location, renamed.replacement);
+      }
+      let val = map.remove(*key).expect("Key was found.");
+      map.insert(renamed.replacement.into(), val);
+      found.push(Deprecation { location, replacement: renamed.replacement, line: None });
+    }
+    (["*", rest @ ..], Value::Sequence(seq)) => {
+      for (i, item) in seq.iter_mut().enumerate() {
+        upgrade_at(item, rest, &join(location, &i.to_string()), renamed, found)?;
+      }
+    }
+    ([key, rest @ ..], Value::Mapping(map)) => {
+      if let Some(item) = map.get_mut(*key) {
+        upgrade_at(item, rest, &join(location, key), renamed, found)?;
+      }
+    }
+    _ => ()
+  }
+  Ok(())
+}
+
+/// The result of rewriting a config file.
+pub struct Fixed {
+  text: String,
+  fixed: Vec<Deprecation>,
+  remaining: Vec<Deprecation>
+}
+
+impl Fixed {
+  /// The rewritten text of the config file.
+  pub fn text(&self) -> &str { &self.text }
+
+  /// The keys that were renamed.
+  pub fn fixed(&self) -> &[Deprecation] { &self.fixed }
+
+  /// The keys that couldn't be renamed (e.g. those in a `{ ... }` mapping), which have to be fixed by hand.
+  pub fn remaining(&self) -> &[Deprecation] { &self.remaining }
+}
+
+/// Rename the deprecated keys in the text of a config file, line by line, so that comments and formatting are
+/// kept.
+pub fn fix(data: &str) -> Result<Fixed> { fix_keys(data, RENAMED) }
+
+fn fix_keys(data: &str, renames: &[Renamed]) -> Result<Fixed> {
+  let mut text = String::with_capacity(data.len());
+  let mut fixed = Vec::new();
+  let mut path: Vec<(usize, String)> = Vec::new();
+  let mut scalar: Option<usize> = None;
+
+  for (num, line) in data.split_inclusive('\n').enumerate() {
+    let trimmed = line.trim_start();
+    let mut indent = line.len() - trimmed.len();
+    let content = trimmed.trim_end();
+
+    if let Some(scalar_indent) = scalar {
+      if content.is_empty() || indent > scalar_indent {
+        text.push_str(line);
+        continue;
+      }
+      scalar = None;
+    }
+    if content.is_empty() || content.starts_with('#') || content.starts_with("---") || content == "..." {
+      text.push_str(line);
+      continue;
+    }
+
+    // Each "- " starts a new list item, which is part of the path.
+    let mut rest = trimmed;
+    while rest.starts_with("- ") || rest.trim_end() == "-" {
+      let mut next = 0;
+      while let Some((top_indent, top)) = path.last() {
+        let is_item = top.parse::<usize>().is_ok();
+        if *top_indent > indent || (*top_indent == indent && is_item) {
+          if *top_indent == indent {
+            next = top.parse::<usize>().expect("Item is a number.") + 1;
+          }
+          path.pop();
+        } else {
+          break;
+        }
+      }
+      path.push((indent, next.to_string()));
+      let after = rest[1 ..].trim_start();
+      indent += rest.len() - after.len();
+      rest = after;
+    }
+
+    let (key, key_len) = match leading_key(rest) {
+      Some(found) => found,
+      None => {
+        text.push_str(line);
+        continue;
+      }
+    };
+    while path.last().map(|(i, _)| *i >= indent).unwrap_or(false) {
+      path.pop();
+    }
+
+    let value = rest[key_len ..].trim_start_matches([' ', ':']).trim();
+    if value.starts_with('|') || value.starts_with('>') {
+      scalar = Some(indent);
+    }
+
+    let location = path.iter().map(|(_, p)| p.as_str()).chain(Some(key.as_str())).collect::<Vec<_>>();
+    match renames.iter().find(|r| matches_path(r.path, &location)) {
+      Some(renamed) => {
+        text.push_str(&line[.. line.len() - rest.len()]);
+        text.push_str(renamed.replacement);
+        text.push_str(&rest[key_len ..]);
+        fixed.push(Deprecation { location: location.join("."), replacement: renamed.replacement, line: Some(num + 1) });
+        path.push((indent, renamed.replacement.to_string()));
+      }
+      None => {
+        text.push_str(line);
+        path.push((indent, key));
+      }
+    }
+  }
+
+  let remaining = upgrade_keys(&mut serde_yaml::from_str(&text)?, renames)?;
+  Ok(Fixed { text, fixed, remaining })
+}
+
+/// Find the mapping key at the start of `line`, and the length of the key as written.
+fn leading_key(line: &str) -> Option<(String, usize)> {
+  let (key, len) = match line.chars().next()? {
+    q @ ('"' | '\'') => {
+      let end = line[1 ..].find(q)? + 1;
+      (line[1 .. end].to_string(), end + 1)
+    }
+    _ => {
+      let end = line.find(':')?;
+      (line[.. end].trim_end().to_string(), line[.. end].trim_end().len())
+    }
+  };
+
+  let after = line[len ..].trim_start();
+  let is_key = after.starts_with(':') && after[1 ..].chars().next().map(|c| c.is_whitespace()).unwrap_or(true);
+  (is_key && !key.is_empty() && !key.contains(['{', '[', '#'])).then_some((key, len))
+}
+
+fn matches_path(pattern: &str, location: &[&str]) -> bool {
+  let parts: Vec<_> = pattern.split('.').collect();
+  parts.len() == location.len()
+    && parts.iter().zip(location).all(|(p, l)| if *p == "*" { l.parse::<usize>().is_ok() } else { p == l })
+}
+
+fn join(location: &str, key: &str) -> String {
+  if location.is_empty() {
+    key.to_string()
+  } else {
+    format!("{}.{}", location, key)
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::{fix_keys, upgrade_keys, Renamed};
+
+  /// No key is deprecated right now, so these test a made-up one.
+  const RENAMED: &[Renamed] = &[Renamed { path: "projects.*.subs.old", replacement: "new" }];
+
+  #[test]
+  fn test_upgrade() {
+    let mut value = serde_yaml::from_str(
+      r#"
+projects:
+  - name: one
+  - name: two
+    subs:
+      old: [0]
+"#
+    )
+    .unwrap();
+
+    let found = upgrade_keys(&mut value, RENAMED).unwrap();
+    assert_eq!(1, found.len());
+    assert_eq!("projects.1.subs.old", found[0].location());
+    assert_eq!("new", found[0].replacement());
+    assert_eq!(&serde_yaml::Value::from(vec![0]), &value["projects"][1]["subs"]["new"]);
+  }
+
+  #[test]
+  fn test_upgrade_conflict() {
+    let mut value = serde_yaml::from_str("projects:\n- subs: { old: [0], new: [1] }\n").unwrap();
+    assert!(upgrade_keys(&mut value, RENAMED).is_err());
+  }
+
+  #[test]
+  fn test_fix() {
+    let data = r#"# comment
+projects:
+- name: one
+  subs:
+    # the old key
+    old: [0]
+  hooks:
+    post_write: |
+      old: not a key
+- name: two
+  subs: { old: [2] }
+"#;
+
+    let fixed = fix_keys(data, RENAMED).unwrap();
+    assert_eq!(data.replace("    old: [0]", "    new: [0]"), fixed.text());
+    assert_eq!(1, fixed.fixed().len());
+    assert_eq!("projects.0.subs.old", fixed.fixed()[0].location());
+    assert_eq!(Some(6), fixed.fixed()[0].line());
+    assert_eq!(1, fixed.remaining().len());
+    assert_eq!("projects.1.subs.old", fixed.remaining()[0].location());
+  }
+}
@@ -0,0 +1,204 @@
+//! Drift between a plan saved by `plan --format json` and the plan that a release makes from the same config.
+//!
+//! In a merge queue, the commit that's released isn't the one that was planned on the PR: other PRs may have been
+//! merged ahead of it. The release plans again, and this reports how that plan differs from the saved one.
+
+use crate::errors::{Context as _, Result};
+use chrono::{DateTime, Duration, Utc};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+
+/// The version of the plan JSON schema that can be read.
+const SAVED_SCHEMA_VERSION: u32 = 1;
+
+/// The parts of a saved plan that are compared: its other fields are ignored.
+#[derive(Deserialize)]
+pub struct SavedPlan {
+  schema: u32,
+  #[serde(default)]
+  created: Option<DateTime<Utc>>,
+  #[serde(default)]
+  commit: Option<String>,
+  projects: Vec<PlannedProject>
+}
+
+/// The size and target version of a single project of a plan.
+#[derive(Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct PlannedProject {
+  id: String,
+  name: String,
+  size: String,
+  target: Option<String>
+}
+
+impl PlannedProject {
+  pub fn new(id: String, name: String, size: String, target: Option<String>) -> PlannedProject {
+    PlannedProject { id, name, size, target }
+  }
+
+  fn describe(&self) -> String {
+    match &self.target {
+      Some(target) => format!("{} ({})", self.size, target),
+      None => self.size.clone()
+    }
+  }
+}
+
+impl SavedPlan {
+  pub fn read<P: AsRef<Path>>(path: P) -> Result<SavedPlan> {
+    let path = path.as_ref();
+    let context = || format!("Unable to read saved plan {}.", path.display());
+    SavedPlan::parse(&std::fs::read_to_string(path).with_context(context)?).with_context(context)
+  }
+
+  fn parse(data: &str) -> Result<SavedPlan> {
+    let plan: SavedPlan = serde_json::from_str(data)?;
+    if plan.schema != SAVED_SCHEMA_VERSION {
+      bail!("plan schema {} isn't {}", plan.schema, SAVED_SCHEMA_VERSION);
+    }
+    Ok(plan)
+  }
+
+  /// Why this plan is too old to release, if it is: it was saved more than `max_hours` before `now`, or the commit
+  /// it was made on is more than `max_commits` behind the released commit, as counted by `behind`. A plan that
+  /// doesn't record when or where it was made (or whose commit `behind` can't find) is too old for either limit.
+  pub fn staleness(
+    &self, max_hours: Option<u32>, max_commits: Option<u32>, now: DateTime<Utc>,
+    behind: impl FnOnce(&str) -> Result<Option<usize>>
+  ) -> Result<Option<String>> {
+    if let Some(max_hours) = max_hours {
+      match self.created {
+        None => return Ok(Some(tr!("drift.no_created"))),
+        Some(created) if now - created > Duration::hours(i64::from(max_hours)) => {
+          let hours = (now - created).num_hours();
+          return Ok(Some(tr!("drift.too_old", hours = hours, max = max_hours)));
+        }
+        Some(_) => ()
+      }
+    }
+
+    if let Some(max_commits) = max_commits {
+      let commits = match &self.commit {
+        Some(commit) => behind(commit)?,
+        None => None
+      };
+      match commits {
+        None => return Ok(Some(tr!("drift.unknown_commit"))),
+        Some(commits) if commits > max_commits as usize => {
+          return Ok(Some(tr!("drift.too_far", commits = commits, max = max_commits)));
+        }
+        Some(_) => ()
+      }
+    }
+    Ok(None)
+  }
+
+  /// How the `current` plan differs from this one, by project ID.
+  pub fn drift(&self, current: &[PlannedProject]) -> Vec<Drift> {
+    let saved: BTreeMap<_, _> = self.projects.iter().map(|p| (p.id.as_str(), p)).collect();
+    let current: BTreeMap<_, _> = current.iter().map(|p| (p.id.as_str(), p)).collect();
+
+    let mut drift = Vec::new();
+    for (id, was) in &saved {
+      match current.get(id) {
+        None => drift.push(Drift::Removed((*was).clone())),
+        Some(now) if now.size != was.size || now.target != was.target => {
+          drift.push(Drift::Changed((*was).clone(), (*now).clone()))
+        }
+        Some(_) => ()
+      }
+    }
+    for (id, now) in &current {
+      if !saved.contains_key(id) {
+        drift.push(Drift::Added((*now).clone()));
+      }
+    }
+    drift
+  }
+}
+
+/// A single difference between the saved plan and the current one.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Drift {
+  /// The project is planned now, but wasn't in the saved plan.
+  Added(PlannedProject),
+  /// The project was in the saved plan, but isn't planned now.
+  Removed(PlannedProject),
+  /// The project's size or target version has changed.
+  Changed(PlannedProject, PlannedProject)
+}
+
+impl fmt::Display for Drift {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Drift::Added(now) => write!(f, "{}", tr!("drift.added", name = now.name, now = now.describe())),
+      Drift::Removed(was) => write!(f, "{}", tr!("drift.removed", name = was.name, was = was.describe())),
+      Drift::Changed(was, now) => {
+        write!(f, "{}", tr!("drift.changed", name = now.name, was = was.describe(), now = now.describe()))
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::{Drift, PlannedProject, SavedPlan};
+  use crate::errors::Result;
+  use chrono::{DateTime, Utc};
+
+  fn proj(id: &str, size: &str, target: Option<&str>) -> PlannedProject {
+    PlannedProject::new(id.into(), format!("proj{}", id), size.into(), target.map(|t| t.into()))
+  }
+
+  fn behind(commits: Option<usize>) -> impl Fn(&str) -> Result<Option<usize>> { move |_| Ok(commits) }
+
+  #[test]
+  fn test_drift() {
+    let saved = SavedPlan::parse(
+      r#"{"schema": 1, "offline": false, "projects": [
+        {"id": "1", "name": "proj1", "size": "minor", "previous": "1.0.0", "current": "1.0.0", "target": "1.1.0",
+         "restricted": false, "prs": [], "deps": []},
+        {"id": "2", "name": "proj2", "size": "patch", "target": "0.1.1"},
+        {"id": "3", "name": "proj3", "size": "patch", "target": "3.0.1"}
+      ]}"#
+    )
+    .unwrap();
+
+    let current = vec![proj("1", "major", Some("2.0.0")), proj("3", "patch", Some("3.0.1")), proj("4", "none", None)];
+    assert_eq!(
+      vec![
+        Drift::Changed(proj("1", "minor", Some("1.1.0")), proj("1", "major", Some("2.0.0"))),
+        Drift::Removed(proj("2", "patch", Some("0.1.1"))),
+        Drift::Added(proj("4", "none", None))
+      ],
+      saved.drift(&current)
+    );
+  }
+
+  #[test]
+  fn test_schema() {
+    assert!(SavedPlan::parse(r#"{"schema": 2, "projects": []}"#).is_err());
+    assert!(SavedPlan::parse(r#"{"schema": 1, "projects": []}"#).unwrap().drift(&[]).is_empty());
+  }
+
+  #[test]
+  fn test_staleness() {
+    let saved = SavedPlan::parse(
+      r#"{"schema": 1, "created": "2024-03-01T12:00:00Z", "commit": "1a2b3c", "projects": []}"#
+    )
+    .unwrap();
+    let now: DateTime<Utc> = "2024-03-02T12:00:00Z".parse().unwrap();
+
+    assert!(saved.staleness(None, None, now, behind(None)).unwrap().is_none());
+    assert!(saved.staleness(Some(24), Some(3), now, behind(Some(3))).unwrap().is_none());
+    assert!(saved.staleness(Some(23), None, now, behind(None)).unwrap().is_some());
+    assert!(saved.staleness(None, Some(2), now, behind(Some(3))).unwrap().is_some());
+    assert!(saved.staleness(None, Some(2), now, behind(None)).unwrap().is_some());
+
+    let unknown = SavedPlan::parse(r#"{"schema": 1, "projects": []}"#).unwrap();
+    assert!(unknown.staleness(Some(24), None, now, behind(Some(0))).unwrap().is_some());
+    assert!(unknown.staleness(None, Some(2), now, behind(Some(0))).unwrap().is_some());
+  }
+}
@@ -0,0 +1,171 @@
+//! Interactions with the Gitea API v1, which Forgejo servers also provide.
+
+use crate::errors::Result;
+use crate::git::{GithubInfo, Repo, Span};
+use crate::github::{ApiCommit, ApiError, PrEdgeNode, API_ATTEMPTS, API_CONCURRENCY};
+use crate::metrics::count_api_call;
+use chrono::{DateTime, FixedOffset, Utc};
+use futures::stream::{self, StreamExt, TryStreamExt};
+use hyper::header::{ACCEPT, AUTHORIZATION};
+use hyper::{Body, Client, Request, StatusCode, Uri};
+use hyper_tls::HttpsConnector;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use tracing::{instrument, warn};
+
+/// Find the commits of a PR's span, each with the PR that merged it (if any).
+///
+/// Gitea can't list a commit's history along with its PRs, as GitHub's GraphQL API can: instead, the commits are
+/// found locally, and Gitea is asked for the PR of each. Gitea only knows the PR that a commit was merged by, so a
+/// PR's own commits are found from its span, as they are for GitHub.
+#[instrument(skip_all)]
+pub async fn commits_from_api(repo: &Repo, gitea_info: &GithubInfo, span: &Span) -> Result<Vec<ApiCommit>> {
+  let commits = match repo.commits_between_buf(span.begin(), span.end())? {
+    Some((commits, _)) => commits,
+    None => return Ok(Vec::new())
+  };
+
+  stream::iter(commits)
+    .map(|commit| async move {
+      let pr: Option<GiteaPr> = get_json(gitea_info, &format!("/commits/{}/pull", commit.id())).await?;
+      let prs = pr.and_then(GiteaPr::into_node).into_iter().collect();
+      Ok(ApiCommit::new(commit.id().to_string(), commit.summary().to_string(), prs))
+    })
+    .buffered(API_CONCURRENCY)
+    .try_collect()
+    .await
+}
+
+/// A PR as described by the REST API of a forge other than GitHub: Gitea's (or Forgejo's), or Bitbucket's.
+pub trait RestPr: DeserializeOwned {
+  /// The URL of the PR with this `number`.
+  fn url(info: &GithubInfo, number: u32) -> String;
+
+  /// The `Authorization` header that sends the forge's `token`.
+  fn authorization(token: String) -> String;
+
+  /// The PR in the shape of GitHub's API, unless it was closed without being merged.
+  fn into_node(self) -> Option<PrEdgeNode>;
+}
+
+/// Look up a single PR by number, if it's merged or open.
+pub async fn pr_from_api<P: RestPr>(info: &GithubInfo, number: u32) -> Result<Option<PrEdgeNode>> {
+  let authorization = info.token().await?.map(P::authorization);
+  let pr: Option<P> = get_json_at(P::url(info, number).parse::<Uri>()?, authorization).await?;
+  Ok(pr.and_then(P::into_node))
+}
+
+/// Get a route of the repository's API. A route that isn't found (such as the PR of a commit that wasn't merged by
+/// one) is `None`.
+async fn get_json<T: DeserializeOwned>(gitea_info: &GithubInfo, route: &str) -> Result<Option<T>> {
+  let uri = format!("{}{}", gitea_info.gitea_api_url(), route).parse::<Uri>()?;
+  let authorization = gitea_info.token().await?.map(GiteaPr::authorization);
+  get_json_at(uri, authorization).await
+}
+
+/// Get JSON from a forge's REST API, retrying like GitHub requests are. A URL that isn't found is `None`.
+pub async fn get_json_at<T: DeserializeOwned>(uri: Uri, authorization: Option<String>) -> Result<Option<T>> {
+  let client = Client::builder().build::<_, Body>(HttpsConnector::new());
+
+  let mut attempt = 0;
+  loop {
+    let mut request = Request::get(uri.clone()).header(ACCEPT, "application/json");
+    if let Some(authorization) = &authorization {
+      request = request.header(AUTHORIZATION, authorization);
+    }
+
+    count_api_call();
+    let failure = match client.request(request.body(Body::empty())?).await {
+      Ok(response) if response.status() == StatusCode::NOT_FOUND => return Ok(None),
+      Ok(response) if response.status().is_success() => {
+        let body = hyper::body::to_bytes(response.into_body()).await?;
+        return Ok(Some(serde_json::from_slice(&body)?));
+      }
+      Ok(response) => {
+        let status = response.status().as_u16();
+        let headers = response.headers().clone();
+        let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok()).map(|v| v.to_string());
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap_or_default();
+        ApiError::from_status(status, header, String::from_utf8_lossy(&body).into_owned(), Utc::now().timestamp())
+      }
+      Err(e) => ApiError::Unavailable { status: None, message: e.to_string() }
+    };
+
+    attempt += 1;
+    let wait = match failure.retry_wait(attempt, Utc::now().timestamp()) {
+      Some(wait) if attempt < API_ATTEMPTS => wait,
+      _ => return Err(failure.into())
+    };
+    warn!("API request to {} failed ({}), retrying in {}s.", uri, failure, wait.as_secs());
+    tokio::time::sleep(wait).await;
+  }
+}
+
+#[derive(Deserialize)]
+pub struct GiteaPr {
+  number: u32,
+  title: String,
+  #[serde(default)]
+  body: Option<String>,
+  state: String,
+  #[serde(default)]
+  merged: bool,
+  #[serde(default)]
+  labels: Vec<GiteaLabel>,
+  head: GiteaBranch,
+  merge_base: String,
+  merged_at: Option<DateTime<FixedOffset>>
+}
+
+#[derive(Deserialize)]
+struct GiteaLabel {
+  name: String
+}
+
+#[derive(Deserialize)]
+struct GiteaBranch {
+  #[serde(rename = "ref")]
+  ref_name: String
+}
+
+impl RestPr for GiteaPr {
+  fn url(info: &GithubInfo, number: u32) -> String { format!("{}/pulls/{}", info.gitea_api_url(), number) }
+  fn authorization(token: String) -> String { format!("token {}", token) }
+
+  fn into_node(self) -> Option<PrEdgeNode> {
+    let merged_at = match (self.merged, self.state.as_str()) {
+      (true, _) => Some(self.merged_at.unwrap_or_else(|| Utc::now().into())),
+      (false, "open") => None,
+      (false, _) => return None
+    };
+    let labels = self.labels.into_iter().map(|l| l.name).collect();
+    Some(PrEdgeNode::new(self.number, self.title, self.body, labels, self.head.ref_name, self.merge_base, merged_at))
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::{GiteaPr, RestPr};
+
+  fn pr(state: &str, merged: bool) -> GiteaPr {
+    let json = format!(
+      r#"{{"number": 12, "title": "feat: widgets", "body": null, "state": "{}", "merged": {},
+          "labels": [{{"name": "ui"}}], "head": {{"ref": "widgets", "sha": "abc"}}, "base": {{"ref": "main"}},
+          "merge_base": "def", "merged_at": {}}}"#,
+      state,
+      merged,
+      if merged { r#""2024-03-01T12:00:00+01:00""# } else { "null" }
+    );
+    serde_json::from_str(&json).unwrap()
+  }
+
+  #[test]
+  fn test_into_node() {
+    let merged = pr("closed", true).into_node().unwrap();
+    assert_eq!(12, merged.number());
+    assert_eq!("MERGED", merged.state());
+
+    assert_eq!("OPEN", pr("open", false).into_node().unwrap().state());
+    assert!(pr("closed", false).into_node().is_none());
+  }
+}
@@ -1,13 +1,32 @@
 //! Interactions with github API v4.
 
-use crate::errors::Result;
-use crate::git::{time_to_datetime, Auth, CommitInfoBuf, FromTag, FromTagBuf, FullPr, GithubInfo, Repo, Span};
+use crate::bitbucket::{self, BitbucketPr};
+use crate::errors::{Context as _, Result};
+use crate::git::{time_to_datetime, Auth, CommitInfoBuf, Forge, FromTag, FromTagBuf, FullPr, GithubApp, GithubInfo, Repo,
+                 Span};
+use crate::gitea::{self, GiteaPr};
+use crate::metrics::count_api_call;
 use chrono::{DateTime, FixedOffset, TimeZone, Utc};
+use futures::stream::{self, StreamExt, TryStreamExt};
 use octocrab::Octocrab;
-use serde::de::{self, Deserializer, Visitor};
+use serde::de::{self, DeserializeOwned, Deserializer, Visitor};
 use serde::Deserialize;
 use std::collections::{HashMap, HashSet, VecDeque};
 use std::fmt;
+use std::time::Duration;
+use tracing::{instrument, trace, warn};
+
+/// How many times to try a GitHub API request before giving up.
+pub const API_ATTEMPTS: u32 = 4;
+
+/// The longest we're willing to wait for a rate limit to reset, in seconds.
+const MAX_RATE_LIMIT_WAIT: i64 = 300;
+
+/// How many PRs' commits to request from the GitHub API at once.
+pub const API_CONCURRENCY: usize = 8;
+
+/// The info string of a fenced block in a PR description that holds the PR's changelog entry.
+const CHANGELOG_FENCE: &str = "versio:changelog";
 
 /// Find all changes in a repo more cleverly than `git rev-parse begin..end` using the GitHub v4 GraphQL API.
 ///
@@ -20,84 +39,137 @@ use std::fmt;
 /// rev-parse, we assume that this is the result of a "squash merge" from that PR (or some other type of PR
 /// rebase). The squash commit is excluded from all PRs: instead the PR's own commits are examined normally. In
 /// this way, the original type and size information from the PR is preserved.
-#[allow(clippy::map_entry)]
+#[instrument(skip_all)]
 pub async fn changes(auth: &Auth, repo: &Repo, baseref: FromTagBuf, headref: String) -> Result<Changes> {
-  let mut all_commits = HashSet::new();
-  let mut all_prs = HashMap::new();
-
-  let mut discover_order = 0;
-  let mut queue = VecDeque::new();
-  let offset = FixedOffset::west_opt(0).expect("0 should be in bounds");
-  let pr_zero = FullPr::lookup(
-    repo,
-    baseref,
-    headref.clone(),
-    0,
-    "".into(),
-    offset.timestamp_opt(Utc::now().timestamp(), 0).single().expect("utc/0 in bounds"),
-    discover_order
-  )?;
-  discover_order += 1;
-  queue.push_back(pr_zero.span().ok_or_else(|| bad!("Unable to get oid for seed ref \"{}\".", headref))?);
-  all_prs.insert(pr_zero.number(), pr_zero);
+  let mut found = Discovery::new();
+  let pr_zero = seed_pr(repo, baseref, &headref, found.discover_order)?;
+  found.discover_order += 1;
+  found.queue.push_back(pr_zero.span().ok_or_else(|| bad!("Unable to get oid for seed ref \"{}\".", headref))?);
+  found.all_prs.insert(pr_zero.number(), pr_zero);
 
   let github_info = match repo.github_info(auth) {
     Ok(github_info) => github_info,
-    Err(_) => return Ok(Changes { groups: all_prs, commits: all_commits })
+    Err(e) => {
+      trace!("Not grouping commits by PR: {:#}", e);
+      return Ok(found.into_changes());
+    }
   };
 
-  while let Some(span) = queue.pop_front() {
-    let commit_list = commits_from_v4_api(&github_info, &span).await?;
-    let commit_list: Vec<_> = commit_list
-      .into_iter()
-      .filter_map(|commit| {
-        if all_commits.contains(commit.oid()) {
-          return None;
-        }
+  // Fetch each wave of newly-discovered PRs concurrently, but handle the results in discovery order, so that the
+  // outcome is the same as fetching them one at a time.
+  while !found.queue.is_empty() {
+    let spans: Vec<_> = found.queue.drain(..).collect();
+    let fetched: Vec<_> = stream::iter(spans.iter())
+      .map(|span| commits_of_span(repo, &github_info, span))
+      .buffered(API_CONCURRENCY)
+      .try_collect()
+      .await?;
+
+    for (span, commits) in spans.iter().zip(fetched) {
+      found.handle_span(repo, &github_info, span, commits).await?;
+    }
+  }
 
-        let mut retain = true;
-        let (oid, prs) = commit.extract();
-        for pr in prs.merged_only() {
-          let number = pr.number();
-          if !all_prs.contains_key(&number) {
-            let full_pr = match pr.lookup(repo, discover_order) {
-              Ok(pr) => pr,
-              Err(e) => return Some(Err(e))
-            };
-            discover_order += 1;
-            if let Some(span) = full_pr.span() {
-              queue.push_back(span);
-            }
-            all_prs.insert(number, full_pr);
-          }
-          let full_pr = all_prs.get_mut(&number).unwrap();
+  Ok(found.into_changes())
+}
 
-          if full_pr.best_guess() {
-            full_pr.add_commit(CommitInfoBuf::guess(oid.clone()));
-          } else if !full_pr.contains(&oid) {
-            retain = false;
+/// The commits and PRs found so far by `changes`, and the PRs whose commits are still to be fetched.
+struct Discovery {
+  all_commits: HashSet<String>,
+  all_prs: HashMap<u32, FullPr>,
+  discover_order: usize,
+  queue: VecDeque<Span>
+}
+
+impl Discovery {
+  fn new() -> Discovery {
+    Discovery { all_commits: HashSet::new(), all_prs: HashMap::new(), discover_order: 0, queue: VecDeque::new() }
+  }
+
+  fn into_changes(self) -> Changes {
+    Changes { commits: self.all_commits, groups: self.all_prs, offline: false, unavailable: None }
+  }
+
+  /// Sort the commits that GitHub found in a PR's span into their own PRs, queueing any newly-discovered PRs.
+  #[allow(clippy::map_entry)]
+  async fn handle_span(
+    &mut self, repo: &Repo, github_info: &GithubInfo, span: &Span, commits: Vec<ApiCommit>
+  ) -> Result<()> {
+    for commit in commits {
+      if self.all_commits.contains(commit.oid()) {
+        continue;
+      }
+
+      let (oid, headline, prs) = commit.extract();
+      let mut prs: Vec<_> = prs.merged_only().collect();
+      if prs.is_empty() {
+        // GitHub doesn't always associate a squash commit with its PR, but the PR number is in the subject.
+        if let Some(number) = squash_pr_number(&headline) {
+          prs.extend(pr_of_number(github_info, number).await?);
+        }
+      }
+
+      let mut retain = true;
+      for pr in prs {
+        let number = pr.number();
+        if !self.all_prs.contains_key(&number) {
+          let full_pr = pr.lookup(repo, self.discover_order)?;
+          self.discover_order += 1;
+          if let Some(span) = full_pr.span() {
+            self.queue.push_back(span);
           }
+          self.all_prs.insert(number, full_pr);
         }
+        let full_pr = self.all_prs.get_mut(&number).unwrap();
 
-        if retain {
-          Some(Ok(oid))
-        } else {
-          all_prs.get_mut(&span.number()).unwrap().add_exclude(&oid);
-          None
+        if full_pr.best_guess() {
+          full_pr.add_commit(CommitInfoBuf::guess(oid.clone()));
+        } else if !full_pr.contains(&oid) {
+          retain = false;
         }
-      })
-      .collect::<Result<_>>()?;
+      }
 
-    all_commits.extend(commit_list.into_iter());
+      if retain {
+        self.all_commits.insert(oid);
+      } else {
+        self.all_prs.get_mut(&span.number()).unwrap().add_exclude(&oid);
+      }
+    }
+    Ok(())
   }
+}
 
-  Ok(Changes { commits: all_commits, groups: all_prs })
+/// Find the PR number at the end of a squash-merge commit subject, like "feat: do the thing (#123)".
+fn squash_pr_number(headline: &str) -> Option<u32> {
+  let headline = headline.trim_end();
+  let rest = headline.strip_suffix(')')?;
+  let start = rest.rfind("(#")?;
+  rest[start + 2 ..].parse().ok()
+}
+
+/// Ask the forge for the commits of a PR's span, each with the PRs that it's associated with.
+async fn commits_of_span(repo: &Repo, github_info: &GithubInfo, span: &Span) -> Result<Vec<ApiCommit>> {
+  match github_info.forge() {
+    Forge::Github => commits_from_v4_api(github_info, span).await,
+    Forge::Gitea => gitea::commits_from_api(repo, github_info, span).await,
+    Forge::Bitbucket => bitbucket::commits_from_api(repo, github_info, span).await
+  }
+}
+
+/// Ask the forge for a single PR by number, if it's merged or open.
+async fn pr_of_number(github_info: &GithubInfo, number: u32) -> Result<Option<PrEdgeNode>> {
+  match github_info.forge() {
+    Forge::Github => pr_from_v4_api(github_info, number).await,
+    Forge::Gitea => gitea::pr_from_api::<GiteaPr>(github_info, number).await,
+    Forge::Bitbucket => gitea::pr_from_api::<BitbucketPr>(github_info, number).await
+  }
 }
 
 pub fn line_commits_head(repo: &Repo, base: FromTag) -> Result<Vec<CommitInfoBuf>> {
   repo.commits_to_head(base, false)?.map(|i| i?.buffer()).collect::<Result<_>>()
 }
 
+#[instrument(skip_all)]
 async fn commits_from_v4_api(github_info: &GithubInfo, span: &Span) -> Result<Vec<ApiCommit>> {
   let query = r#"query associatedPRs($since:GitTimestamp!, $sha:String!, $repo:String!, $owner:String!){
   repository(name:$repo, owner:$owner){
@@ -118,11 +190,14 @@ async fn commits_from_v4_api(github_info: &GithubInfo, span: &Span) -> Result<Ve
 
 fragment commitResult on Commit {
     oid
+    messageHeadline
     associatedPullRequests(first:10) {
       edges {
         node {
           number
           title
+          body
+          labels(first:20) { nodes { name } }
           state
           headRefName
           baseRefOid
@@ -147,12 +222,8 @@ fragment commitResult on Commit {
     github_info.repo_name()
   );
 
-  let octo = Octocrab::builder();
-  let token = github_info.token().clone();
-  let octo = if let Some(token) = token { octo.personal_token(token) } else { octo };
-  let octo = octo.build()?;
   let full_query = serde_json::json!({"query": &query, "variables": &variables});
-  let changes: ChangesResponse = octo.post("/graphql", Some(&full_query)).await?;
+  let changes: ChangesResponse = post_graphql(github_info, &full_query).await?;
 
   let changes = changes.data.repository.commit.history.nodes;
   let mut changes: HashMap<String, ApiCommit> = changes.into_iter().map(|c| (c.oid().to_string(), c)).collect();
@@ -171,15 +242,234 @@ fragment commitResult on Commit {
   Ok(changes.into_values().collect())
 }
 
+/// Look up a single PR by number in GitHub's GraphQL API, if it's merged or open.
+async fn pr_from_v4_api(github_info: &GithubInfo, number: u32) -> Result<Option<PrEdgeNode>> {
+  let query = r#"query pullRequest($number:Int!, $repo:String!, $owner:String!){
+  repository(name:$repo, owner:$owner){
+    pullRequest(number: $number){
+      number
+      title
+      body
+      labels(first:20) { nodes { name } }
+      state
+      headRefName
+      baseRefOid
+      closedAt
+    }
+  }
+}"#;
+
+  let variables = serde_json::json!({
+    "number": number,
+    "owner": github_info.owner_name(),
+    "repo": github_info.repo_name()
+  });
+
+  let full_query = serde_json::json!({"query": &query, "variables": &variables});
+  let response: PrResponse = post_graphql(github_info, &full_query).await?;
+
+  let pr = response.data.repository.pull_request;
+  Ok(pr.filter(|pr| pr.state() == "MERGED" || pr.state() == "OPEN"))
+}
+
+/// Send a GraphQL query, retrying on rate limits and transient server or network failures. If the request
+/// still fails, the error is an `ApiError`.
+async fn post_graphql<T: DeserializeOwned>(github_info: &GithubInfo, query: &serde_json::Value) -> Result<T> {
+  let octo = Octocrab::builder();
+  let token = github_info.token().await?;
+  let octo = if let Some(token) = token { octo.personal_token(token) } else { octo };
+  let octo = octo.build()?;
+  let url = octo.absolute_url(github_info.graphql_url())?;
+
+  let mut attempt = 0;
+  loop {
+    count_api_call();
+    let failure = match octo._post(url.clone(), Some(query)).await {
+      Ok(response) if response.status().is_success() => return Ok(response.json().await?),
+      Ok(response) => {
+        let status = response.status().as_u16();
+        let headers = response.headers().clone();
+        let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok()).map(|v| v.to_string());
+        let message = response.text().await.unwrap_or_default();
+        ApiError::from_status(status, header, message, Utc::now().timestamp())
+      }
+      Err(e) => ApiError::Unavailable { status: None, message: e.to_string() }
+    };
+
+    attempt += 1;
+    let wait = match failure.retry_wait(attempt, Utc::now().timestamp()) {
+      Some(wait) if attempt < API_ATTEMPTS => wait,
+      _ => return Err(failure.into())
+    };
+    warn!("GitHub API request failed ({}), retrying in {}s.", failure, wait.as_secs());
+    tokio::time::sleep(wait).await;
+  }
+}
+
+/// Mint an installation token for a GitHub App, returning the token and the time that it expires. If the app
+/// doesn't name its installation, the installation for the given repo is used.
+pub async fn mint_installation_token(app: &GithubApp, owner: &str, repo: &str) -> Result<(String, DateTime<Utc>)> {
+  let key = jsonwebtoken::EncodingKey::from_rsa_pem(app.private_key_pem()?.as_bytes())?;
+  let octo = Octocrab::builder().app(app.app_id().into(), key).build()?;
+
+  let installation_id = match app.installation_id() {
+    Some(id) => id,
+    None => {
+      let route = format!("/repos/{}/{}/installation", owner, repo);
+      count_api_call();
+      let installation: InstallationResponse = octo.get(route, None::<&()>).await.with_context(|| {
+        format!("Can't find an installation of GitHub App {} for {}/{}.", app.app_id(), owner, repo)
+      })?;
+      installation.id
+    }
+  };
+
+  let route = format!("/app/installations/{}/access_tokens", installation_id);
+  count_api_call();
+  let token: TokenResponse = octo.post(route, None::<&()>).await.with_context(|| {
+    format!("Can't mint a token for installation {} of GitHub App {}.", installation_id, app.app_id())
+  })?;
+
+  // Installation tokens last an hour, if GitHub doesn't say otherwise.
+  let expires = token
+    .expires_at
+    .and_then(|at| DateTime::parse_from_rfc3339(&at).ok())
+    .map(|at| at.with_timezone(&Utc))
+    .unwrap_or_else(|| Utc::now() + chrono::Duration::hours(1));
+  Ok((token.token, expires))
+}
+
+#[derive(Deserialize)]
+struct InstallationResponse {
+  id: u64
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+  token: String,
+  expires_at: Option<String>
+}
+
+/// A GitHub API request that failed, even after retrying.
+///
+/// This can be recovered from an `Error` with `downcast_ref`, which lets callers choose to carry on without PR
+/// information instead of failing outright.
+#[derive(Debug)]
+pub enum ApiError {
+  /// The rate limit was exceeded; `reset` is the epoch second when it's lifted, if known.
+  RateLimited { reset: Option<i64> },
+  /// The API couldn't be reached, or it had a server error.
+  Unavailable { status: Option<u16>, message: String },
+  /// The API refused the request: bad credentials, no access to the repo, etc.
+  Rejected { status: u16, message: String }
+}
+
+impl ApiError {
+  pub fn from_status<F: Fn(&str) -> Option<String>>(status: u16, header: F, message: String, now: i64) -> ApiError {
+    let retry_after = header("retry-after").and_then(|v| v.trim().parse::<i64>().ok());
+    let exhausted = header("x-ratelimit-remaining").map(|v| v.trim() == "0").unwrap_or(false);
+
+    if status == 429 || (status == 403 && (exhausted || retry_after.is_some())) {
+      let reset = match retry_after {
+        Some(after) => Some(now + after),
+        None => header("x-ratelimit-reset").and_then(|v| v.trim().parse().ok())
+      };
+      ApiError::RateLimited { reset }
+    } else if status >= 500 {
+      ApiError::Unavailable { status: Some(status), message }
+    } else {
+      ApiError::Rejected { status, message }
+    }
+  }
+
+  /// How long to wait before the next attempt, or `None` if retrying won't help.
+  pub fn retry_wait(&self, attempt: u32, now: i64) -> Option<Duration> {
+    match self {
+      ApiError::RateLimited { reset: Some(reset) } => {
+        let wait = (reset - now).max(0) + 1;
+        (wait <= MAX_RATE_LIMIT_WAIT).then(|| Duration::from_secs(wait as u64))
+      }
+      ApiError::RateLimited { reset: None } | ApiError::Unavailable { .. } => Some(Duration::from_secs(1 << attempt)),
+      ApiError::Rejected { .. } => None
+    }
+  }
+}
+
+impl fmt::Display for ApiError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      ApiError::RateLimited { reset: Some(reset) } => write!(f, "GitHub API rate limit exceeded until {}", reset),
+      ApiError::RateLimited { reset: None } => write!(f, "GitHub API rate limit exceeded"),
+      ApiError::Unavailable { status: Some(status), message } => {
+        write!(f, "GitHub API unavailable: HTTP {}: {}", status, message.trim())
+      }
+      ApiError::Unavailable { status: None, message } => write!(f, "GitHub API unavailable: {}", message.trim()),
+      ApiError::Rejected { status, message } => {
+        write!(f, "GitHub API rejected request: HTTP {}: {}", status, message.trim())
+      }
+    }
+  }
+}
+
+impl std::error::Error for ApiError {}
+
+/// Find the changes between two refs without asking the forge: every commit is placed in "PR zero".
+pub fn local_changes(repo: &Repo, baseref: FromTagBuf, headref: String) -> Result<Changes> {
+  let pr_zero = seed_pr(repo, baseref, &headref, 0)?;
+  let groups = Some((pr_zero.number(), pr_zero)).into_iter().collect();
+  Ok(Changes { groups, commits: HashSet::new(), offline: true, unavailable: None })
+}
+
+/// The "PR zero" that holds all commits between the two refs that aren't found in some other PR.
+fn seed_pr(repo: &Repo, baseref: FromTagBuf, headref: &str, discovery_order: usize) -> Result<FullPr> {
+  let offset = FixedOffset::west_opt(0).expect("0 should be in bounds");
+  FullPr::lookup(
+    repo,
+    baseref,
+    headref.to_string(),
+    0,
+    "".into(),
+    offset.timestamp_opt(Utc::now().timestamp(), 0).single().expect("utc/0 in bounds"),
+    discovery_order
+  )
+}
+
 pub struct Changes {
   commits: HashSet<String>,
-  groups: HashMap<u32, FullPr>
+  groups: HashMap<u32, FullPr>,
+  offline: bool,
+  unavailable: Option<String>
 }
 
 impl Changes {
   pub fn commits(&self) -> &HashSet<String> { &self.commits }
   pub fn groups(&self) -> &HashMap<u32, FullPr> { &self.groups }
   pub fn into_groups(self) -> HashMap<u32, FullPr> { self.groups }
+
+  /// True if the forge wasn't consulted, so commits aren't grouped by PR.
+  pub fn offline(&self) -> bool { self.offline }
+
+  /// Why the forge couldn't be consulted, if it was supposed to be.
+  pub fn unavailable(&self) -> Option<&str> { self.unavailable.as_deref() }
+
+  /// Record that these changes are local only, because the forge's API failed.
+  pub fn with_unavailable(mut self, reason: String) -> Changes {
+    self.unavailable = Some(reason);
+    self
+  }
+
+  /// Keep only the PRs that pass `keep`, and the commits of those PRs.
+  pub fn retain_prs<F: FnMut(&FullPr) -> Result<bool>>(mut self, mut keep: F) -> Result<Changes> {
+    let mut kept = HashMap::new();
+    for (number, pr) in self.groups {
+      if keep(&pr)? {
+        kept.insert(number, pr);
+      }
+    }
+    self.commits.retain(|oid| kept.values().any(|pr| pr.commits().iter().any(|c| c.id() == oid)));
+    self.groups = kept;
+    Ok(self)
+  }
 }
 
 #[derive(Deserialize)]
@@ -208,15 +498,40 @@ struct History {
 }
 
 #[derive(Deserialize)]
-struct ApiCommit {
+struct PrResponse {
+  data: PrData
+}
+
+#[derive(Deserialize)]
+struct PrData {
+  repository: PrRepository
+}
+
+#[derive(Deserialize)]
+struct PrRepository {
+  #[serde(rename = "pullRequest")]
+  pull_request: Option<PrEdgeNode>
+}
+
+#[derive(Deserialize)]
+pub struct ApiCommit {
   oid: String,
+  #[serde(rename = "messageHeadline", default)]
+  message_headline: String,
   #[serde(rename = "associatedPullRequests")]
   associated_pull_requests: PrList,
   parents: ParentList
 }
 
 impl ApiCommit {
-  fn extract(self) -> (String, PrList) { (self.oid, self.associated_pull_requests) }
+  /// A commit from another forge's API, which doesn't give its parents.
+  pub fn new(oid: String, message_headline: String, prs: Vec<PrEdgeNode>) -> ApiCommit {
+    let edges = prs.into_iter().map(|node| PrEdge { node }).collect();
+    let associated_pull_requests = PrList { edges };
+    ApiCommit { oid, message_headline, associated_pull_requests, parents: ParentList { edges: Vec::new() } }
+  }
+
+  fn extract(self) -> (String, String, PrList) { (self.oid, self.message_headline, self.associated_pull_requests) }
   fn oid(&self) -> &str { &self.oid }
 }
 
@@ -252,10 +567,14 @@ struct PrEdge {
 }
 
 #[derive(Deserialize)]
-struct PrEdgeNode {
+pub struct PrEdgeNode {
   number: u32,
   state: String,
   title: String,
+  #[serde(default)]
+  body: Option<String>,
+  #[serde(default)]
+  labels: Option<LabelList>,
   #[serde(rename = "headRefName")]
   head_ref_name: String,
   #[serde(rename = "baseRefOid")]
@@ -264,12 +583,38 @@ struct PrEdgeNode {
   closed_at: DateTime<FixedOffset>
 }
 
+#[derive(Deserialize)]
+struct LabelList {
+  nodes: Vec<Label>
+}
+
+#[derive(Deserialize)]
+struct Label {
+  name: String
+}
+
 impl PrEdgeNode {
+  /// A PR from another forge's API: it's merged at `merged_at`, or else it's still open.
+  pub fn new(
+    number: u32, title: String, body: Option<String>, labels: Vec<String>, head_ref_name: String,
+    base_ref_oid: String, merged_at: Option<DateTime<FixedOffset>>
+  ) -> PrEdgeNode {
+    let state = if merged_at.is_some() { "MERGED" } else { "OPEN" }.to_string();
+    let labels = Some(LabelList { nodes: labels.into_iter().map(|name| Label { name }).collect() });
+    let offset = FixedOffset::west_opt(0).expect("0 in bounds");
+    let closed_at =
+      merged_at.unwrap_or_else(|| offset.timestamp_opt(Utc::now().timestamp(), 0).single().expect("utc/0 in bounds"));
+    PrEdgeNode { number, state, title, body, labels, head_ref_name, base_ref_oid, closed_at }
+  }
+
   pub fn number(&self) -> u32 { self.number }
   pub fn state(&self) -> &str { &self.state }
 
   pub fn lookup(self, repo: &Repo, discover_order: usize) -> Result<FullPr> {
-    FullPr::lookup(
+    let changelog = self.body.as_deref().and_then(changelog_override);
+    let body = self.body.unwrap_or_default();
+    let labels = self.labels.map(|l| l.nodes.into_iter().map(|n| n.name).collect()).unwrap_or_default();
+    let pr = FullPr::lookup(
       repo,
       FromTagBuf::new(self.base_ref_oid, false),
       self.head_ref_name,
@@ -277,10 +622,21 @@ impl PrEdgeNode {
       self.title,
       self.closed_at,
       discover_order
-    )
+    )?;
+    Ok(pr.with_changelog(changelog).with_labels(labels).with_body(body))
   }
 }
 
+/// The text of a fenced `versio:changelog` block in a PR description, which replaces the PR's commits in the
+/// changelog.
+fn changelog_override(body: &str) -> Option<String> {
+  let mut lines = body.lines().map(|line| line.trim_end());
+  lines.find(|line| line.trim_start().starts_with("```") && line.trim_start()[3 ..].trim() == CHANGELOG_FENCE)?;
+  let text = lines.take_while(|line| line.trim() != "```").collect::<Vec<_>>().join("\n");
+  let text = text.trim();
+  (!text.is_empty()).then(|| text.to_string())
+}
+
 fn deserialize_datetime<'de, D: Deserializer<'de>>(desr: D) -> std::result::Result<DateTime<FixedOffset>, D::Error> {
   struct DateTimeVisitor;
 
@@ -306,3 +662,54 @@ fn deserialize_datetime<'de, D: Deserializer<'de>>(desr: D) -> std::result::Resu
 
   desr.deserialize_any(DateTimeVisitor)
 }
+
+#[cfg(test)]
+mod test {
+  use super::{changelog_override, squash_pr_number, ApiError};
+  use std::time::Duration;
+
+  #[test]
+  fn test_squash_pr_number() {
+    assert_eq!(Some(123), squash_pr_number("feat: do the thing (#123)"));
+    assert_eq!(Some(7), squash_pr_number("fix(api)!: break it (#7) "));
+    assert_eq!(None, squash_pr_number("fix: see (#123) for details"));
+    assert_eq!(None, squash_pr_number("fix: nothing (#abc)"));
+    assert_eq!(None, squash_pr_number("fix: nothing"));
+  }
+
+  #[test]
+  fn test_changelog_override() {
+    let body = "Some details.\r\n\r\n```versio:changelog\r\nAdded *widgets*.\r\n\r\nFixed the frobber.\r\n```\r\nMore.";
+    assert_eq!(Some("Added *widgets*.\n\nFixed the frobber.".into()), changelog_override(body));
+    assert_eq!(None, changelog_override("```rust\nfn main() {}\n```"));
+    assert_eq!(None, changelog_override("```versio:changelog\n\n```"));
+  }
+
+  #[test]
+  fn test_api_error_retry() {
+    let headers = |pairs: &'static [(&'static str, &'static str)]| {
+      move |name: &str| pairs.iter().find(|(k, _)| *k == name).map(|(_, v)| v.to_string())
+    };
+
+    let limited = ApiError::from_status(
+      403,
+      headers(&[("x-ratelimit-remaining", "0"), ("x-ratelimit-reset", "1030")]),
+      "".into(),
+      1000
+    );
+    assert!(matches!(limited, ApiError::RateLimited { reset: Some(1030) }));
+    assert_eq!(Some(Duration::from_secs(31)), limited.retry_wait(1, 1000));
+    assert_eq!(None, limited.retry_wait(1, 500));
+
+    let secondary = ApiError::from_status(403, headers(&[("retry-after", "60")]), "".into(), 1000);
+    assert!(matches!(secondary, ApiError::RateLimited { reset: Some(1060) }));
+
+    let server = ApiError::from_status(502, headers(&[]), "bad gateway".into(), 1000);
+    assert!(matches!(server, ApiError::Unavailable { status: Some(502), .. }));
+    assert_eq!(Some(Duration::from_secs(4)), server.retry_wait(2, 1000));
+
+    let denied = ApiError::from_status(403, headers(&[("x-ratelimit-remaining", "4999")]), "no".into(), 1000);
+    assert!(matches!(denied, ApiError::Rejected { status: 403, .. }));
+    assert_eq!(None, denied.retry_wait(1, 1000));
+  }
+}
@@ -0,0 +1,133 @@
+//! Localized user-facing messages.
+//!
+//! Messages are looked up by key in a catalog: a TOML file of (possibly nested) tables of strings, in which
+//! `{name}` placeholders are filled in when the message is shown. English is built in; a catalog for another
+//! locale is read from `.versio/locales/<locale>.toml` in the repo or in the user's home directory (which takes
+//! precedence). Messages that a catalog leaves out are shown in English.
+
+use crate::errors::{Context as _, Result};
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+const PREFS_DIR: &str = ".versio";
+const LOCALES_DIR: &str = "locales";
+const DEFAULT_LOCALE: &str = "en";
+const LOCALE_ENV: &str = "VERSIO_LOCALE";
+
+static CATALOG: OnceLock<Catalog> = OnceLock::new();
+
+/// Look up a message, filling in its placeholders: `tr!("plan.pr", number = 12, size = "minor")`.
+#[macro_export]
+macro_rules! tr {
+  ($key:expr) => ($crate::i18n::tr($key, &[]));
+  ($key:expr, $($name:ident = $val:expr),+) => (
+    $crate::i18n::tr($key, &[$((stringify!($name), &$val as &dyn std::fmt::Display)),+])
+  )
+}
+
+/// Choose the locale, and load its catalog. `VERSIO_LOCALE` takes precedence over `pref` (the `locale` from the
+/// preferences files), which takes precedence over the system locale.
+pub fn init(root: &Path, pref: Option<&str>) -> Result<()> {
+  let locale = std::env::var(LOCALE_ENV).ok().or_else(|| pref.map(|p| p.to_string())).or_else(system_locale);
+  let mut catalog = Catalog::builtin();
+  if let Some(locale) = locale.as_deref().and_then(normalize_locale) {
+    for dir in [Some(root.to_path_buf()), dirs::home_dir()].into_iter().flatten() {
+      if let Some(path) = find_catalog(&dir, &locale) {
+        let data = std::fs::read_to_string(&path)?;
+        let found = Catalog::parse(&data).with_context(|| format!("Unable to read catalog {}.", path.display()))?;
+        catalog.overlay(found);
+      }
+    }
+  }
+
+  // Only the first call has any effect.
+  let _ = CATALOG.set(catalog);
+  Ok(())
+}
+
+/// Look up the message `key`, filling in its `{name}` placeholders from `args`.
+pub fn tr(key: &str, args: &[(&str, &dyn Display)]) -> String {
+  let catalog = CATALOG.get_or_init(Catalog::builtin);
+  let mut message = catalog.messages.get(key).cloned().unwrap_or_else(|| key.to_string());
+  for (name, val) in args {
+    message = message.replace(&format!("{{{}}}", name), &val.to_string());
+  }
+  message
+}
+
+struct Catalog {
+  messages: HashMap<String, String>
+}
+
+impl Catalog {
+  fn builtin() -> Catalog { Catalog::parse(include_str!("locale/en.toml")).expect("Built-in catalog is valid.") }
+
+  fn parse(data: &str) -> Result<Catalog> {
+    let mut messages = HashMap::new();
+    flatten("", toml::from_str(data)?, &mut messages)?;
+    Ok(Catalog { messages })
+  }
+
+  fn overlay(&mut self, other: Catalog) { self.messages.extend(other.messages); }
+}
+
+fn flatten(prefix: &str, value: toml::Value, messages: &mut HashMap<String, String>) -> Result<()> {
+  match value {
+    toml::Value::String(s) => {
+      messages.insert(prefix.to_string(), s);
+    }
+    toml::Value::Table(table) => {
+      for (key, value) in table {
+        let key = if prefix.is_empty() { key } else { format!("{}.{}", prefix, key) };
+        flatten(&key, value, messages)?;
+      }
+    }
+    other => bail!("Message \"{}\" isn't a string: {}", prefix, other)
+  }
+  Ok(())
+}
+
+fn system_locale() -> Option<String> {
+  ["LC_ALL", "LC_MESSAGES", "LANG"].iter().filter_map(|var| std::env::var(var).ok()).find(|v| !v.is_empty())
+}
+
+/// Turn a locale like "pt_BR.UTF-8" into "pt_br", or `None` for the built-in English.
+fn normalize_locale(locale: &str) -> Option<String> {
+  let locale = locale.split(['.', '@']).next().unwrap_or_default().replace('-', "_").to_lowercase();
+  match locale.as_str() {
+    "" | "c" | "posix" => None,
+    l if l == DEFAULT_LOCALE || l.starts_with("en_") => None,
+    _ => Some(locale)
+  }
+}
+
+/// Find the catalog for `locale` (e.g. "pt_br"), or else for its language ("pt").
+fn find_catalog(dir: &Path, locale: &str) -> Option<PathBuf> {
+  let locales = dir.join(PREFS_DIR).join(LOCALES_DIR);
+  let lang = locale.split('_').next().unwrap_or(locale);
+  [locale, lang].iter().map(|l| locales.join(format!("{}.toml", l))).find(|p| p.exists())
+}
+
+#[cfg(test)]
+mod test {
+  use super::{normalize_locale, Catalog};
+
+  #[test]
+  fn test_catalog_parse() {
+    let mut catalog = Catalog::builtin();
+    catalog.overlay(Catalog::parse("[release]\ncomplete = \"Fertig.\"\n").unwrap());
+    assert_eq!("Fertig.", catalog.messages["release.complete"]);
+    assert_eq!("Check complete.", catalog.messages["check.complete"]);
+    assert!(Catalog::parse("[release]\ncomplete = 3\n").is_err());
+  }
+
+  #[test]
+  fn test_normalize_locale() {
+    assert_eq!(Some("pt_br".to_string()), normalize_locale("pt_BR.UTF-8"));
+    assert_eq!(Some("de".to_string()), normalize_locale("de"));
+    assert_eq!(None, normalize_locale("en_US.UTF-8"));
+    assert_eq!(None, normalize_locale("C"));
+  }
+}
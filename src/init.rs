@@ -4,7 +4,7 @@ use crate::bail;
 use crate::config::CONFIG_FILENAME;
 use crate::errors::{Error, Result};
 use crate::mark::Mark;
-use crate::scan::{find_reg_data, JsonScanner, Scanner, TomlScanner, XmlScanner};
+use crate::scan::{find_reg_data, JsonScanner, Scanner, TomlScanner, XmlScanner, YamlScanner};
 use ignore::WalkBuilder;
 use path_slash::PathExt;
 use std::collections::{HashMap, HashSet};
@@ -15,24 +15,133 @@ use std::io::Write;
 use std::path::Path;
 use tracing::warn;
 
-pub fn init(max_depth: u16) -> Result<()> {
+/// A preset of `init --template`, which only finds the projects of a common stack, and configures them with that
+/// stack's conventions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stack {
+  /// The crates of a Cargo workspace.
+  RustWorkspace,
+  /// The packages of an npm workspace.
+  NpmMonorepo,
+  /// Python projects built with Poetry.
+  PythonPoetry
+}
+
+pub fn init(max_depth: u16, stack: Option<Stack>, propagate_parents: bool) -> Result<()> {
   if Path::new(CONFIG_FILENAME).exists() {
     bail!("Versio is already initialized.");
   }
 
+  let find = |name: &OsStr, path: &Path| match stack {
+    Some(stack) => stack.find_project(name, path),
+    None => find_project(name, path)
+  };
   let walk = WalkBuilder::new("./").max_depth(Some(max_depth as usize)).build();
-  let projs: Vec<_> = walk
-    .filter_map(|r| r.map_err(Error::from).and_then(|e| find_project(e.file_name(), e.path())).transpose())
+  let mut projs: Vec<_> = walk
+    .filter_map(|r| r.map_err(Error::from).and_then(|e| find(e.file_name(), e.path())).transpose())
     .collect::<Result<_>>()?;
+  link_maven_modules(&mut projs, propagate_parents);
+  link_helm_charts(&mut projs);
+
+  if let Some(stack) = stack {
+    let workspace = stack.is_workspace();
+    for proj in &mut projs {
+      stack.tune(proj, workspace);
+    }
+  }
 
   if projs.is_empty() {
     println!("No projects found.");
   }
-  write_yaml(&projs)?;
+  write_yaml(&projs, stack)?;
   append_ignore()?;
   Ok(())
 }
 
+impl Stack {
+  /// Find a project of this stack: the stack's top-level workspace manifest, and other kinds of projects, aren't
+  /// projects.
+  fn find_project(self, name: &OsStr, file: &Path) -> Result<Option<ProjSummary>> {
+    let dir = match file.parent() {
+      Some(dir) => dir.to_slash_lossy(),
+      None => return Ok(None)
+    };
+
+    match (self, name.to_str()) {
+      (Stack::RustWorkspace, Some("Cargo.toml")) => {
+        let data = std::fs::read_to_string(file)?;
+        let found = TomlScanner::new("package.name").find(&data).ok();
+        let version = "package.version";
+        Ok(found.map(|name| ProjSummary::new_file(name.value(), dir, "Cargo.toml", "toml", version, &["cargo"])))
+      }
+      (Stack::NpmMonorepo, Some("package.json")) => {
+        let data: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(file)?)?;
+        let name = match data.get("name").and_then(|n| n.as_str()) {
+          Some(name) if data.get("workspaces").is_none() => name,
+          _ => return Ok(None)
+        };
+        Ok(Some(ProjSummary::new_file(name, dir, "package.json", "json", "version", &["npm"])))
+      }
+      (Stack::PythonPoetry, Some("pyproject.toml")) => {
+        let data = std::fs::read_to_string(file)?;
+        let found = TomlScanner::new("tool.poetry.name").find(&data).ok();
+        let version = "tool.poetry.version";
+        Ok(found.map(|name| ProjSummary::new_file(name.value(), dir, "pyproject.toml", "toml", version, &["poetry"])))
+      }
+      _ => Ok(None)
+    }
+  }
+
+  /// Whether the top-level manifest declares a workspace, whose packages can be found by name.
+  fn is_workspace(self) -> bool {
+    let read = |file: &str| std::fs::read_to_string(file).unwrap_or_default();
+    match self {
+      Stack::RustWorkspace => read("Cargo.toml").lines().any(|line| line.trim() == "[workspace]"),
+      Stack::NpmMonorepo => {
+        let data: Option<serde_json::Value> = serde_json::from_str(&read("package.json")).ok();
+        data.map(|data| data.get("workspaces").is_some()).unwrap_or(false)
+      }
+      Stack::PythonPoetry => false
+    }
+  }
+
+  /// Configure a project with the stack's conventions. Each of these stacks' package managers treats a new 0.x minor
+  /// version as breaking, so a breaking change before 1.0.0 only needs a minor increment.
+  fn tune(self, proj: &mut ProjSummary, workspace: bool) {
+    proj.changelog = Some("CHANGELOG.md".into());
+    proj.zero_major_policy = Some("minor".into());
+    if workspace {
+      proj.workspace = Some(proj.name.clone());
+    }
+
+    match self {
+      Stack::RustWorkspace => {
+        if Path::new("Cargo.lock").exists() {
+          proj.cargo_lock = Some("Cargo.lock".into());
+        }
+        proj.hook("post_release", "cargo publish");
+      }
+      Stack::NpmMonorepo => {
+        proj.hook("post_write", "npm install --package-lock-only --ignore-scripts");
+        proj.hook("post_release", "npm publish");
+      }
+      Stack::PythonPoetry => {
+        proj.hook("post_write", "poetry lock --no-update");
+        proj.hook("post_release", "poetry publish --build");
+      }
+    }
+  }
+
+  /// The commit types that are a patch for this stack, on top of the angular conventions: a performance fix
+  /// is released like any other fix, and so is a change of an npm package's dependencies.
+  fn patch_kinds(self) -> &'static [&'static str] {
+    match self {
+      Stack::RustWorkspace | Stack::PythonPoetry => &["perf"],
+      Stack::NpmMonorepo => &["perf", "deps"]
+    }
+  }
+}
+
 fn find_project(name: &OsStr, file: &Path) -> Result<Option<ProjSummary>> {
   let fname = match name.to_str() {
     Some(n) => n,
@@ -64,9 +173,31 @@ fn find_project(name: &OsStr, file: &Path) -> Result<Option<ProjSummary>> {
   }
 
   if fname == "pom.xml" {
-    let name = extract_name(file, |d| XmlScanner::new("project.artifactId").find(&d))?;
+    let data = std::fs::read_to_string(file)?;
+    let name = XmlScanner::new("project.artifactId").find(&data)?.value().to_string();
+    let dir = file.parent().unwrap().to_slash_lossy();
+    let mut proj = ProjSummary::new_file(&name, dir, "pom.xml", "xml", "project.version", &["mvn"]);
+    proj.maven = Some(MavenPom {
+      artifact: name,
+      parent: XmlScanner::new("project.parent.artifactId").find(&data).ok().map(|m| m.value().to_string()),
+      versioned: XmlScanner::new("project.version").find(&data).is_ok()
+    });
+    return Ok(Some(proj));
+  }
+
+  if fname == "Chart.yaml" {
+    let data = std::fs::read_to_string(file)?;
+    let name = YamlScanner::new("name").find(&data)?.value().to_string();
     let dir = file.parent().unwrap().to_slash_lossy();
-    return Ok(Some(ProjSummary::new_file(name, dir, "pom.xml", "xml", "project.version", &["mvn"])));
+    let mut proj = ProjSummary::new_file(&name, dir, "Chart.yaml", "yaml", "version", &["helm"]);
+    if YamlScanner::new("appVersion").find(&data).is_ok() {
+      proj.helm_app = Some(name);
+    }
+    return Ok(Some(proj));
+  }
+
+  if let Some(ps) = add_gradle(fname, file)? {
+    return Ok(Some(ps));
   }
 
   if fname == "setup.py" {
@@ -98,6 +229,93 @@ fn find_project(name: &OsStr, file: &Path) -> Result<Option<ProjSummary>> {
   Ok(None)
 }
 
+/// A Gradle project: its version is in `gradle.properties` if that has one, or else in the build script. A build
+/// script without a version (such as a subproject that gets its version from the root project) isn't a project.
+fn add_gradle(fname: &str, file: &Path) -> Result<Option<ProjSummary>> {
+  let dir = file.parent().unwrap();
+  let properties_reg = r#"(?m)^version *= *(\d+\.\d+\.\d+)"#;
+  let script_reg = r#"(?m)^version *=? *['"](\d+\.\d+\.\d+)['"]"#;
+  let has = |path: &Path, reg: &str| {
+    std::fs::read_to_string(path).map(|d| find_reg_data(&d, reg).is_ok()).unwrap_or(false)
+  };
+
+  let reg = match fname {
+    "gradle.properties" => properties_reg,
+    "build.gradle" | "build.gradle.kts" if !has(&dir.join("gradle.properties"), properties_reg) => script_reg,
+    _ => return Ok(None)
+  };
+  if !has(file, reg) {
+    return Ok(None);
+  }
+
+  let name_reg = r#"rootProject\.name *= *['"]([^'"]*)['"]"#;
+  let settings = ["settings.gradle", "settings.gradle.kts"].iter().map(|s| dir.join(s)).find(|s| s.exists());
+  let name = match settings.map(|s| extract_name(&s, |d| find_reg_data(&d, name_reg))) {
+    Some(Ok(name)) => name,
+    _ => dir.canonicalize()?.file_name().and_then(|n| n.to_str()).unwrap_or("project").to_string()
+  };
+  Ok(Some(ProjSummary::new_file(name, dir.to_slash_lossy(), fname, "pattern", reg, &["gradle"])))
+}
+
+/// Connect each Maven module to the parent `pom.xml` that it names, if that was found too. A module with its own
+/// version depends on its parent, and the parent's new version is written to the module's `<parent>`; the module is
+/// only increased with its parent if `propagate` is set. A module that inherits its parent's version isn't a project
+/// of its own: the parent's new version is written to the module's `<parent>` instead.
+fn link_maven_modules(projs: &mut Vec<ProjSummary>, propagate: bool) {
+  let parent_of = |projs: &[ProjSummary], proj: &ProjSummary| {
+    let parent = proj.maven.as_ref().and_then(|m| m.parent.as_ref())?;
+    projs.iter().position(|p| p.maven.as_ref().map(|m| &m.artifact == parent).unwrap_or(false))
+  };
+
+  let mut i = 0;
+  while i < projs.len() {
+    let inherits = projs[i].maven.as_ref().map(|m| !m.versioned).unwrap_or(false);
+    let parent = if inherits { parent_of(&projs[..], &projs[i]) } else { None };
+    let file = parent
+      .and_then(|p| Path::new(&projs[i].root).strip_prefix(&projs[p].root).ok())
+      .map(|dir| dir.join("pom.xml"));
+    match (parent, file) {
+      (Some(p), Some(file)) if p != i => {
+        let file = FileVersionSummary::new(file.to_slash_lossy().into(), "xml".into(), "project.parent.version".into());
+        projs[p].also.push(file);
+        projs.remove(i);
+      }
+      _ => {
+        if inherits {
+          warn!("Couldn't find the parent of \"{}\". Please edit the .versio.yaml file.", projs[i].name);
+        }
+        i += 1;
+      }
+    }
+  }
+
+  let size = if propagate { "match" } else { "none" };
+  for i in 0 .. projs.len() {
+    if let Some(p) = parent_of(&projs[..], &projs[i]).filter(|p| *p != i) {
+      let parts = "project.parent.version";
+      projs[i].depends.push(DependsSummary { id: p + 1, size, file: "pom.xml", file_type: "xml", parts });
+    }
+  }
+}
+
+/// Connect each Helm chart with an `appVersion` to the project of the same name, if there is one: the project's new
+/// version is written to the chart's `appVersion`, while the chart's own `version` is only increased by changes to
+/// the chart. The chart is renamed, since project names must be unique.
+fn link_helm_charts(projs: &mut [ProjSummary]) {
+  for i in 0 .. projs.len() {
+    let app = match &projs[i].helm_app {
+      Some(app) => app.clone(),
+      None => continue
+    };
+    if let Some(p) = projs.iter().position(|p| p.helm_app.is_none() && p.name == app) {
+      let chart = &mut projs[i];
+      chart.name = format!("{}-chart", app);
+      let dep = DependsSummary { id: p + 1, size: "none", file: "Chart.yaml", file_type: "yaml", parts: "appVersion" };
+      chart.depends.push(dep);
+    }
+  }
+}
+
 fn add_gemspec(fname: &str, file: &Path) -> Result<Option<ProjSummary>> {
   let spec_suffix = ".gemspec";
   if let Some(fname_pref) = fname.strip_suffix(spec_suffix) {
@@ -141,12 +359,12 @@ fn extract_name<F: FnOnce(String) -> Result<Mark>>(file: &Path, find: F) -> Resu
   std::fs::read_to_string(file).map_err(|e| e.into()).and_then(find).map(|mark| mark.value().to_string())
 }
 
-fn write_yaml(projs: &[ProjSummary]) -> Result<()> {
-  let yaml = generate_yaml(projs);
+fn write_yaml(projs: &[ProjSummary], stack: Option<Stack>) -> Result<()> {
+  let yaml = generate_yaml(projs, stack);
   Ok(std::fs::write(CONFIG_FILENAME, yaml)?)
 }
 
-fn generate_yaml(projs: &[ProjSummary]) -> String {
+fn generate_yaml(projs: &[ProjSummary], stack: Option<Stack>) -> String {
   let mut yaml = String::new();
   yaml.push_str("options:\n");
   yaml.push_str("  prev_tag: \"versio-prev\"\n");
@@ -159,7 +377,9 @@ fn generate_yaml(projs: &[ProjSummary]) -> String {
   let mut prefixes = HashSet::new();
   for (id, proj) in projs.iter().enumerate() {
     writeln!(yaml, "  - name: \"{}\"", proj.name()).unwrap();
-    if let Some(root) = proj.root() {
+    if let Some(workspace) = &proj.workspace {
+      writeln!(yaml, "    workspace: \"{}\"", workspace).unwrap();
+    } else if let Some(root) = proj.root() {
       writeln!(yaml, "    root: \"{}\"", root).unwrap();
     }
     writeln!(yaml, "    id: {}", id + 1).unwrap();
@@ -180,6 +400,35 @@ fn generate_yaml(projs: &[ProjSummary]) -> String {
     yaml.push_str("    version:\n");
     proj.append_version(&mut yaml);
 
+    if !proj.also.is_empty() {
+      yaml.push_str("    also:\n");
+      for also in &proj.also {
+        also.append_item(&mut yaml);
+      }
+    }
+    if !proj.depends.is_empty() {
+      yaml.push_str("    depends:\n");
+      for dep in &proj.depends {
+        writeln!(yaml, "      {}:", dep.id).unwrap();
+        writeln!(yaml, "        size: {}", dep.size).unwrap();
+        yaml.push_str("        files:\n");
+        writeln!(yaml, "          - file: \"{}\"", dep.file).unwrap();
+        writeln!(yaml, "            {}: \"{}\"", dep.file_type, dep.parts).unwrap();
+      }
+    }
+
+    if let Some(changelog) = &proj.changelog {
+      yaml.push_str("    changelog:\n");
+      writeln!(yaml, "      file: \"{}\"", changelog).unwrap();
+      yaml.push_str("      format: keep-a-changelog\n");
+    }
+    if let Some(cargo_lock) = &proj.cargo_lock {
+      writeln!(yaml, "    cargo_lock: \"{}\"", cargo_lock).unwrap();
+    }
+    if let Some(policy) = &proj.zero_major_policy {
+      writeln!(yaml, "    zero_major_policy: {}", policy).unwrap();
+    }
+
     if !proj.hooks().is_empty() {
       let mut hooks: Vec<_> = proj.hooks().iter().collect();
       hooks.sort_by(|(k1, _), (k2, _)| k1.cmp(k2));
@@ -197,6 +446,9 @@ fn generate_yaml(projs: &[ProjSummary]) -> String {
 
   yaml.push_str("sizes:\n");
   yaml.push_str("  use_angular: true\n");
+  if let Some(stack) = stack {
+    writeln!(yaml, "  patch: [{}]", stack.patch_kinds().join(", ")).unwrap();
+  }
   yaml.push_str("  fail: [\"*\"]\n");
 
   yaml
@@ -214,7 +466,33 @@ struct ProjSummary {
   root: String,
   subs: bool,
   version: VersionSummary,
-  hooks: HashMap<String, String>
+  hooks: HashMap<String, String>,
+  workspace: Option<String>,
+  changelog: Option<String>,
+  cargo_lock: Option<String>,
+  zero_major_policy: Option<String>,
+  also: Vec<FileVersionSummary>,
+  depends: Vec<DependsSummary>,
+  maven: Option<MavenPom>,
+  /// The name of a Helm chart that has an `appVersion`.
+  helm_app: Option<String>
+}
+
+/// What's needed of a `pom.xml` to connect it to its parent.
+struct MavenPom {
+  artifact: String,
+  parent: Option<String>,
+  /// Whether the module has its own version, instead of inheriting its parent's.
+  versioned: bool
+}
+
+/// A dependency on another project (by its generated ID), whose version is written to one of this project's files.
+struct DependsSummary {
+  id: usize,
+  size: &'static str,
+  file: &'static str,
+  file_type: &'static str,
+  parts: &'static str
 }
 
 impl ProjSummary {
@@ -233,7 +511,15 @@ impl ProjSummary {
         file_type.to_string(),
         parts.to_string()
       )),
-      hooks: HashMap::new()
+      hooks: HashMap::new(),
+      workspace: None,
+      changelog: None,
+      cargo_lock: None,
+      zero_major_policy: None,
+      also: Vec::new(),
+      depends: Vec::new(),
+      maven: None,
+      helm_app: None
     }
   }
 
@@ -250,7 +536,15 @@ impl ProjSummary {
       labels: labels.iter().map(|s| s.to_string()).collect(),
       tag_prefix_separator: "-".into(),
       version: VersionSummary::Tag(TagVersionSummary::new()),
-      hooks: HashMap::new()
+      hooks: HashMap::new(),
+      workspace: None,
+      changelog: None,
+      cargo_lock: None,
+      zero_major_policy: None,
+      also: Vec::new(),
+      depends: Vec::new(),
+      maven: None,
+      helm_app: None
     }
   }
 
@@ -308,12 +602,17 @@ impl FileVersionSummary {
     FileVersionSummary { file, file_type, parts }
   }
 
-  pub fn append(&self, yaml: &mut String) {
-    writeln!(yaml, "      file: \"{}\"", self.file).unwrap();
+  pub fn append(&self, yaml: &mut String) { self.append_at(yaml, "      ", "      ") }
+
+  /// Append this as an item of a list, such as `also`.
+  pub fn append_item(&self, yaml: &mut String) { self.append_at(yaml, "      - ", "        ") }
+
+  fn append_at(&self, yaml: &mut String, first: &str, rest: &str) {
+    writeln!(yaml, "{}file: \"{}\"", first, self.file).unwrap();
     if self.file_type == "pattern" {
-      writeln!(yaml, "      {}: '{}'", self.file_type, yaml_escape_single(&self.parts)).unwrap();
+      writeln!(yaml, "{}{}: '{}'", rest, self.file_type, yaml_escape_single(&self.parts)).unwrap();
     } else {
-      writeln!(yaml, "      {}: \"{}\"", self.file_type, self.parts).unwrap();
+      writeln!(yaml, "{}{}: \"{}\"", rest, self.file_type, self.parts).unwrap();
     }
   }
 }
@@ -347,3 +646,43 @@ fn tag_sanitize(name: &str) -> String {
 
   prefix
 }
+
+#[cfg(test)]
+mod test {
+  use super::{generate_yaml, link_helm_charts, link_maven_modules, MavenPom, ProjSummary};
+
+  fn pom(root: &str, artifact: &str, parent: Option<&str>, versioned: bool) -> ProjSummary {
+    let mut proj = ProjSummary::new_file(artifact, root, "pom.xml", "xml", "project.version", &["mvn"]);
+    proj.maven = Some(MavenPom { artifact: artifact.into(), parent: parent.map(|p| p.into()), versioned });
+    proj
+  }
+
+  #[test]
+  fn test_maven_modules() {
+    let mut projs = vec![
+      pom(".", "widgets", Some("spring-boot-starter-parent"), true),
+      pom("./api", "widgets-api", Some("widgets"), false),
+      pom("./core", "widgets-core", Some("widgets"), true)
+    ];
+    link_maven_modules(&mut projs, false);
+    assert_eq!(vec!["widgets", "widgets-core"], projs.iter().map(|p| p.name()).collect::<Vec<_>>());
+
+    let yaml = generate_yaml(&projs, None);
+    assert!(yaml.contains("    also:\n      - file: \"api/pom.xml\"\n        xml: \"project.parent.version\"\n"));
+    assert!(yaml.contains("    depends:\n      1:\n        size: none\n        files:\n"));
+    assert!(yaml.contains("          - file: \"pom.xml\"\n            xml: \"project.parent.version\"\n"));
+  }
+
+  #[test]
+  fn test_helm_charts() {
+    let mut chart = ProjSummary::new_file("widgets", "./charts/widgets", "Chart.yaml", "yaml", "version", &["helm"]);
+    chart.helm_app = Some("widgets".into());
+    let mut projs = vec![ProjSummary::new_file("widgets", ".", "package.json", "json", "version", &["npm"]), chart];
+    link_helm_charts(&mut projs);
+    assert_eq!("widgets-chart", projs[1].name());
+
+    let yaml = generate_yaml(&projs, None);
+    assert!(yaml.contains("    depends:\n      1:\n        size: none\n        files:\n"));
+    assert!(yaml.contains("          - file: \"Chart.yaml\"\n            yaml: \"appVersion\"\n"));
+  }
+}
@@ -0,0 +1,122 @@
+//! Environment variables in config values.
+//!
+//! A string value of the config can use `${NAME}`, which is replaced by the value of the environment variable
+//! `NAME` when the config is read; it's an error if the variable isn't set. `${NAME:-default}` uses `default`
+//! instead if the variable is unset or empty, and `$${` is a literal `${`. Since Versio sets the `VERSIO_*`
+//! variables itself when it runs hooks, `${VERSIO_...}` is left as it is.
+
+use crate::errors::Result;
+use serde_yaml::Value;
+
+/// Replace the variables in every string value (but not the keys) of the config, returning each variable that was
+/// used, as "NAME=value".
+pub fn interpolate(value: &mut Value) -> Result<Vec<String>> {
+  let mut used = Vec::new();
+  interpolate_value(value, &mut used, &|name| std::env::var(name).ok())?;
+  used.sort();
+  used.dedup();
+  Ok(used)
+}
+
+fn interpolate_value<E: Fn(&str) -> Option<String>>(value: &mut Value, used: &mut Vec<String>, env: &E) -> Result<()> {
+  match value {
+    Value::String(s) => {
+      if let Some(replaced) = interpolate_str(s, used, env)? {
+        *s = replaced;
+      }
+    }
+    Value::Sequence(seq) => {
+      for v in seq {
+        interpolate_value(v, used, env)?;
+      }
+    }
+    Value::Mapping(map) => {
+      for (_, v) in map.iter_mut() {
+        interpolate_value(v, used, env)?;
+      }
+    }
+    Value::Tagged(tagged) => interpolate_value(&mut tagged.value, used, env)?,
+    _ => ()
+  }
+  Ok(())
+}
+
+/// The string with its variables replaced, or `None` if it doesn't have any.
+fn interpolate_str<E: Fn(&str) -> Option<String>>(s: &str, used: &mut Vec<String>, env: &E) -> Result<Option<String>> {
+  if !s.contains("${") {
+    return Ok(None);
+  }
+
+  let mut out = String::new();
+  let mut rest = s;
+  while let Some(start) = rest.find("${") {
+    if rest[.. start].ends_with('$') {
+      out.push_str(&rest[.. start - 1]);
+      out.push_str("${");
+      rest = &rest[start + 2 ..];
+      continue;
+    }
+
+    out.push_str(&rest[.. start]);
+    let end = rest[start ..].find('}').ok_or_else(|| bad!("Unclosed \"${{\" in config value \"{}\".", s))? + start;
+    let var = &rest[start + 2 .. end];
+    let (name, default) = match var.split_once(":-") {
+      Some((name, default)) => (name, Some(default)),
+      None => (var, None)
+    };
+
+    if name.starts_with("VERSIO_") {
+      out.push_str(&rest[start ..= end]);
+    } else {
+      let value = match (env(name).filter(|v| !v.is_empty() || default.is_none()), default) {
+        (Some(value), _) => value,
+        (None, Some(default)) => default.to_string(),
+        (None, None) => bail!("Environment variable {} isn't set, for config value \"{}\".", name, s)
+      };
+      used.push(format!("{}={}", name, value));
+      out.push_str(&value);
+    }
+    rest = &rest[end + 1 ..];
+  }
+  out.push_str(rest);
+  Ok(Some(out))
+}
+
+#[cfg(test)]
+mod test {
+  use super::{interpolate_str, interpolate_value};
+  use serde_yaml::Value;
+
+  fn env(name: &str) -> Option<String> {
+    match name {
+      "BRANCH" => Some("trunk".into()),
+      "EMPTY" => Some("".into()),
+      _ => None
+    }
+  }
+
+  fn interp(s: &str) -> Option<String> { interpolate_str(s, &mut Vec::new(), &env).unwrap() }
+
+  #[test]
+  fn test_interpolate_str() {
+    assert_eq!(None, interp("main"));
+    assert_eq!(Some("trunk".into()), interp("${BRANCH}"));
+    assert_eq!(Some("release/trunk/*".into()), interp("release/${BRANCH}/*"));
+    assert_eq!(Some("main".into()), interp("${MISSING:-main}"));
+    assert_eq!(Some("main".into()), interp("${EMPTY:-main}"));
+    assert_eq!(Some("trunk".into()), interp("${BRANCH:-main}"));
+    assert_eq!(Some("${BRANCH}".into()), interp("$${BRANCH}"));
+    assert_eq!(Some("echo ${VERSIO_VERSION} trunk".into()), interp("echo ${VERSIO_VERSION} ${BRANCH}"));
+    assert!(interpolate_str("${MISSING}", &mut Vec::new(), &env).is_err());
+    assert!(interpolate_str("${BRANCH", &mut Vec::new(), &env).is_err());
+  }
+
+  #[test]
+  fn test_interpolate_value() {
+    let mut value: Value = serde_yaml::from_str("options:\n  branch: [\"${BRANCH}\", dev]\n\"${KEY}\": 1\n").unwrap();
+    let mut used = Vec::new();
+    interpolate_value(&mut value, &mut used, &env).unwrap();
+    assert_eq!("trunk", value["options"]["branch"][0].as_str().unwrap());
+    assert_eq!(vec!["BRANCH=trunk".to_string()], used);
+  }
+}
@@ -4,18 +4,39 @@
 
 #[macro_use]
 pub mod errors;
+#[macro_use]
+pub mod i18n;
+pub mod api;
 pub mod commands;
 pub mod init;
 pub(crate) mod scan;
 pub mod vcs;
 
 mod analyze;
+mod backend;
+mod bitbucket;
 mod config;
+mod deprecate;
+mod drift;
 mod either;
 mod git;
+mod gitea;
 mod github;
+mod interpolate;
+mod links;
+mod lock;
 mod mark;
+mod metrics;
 mod mono;
+mod notify;
 mod output;
+mod progress;
+mod receipt;
+mod review;
+mod schema;
+mod simulate;
 mod state;
 mod template;
+#[cfg(test)]
+mod testing;
+mod unidiff;
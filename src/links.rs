@@ -0,0 +1,143 @@
+//! Links to issues, found in commit messages and PR descriptions.
+
+use crate::errors::Result;
+use crate::git::GithubInfo;
+use regex::{escape, Regex};
+use serde::{Deserialize, Serialize};
+
+/// GitHub's closing keywords, followed by an issue in the same repo (`#123`) or another (`owner/repo#45`).
+const GITHUB_PATTERN: &str = r"(?i)\b(?:close[sd]?|fix(?:e[sd])?|resolve[sd]?):?\s+(?:([\w.-]+/[\w.-]+))?#(\d+)\b";
+
+/// An issue tracker from the `links` list of the config, whose issues are named by keys like `ABC-123`.
+#[derive(Deserialize, Debug, Clone)]
+pub struct LinkConfig {
+  keys: Vec<String>,
+  url: String
+}
+
+impl LinkConfig {
+  /// Check that there are keys, and that each is made of letters, digits, and underscores.
+  pub fn validate(&self) -> Result<()> {
+    if self.keys.is_empty() {
+      bail!("link to {} has no keys", self.url);
+    }
+    for key in &self.keys {
+      if key.is_empty() || !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        bail!("illegal link key \"{}\"", key);
+      }
+    }
+    Ok(())
+  }
+
+  /// The URL of an issue: `{issue}` in the URL is the whole name (`ABC-123`), `{key}` is its key (`ABC`), and
+  /// `{number}` is its number (`123`).
+  fn url_of(&self, key: &str, number: &str) -> String {
+    self.url.replace("{issue}", &format!("{}-{}", key, number)).replace("{key}", key).replace("{number}", number)
+  }
+}
+
+/// An issue referred to by a commit message or PR description, and its URL if it's known.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct IssueLink {
+  name: String,
+  url: Option<String>
+}
+
+impl IssueLink {
+  pub fn name(&self) -> &str { &self.name }
+  pub fn url(&self) -> Option<&str> { self.url.as_deref() }
+}
+
+/// Finds the issues referred to by text: GitHub issues after a closing keyword (like `Fixes #123`), and any issue
+/// with a key from the `links` config.
+pub struct IssueFinder {
+  github: Regex,
+  keyed: Vec<(Regex, LinkConfig)>
+}
+
+impl IssueFinder {
+  pub fn new(links: &[LinkConfig]) -> IssueFinder {
+    let github = Regex::new(GITHUB_PATTERN).expect("GitHub issue pattern should be valid");
+    let keyed = links
+      .iter()
+      .map(|link| {
+        let keys: Vec<_> = link.keys.iter().map(|k| escape(k)).collect();
+        let pattern = format!(r"\b({})-(\d+)\b", keys.join("|"));
+        (Regex::new(&pattern).expect("validated link keys should make a valid pattern"), link.clone())
+      })
+      .collect();
+    IssueFinder { github, keyed }
+  }
+
+  /// Find the issues in `text`, in the order they first appear. GitHub issues only have a URL if the `github` repo
+  /// is known.
+  pub fn find(&self, text: &str, github: Option<&GithubInfo>) -> Vec<IssueLink> {
+    let mut found: Vec<(usize, IssueLink)> = Vec::new();
+
+    for caps in self.github.captures_iter(text) {
+      let at = caps.get(0).map(|m| m.start()).unwrap_or(0);
+      let number = &caps[2];
+      let (name, url) = match caps.get(1).map(|m| m.as_str()) {
+        Some(repo) => {
+          let url = github.map(|gh| format!("https://{}/{}/issues/{}", gh.host(), repo, number));
+          (format!("{}#{}", repo, number), url)
+        }
+        None => (format!("#{}", number), github.map(|gh| format!("{}/issues/{}", gh.web_url(), number)))
+      };
+      found.push((at, IssueLink { name, url }));
+    }
+
+    for (regex, link) in &self.keyed {
+      for caps in regex.captures_iter(text) {
+        let at = caps.get(0).map(|m| m.start()).unwrap_or(0);
+        let name = format!("{}-{}", &caps[1], &caps[2]);
+        found.push((at, IssueLink { name, url: Some(link.url_of(&caps[1], &caps[2])) }));
+      }
+    }
+
+    found.sort_by_key(|(at, _)| *at);
+    let mut issues: Vec<IssueLink> = Vec::new();
+    for (_, issue) in found {
+      if !issues.iter().any(|i| i.name == issue.name) {
+        issues.push(issue);
+      }
+    }
+    issues
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::{IssueFinder, LinkConfig};
+
+  fn jira() -> LinkConfig {
+    LinkConfig { keys: vec!["ABC".into(), "DEF".into()], url: "https://jira.example.com/browse/{issue}".into() }
+  }
+
+  #[test]
+  fn test_find_issues() {
+    let finder = IssueFinder::new(&[jira()]);
+    let msg = "fix: the frobber\n\nThis is for ABC-12 and XYZ-3.\n\n\
+               Fixes #123\nCloses my-org/other.repo#45\nRefs DEF-7, ABC-12";
+    let issues = finder.find(msg, None);
+
+    let names: Vec<_> = issues.iter().map(|i| i.name()).collect();
+    assert_eq!(vec!["ABC-12", "#123", "my-org/other.repo#45", "DEF-7"], names);
+    assert_eq!(Some("https://jira.example.com/browse/ABC-12"), issues[0].url());
+    assert_eq!(None, issues[1].url());
+  }
+
+  #[test]
+  fn test_find_no_keyword() {
+    let finder = IssueFinder::new(&[]);
+    assert!(finder.find("feat: add #123 support\n\nSee #45.", None).is_empty());
+    assert_eq!(1, finder.find("fixed: #45", None).len());
+  }
+
+  #[test]
+  fn test_validate_keys() {
+    assert!(jira().validate().is_ok());
+    assert!(LinkConfig { keys: vec!["A-B".into()], url: "u".into() }.validate().is_err());
+    assert!(LinkConfig { keys: vec![], url: "u".into() }.validate().is_err());
+  }
+}
@@ -0,0 +1,163 @@
+//! An advisory lock on the repo, so that two commands that change it (such as two `versio release`s, or a release
+//! and a `set`) can't interleave their writes.
+//!
+//! The lock is a file that records which process holds it. It's removed when the holder is done, even if it fails.
+//! If the holder was killed instead, the file is left behind: a lock that's held by a process of this host that no
+//! longer exists is taken over.
+
+use crate::errors::{Context as _, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs::OpenOptions;
+use std::io::{ErrorKind, Write as _};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use tracing::{info, warn};
+
+const LOCK_FILE: &str = "lock";
+const LOCK_POLL: Duration = Duration::from_secs(1);
+
+static WAIT: AtomicBool = AtomicBool::new(false);
+static FORCE: AtomicBool = AtomicBool::new(false);
+
+/// Whether to wait for a lock that's held by another process, instead of failing; and whether to take it over
+/// regardless.
+pub fn set_lock_policy(wait: bool, force: bool) {
+  WAIT.store(wait, Ordering::Relaxed);
+  FORCE.store(force, Ordering::Relaxed);
+}
+
+/// A held lock, which is released when it's dropped.
+pub struct RepoLock {
+  path: PathBuf
+}
+
+impl RepoLock {
+  /// Lock the repo whose versio files are kept in `dir`.
+  pub fn acquire(dir: &Path) -> Result<RepoLock> {
+    std::fs::create_dir_all(dir).with_context(|| format!("Can't create {}.", dir.display()))?;
+    let path = dir.join(LOCK_FILE);
+    let me = LockHolder::me();
+    let mut waiting = false;
+
+    loop {
+      match OpenOptions::new().write(true).create_new(true).open(&path) {
+        Ok(mut file) => {
+          file.write_all(serde_json::to_string(&me)?.as_bytes())?;
+          return Ok(RepoLock { path });
+        }
+        Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+          // The holder might not have written itself yet.
+          let holder = LockHolder::read(&path).ok();
+          if FORCE.load(Ordering::Relaxed) {
+            warn!("Taking over the lock of {}.", describe(holder.as_ref()));
+          } else if let Some(holder) = holder.as_ref().filter(|h| h.is_dead()) {
+            warn!("Taking over the stale lock of {}.", holder);
+          } else if WAIT.load(Ordering::Relaxed) {
+            if !waiting {
+              info!("Waiting for the lock of {}.", describe(holder.as_ref()));
+              waiting = true;
+            }
+            std::thread::sleep(LOCK_POLL);
+            continue;
+          } else {
+            bail!("{}", tr!("lock.held", holder = describe(holder.as_ref()), path = path.display()));
+          }
+          remove_lock(&path)?;
+        }
+        Err(e) => return Err(e).with_context(|| format!("Can't create lock {}.", path.display()))
+      }
+    }
+  }
+}
+
+impl Drop for RepoLock {
+  fn drop(&mut self) {
+    // Only remove the lock if it wasn't taken over by another process.
+    if LockHolder::read(&self.path).map(|h| h.pid == std::process::id()).unwrap_or(false) {
+      if let Err(e) = std::fs::remove_file(&self.path) {
+        warn!("Unable to release lock {}: {}", self.path.display(), e);
+      }
+    }
+  }
+}
+
+fn remove_lock(path: &Path) -> Result<()> {
+  match std::fs::remove_file(path) {
+    Ok(()) => Ok(()),
+    Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+    Err(e) => Err(e).with_context(|| format!("Can't remove lock {}.", path.display()))
+  }
+}
+
+fn describe(holder: Option<&LockHolder>) -> String {
+  holder.map(|h| h.to_string()).unwrap_or_else(|| tr!("lock.unknown"))
+}
+
+/// The process that holds a lock.
+#[derive(Serialize, Deserialize)]
+struct LockHolder {
+  pid: u32,
+  host: String,
+  since: DateTime<Utc>
+}
+
+impl LockHolder {
+  fn me() -> LockHolder { LockHolder { pid: std::process::id(), host: host_name(), since: Utc::now() } }
+
+  fn read(path: &Path) -> Result<LockHolder> { Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?) }
+
+  /// Whether the holder is known to have exited without releasing the lock. A process of another host can't be
+  /// checked, and neither can a process of this one except on Linux.
+  fn is_dead(&self) -> bool { self.host == host_name() && !process_exists(self.pid) }
+}
+
+impl fmt::Display for LockHolder {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let since = self.since.format("%Y-%m-%d %H:%M:%S UTC");
+    write!(f, "{}", tr!("lock.holder", pid = self.pid, host = self.host, since = since))
+  }
+}
+
+fn host_name() -> String {
+  std::env::var("HOSTNAME")
+    .or_else(|_| std::env::var("COMPUTERNAME"))
+    .ok()
+    .or_else(|| std::fs::read_to_string("/etc/hostname").ok().map(|h| h.trim().to_string()))
+    .filter(|h| !h.is_empty())
+    .unwrap_or_else(|| "localhost".to_string())
+}
+
+#[cfg(target_os = "linux")]
+fn process_exists(pid: u32) -> bool { Path::new("/proc").join(pid.to_string()).exists() }
+
+#[cfg(not(target_os = "linux"))]
+fn process_exists(_pid: u32) -> bool { true }
+
+#[cfg(test)]
+mod test {
+  use super::{host_name, LockHolder, RepoLock, LOCK_FILE};
+  use chrono::Utc;
+
+  #[test]
+  fn test_lock() {
+    let dir = std::env::temp_dir().join(format!("versio-lock-{}", std::process::id()));
+    let lock = RepoLock::acquire(&dir).unwrap();
+    assert!(RepoLock::acquire(&dir).is_err());
+    drop(lock);
+    assert!(!dir.join(LOCK_FILE).exists());
+
+    let lock = RepoLock::acquire(&dir).unwrap();
+    drop(lock);
+
+    if cfg!(target_os = "linux") {
+      // A lock left by a process that no longer exists is taken over.
+      let stale = LockHolder { pid: u32::MAX, host: host_name(), since: Utc::now() };
+      std::fs::write(dir.join(LOCK_FILE), serde_json::to_string(&stale).unwrap()).unwrap();
+      drop(RepoLock::acquire(&dir).unwrap());
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+  }
+}
@@ -3,8 +3,9 @@
 mod cli;
 
 use tokio::runtime::Runtime;
+use tracing_chrome::ChromeLayerBuilder;
 use tracing_subscriber::{fmt, prelude::*, EnvFilter};
-use versio::commands::early_info;
+use versio::commands::{early_info, eprintln_pending_warnings, StepTimer};
 use versio::errors::Result;
 
 fn main() {
@@ -12,7 +13,7 @@ fn main() {
     use std::io::Write;
     let stderr = &mut std::io::stderr();
 
-    writeln!(stderr, "Error: {:?}", e).expect("Error writing to stderr.");
+    writeln!(stderr, "{}", versio::tr!("error", message = format!("{:?}", e))).expect("Error writing to stderr.");
     std::process::exit(1);
   }
 }
@@ -27,9 +28,25 @@ async fn run() -> Result<()> {
     .pretty()
     .with_source_location(false);
 
-  tracing_subscriber::registry().with(fmt::layer().event_format(format)).with(EnvFilter::from_default_env()).init();
+  // Log events as asked for by `RUST_LOG`, but trace all spans for `--profile` and `--verbose`.
+  let (chrome, _flush) = match cli::profile_file() {
+    Some(file) => {
+      let (layer, flush) = ChromeLayerBuilder::new().file(file).include_args(true).build();
+      (Some(layer), Some(flush))
+    }
+    None => (None, None)
+  };
+  let log = fmt::layer().event_format(format).with_filter(EnvFilter::from_default_env());
+  let steps = if cli::verbose() { Some(StepTimer) } else { None };
+  tracing_subscriber::registry().with(log).with(chrome).with(steps).init();
+
+  if cli::generate()? {
+    return Ok(());
+  }
 
   let info = early_info()?;
   std::env::set_current_dir(info.working_dir())?;
-  cli::execute(&info).await
+  let result = cli::execute(&info).await;
+  eprintln_pending_warnings();
+  result
 }
@@ -153,6 +153,12 @@ impl MarkedData {
     Ok(())
   }
 
+  /// The data as it would be with the new value, without writing it.
+  pub fn into_new_data(mut self, new_val: &str) -> String {
+    self.set_value(new_val);
+    self.data
+  }
+
   fn set_value(&mut self, new_val: &str) {
     let st = self.start();
     let ed = st + self.value().len();
@@ -0,0 +1,237 @@
+//! Metrics about a release, sent to a StatsD or OpenTelemetry (OTLP) collector.
+
+use crate::config::Size;
+use crate::errors::{Error, Result};
+use crate::notify::post;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::net::{ToSocketAddrs, UdpSocket};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const DEFAULT_PREFIX: &str = "versio";
+
+static API_CALLS: AtomicU64 = AtomicU64::new(0);
+
+/// Count a request to the GitHub API.
+pub fn count_api_call() { API_CALLS.fetch_add(1, Ordering::Relaxed); }
+
+/// The number of requests made to the GitHub API so far.
+pub fn api_calls() -> u64 { API_CALLS.load(Ordering::Relaxed) }
+
+/// Where to send metrics, from the `[metrics]` table of the user preferences.
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct MetricsConfig {
+  statsd: Option<String>,
+  otlp: Option<String>,
+  prefix: Option<String>
+}
+
+impl MetricsConfig {
+  pub fn is_empty(&self) -> bool { self.statsd.is_none() && self.otlp.is_none() }
+  fn prefix(&self) -> &str { self.prefix.as_deref().unwrap_or(DEFAULT_PREFIX) }
+}
+
+/// A single measurement, which StatsD and OTLP each report in their own way.
+struct Metric {
+  name: &'static str,
+  kind: MetricKind,
+  value: u64,
+  size: Option<String>
+}
+
+enum MetricKind {
+  /// A duration in milliseconds.
+  Timing,
+  /// A number of things that happened during this release.
+  Count
+}
+
+/// What happened during a release.
+pub struct ReleaseMetrics {
+  repository: Option<String>,
+  duration: Duration,
+  released: usize,
+  sizes: BTreeMap<String, usize>,
+  api_calls: u64
+}
+
+impl ReleaseMetrics {
+  /// The metrics of a release that took `duration`, and released `released` of the planned projects, whose
+  /// sizes are `planned`.
+  pub fn new<'a, I: IntoIterator<Item = &'a Size>>(
+    repository: Option<String>, duration: Duration, released: usize, planned: I
+  ) -> ReleaseMetrics {
+    let mut sizes = BTreeMap::new();
+    for size in planned {
+      *sizes.entry(size.to_string()).or_insert(0) += 1;
+    }
+    ReleaseMetrics { repository, duration, released, sizes, api_calls: api_calls() }
+  }
+
+  fn metrics(&self) -> Vec<Metric> {
+    let millis = self.duration.as_millis() as u64;
+    let mut metrics = vec![
+      Metric { name: "release.duration", kind: MetricKind::Timing, value: millis, size: None },
+      Metric { name: "release.projects", kind: MetricKind::Count, value: self.released as u64, size: None },
+      Metric { name: "github.api_calls", kind: MetricKind::Count, value: self.api_calls, size: None }
+    ];
+    metrics.extend(self.sizes.iter().map(|(size, count)| Metric {
+      name: "plan.projects",
+      kind: MetricKind::Count,
+      value: *count as u64,
+      size: Some(size.clone())
+    }));
+    metrics
+  }
+
+  /// The metrics as StatsD lines, with DogStatsD-style tags.
+  fn statsd(&self, prefix: &str) -> String {
+    self
+      .metrics()
+      .into_iter()
+      .map(|m| {
+        let kind = match m.kind {
+          MetricKind::Timing => "ms",
+          MetricKind::Count => "c"
+        };
+        let tags: Vec<_> = self
+          .repository
+          .iter()
+          .map(|r| format!("repository:{}", r))
+          .chain(m.size.iter().map(|s| format!("size:{}", s)))
+          .collect();
+        let tags = if tags.is_empty() { String::new() } else { format!("|#{}", tags.join(",")) };
+        format!("{}.{}:{}|{}{}", prefix, m.name, m.value, kind, tags)
+      })
+      .collect::<Vec<_>>()
+      .join("\n")
+  }
+
+  /// The metrics as an OTLP/HTTP JSON request, ending at `now` (in nanoseconds since the epoch).
+  fn otlp(&self, prefix: &str, now: u128) -> Value {
+    let start = now.saturating_sub(self.duration.as_nanos());
+    let metrics: Vec<_> = self
+      .metrics()
+      .into_iter()
+      .map(|m| {
+        let attributes: Vec<_> =
+          m.size.iter().map(|s| json!({ "key": "size", "value": { "stringValue": s } })).collect();
+        let name = format!("{}.{}", prefix, m.name);
+        match m.kind {
+          MetricKind::Timing => json!({
+            "name": name,
+            "unit": "ms",
+            "gauge": {
+              "dataPoints": [{ "asInt": m.value.to_string(), "timeUnixNano": now.to_string() }]
+            }
+          }),
+          MetricKind::Count => json!({
+            "name": name,
+            "sum": {
+              "aggregationTemporality": 1,
+              "isMonotonic": true,
+              "dataPoints": [{
+                "asInt": m.value.to_string(),
+                "startTimeUnixNano": start.to_string(),
+                "timeUnixNano": now.to_string(),
+                "attributes": attributes
+              }]
+            }
+          })
+        }
+      })
+      .collect();
+
+    let mut resource = vec![json!({ "key": "service.name", "value": { "stringValue": "versio" } })];
+    if let Some(repository) = &self.repository {
+      resource.push(json!({ "key": "vcs.repository.name", "value": { "stringValue": repository } }));
+    }
+    json!({
+      "resourceMetrics": [{
+        "resource": { "attributes": resource },
+        "scopeMetrics": [{
+          "scope": { "name": "versio", "version": env!("CARGO_PKG_VERSION") },
+          "metrics": metrics
+        }]
+      }]
+    })
+  }
+}
+
+/// Send the metrics to each configured collector, returning those that failed, with why. A failure doesn't stop
+/// the metrics from being sent to the other.
+pub async fn emit(config: &MetricsConfig, metrics: &ReleaseMetrics) -> Vec<(String, Error)> {
+  let mut failed = Vec::new();
+  if let Some(addr) = &config.statsd {
+    if let Err(e) = send_statsd(addr, &metrics.statsd(config.prefix())) {
+      failed.push((addr.clone(), e));
+    }
+  }
+  if let Some(url) = &config.otlp {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_nanos()).unwrap_or(0);
+    let body = metrics.otlp(config.prefix(), now).to_string();
+    if let Err(e) = post(url, "application/json", body).await {
+      failed.push((url.clone(), e));
+    }
+  }
+  failed
+}
+
+/// Send the lines to a StatsD server (`host:port`) in a single UDP packet.
+fn send_statsd(addr: &str, lines: &str) -> Result<()> {
+  let addr = addr.to_socket_addrs()?.next().ok_or_else(|| bad!("Can't resolve StatsD address {}.", addr))?;
+  let socket = UdpSocket::bind(if addr.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" })?;
+  socket.send_to(lines.as_bytes(), addr)?;
+  Ok(())
+}
+
+#[cfg(test)]
+mod test {
+  use super::{MetricsConfig, ReleaseMetrics};
+  use crate::config::Size;
+  use std::time::Duration;
+
+  fn metrics() -> ReleaseMetrics {
+    let planned = [Size::Minor, Size::Patch, Size::Minor];
+    let mut metrics = ReleaseMetrics::new(Some("owner/repo".into()), Duration::from_millis(1500), 2, &planned);
+    metrics.api_calls = 4;
+    metrics
+  }
+
+  #[test]
+  fn test_statsd() {
+    let lines = metrics().statsd("versio");
+    assert_eq!(
+      vec![
+        "versio.release.duration:1500|ms|#repository:owner/repo",
+        "versio.release.projects:2|c|#repository:owner/repo",
+        "versio.github.api_calls:4|c|#repository:owner/repo",
+        "versio.plan.projects:2|c|#repository:owner/repo,size:minor",
+        "versio.plan.projects:1|c|#repository:owner/repo,size:patch"
+      ],
+      lines.lines().collect::<Vec<_>>()
+    );
+  }
+
+  #[test]
+  fn test_otlp() {
+    let body = metrics().otlp("ci.versio", 10_000_000_000);
+    let scope = &body["resourceMetrics"][0]["scopeMetrics"][0];
+    assert_eq!("ci.versio.release.duration", scope["metrics"][0]["name"]);
+    assert_eq!("1500", scope["metrics"][0]["gauge"]["dataPoints"][0]["asInt"]);
+    let point = &scope["metrics"][3]["sum"]["dataPoints"][0];
+    assert_eq!("8500000000", point["startTimeUnixNano"]);
+    assert_eq!("minor", point["attributes"][0]["value"]["stringValue"]);
+  }
+
+  #[test]
+  fn test_config() {
+    let config: MetricsConfig = toml::from_str("statsd = \"localhost:8125\"").unwrap();
+    assert!(!config.is_empty());
+    assert_eq!("versio", config.prefix());
+    assert!(toml::from_str::<MetricsConfig>("stats = \"localhost:8125\"").is_err());
+  }
+}
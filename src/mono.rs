@@ -1,254 +1,1819 @@
 //! A monorepo can read and alter the current state of all projects.
 
-use crate::analyze::{analyze, Analysis};
+use crate::analyze::{analyze, Analysis, AnnotatedMark};
 use crate::bail;
-use crate::config::{Config, ConfigFile, Depends, FsConfig, Project, ProjectId, Size};
+use crate::config::{compare_parts, BuildInfo, Config, ConfigFile, Depends, Project, ProjectHooks, ProjectId,
+                    SigningConfig, Size, CONFIG_FILENAME, PINS_FILENAME};
+use crate::drift::PlannedProject;
 use crate::either::{IterEither2 as E2, IterEither3 as E3};
-use crate::errors::Result;
-use crate::git::{Auth, CommitInfoBuf, FromTag, FromTagBuf, FullPr, GithubInfo, Repo};
-use crate::github::{changes, line_commits_head, Changes};
-use crate::state::{CommitArgs, CurrentState, OldTags, PrevFiles, PrevTagMessage, StateRead, StateWrite};
-use crate::vcs::VcsState;
-use chrono::{DateTime, FixedOffset};
-use serde::Deserialize;
+use crate::errors::{Context as _, Error, Result};
+use crate::git::{Auth, CommitInfoBuf, FromTag, FromTagBuf, FullPr, GithubApp, GithubInfo, RangeWarning, Repo,
+                 BREAKING_KIND, NO_KIND};
+use crate::github::{changes, line_commits_head, local_changes, ApiError, Changes};
+use crate::links::{IssueFinder, IssueLink};
+use crate::lock::RepoLock;
+use crate::metrics::{emit, MetricsConfig, ReleaseMetrics};
+use crate::notify::{notify_all, NotifiedProject, ReleaseSummary};
+use crate::receipt::{Receipt, ReceiptProject};
+use crate::state::{CommitArgs, CurrentState, FilesRead, OldTags, PrevFiles, PrevTagMessage, StateRead, StateWrite};
+use crate::output::{warn_later, ProjLine};
+use crate::progress::Progress;
+use crate::template::{construct_release_notes, read_template, NotesProject, RenderedLog};
+use crate::unidiff::unified_diff;
+use crate::vcs::{VcsRange, VcsState};
+use chrono::{DateTime, FixedOffset, SecondsFormat, Utc};
+use futures::stream::{self, StreamExt, TryStreamExt};
+use path_slash::{PathBufExt as _, PathExt as _};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::borrow::Cow;
 use std::cmp::{max, Ordering};
-use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
 use std::iter::{empty, once};
 use std::path::{Path, PathBuf};
-use tracing::trace;
+use std::time::Duration;
+use tracing::{info, instrument, trace, warn};
 
 const USER_PREFS_DIR: &str = ".versio";
 const USER_PREFS_FILE: &str = "prefs.toml";
+const XDG_PREFS_DIR: &str = "versio";
+const PREFS_ENV: &str = "VERSIO_PREFS";
+
+/// How many changelogs to render at once during a release.
+const CHANGELOG_CONCURRENCY: usize = 8;
+
+/// The directory in `.git` where versio keeps its own files, and the file there where the latest plan is cached.
+const GIT_VERSIO_DIR: &str = "versio";
+const PLAN_CACHE_FILE: &str = "plan.json";
+/// Where the plan was cached before it moved into the versio directory, as a file of the git directory itself.
+const OLD_PLAN_CACHE_FILE: &str = "versio-plan.json";
+const LAST_COMMITS_CACHE_FILE: &str = "last_commits.json";
+const ADOPT_PROGRESS_FILE: &str = "adopt.json";
 
 pub struct Mono {
   current: Config<CurrentState>,
   next: StateWrite,
   last_commits: HashMap<ProjectId, String>,
+  baselines: HashMap<ProjectId, String>,
   repo: Repo,
-  user_prefs: UserPrefs
+  user_prefs: UserPrefs,
+  offline: bool,
+  fallback: bool,
+  labels: Vec<String>,
+  since: Option<String>,
+  until: Option<String>,
+  // Held until the command is done with the repo.
+  _lock: Option<RepoLock>
 }
 
 impl Mono {
   pub fn here(vcs: VcsState) -> Result<Mono> { Mono::open(".", vcs) }
 
+  #[instrument(skip_all)]
   pub fn open<P: AsRef<Path>>(dir: P, vcs: VcsState) -> Result<Mono> {
     let root = Repo::find_working_dir(dir.as_ref(), *vcs.level(), false)?;
 
     // A little dance to construct a state and config.
-    let file = ConfigFile::from_dir(&root)?;
+    let branch = Repo::current_branch(dir.as_ref(), *vcs.level())?;
+    let file = ConfigFile::from_dir(&root)?.for_branch(branch.as_deref());
     trace!("Using commit message: {}", file.commit_config().message());
 
-    let repo = Repo::open(dir.as_ref(), vcs, file.commit_config().clone())?;
+    let user_prefs = read_env_prefs()?;
+    user_prefs.proxy.clone().overlay(read_repo_prefs(&root)?.proxy).export();
+    let commit_config = file.commit_config().with_signing(user_prefs.sign());
+    Repo::ensure_history(dir.as_ref(), *vcs.level(), file.prev_tag())?;
+    let repo = Repo::open(dir.as_ref(), vcs, commit_config)?
+      .with_tag_remote(file.tag_remote().as_ref())?
+      .with_mirrors(file.mirrors(), file.parallel_push())?
+      .with_auth(user_prefs.auth().clone());
+    // Lock before reading any state, which another command might be in the middle of changing.
+    let lock = match repo.git_dir() {
+      Ok(dir) if vcs.locks() => Some(RepoLock::acquire(&dir.join(GIT_VERSIO_DIR))?),
+      _ => None
+    };
+    let baselines = find_baselines(&file, &repo)?;
     let projects = file.projects().iter();
-    let old_tags = find_old_tags(projects, file.prev_tag(), &repo)?;
+    let old_tags = find_old_tags(projects, file.prev_tag(), &baselines, &repo)?;
     let state = CurrentState::new(root, old_tags);
     let current = Config::new(state, file);
 
-    let last_commits = find_last_commits(&current, &repo)?;
+    let last_commits = find_last_commits(&current, &baselines, &repo)?;
     let next = StateWrite::new();
-    let user_prefs = read_env_prefs()?;
 
-    Ok(Mono { current, next, last_commits, repo, user_prefs })
+    Ok(Mono {
+      current,
+      next,
+      last_commits,
+      baselines,
+      repo,
+      user_prefs,
+      offline: false,
+      fallback: false,
+      labels: Vec::new(),
+      since: None,
+      until: None,
+      _lock: lock
+    })
+  }
+
+  /// Check that the current branch is allowed by the `branch` option, returning the current branch (if any). If
+  /// it isn't allowed, the allowed branches and the current branch are returned instead.
+  pub fn check_branch(&self) -> std::result::Result<Option<String>, (String, String)> {
+    let branch_name = match self.repo.branch_name() {
+      Ok(branch_name) => branch_name.clone(),
+      Err(_) => return Ok(None)
+    };
+    let file = self.current.file();
+    let expected = || file.branches().iter().map(|b| format!("\"{}\"", b)).collect::<Vec<_>>().join(" or ");
+
+    match branch_name {
+      Some(branch_name) if file.allows_branch(&branch_name) => Ok(Some(branch_name)),
+      Some(branch_name) => Err((expected(), branch_name)),
+      None if file.branches().is_empty() => Ok(None),
+      None => Err((expected(), "((No branch))".to_string()))
+    }
+  }
+
+  /// The files to be written by the release which aren't under any of the `allowed_paths` option.
+  pub fn disallowed_writes(&self) -> Vec<PathBuf> {
+    let file = self.current.file();
+    // The pins are versio's own, so they can always be cleared.
+    let allowed = |p: &Path| file.allows_write(p) || p == Path::new(PINS_FILENAME);
+    self.next.written_paths().filter(|p| !allowed(p)).map(|p| p.to_path_buf()).collect()
+  }
+
+  /// A unified diff of each file that the release would change, without changing anything on disk.
+  pub fn pending_diffs(&self) -> Result<Vec<String>> {
+    let previews = self.next.preview()?;
+    let diffs = previews.iter().map(|p| {
+      let path = p.path().to_slash_lossy();
+      unified_diff(path.trim_start_matches("./"), p.old(), p.new())
+    });
+    Ok(diffs.filter(|d| !d.is_empty()).collect())
+  }
+
+  /// Don't consult the forge API while planning: all commits are treated as direct commits to the branch.
+  pub fn set_offline(&mut self, offline: bool) { self.offline = offline; }
+
+  /// If the forge API fails, plan from the commits alone (as if offline) instead of failing. This is only for
+  /// commands that don't write anything: a release from such a plan would lose its PRs' grouping, labels, and
+  /// changelog text.
+  pub fn set_fallback(&mut self, fallback: bool) { self.fallback = fallback; }
+
+  /// Only consider the projects with any of these labels when planning and listing changes. If there are no
+  /// labels, all projects are considered.
+  pub fn set_labels(&mut self, labels: &[String]) { self.labels = labels.to_vec(); }
+
+  /// Plan and list the changes after `since` up to `until`, instead of the changes after the prev tag (or a
+  /// project's own `prev_tag`) up to HEAD. Each can be any revision.
+  pub fn set_range(&mut self, since: Option<&str>, until: Option<&str>) -> Result<()> {
+    for rev in since.iter().chain(until.iter()) {
+      if self.repo.peek_commit_oid(rev).is_none() {
+        bail!("{}", tr!("changes.unknown_rev", rev = rev));
+      }
+    }
+    self.since = since.map(|s| s.to_string());
+    self.until = until.map(|u| u.to_string());
+    Ok(())
+  }
+
+  /// Whether a file belongs to any of the projects picked by `set_labels`.
+  /// The projects that cover each of `paths`, by name. A file should be covered by exactly one project, or else its
+  /// changes are released by none, or by several.
+  pub fn owners(&self, paths: &[String]) -> Result<Vec<FileOwners>> {
+    let mut owners = Vec::new();
+    for path in paths {
+      let mut names = Vec::new();
+      for project in self.current.projects() {
+        if project.does_cover(path)? {
+          names.push(project.name().to_string());
+        }
+      }
+      owners.push(FileOwners { path: path.clone(), owners: names });
+    }
+    Ok(owners)
+  }
+
+  /// The files that were changed in the current range, each once.
+  pub async fn changed_files(&self) -> Result<Vec<String>> {
+    let mut files = Vec::new();
+    for keyed in self.keyed_files().await? {
+      let (_, file) = keyed?;
+      if !files.contains(&file) {
+        files.push(file);
+      }
+    }
+    Ok(files)
+  }
+
+  fn covered_by_labeled(&self, file: &str) -> Result<bool> {
+    for project in self.current.projects().iter().filter(|p| has_label(p, &self.labels)) {
+      if project.does_cover(file)? {
+        return Ok(true);
+      }
+    }
+    Ok(false)
+  }
+
+  pub fn write_changelogs(&mut self) -> Result<()> { self.next.write_changelogs() }
+
+  #[instrument(skip_all)]
+  pub fn commit(&mut self, advance_prev: bool, pause: bool) -> Result<Vec<(String, Error)>> {
+    let branch = self.repo.branch_name().ok().cloned().flatten().unwrap_or_default();
+    let mut hooks = HashMap::new();
+    for proj in self.current.projects().iter().filter(|p| self.next.writes_project(p.id())) {
+      let previous = self.current.get_value(proj.id()).ok().flatten();
+      let version = self.next.version(proj.id()).map(|v| v.as_str()).unwrap_or("");
+      let env = hook_env(proj, &branch, previous.as_deref(), version);
+      hooks.insert(proj.id().clone(), ProjectHooks::new(proj, env));
+    }
+
+    self.next.commit(
+      &self.repo,
+      CommitArgs::new(
+        self.current.prev_tag(),
+        &self.last_commits,
+        self.current.old_tags().current(),
+        advance_prev,
+        &hooks,
+        pause
+      )
+    )
+  }
+
+  pub fn get_project(&self, id: &ProjectId) -> Result<&Project> {
+    self.current.get_project(id).ok_or_else(|| bad!("No such project {}", id))
+  }
+
+  /// Figure out how to undo the latest release, from what it recorded in the prev tag.
+  pub fn plan_rollback(&self) -> Result<Rollback> {
+    let prev_tag = self.current.prev_tag();
+    let release = self.repo.tag_commit_oid(prev_tag)?.ok_or_else(|| bad!("No \"{}\" tag: no release.", prev_tag))?;
+    let msg = self
+      .repo
+      .prev_message(prev_tag)
+      .map(|anno| PrevTagMessage::parse(&anno))
+      .transpose()?
+      .flatten()
+      .ok_or_else(|| bad!("The \"{}\" tag has no release annotation.", prev_tag))?;
+    let previous =
+      msg.previous().ok_or_else(|| bad!("The latest release didn't record its previous state: can't roll back."))?;
+
+    let release_commit = if self.repo.is_release_commit(&release)? { Some(release) } else { None };
+
+    let mut tags = Vec::new();
+    for (id, vers) in msg.versions() {
+      if previous.versions().get(id) == Some(vers) {
+        continue;
+      }
+      if let Some(tag) = self.current.get_project(id).and_then(|proj| proj.full_version(vers)) {
+        tags.push(tag);
+      }
+    }
+    tags.sort();
+
+    Ok(Rollback {
+      prev_tag: prev_tag.to_string(),
+      release_commit,
+      tags,
+      prev_commit: previous.commit().to_string(),
+      prev_versions: previous.versions().clone()
+    })
+  }
+
+  /// Undo the latest release: revert its commit, delete the tags it created, and move the prev tag back.
+  pub fn rollback(&self, rollback: &Rollback) -> Result<()> {
+    if let Some(commit) = &rollback.release_commit {
+      self.repo.revert(commit)?;
+    }
+    for tag in &rollback.tags {
+      self.repo.delete_tag(tag)?;
+    }
+
+    let msg = serde_json::to_string(&PrevTagMessage::new(rollback.prev_versions.clone(), None))?;
+    let msg = self.repo.commit_config().tag_message(&msg);
+    self.repo.write_prev(&rollback.prev_tag, &rollback.prev_commit, &msg)
   }
 
-  pub fn check_branch(&self) -> std::result::Result<(), (String, String)> {
-    if let Ok(branch_name) = self.repo.branch_name() {
-      if let Some(branch_name) = branch_name {
-        if let Some(cfg_name) = self.current.branch() {
-          if branch_name != cfg_name {
-            Err((cfg_name.clone(), branch_name.clone()))
+  /// Whether versio has already made a release here: that is, whether the prev tag exists.
+  pub fn has_released(&self) -> bool { self.repo.peek_commit_oid(self.current.prev_tag()).is_some() }
+
+  /// The versions of a project that were tagged before versio was adopted: those with a tag that is reachable from
+  /// HEAD, oldest first.
+  pub fn past_releases(&self, id: &ProjectId) -> Result<Vec<PastRelease>> {
+    let proj = self.get_project(id)?;
+    let mut releases = Vec::new();
+    for (version, tags) in find_version_tags(proj, &self.repo)? {
+      // Only the first tag of each version counts: see `find_version_tags`.
+      if let Some(time) = self.repo.time_in_head(&tags[0])? {
+        let date = time.format("%Y-%m-%d").to_string();
+        releases.push(PastRelease { version, tag: tags[0].clone(), date });
+      }
+    }
+    releases.sort_by(|a, b| version_sort(&b.version, &a.version));
+    Ok(releases)
+  }
+
+  /// Whether any commit since the tag of a past release has changed the project. Adopting versio treats those
+  /// changes as released, since the prev tag is created at HEAD.
+  pub fn changed_since(&self, id: &ProjectId, release: &PastRelease) -> Result<bool> {
+    let proj = self.get_project(id)?;
+    let changes = local_changes(&self.repo, FromTagBuf::new(release.tag.clone(), false), "HEAD".into())?;
+    for pr in changes.groups().values() {
+      for commit in pr.included_commits() {
+        for file in commit.files() {
+          if proj.does_cover(file)? {
+            return Ok(true);
+          }
+        }
+      }
+    }
+    Ok(false)
+  }
+
+  /// Render the changelog entry of a past release, on top of the `previous` rendering of the changelog (or else the
+  /// changelog file). Returns nothing if the project doesn't have a changelog, or there were no changes.
+  pub async fn render_past_changelog(
+    &self, id: &ProjectId, since: Option<&PastRelease>, release: &PastRelease, previous: Option<&str>
+  ) -> Result<Option<(PathBuf, String)>> {
+    let proj = self.get_project(id)?;
+    match self.past_changelog(id, since, release).await? {
+      Some(changelog) => proj.render_changelog_onto(&changelog, &release.version, previous).await,
+      None => Ok(None)
+    }
+  }
+
+  /// Render the changelog entry of a past release of a project again, and patch it over the release's section of
+  /// the changelog file. This uses the current template and configuration, like `adopt --changelogs` does.
+  pub async fn regenerate_changelog(&mut self, id: &ProjectId, version: &str) -> Result<PathBuf> {
+    let releases = self.past_releases(id)?;
+    let i = releases
+      .iter()
+      .position(|r| r.version == version)
+      .ok_or_else(|| bad!("No tag of version {} of project {} is reachable from HEAD.", version, id))?;
+    let since = if i == 0 { None } else { Some(&releases[i - 1]) };
+    let changelog = self.past_changelog(id, since, &releases[i]).await?;
+    let changelog = changelog
+      .filter(|cl| !cl.is_empty())
+      .ok_or_else(|| bad!("Version {} of project {} has no changes to log.", version, id))?;
+
+    let proj = self.get_project(id)?;
+    let (path, content) = proj
+      .regenerate_changelog_section(&changelog, version)
+      .await?
+      .ok_or_else(|| bad!("Project {} doesn't have a changelog.", id))?;
+    self.next.write_file(path.clone(), content, id, true)?;
+    Ok(path)
+  }
+
+  /// The changelog of a past release: the changes after the `since` release (or from the start of the repo) up to
+  /// the release's tag. Since old commits don't have the current configuration, it's used to decide which changes
+  /// belong to the project. Returns nothing if there were no changes.
+  async fn past_changelog(
+    &self, id: &ProjectId, since: Option<&PastRelease>, release: &PastRelease
+  ) -> Result<Option<Changelog>> {
+    Ok(self.past_incr(id, since, release).await?.map(|(_, changelog)| changelog))
+  }
+
+  /// The size and changelog of a past release, like `past_changelog`.
+  async fn past_incr(
+    &self, id: &ProjectId, since: Option<&PastRelease>, release: &PastRelease
+  ) -> Result<Option<(Size, Changelog)>> {
+    // An empty ref can't be found, so the first release's range starts at the beginning of the repo.
+    let base = FromTagBuf::new(since.map(|s| s.tag.clone()).unwrap_or_default(), true);
+    let changes = self.changes_between(base, format!("refs/tags/{}", release.tag)).await?;
+    if let Some(warning) = unavailable_warning(&changes) {
+      warn_later(warning);
+    }
+
+    let mut builder = PlanBuilder::fixed(self.current.file());
+    builder.github_info = self.repo.github_info(self.user_prefs.auth()).ok();
+    let mut plan = builder.build_from(changes.groups().values())?;
+    if changes.offline() {
+      plan.mark_offline();
+    }
+    let (size, mut changelog) = match plan.incrs.remove(id) {
+      Some(incr) => incr,
+      None => return Ok(None)
+    };
+    changelog.date = Some(release.date.clone());
+    Ok(Some((size, changelog)))
+  }
+
+  /// Check a release `tag` against what versio would have released at its commit, with the current config: that
+  /// its version is the one that its changes make of the release before it, that the version file at its commit
+  /// has its version, that its annotation is the one that versio writes, and that its changelog has the release.
+  /// Returns the name of the tag's project, and each discrepancy that was found.
+  pub async fn verify_release(&self, tag: &str) -> Result<(String, Vec<String>)> {
+    let (proj, version) = self
+      .current
+      .projects()
+      .iter()
+      .find_map(|p| p.tag_version(tag).map(|v| (p, v)))
+      .ok_or_else(|| bad!("{}", tr!("verify.unknown_tag", tag = tag)))?;
+    let (id, name) = (proj.id(), proj.name());
+    let commit = self.repo.tag_commit_oid(tag)?.ok_or_else(|| bad!("{}", tr!("verify.unknown_tag", tag = tag)))?;
+    let releases = self.past_releases(id)?;
+    let i = releases
+      .iter()
+      .position(|r| r.version == version)
+      .ok_or_else(|| bad!("{}", tr!("verify.unreachable", tag = tag)))?;
+    // The release is checked at this tag, even if another tag of the version is the one that counts.
+    let release = PastRelease { version: version.clone(), tag: tag.to_string(), date: releases[i].date.clone() };
+    let since = if i == 0 { None } else { Some(&releases[i - 1]) };
+    let incr = self.past_incr(id, since, &release).await?.filter(|(_, cl)| !cl.is_empty());
+
+    let mut found = Vec::new();
+    match (&incr, since) {
+      (None, _) => found.push(tr!("verify.no_changes", tag = tag)),
+      (Some((size, _)), Some(since)) => {
+        let no_incr = size.is_failure() || *size == Size::Empty;
+        let expected = if no_incr { None } else { Some(proj.apply_size(*size, &since.version)?) };
+        match expected {
+          Some(expected) if expected == version => (),
+          Some(expected) => found.push(tr!(
+            "verify.version",
+            tag = tag,
+            version = version,
+            expected = expected,
+            previous = since.version
+          )),
+          None => found.push(tr!("verify.no_increment", tag = tag, previous = since.version))
+        }
+      }
+      // A project's first release can be at any version.
+      (Some(_), None) => ()
+    }
+
+    let summary = incr.as_ref().map(|(_, cl)| cl.summary()).unwrap_or_default();
+    let expected = proj.tag_annotation(&version, &summary)?;
+    let actual = self.repo.annotation_of(tag).map(|a| a.trim().to_string());
+    match (expected, actual) {
+      (None, Some(_)) => found.push(tr!("verify.annotated", tag = tag)),
+      (Some(_), None) => found.push(tr!("verify.lightweight", tag = tag)),
+      (Some(expected), Some(actual)) if expected != actual => found.push(tr!("verify.annotation", tag = tag)),
+      _ => ()
+    }
+
+    let at_tag = self.current.slice_to_commit(&self.repo, &commit)?;
+    if !proj.is_tag_version() {
+      match at_tag.get_value(id) {
+        Ok(Some(value)) if value == version => (),
+        Ok(Some(value)) => found.push(tr!("verify.version_file", tag = tag, version = version, found = value)),
+        _ => found.push(tr!("verify.no_version_file", tag = tag))
+      }
+    }
+
+    if let (Some(log), Some(_)) = (proj.changelogs().first(), &incr) {
+      let path = proj.changelog_path(log, &version)?;
+      let files = at_tag.state_read();
+      let logged = files.has_file(&path)? && files.read_file(&path)?.contains(version.as_str());
+      if !logged {
+        found.push(tr!("verify.changelog", tag = tag, path = path.to_string_lossy(), version = version));
+      }
+    }
+
+    Ok((name.to_string(), found))
+  }
+
+  /// Check the whole tag history of each project: that its versions only go up along the history, that no version
+  /// is skipped or tagged on two commits, and that the version file at each tag has the tag's version. This doesn't
+  /// need the config at each tag, so it can check history from before versio was adopted.
+  pub fn audit(&self) -> Result<Vec<AuditReport>> {
+    let mut reports = Vec::new();
+    for proj in self.current.projects() {
+      let mut found = Vec::new();
+      let mut releases = Vec::new();
+      for (version, tags) in find_version_tags(proj, &self.repo)? {
+        let mut commits = Vec::new();
+        for tag in tags {
+          if let Some(commit) = self.repo.tag_commit_oid(&tag)? {
+            commits.push((tag, commit));
+          }
+        }
+        if let Some((tag, commit)) = commits.first() {
+          for (other, _) in commits.iter().skip(1).filter(|(_, c)| c != commit) {
+            found.push(tr!("audit.split", version = version, tag = tag, other = other));
+          }
+          releases.push((version, tag.clone(), commit.clone()));
+        }
+      }
+      releases.sort_by(|a, b| version_sort(&b.0, &a.0));
+
+      for pair in releases.windows(2) {
+        let ((prev, prev_tag, prev_commit), (next, tag, commit)) = (&pair[0], &pair[1]);
+        if commit == prev_commit {
+          found.push(tr!("audit.same_commit", tag = tag, other = prev_tag));
+        } else if self.repo.is_ancestor(commit, prev_commit)? {
+          found.push(tr!("audit.backwards", tag = tag, other = prev_tag));
+        }
+        if let Some(expected) = next_versions(prev, next) {
+          if !expected.contains(next) {
+            found.push(tr!("audit.gap", tag = tag, version = next, previous = prev));
+          }
+        }
+      }
+
+      if !proj.is_tag_version() {
+        for (version, tag, commit) in &releases {
+          let at_tag = self.current.slice_to_commit(&self.repo, commit)?;
+          let then = at_tag.get_project(proj.id()).unwrap_or(proj);
+          match then.get_value(at_tag.state_read()) {
+            Ok(value) if &value == version => (),
+            Ok(value) => found.push(tr!("verify.version_file", tag = tag, version = version, found = value)),
+            Err(_) => found.push(tr!("verify.no_version_file", tag = tag))
+          }
+        }
+      }
+
+      let (id, name) = (proj.id().to_string(), proj.name().to_string());
+      reports.push(AuditReport { id, name, versions: releases.len(), problems: found });
+    }
+    Ok(reports)
+  }
+
+  /// The progress of an interrupted `adopt`, if any.
+  pub fn adopt_progress(&self) -> Result<Option<AdoptProgress>> {
+    match self.adopt_progress_path() {
+      Some(path) if path.exists() => Ok(Some(serde_json::from_str(&std::fs::read_to_string(path)?)?)),
+      _ => Ok(None)
+    }
+  }
+
+  /// Save the progress of `adopt`, so that it can pick up where it left off if it's interrupted.
+  pub fn save_adopt_progress(&self, progress: &AdoptProgress) -> Result<()> {
+    if let Some(path) = self.adopt_progress_path() {
+      if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+      }
+      std::fs::write(path, serde_json::to_string(progress)?)?;
+    }
+    Ok(())
+  }
+
+  fn adopt_progress_path(&self) -> Option<PathBuf> {
+    self.repo.git_dir().ok().map(|dir| dir.join(GIT_VERSIO_DIR).join(ADOPT_PROGRESS_FILE))
+  }
+
+  /// Finish adopting versio: commit the backfilled changelogs, and create the prev tag, which records the latest
+  /// tagged version of each project.
+  pub fn finish_adopt(&mut self, progress: AdoptProgress) -> Result<Vec<(String, Error)>> {
+    for (id, (path, content)) in progress.logs {
+      self.next.write_file(path, content, &id, true)?;
+    }
+    let mirror_failed = self.commit(true, false)?;
+    if let Some(path) = self.adopt_progress_path().filter(|p| p.exists()) {
+      std::fs::remove_file(path)?;
+    }
+    Ok(mirror_failed)
+  }
+
+  pub fn write_chains(&mut self, ids: &[(ProjectId, ProjectId)], vers: &HashMap<ProjectId, String>) -> Result<()> {
+    for (id, dpid) in ids {
+      let dproj =
+        self.current.get_project(dpid).ok_or_else(|| bad!("No such dependent {} project for {}.", dpid, id))?;
+      let deps = dproj.depends().get(id).ok_or_else(|| bad!("No such depends {} in project {}.", id, dpid))?;
+      let val = vers.get(id).ok_or_else(|| bad!("No new value for {}.", id))?;
+      deps.write_values(&mut self.next, self.current.state_read(), dproj.root(), val, dpid)?;
+    }
+    Ok(())
+  }
+
+  /// Write `val` as the version of `id` into the `depends` of each project that depends on it, as a release would.
+  pub fn write_dependents(&mut self, id: &ProjectId, val: &str) -> Result<()> {
+    let chains: Vec<_> = self
+      .current
+      .projects()
+      .iter()
+      .filter(|p| p.depends().contains_key(id))
+      .map(|p| (id.clone(), p.id().clone()))
+      .collect();
+    self.write_chains(&chains, &HashMap::from([(id.clone(), val.to_string())]))
+  }
+
+  /// The `depends` requirements that releasing the `plan` would rewrite, in the order they'd be written. A chain
+  /// write that wouldn't change the dependent's requirement is left out.
+  pub fn planned_chain_writes(&self, plan: &Plan) -> Result<Vec<ChainWrite>> {
+    let curt_config = &self.current;
+    let prev_config = curt_config.slice_to_prev(&self.repo)?;
+
+    let mut writes = Vec::new();
+    for (id, depd_id) in plan.chain_writes() {
+      let size = match plan.incrs().get(id) {
+        Some((size, _)) if !size.is_failure() => *size,
+        _ => continue
+      };
+      let proj = self.get_project(id)?;
+      let depd = self.get_project(depd_id)?;
+      let deps = depd.depends().get(id).ok_or_else(|| bad!("No such depends {} in project {}.", id, depd_id))?;
+
+      let current = proj.get_value(curt_config.state_read())?;
+      let previous = prev_config.get_value(id).with_context(|| format!("Unable to find prev {} value.", id))?;
+      let version = match previous {
+        Some(prev) if size != Size::Empty => {
+          let target = proj.apply_size(size, &prev)?;
+          if Size::less_than(&current, &target)? {
+            target
           } else {
-            Ok(())
+            current
           }
-        } else {
-          Ok(())
         }
-      } else if let Some(cfg_name) = self.current.branch() {
-        Err((cfg_name.clone(), "((No branch))".to_string()))
-      } else {
-        Ok(())
+        _ => current
+      };
+
+      let rewrites = deps.rewrites(curt_config.state_read(), depd.root(), &version, depd_id)?;
+      if let Some((before, after)) = rewrites.into_iter().find(|(before, after)| before != after) {
+        writes.push(ChainWrite { dependency: id.clone(), dependent: depd_id.clone(), before, after, size });
       }
+    }
+    Ok(writes)
+  }
+
+  /// Compare the projects and their versions at `from` (or else the previous tag) with those at `to` (or else the
+  /// current files).
+  pub fn diff(&self, from: Option<&str>, to: Option<&str>) -> Result<Analysis> {
+    let prev_annotate = match from {
+      Some(rev) => self.annotate_at(rev)?,
+      None => self.current.slice_to_prev(&self.repo)?.annotate()?
+    };
+    let curt_annotate = match to {
+      Some(rev) => self.annotate_at(rev)?,
+      None => self.current.annotate()?
+    };
+
+    Ok(analyze(prev_annotate, curt_annotate))
+  }
+
+  /// The projects and their versions at `rev`. A project that's versioned by tags is at its highest version that's
+  /// tagged on `rev` or one of its ancestors.
+  fn annotate_at(&self, rev: &str) -> Result<Vec<AnnotatedMark>> {
+    if self.repo.peek_commit_oid(rev).is_none() {
+      bail!("{}", tr!("diff.unknown_ref", rev = rev));
+    }
+    let mut tag_versions = HashMap::new();
+    for proj in self.current.projects().iter().filter(|p| p.is_tag_version()) {
+      let mut versions = Vec::new();
+      for (version, tags) in find_version_tags(proj, &self.repo)? {
+        if self.repo.is_ancestor(&format!("{}^{{}}", tags[0]), rev)? {
+          versions.push(version);
+        }
+      }
+      versions.sort_unstable_by(version_sort);
+      if let Some(version) = versions.into_iter().next() {
+        tag_versions.insert(proj.id().clone(), version);
+      }
+    }
+    self.current.slice_to_ref(&self.repo, rev, tag_versions)?.annotate()
+  }
+
+  pub fn config(&self) -> &Config<CurrentState> { &self.current }
+  pub fn repo(&self) -> &Repo { &self.repo }
+
+  pub fn set_by_id(&mut self, id: &ProjectId, val: &str) -> Result<()> {
+    let build = BuildInfo::new(self.repo.peek_commit_oid("HEAD"), Utc::now());
+    self.do_project_write(id, move |p, n| {
+      n.set_version(p.id(), val);
+      p.set_value(n, val, &build)
+    })
+  }
+
+  /// The versions pinned by `versio pin`, which the next release uses instead of the planned sizes.
+  pub fn pins(&self) -> Result<HashMap<ProjectId, String>> {
+    let reader = self.current.state_read();
+    if !reader.has_file(PINS_FILENAME.as_ref())? {
+      return Ok(HashMap::new());
+    }
+    let data = reader.read_file(PINS_FILENAME.as_ref())?;
+    let pins: Option<_> = serde_yaml::from_str(&data).with_context(|| format!("Unable to parse {}.", PINS_FILENAME))?;
+    Ok(pins.unwrap_or_default())
+  }
+
+  /// Pin the next version of a project, which must be greater than its current version; or unpin it, if there's
+  /// no `version`.
+  pub fn pin(&mut self, id: &ProjectId, version: Option<&str>) -> Result<()> {
+    let mut pins = self.pins()?;
+    match version {
+      Some(version) => {
+        let curt = self.current.get_value(id)?.ok_or_else(|| bad!("Unable to find project {} value.", id))?;
+        if !Size::less_than(&curt, version)? {
+          bail!("{}", tr!("pin.not_greater", version = version, current = curt));
+        }
+        self.get_project(id)?.verify_restrictions(version)?;
+        pins.insert(id.clone(), version.to_string());
+      }
+      None => {
+        pins.remove(id);
+      }
+    }
+    self.write_pins(&pins)
+  }
+
+  /// Forget the pins of these projects, once they've been released at their pinned versions.
+  pub fn clear_pins<'a, I: IntoIterator<Item = &'a ProjectId>>(&mut self, ids: I) -> Result<()> {
+    let mut pins = self.pins()?;
+    let count = pins.len();
+    for id in ids {
+      pins.remove(id);
+    }
+    if pins.len() != count {
+      self.write_pins(&pins)?;
+    }
+    Ok(())
+  }
+
+  fn write_pins(&mut self, pins: &HashMap<ProjectId, String>) -> Result<()> {
+    if pins.is_empty() {
+      self.next.remove_other_file(PINS_FILENAME.into());
     } else {
+      let sorted: BTreeMap<_, _> = pins.iter().map(|(id, version)| (id.to_string(), version)).collect();
+      self.next.write_other_file(PINS_FILENAME.into(), serde_yaml::to_string(&sorted)?);
+    }
+    Ok(())
+  }
+
+  pub fn forward_by_id(&mut self, id: &ProjectId, val: &str) -> Result<()> {
+    self.do_project_write(id, move |p, n| {
+      n.set_version(p.id(), val);
+      p.forward_tag(n, val)
+    })
+  }
+
+  /// Create the release's tags at `rev` (like a merge queue's merge commit) instead of HEAD. This is only for
+  /// tag-only releases, since nothing is committed to `rev`.
+  pub fn tag_at(&mut self, rev: &str) -> Result<()> {
+    let oid = self.repo.peek_commit_oid(rev).ok_or_else(|| bad!("{}", tr!("release.unknown_target", rev = rev)))?;
+    self.next.tag_at(oid);
+    Ok(())
+  }
+
+  /// Include the summary of the `changelog` in the message of the project's new version tag, if it's annotated.
+  pub fn annotate_tag(&mut self, id: &ProjectId, val: &str, changelog: &Changelog) -> Result<()> {
+    self.do_project_write(id, move |p, n| {
+      if let (Some(tag), Some(msg)) = (p.full_version(val), p.tag_annotation(val, &changelog.summary())?) {
+        n.annotate_tag(tag, msg);
+      }
       Ok(())
+    })
+  }
+
+  /// Render the changelogs for a batch of projects concurrently, and queue them to be written. Returns the
+  /// paths of the changelogs that will be written, in the same order as `logs`: a project with several changelog
+  /// targets has a path for each.
+  #[instrument(skip_all, fields(count = logs.len()))]
+  pub async fn write_changelogs_for(&mut self, logs: &[(ProjectId, &Changelog, String)]) -> Result<Vec<PathBuf>> {
+    let current = &self.current;
+    let rendered: Vec<_> = stream::iter(logs.iter())
+      .map(|(id, changelog, new_vers)| async move {
+        let proj = current.get_project(id).ok_or_else(|| bad!("No such project {}", id))?;
+        Ok::<_, Error>((id, proj.render_changelog(changelog, new_vers).await?))
+      })
+      .buffered(CHANGELOG_CONCURRENCY)
+      .try_collect()
+      .await?;
+
+    let mut wrote = Vec::new();
+    for (id, rendered) in rendered {
+      for (log_path, log) in rendered {
+        match log {
+          RenderedLog::Full(content) => self.next.write_file(log_path.clone(), content, id, true)?,
+          RenderedLog::Section { section, version, tag } => {
+            self.next.splice_changelog(log_path.clone(), section, version, tag, id)
+          }
+        }
+        wrote.push(log_path);
+      }
+    }
+    Ok(wrote)
+  }
+
+  /// Post a summary of the `released` projects (each with its previous and new version) to the webhooks of the
+  /// `notifications` config. A failed notification doesn't fail the release: the failures are returned instead.
+  pub async fn notify(&self, released: &[(ProjectId, Option<String>, String)]) -> Result<Vec<(String, Error)>> {
+    let notifications = self.current.file().notifications();
+    if notifications.is_empty() {
+      return Ok(Vec::new());
+    }
+    Ok(notify_all(notifications, &self.release_summary(released)?).await)
+  }
+
+  /// Render the release notes of the `released` projects of the `plan` for each of the `notes_publishers` of the
+  /// config, and send them, unless this is a `dry` run. Like a notification, a publisher that fails doesn't fail
+  /// the release: each publisher is returned with whether it succeeded.
+  pub async fn publish_notes(
+    &self, plan: &Plan, released: &[(ProjectId, Option<String>, String)], dry: bool
+  ) -> Result<Vec<(String, Result<()>)>> {
+    let publishers = self.current.file().notes_publishers();
+    if publishers.is_empty() {
+      return Ok(Vec::new());
+    }
+
+    let summary = self.release_summary(released)?;
+    let mut results = Vec::new();
+    for publisher in publishers {
+      let result = async {
+        let template = read_template(publisher.template(), Some(self.repo.working_dir()?), true).await?;
+        let notes = self.release_notes(plan, released, template).await?;
+        publisher.publish(&summary, &notes, dry).await
+      };
+      results.push((publisher.describe(), result.await));
+    }
+    Ok(results)
+  }
+
+  /// What was released, as it's sent to webhooks and notes publishers.
+  fn release_summary(&self, released: &[(ProjectId, Option<String>, String)]) -> Result<ReleaseSummary> {
+    let github = self.repo.github_info(self.user_prefs.auth()).ok();
+    let commit = self.repo.peek_commit_oid("HEAD");
+    let projects = released
+      .iter()
+      .map(|(id, previous, version)| {
+        let proj = self.get_project(id)?;
+        let changelog = proj.changelogs().first().map(|log| proj.changelog_path(log, version)).transpose()?;
+        let changelog = changelog.map(|path| path.to_slash_lossy().into_owned()).map(|path| match (&github, &commit) {
+          (Some(gh), Some(commit)) => gh.file_url(commit, &path),
+          _ => path
+        });
+        let tag = proj.full_version(version);
+        Ok(NotifiedProject::new(id.to_string(), proj.name().into(), previous.clone(), version.clone(), tag, changelog))
+      })
+      .collect::<Result<_>>()?;
+
+    let repository = github.map(|gh| format!("{}/{}", gh.owner_name(), gh.repo_name()));
+    Ok(ReleaseSummary::new(repository, commit, projects))
+  }
+
+  /// Send metrics about a release that took `duration`, and released `released` of the `plan`'s projects, to the
+  /// collectors in the `[metrics]` preferences. Like notifications, a failure is returned instead of failing.
+  pub async fn emit_metrics(&self, duration: Duration, released: usize, plan: &Plan) -> Vec<(String, Error)> {
+    let config = self.user_prefs.metrics();
+    if config.is_empty() {
+      return Vec::new();
+    }
+
+    let github = self.repo.github_info(self.user_prefs.auth()).ok();
+    let repository = github.map(|gh| format!("{}/{}", gh.owner_name(), gh.repo_name()));
+    let sizes = plan.incrs().values().map(|(size, _)| size);
+    emit(config, &ReleaseMetrics::new(repository, duration, released, sizes)).await
+  }
+
+  /// Queue a manifest of the `released` projects (each with its previous and new version) to be committed with
+  /// the release, if the config has a `releases_dir`. Returns the path of the manifest.
+  pub fn write_manifest(&mut self, released: &[(ProjectId, Option<String>, String)]) -> Result<Option<PathBuf>> {
+    let dir = match self.current.file().releases_dir() {
+      Some(dir) => PathBuf::from_slash(dir),
+      None => return Ok(None)
+    };
+
+    let now = Utc::now();
+    let manifest = self.manifest(released, "HEAD", now)?;
+    let suffix =
+      manifest.commit.as_ref().map(|c| c[.. 7].to_string()).unwrap_or_else(|| now.format("%H%M%S").to_string());
+    let path = dir.join(format!("{}-{}.json", now.format("%Y-%m-%d"), suffix));
+    self.next.write_other_file(path.clone(), serde_json::to_string_pretty(&manifest)? + "\n");
+    Ok(Some(path))
+  }
+
+  /// A manifest of the `released` projects, as of the commit `rev`, for `release --manifest`.
+  pub fn release_manifest(
+    &self, released: &[(ProjectId, Option<String>, String)], rev: &str, format: ManifestFormat
+  ) -> Result<String> {
+    let manifest = self.manifest(released, rev, Utc::now())?;
+    let value = match format {
+      ManifestFormat::Json => serde_json::to_value(&manifest)?,
+      ManifestFormat::CycloneDx => manifest.cyclonedx()
+    };
+    Ok(serde_json::to_string_pretty(&value)? + "\n")
+  }
+
+  fn manifest(
+    &self, released: &[(ProjectId, Option<String>, String)], rev: &str, now: DateTime<Utc>
+  ) -> Result<ReleaseManifest> {
+    let commit = self.repo.peek_commit_oid(rev);
+    let projects = released
+      .iter()
+      .map(|(id, previous, version)| {
+        let proj = self.get_project(id)?;
+        Ok(ManifestProject {
+          id: id.to_string(),
+          name: proj.name().to_string(),
+          previous: previous.clone(),
+          version: version.clone(),
+          tag: proj.full_version(version),
+          changelog: proj
+            .changelogs()
+            .first()
+            .map(|log| proj.changelog_path(log, version).map(|path| path.to_slash_lossy().into_owned()))
+            .transpose()?
+        })
+      })
+      .collect::<Result<_>>()?;
+    Ok(ReleaseManifest { date: now.to_rfc3339_opts(SecondsFormat::Secs, true), commit, projects })
+  }
+
+  /// A receipt of the `released` projects (each with its previous and new version), with the commits that the
+  /// plan found for each.
+  pub fn receipt(&self, released: &[(ProjectId, Option<String>, String)], plan: &Plan) -> Result<Receipt> {
+    let projects = released
+      .iter()
+      .map(|(id, previous, version)| {
+        let proj = self.get_project(id)?;
+        let commits = plan
+          .incrs()
+          .get(id)
+          .into_iter()
+          .flat_map(|(_, log)| log.entries())
+          .filter_map(|entry| match entry {
+            ChangelogEntry::Pr(pr, _) => {
+              Some(pr.commits().iter().filter(|c| c.included()).map(|c| c.oid().to_string()))
+            }
+            ChangelogEntry::Dep(..) => None
+          })
+          .flatten()
+          .collect();
+        let (id, name) = (id.to_string(), proj.name().to_string());
+        Ok(ReceiptProject::new(id, name, previous.clone(), version.clone(), proj.full_version(version), commits))
+      })
+      .collect::<Result<_>>()?;
+    Ok(Receipt::new(self.repo.peek_commit_oid("HEAD"), projects))
+  }
+
+  /// Find what a release would do to the version of each of the plan's `picked` projects (or all of them), without
+  /// changing anything. This is shared by `release`, which then makes the changes, and by `preview` and the API's
+  /// dry release, which only show them. With `force`, a version that breaks its project's `constraints` is allowed,
+  /// but is marked as forced.
+  pub fn next_versions<'p>(
+    &self, plan: &'p Plan, picked: Option<&HashSet<ProjectId>>, locktags: bool, force: bool
+  ) -> Result<Vec<NextVersion<'p>>> {
+    let curt_config = &self.current;
+    let prev_config = curt_config.slice_to_prev(&self.repo)?;
+    let pins = self.pins()?;
+
+    let mut nexts = Vec::new();
+    for (id, (size, changelog)) in plan.incrs() {
+      if !picked.map(|p| p.contains(id)).unwrap_or(true) {
+        continue;
+      }
+      let proj = self.get_project(id)?;
+      let curt_vers = curt_config
+        .get_value(id)
+        .with_context(|| format!("Unable to find project {} value.", id))?
+        .unwrap_or_else(|| panic!("No such project {}.", id));
+      let prev_vers = prev_config.get_value(id).with_context(|| format!("Unable to find prev {} value.", id))?;
+      let applied = match &prev_vers {
+        Some(prev_vers) => proj.applied_size(*size, prev_vers)?,
+        None => *size
+      };
+
+      let pin = pins.get(id).filter(|_| size != &Size::Empty);
+      let mut forced = None;
+      let (version, change) = if size == &Size::Empty {
+        (curt_vers.clone(), VersionChange::NoChange { prev: prev_vers, curt: curt_vers, locked: false })
+      } else if let Some(prev_vers) = prev_vers {
+        let target = match pin {
+          Some(pin) if !Size::less_than(&curt_vers, pin)? => {
+            bail!("{}", tr!("pin.passed", name = proj.name(), version = pin, current = curt_vers))
+          }
+          Some(pin) => pin.clone(),
+          None if size.is_failure() => bail!("Couldn't parse conventional commit(s): {}", failed_hashes(plan)),
+          None => proj.bump(applied, &prev_vers)?
+        };
+
+        if curt_config.old_tags().latest(id) == Some(&curt_vers) && !Size::less_than(&curt_vers, &target)? {
+          (curt_vers.clone(), VersionChange::Released { prev: prev_vers, curt: curt_vers })
+        } else if Size::less_than(&curt_vers, &target)? {
+          forced = verify_next(proj, &target, force)?;
+          (target.clone(), VersionChange::Changed { prev: prev_vers, curt: curt_vers, target })
+        } else {
+          forced = verify_next(proj, &curt_vers, force)?;
+          let change = if locktags {
+            VersionChange::NoChange { prev: Some(prev_vers), curt: curt_vers, locked: true }
+          } else {
+            VersionChange::Forward { prev: prev_vers, curt: curt_vers, target: target.clone() }
+          };
+          (target, change)
+        }
+      } else {
+        forced = verify_next(proj, &curt_vers, force)?;
+        if locktags {
+          (curt_vers.clone(), VersionChange::NoChange { prev: None, curt: curt_vers, locked: true })
+        } else {
+          (curt_vers.clone(), VersionChange::New { curt: curt_vers })
+        }
+      };
+
+      let name = proj.name().to_string();
+      let pin = pin.is_some() && matches!(change, VersionChange::Changed { .. });
+      let changelog = Cow::Borrowed(changelog);
+      let next = NextVersion { id: id.clone(), name, size: *size, applied, changelog, version, change, pin, forced };
+      nexts.push(next);
+    }
+    Ok(nexts)
+  }
+
+  /// The size and target version of each project of the plan, to compare with a saved plan.
+  pub fn planned_projects(&self, plan: &Plan) -> Result<Vec<PlannedProject>> {
+    let prev_config = self.current.slice_to_prev(&self.repo)?;
+    let mut planned = Vec::new();
+    for (id, (size, _)) in plan.incrs() {
+      let proj = self.get_project(id)?;
+      let previous = prev_config.get_value(id).with_context(|| format!("Unable to find prev {} value.", id))?;
+      let target = match &previous {
+        Some(prev) if !size.is_failure() && size != &Size::Empty => Some(proj.apply_size(*size, prev)?),
+        _ => None
+      };
+      planned.push(PlannedProject::new(id.to_string(), proj.name().to_string(), size.to_string(), target));
+    }
+    Ok(planned)
+  }
+
+  /// The projects that the `plan` would release, each with its previous version (if it isn't new) and the version
+  /// it would be released at, like the `released` projects of a release.
+  pub fn planned_releases(&self, plan: &Plan) -> Result<Vec<(ProjectId, Option<String>, String)>> {
+    let prev_config = self.current.slice_to_prev(&self.repo)?;
+    let mut planned = Vec::new();
+    for (id, (size, _)) in plan.incrs() {
+      if size.is_failure() || size == &Size::Empty {
+        continue;
+      }
+      let proj = self.get_project(id)?;
+      let previous = prev_config.get_value(id).with_context(|| format!("Unable to find prev {} value.", id))?;
+      let version = match &previous {
+        Some(prev) => proj.apply_size(*size, prev)?,
+        None => self.current.get_value(id)?.ok_or_else(|| bad!("No such project {}", id))?
+      };
+      planned.push((id.clone(), previous, version));
+    }
+    Ok(planned)
+  }
+
+  /// Render the release notes of the `released` projects of the `plan` (each with its previous and new version)
+  /// from the notes `template`: one document for the whole release, with each project's changelog section in the
+  /// dependency order of the plan.
+  pub async fn release_notes(
+    &self, plan: &Plan, released: &[(ProjectId, Option<String>, String)], template: String
+  ) -> Result<String> {
+    let mut projects = Vec::new();
+    for (id, previous, version) in release_order(plan.dep_order(), released) {
+      let proj = self.get_project(id)?;
+      let (size, changelog) = plan.incrs().get(id).ok_or_else(|| bad!("Project {} isn't in the plan.", id))?;
+      let size = match previous {
+        Some(prev) => proj.applied_size(*size, prev)?,
+        None => *size
+      };
+      projects.push(NotesProject {
+        proj: ProjLine::from_version(proj, version.clone())?,
+        previous: previous.clone(),
+        size: size.to_string(),
+        section: proj.render_section(changelog, version).await?
+      });
+    }
+    construct_release_notes(&projects, template)
+  }
+
+  /// Run the `post_release` hooks of the `released` projects (each with its previous and new version), in the
+  /// dependency `order` of the plan. This stops at the first failure, since the projects that depend on the failed
+  /// one probably can't be published either.
+  pub fn publish(&self, order: &[ProjectId], released: &[(ProjectId, Option<String>, String)]) -> Result<Published> {
+    let mut published = Published::new();
+    for (id, previous, version) in release_order(order, released) {
+      let proj = self.get_project(id)?;
+      if !proj.hooks().has("post_release") {
+        continue;
+      }
+      let name = proj.name().to_string();
+      if published.failed.is_some() {
+        published.skipped.push(name);
+        continue;
+      }
+
+      let hooks = ProjectHooks::new(proj, self.release_env(proj, previous.as_deref(), version));
+      match hooks.execute("post_release") {
+        Ok(()) => published.ran.push(name),
+        Err(e) => published.failed = Some((name, e))
+      }
+    }
+    Ok(published)
+  }
+
+  /// Run the `pre_release` hooks of the `released` projects (each with its previous and new version), in the
+  /// dependency `order` of the plan, before anything is written. The first hook to fail stops the release.
+  pub fn pre_release(&self, order: &[ProjectId], released: &[(ProjectId, Option<String>, String)]) -> Result<()> {
+    for (id, previous, version) in release_order(order, released) {
+      let proj = self.get_project(id)?;
+      ProjectHooks::new(proj, self.release_env(proj, previous.as_deref(), version)).execute("pre_release")?;
+    }
+    Ok(())
+  }
+
+  /// Run the hooks that are declared safe, in place of a release that's only a dry run: the `pre_release` hooks of
+  /// the `released` projects, the `post_write` hooks of the projects that would have been written, and then (if
+  /// `publish`) the `post_release` hooks. Each runs with `VERSIO_DRY_RUN=true` in its environment. Unsafe hooks are
+  /// skipped, and a failed hook fails the dry run.
+  pub fn dry_run_hooks(
+    &self, order: &[ProjectId], released: &[(ProjectId, Option<String>, String)], publish: bool
+  ) -> Result<DryRunHooks> {
+    let mut hooks = DryRunHooks::new();
+    for (id, previous, version) in release_order(order, released) {
+      let proj = self.get_project(id)?;
+      hooks.run(ProjectHooks::new(proj, self.release_env(proj, previous.as_deref(), version)), "pre_release")?;
+    }
+
+    for proj in self.current.projects().iter().filter(|p| self.next.writes_project(p.id())) {
+      let previous = self.current.get_value(proj.id()).ok().flatten();
+      let version = released.iter().find(|(id, ..)| id == proj.id()).map(|(.., v)| v.as_str()).unwrap_or("");
+      hooks.run(ProjectHooks::new(proj, self.release_env(proj, previous.as_deref(), version)), "post_write")?;
+    }
+
+    if publish {
+      for (id, previous, version) in release_order(order, released) {
+        let proj = self.get_project(id)?;
+        hooks.run(ProjectHooks::new(proj, self.release_env(proj, previous.as_deref(), version)), "post_release")?;
+      }
+    }
+    Ok(hooks)
+  }
+
+  /// The environment of a project's release hooks.
+  fn release_env(&self, proj: &Project, previous: Option<&str>, version: &str) -> Vec<(&'static str, String)> {
+    let branch = self.repo.branch_name().ok().cloned().flatten().unwrap_or_default();
+    hook_env(proj, &branch, previous, version)
+  }
+
+  fn do_project_write<F, T>(&mut self, id: &ProjectId, f: F) -> Result<T>
+  where
+    F: FnOnce(&Project, &mut StateWrite) -> Result<T>
+  {
+    let proj = self.current.get_project(id).ok_or_else(|| bad!("No such project {}", id))?;
+    f(proj, &mut self.next)
+  }
+
+  pub fn check(&self) -> Result<()> {
+    for project in self.current.projects() {
+      project.check(self.current.state_read())?;
+    }
+    Ok(())
+  }
+
+  /// Find the versions that are tagged more than once for the same project, as (project name, version, tags). Only
+  /// the first of the tags is used.
+  pub fn ambiguous_tags(&self) -> Result<Vec<(String, String, Vec<String>)>> {
+    let mut ambiguous = Vec::new();
+    for project in self.current.projects() {
+      let mut found: Vec<_> =
+        find_version_tags(project, &self.repo)?.into_iter().filter(|(_, t)| t.len() > 1).collect();
+      found.sort_by(|(v1, _), (v2, _)| version_sort(v1, v2));
+      ambiguous.extend(found.into_iter().map(|(v, tags)| (project.name().to_string(), v, tags)));
+    }
+    Ok(ambiguous)
+  }
+
+  /// Find the changes to the projects' versions and changelogs since HEAD branched from `base`, which should only be
+  /// made by a release. Projects that are new since then, or are versioned by tags, aren't checked.
+  pub fn check_release_only(&self, base: &str) -> Result<Vec<String>> {
+    let base = self.repo.merge_base_with_head(base)?;
+    let base_config = self.current.slice_to_commit(&self.repo, &base)?;
+    let base_state = base_config.state_read();
+    let state = self.current.state_read();
+    let mut violations = Vec::new();
+
+    for project in self.current.projects() {
+      let name = project.name();
+      if base_config.get_project(project.id()).is_none() {
+        continue;
+      }
+
+      if !project.is_tag_version() {
+        // A version file that's missing or unreadable at the base was new, not changed.
+        if let (Ok(Some(was)), Some(is)) = (base_config.get_value(project.id()), self.current.get_value(project.id())?)
+        {
+          if was != is {
+            violations.push(tr!("check.manual_version", name = name, was = was, version = is));
+          }
+        }
+      }
+
+      // A templated changelog is a new file for each release, so there's no one file to compare.
+      for log in project.changelogs().iter().filter(|log| !log.is_templated()) {
+        let path = PathBuf::from_slash(project.rooted_pattern(log.file()));
+        let path = path.as_path();
+        let read = |files: &dyn FilesRead| -> Result<Option<String>> {
+          if files.has_file(path)? {
+            Ok(Some(files.read_file(path)?))
+          } else {
+            Ok(None)
+          }
+        };
+        if read(base_state)? != read(state)? {
+          violations.push(tr!("check.manual_changelog", name = name, path = path.to_string_lossy()));
+        }
+      }
+    }
+
+    Ok(violations)
+  }
+
+  /// Like `check`, but also check that the repo agrees with the config: that each project's latest tag is for its
+  /// current version, that no two tag prefixes differ only by case (their tags would collide on a case-insensitive
+  /// file system), that no two projects cover the same files, and that every `depends` is a known project. Every
+  /// problem found is returned, not just the first. The majors of a `subs` project, umbrellas, and projects that
+  /// `allow_overlap` may share files.
+  pub fn check_strict(&self) -> Result<Vec<String>> {
+    let projects = self.current.projects();
+    let state = self.current.state_read();
+    let mut violations = Vec::new();
+
+    for project in projects {
+      if let Err(e) = project.check(state) {
+        violations.push(format!("{:#}", e));
+      }
+    }
+
+    let tags = self.current.old_tags().current();
+    for project in projects.iter().filter(|p| !p.is_tag_version()) {
+      if let (Some(tag), Ok(version)) = (tags.get(project.id()), project.get_value(state)) {
+        if tag != &version {
+          violations.push(tr!("check.tag_mismatch", name = project.name(), version = version, tag = tag));
+        }
+      }
+    }
+
+    let mut prefixes: HashMap<String, &Project> = HashMap::new();
+    for project in projects {
+      // Compare the fixed start of each project's tags, before its version.
+      let prefix = match project.tag_prefix().as_deref() {
+        None => continue,
+        Some(pref) => {
+          let tmpl = project.tag_template_for(pref);
+          tmpl[.. tmpl.find('{').unwrap_or(tmpl.len())].to_lowercase()
+        }
+      };
+      match prefixes.get(&prefix) {
+        Some(other) if !other.id().same_project(project.id()) => {
+          violations.push(tr!("check.tag_collision", name = other.name(), other = project.name(), prefix = prefix))
+        }
+        Some(_) => (),
+        None => {
+          prefixes.insert(prefix, project);
+        }
+      }
+    }
+
+    let files = self.repo.tracked_files()?;
+    let mut overlaps: BTreeMap<(usize, usize), (usize, &str)> = BTreeMap::new();
+    for file in &files {
+      let mut covers = Vec::new();
+      for (i, project) in projects.iter().enumerate() {
+        if !project.is_umbrella() && !project.allows_overlap() && project.does_cover(file)? {
+          covers.push(i);
+        }
+      }
+      for (n, i) in covers.iter().enumerate() {
+        for j in covers[n + 1 ..].iter().filter(|j| !projects[**j].id().same_project(projects[*i].id())) {
+          overlaps.entry((*i, *j)).or_insert((0, file)).0 += 1;
+        }
+      }
+    }
+    for ((i, j), (count, file)) in overlaps {
+      let (name, other) = (projects[i].name(), projects[j].name());
+      violations.push(tr!("check.overlap", name = name, other = other, count = count, file = file));
+    }
+
+    for project in projects {
+      let mut unknown: Vec<_> =
+        project.depends().keys().filter(|dep| self.current.get_project(dep).is_none()).map(|d| d.to_string()).collect();
+      unknown.sort();
+      for dep in unknown {
+        violations.push(tr!("check.unknown_dep", name = project.name(), dep = dep));
+      }
+    }
+
+    Ok(violations)
+  }
+
+  pub async fn keyed_files(&self) -> Result<impl Iterator<Item = Result<(String, String)>> + '_> {
+    let changes = self.changes().await?;
+    let prs = changes.into_groups().into_values().filter(|pr| !pr.best_guess());
+
+    let mut vec = Vec::new();
+    for pr in prs {
+      vec.push(pr_keyed_files(&self.repo, pr));
+    }
+
+    Ok(vec.into_iter().flatten().filter(move |kf| match kf {
+      Ok((_, file)) => self.labels.is_empty() || self.covered_by_labeled(file).unwrap_or(false),
+      Err(_) => true
+    }))
+  }
+
+  #[instrument(skip_all)]
+  pub async fn build_plan(&self) -> Result<Plan> {
+    let file = self.current.file();
+    // The warnings are about the usual range, which an explicit range replaces.
+    let mut warnings = match (&self.since, &self.until) {
+      (None, None) => self.repo.range_warnings(file.prev_tag(), file.stale_months())?,
+      _ => Vec::new()
+    };
+    let mut builder = PlanBuilder::create(&self.repo, file, self.user_prefs.auth()).with_labels(&self.labels);
+    let baselines = if self.since.is_some() { HashMap::new() } else { self.baselines.clone() };
+    let groups = baseline_groups(&baselines);
+    let mut progress = Progress::of(tr!("progress.changes"), groups.len() + 1);
+    let changes = self.changes().await?;
+    progress.inc();
+    let mut offline = changes.offline();
+    warnings.extend(unavailable_warning(&changes));
+    let main = file.projects().iter().map(|p| p.id()).filter(|id| !baselines.contains_key(*id)).cloned().collect();
+    builder.plan_range(changes.groups().values(), main, true)?;
+
+    // Projects with their own `prev_tag` are planned from the changes since then, unless there's an explicit range.
+    for (base, ids) in groups {
+      let changes = self.changes_from(&base).await?;
+      progress.inc();
+      offline = offline || changes.offline();
+      if warnings.iter().all(|w| !matches!(w, RangeWarning::Unavailable { .. })) {
+        warnings.extend(unavailable_warning(&changes));
+      }
+      builder.plan_range(changes.groups().values(), ids, false)?;
+    }
+    drop(progress);
+
+    let mut plan = builder.finish()?;
+    if offline {
+      plan.mark_offline();
+    }
+    plan.warnings = warnings;
+
+    let github = self.repo.github_info(self.user_prefs.auth()).ok();
+    for (id, (_, changelog)) in plan.incrs.iter_mut() {
+      changelog.compare = self.compare_base(github.as_ref(), id);
+    }
+    Ok(plan)
+  }
+
+  /// The start of the forge's URL that compares a new release of the project with its current version.
+  fn compare_base(&self, github: Option<&GithubInfo>, id: &ProjectId) -> Option<String> {
+    let tag = self.current.get_project(id)?.full_version(&self.current.get_value(id).ok().flatten()?)?;
+    github?.compare_base(&tag)
+  }
+
+  /// Build the plan, and cache it on disk so that a later `cached_plan` at the same state can skip the work.
+  pub async fn build_and_cache_plan(&self) -> Result<Plan> {
+    let plan = self.build_plan().await?;
+    if !self.labels.is_empty() || self.since.is_some() || self.until.is_some() {
+      // A plan of only some projects, or of some other range, can't stand in for the whole plan.
+      return Ok(plan);
+    }
+    if plan.warnings().iter().any(|w| matches!(w, RangeWarning::Unavailable { .. })) {
+      // A plan that fell back from the forge's API mustn't be released from.
+      return Ok(plan);
+    }
+    if let Err(e) = self.write_plan_cache(&plan) {
+      warn!("Unable to cache plan: {:#}", e);
+    }
+    Ok(plan)
+  }
+
+  /// Use the plan cached by `build_and_cache_plan`, if it was built from the same HEAD, previous tag,
+  /// configuration, and offline mode as now. Otherwise, build the plan from scratch.
+  pub async fn cached_plan(&self) -> Result<Plan> {
+    match self.read_plan_cache() {
+      Ok(Some(plan)) => {
+        info!("Using cached plan.");
+        return Ok(plan);
+      }
+      Ok(None) => trace!("No matching cached plan."),
+      Err(e) => warn!("Unable to read cached plan: {:#}", e)
+    }
+    self.build_plan().await
+  }
+
+  fn read_plan_cache(&self) -> Result<Option<Plan>> {
+    let (path, key) = match (self.plan_cache_path(), self.plan_key()?) {
+      (Some(path), Some(key)) if path.exists() => (path, key),
+      _ => return Ok(None)
+    };
+    let cached: CachedPlan = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+    Ok(if cached.key == key { Some(cached.plan) } else { None })
+  }
+
+  fn write_plan_cache(&self, plan: &Plan) -> Result<()> {
+    if let (Some(path), Some(key)) = (self.plan_cache_path(), self.plan_key()?) {
+      if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+      }
+      std::fs::write(path, serde_json::to_string(&CachedPlanRef { key, plan })?)?;
+    }
+    Ok(())
+  }
+
+  /// Where the plan is cached. A plan cached where an older versio put it is deleted, since it's never used.
+  fn plan_cache_path(&self) -> Option<PathBuf> {
+    let dir = self.repo.git_dir().ok()?;
+    let old = dir.join(OLD_PLAN_CACHE_FILE);
+    if old.exists() {
+      if let Err(e) = std::fs::remove_file(&old) {
+        warn!("Unable to remove old plan cache {}: {:#}", old.display(), e);
+      }
+    }
+    Some(dir.join(GIT_VERSIO_DIR).join(PLAN_CACHE_FILE))
+  }
+
+  fn plan_key(&self) -> Result<Option<PlanKey>> {
+    let head = match self.repo.peek_commit_oid("HEAD") {
+      Some(head) => head,
+      None => return Ok(None)
+    };
+    let prev_tag = self.repo.peek_commit_oid(self.current.prev_tag());
+    let config = match config_key(&self.current, &self.repo)? {
+      Some(config) => config,
+      None => return Ok(None)
+    };
+    Ok(Some(PlanKey { head, prev_tag, config, offline: self.offline }))
+  }
+
+  /// Plan a manual `size` increment of a single project, cascaded to its dependents and umbrellas.
+  pub fn bump_plan(&self, id: &ProjectId, size: Size) -> Result<Plan> {
+    self.get_project(id)?;
+    PlanBuilder::fixed(self.current.file()).build_bump(id, size)
+  }
+
+  /// Build a plan from hypothetical PRs, using the current configuration to decide which projects they cover.
+  pub fn simulate_plan(&self, prs: &[FullPr]) -> Result<Plan> {
+    let plan = PlanBuilder::fixed(self.current.file());
+    plan.build_from(prs.iter())
+  }
+
+  #[instrument(skip_all)]
+  pub async fn changes(&self) -> Result<Changes> {
+    self.changes_from(self.since.as_deref().unwrap_or(self.current.prev_tag())).await
+  }
+
+  /// The changes since `base`, which doesn't have to exist, up to HEAD (or `until`, if it's set).
+  async fn changes_from(&self, base: &str) -> Result<Changes> {
+    let head = self.until.clone().unwrap_or_else(|| "HEAD".into());
+    let changes = self.changes_between(FromTagBuf::new(base.to_string(), true), head).await?;
+
+    if self.labels.is_empty() {
+      return Ok(changes);
+    }
+    changes.retain_prs(|pr| {
+      for commit in pr.included_commits() {
+        for file in commit.files() {
+          if self.covered_by_labeled(file)? {
+            return Ok(true);
+          }
+        }
+      }
+      Ok(false)
+    })
+  }
+
+  /// The changes from `base` up to `head`: the forge's PRs; or else just the commits, if versio is offline or (with
+  /// `set_fallback`) the forge's API fails, in which case the changes record why.
+  async fn changes_between(&self, base: FromTagBuf, head: String) -> Result<Changes> {
+    if self.offline {
+      return local_changes(&self.repo, base, head);
+    }
+    match changes(&self.user_prefs.auth, &self.repo, base.clone(), head.clone()).await {
+      Err(e) if self.fallback && e.downcast_ref::<ApiError>().is_some() => {
+        Ok(local_changes(&self.repo, base, head)?.with_unavailable(format!("{:#}", e)))
+      }
+      other => other
     }
   }
+}
 
-  pub fn write_changelogs(&mut self) -> Result<()> { self.next.write_changelogs() }
+/// A warning that the changes were planned without PRs because the forge's API failed, if it did.
+fn unavailable_warning(changes: &Changes) -> Option<RangeWarning> {
+  changes.unavailable().map(|reason| RangeWarning::Unavailable { reason: reason.to_string() })
+}
 
-  pub fn commit(&mut self, advance_prev: bool, pause: bool) -> Result<()> {
-    self.next.commit(
-      &self.repo,
-      CommitArgs::new(
-        self.current.prev_tag(),
-        &self.last_commits,
-        self.current.old_tags().current(),
-        advance_prev,
-        &self.current.hooks(),
-        pause
-      )
-    )
+/// Read the user preferences file, with some values override with environment variables.
+fn read_env_prefs() -> Result<UserPrefs> {
+  let mut prefs = read_user_prefs()?;
+  if let Ok(token) = std::env::var("GITHUB_TOKEN") {
+    prefs.auth_mut().set_github_token(Some(token))
+  }
+  if let Ok(token) = std::env::var("GITEA_TOKEN") {
+    prefs.auth_mut().set_gitea_token(Some(token))
+  }
+  if let Ok(user) = std::env::var("BITBUCKET_USER") {
+    prefs.auth_mut().set_bitbucket_user(Some(user))
+  }
+  if let Ok(password) = std::env::var("BITBUCKET_APP_PASSWORD") {
+    prefs.auth_mut().set_bitbucket_app_password(Some(password))
   }
 
-  pub fn get_project(&self, id: &ProjectId) -> Result<&Project> {
-    self.current.get_project(id).ok_or_else(|| bad!("No such project {}", id))
+  if let (Ok(app_id), Ok(key)) = (std::env::var("GITHUB_APP_ID"), std::env::var("GITHUB_APP_PRIVATE_KEY")) {
+    let app_id = app_id.trim().parse().context("Bad GITHUB_APP_ID.")?;
+    let installation_id = std::env::var("GITHUB_APP_INSTALLATION_ID").ok();
+    let installation_id =
+      installation_id.map(|id| id.trim().parse()).transpose().context("Bad GITHUB_APP_INSTALLATION_ID.")?;
+    prefs.auth_mut().set_github_app(Some(GithubApp::new(app_id, key, installation_id)));
   }
+  Ok(prefs)
+}
 
-  pub fn write_chains(&mut self, ids: &[(ProjectId, ProjectId)], vers: &HashMap<ProjectId, String>) -> Result<()> {
-    for (id, dpid) in ids {
-      let dproj =
-        self.current.get_project(dpid).ok_or_else(|| bad!("No such dependent {} project for {}.", dpid, id))?;
-      let deps = dproj.depends().get(id).ok_or_else(|| bad!("No such depends {} in project {}.", id, dpid))?;
-      let val = vers.get(id).ok_or_else(|| bad!("No new value for {}.", id))?;
-      deps.write_values(&mut self.next, dproj.root(), val, dpid)?;
+fn read_user_prefs() -> Result<UserPrefs> { read_prefs_file(user_prefs_file()) }
+
+fn read_repo_prefs(root: &Path) -> Result<RepoPrefs> {
+  read_prefs_file(Some(root.join(USER_PREFS_DIR).join(USER_PREFS_FILE)))
+}
+
+/// The user preferences file: the one named by `VERSIO_PREFS`; or else `versio/prefs.toml` in the XDG config
+/// directory (`$XDG_CONFIG_HOME`, or `~/.config`), if it's there; or else `~/.versio/prefs.toml`.
+fn user_prefs_file() -> Option<PathBuf> {
+  if let Some(file) = std::env::var_os(PREFS_ENV).filter(|f| !f.is_empty()) {
+    return Some(PathBuf::from(file));
+  }
+  let home = dirs::home_dir();
+  let config = std::env::var_os("XDG_CONFIG_HOME").filter(|d| !d.is_empty()).map(PathBuf::from);
+  match config.or_else(|| home.as_ref().map(|h| h.join(".config"))) {
+    Some(config) if config.join(XDG_PREFS_DIR).join(USER_PREFS_FILE).exists() => {
+      Some(config.join(XDG_PREFS_DIR).join(USER_PREFS_FILE))
     }
-    Ok(())
+    _ => home.map(|h| h.join(USER_PREFS_DIR).join(USER_PREFS_FILE))
   }
+}
 
-  pub fn diff(&self) -> Result<Analysis> {
-    let prev_config = self.current.slice_to_prev(&self.repo)?;
+/// Read the default command options: the user's own preferences take precedence over the repo's.
+pub fn read_defaults(root: &Path) -> Result<Defaults> {
+  let repo_prefs = read_repo_prefs(root)?;
+  let defaults = read_user_prefs()?.defaults.overlay(repo_prefs.defaults);
+  defaults.vcs_range()?;
+  Ok(defaults)
+}
 
-    let curt_annotate = self.current.annotate()?;
-    let prev_annotate = prev_config.annotate()?;
+fn read_prefs_file<T: DeserializeOwned + Default>(file: Option<PathBuf>) -> Result<T> {
+  let file = match file {
+    Some(f) if f.exists() => f,
+    _ => return Ok(Default::default())
+  };
 
-    Ok(analyze(prev_annotate, curt_annotate))
-  }
+  toml::from_str(&std::fs::read_to_string(&file)?)
+    .with_context(|| format!("Unable to read preferences {}.", file.display()))
+}
 
-  pub fn config(&self) -> &Config<CurrentState> { &self.current }
-  pub fn repo(&self) -> &Repo { &self.repo }
+#[derive(Deserialize, Debug, Default)]
+struct UserPrefs {
+  #[serde(default)]
+  auth: Auth,
+  #[serde(default)]
+  sign: SigningConfig,
+  #[serde(default)]
+  defaults: Defaults,
+  #[serde(default)]
+  metrics: MetricsConfig,
+  #[serde(default)]
+  proxy: ProxyPrefs
+}
 
-  pub fn set_by_id(&mut self, id: &ProjectId, val: &str) -> Result<()> {
-    self.do_project_write(id, move |p, n| p.set_value(n, val))
-  }
+impl UserPrefs {
+  fn auth(&self) -> &Auth { &self.auth }
+  fn sign(&self) -> &SigningConfig { &self.sign }
+  fn metrics(&self) -> &MetricsConfig { &self.metrics }
+  fn auth_mut(&mut self) -> &mut Auth { &mut self.auth }
+}
 
-  pub fn set_by_name(&mut self, name: &str, val: &str) -> Result<()> {
-    let id = self.current.find_unique(name)?.clone();
-    self.set_by_id(&id, val)
-  }
+/// The preferences that can be shared in a repo's own `.versio/prefs.toml`. This deliberately can't hold
+/// anything secret, like auth tokens.
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+struct RepoPrefs {
+  #[serde(default)]
+  defaults: Defaults,
+  #[serde(default)]
+  proxy: ProxyPrefs
+}
 
-  pub fn set_by_exact_name(&mut self, name: &str, val: &str) -> Result<()> {
-    let id = self.current.find_exact(name)?.clone();
-    self.set_by_id(&id, val)
-  }
+/// The proxies for the GitHub API and for git, from the `[proxy]` table of the preferences. The usual `HTTPS_PROXY`,
+/// `HTTP_PROXY`, and `NO_PROXY` environment variables take precedence.
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+struct ProxyPrefs {
+  http: Option<String>,
+  https: Option<String>,
+  no_proxy: Option<Vec<String>>
+}
 
-  pub fn set_by_only(&mut self, val: &str) -> Result<()> {
-    if self.current.projects().len() != 1 {
-      bail!("No solo project.");
+impl ProxyPrefs {
+  fn overlay(self, base: ProxyPrefs) -> ProxyPrefs {
+    ProxyPrefs {
+      http: self.http.or(base.http),
+      https: self.https.or(base.https),
+      no_proxy: self.no_proxy.or(base.no_proxy)
     }
-    let id = self.current.projects().get(0).unwrap().id().clone();
-    self.set_by_id(&id, val)
   }
 
-  pub fn forward_by_id(&mut self, id: &ProjectId, val: &str) -> Result<()> {
-    self.do_project_write(id, move |p, n| p.forward_tag(n, val))
+  /// The environment variables to set for the proxies, which is where both the GitHub API client and git look for
+  /// them.
+  fn vars(&self) -> Vec<(&'static str, String)> {
+    let vars = [
+      ("HTTPS_PROXY", self.https.clone()),
+      ("HTTP_PROXY", self.http.clone()),
+      ("NO_PROXY", self.no_proxy.as_ref().map(|hosts| hosts.join(",")))
+    ];
+    vars.into_iter().filter_map(|(name, value)| value.map(|v| (name, v))).collect()
   }
 
-  pub async fn write_changelog(
-    &mut self, id: &ProjectId, changelog: &Changelog, new_vers: &str
-  ) -> Result<Option<PathBuf>> {
-    let proj = self.current.get_project(id).ok_or_else(|| bad!("No such project {}", id))?;
-    proj.write_changelog(&mut self.next, changelog, new_vers).await
+  /// Set the environment variables of the proxies, except those that are already set.
+  fn export(&self) {
+    for (name, value) in self.vars() {
+      if std::env::var_os(name).is_none() && std::env::var_os(name.to_lowercase()).is_none() {
+        std::env::set_var(name, value);
+      }
+    }
   }
+}
 
-  fn do_project_write<F, T>(&mut self, id: &ProjectId, f: F) -> Result<T>
-  where
-    F: FnOnce(&Project, &mut StateWrite) -> Result<T>
-  {
-    let proj = self.current.get_project(id).ok_or_else(|| bad!("No such project {}", id))?;
-    f(proj, &mut self.next)
-  }
+/// Default values for some command-line options, used when the option isn't given.
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Defaults {
+  vcs_level: Option<String>,
+  offline: Option<bool>,
+  wide: Option<bool>,
+  locale: Option<String>
+}
 
-  pub fn check(&self) -> Result<()> {
-    for project in self.current.projects() {
-      project.check(self.current.state_read())?;
-    }
-    Ok(())
+impl Defaults {
+  pub fn vcs_range(&self) -> Result<Option<VcsRange>> {
+    self.vcs_level.as_deref().map(|level| level.parse().map(VcsRange::exact)).transpose()
   }
 
-  pub async fn keyed_files(&self) -> Result<impl Iterator<Item = Result<(String, String)>> + '_> {
-    let changes = self.changes().await?;
-    let prs = changes.into_groups().into_values().filter(|pr| !pr.best_guess());
+  pub fn offline(&self) -> bool { self.offline.unwrap_or(false) }
+  pub fn wide(&self) -> bool { self.wide.unwrap_or(false) }
+  pub fn locale(&self) -> Option<&str> { self.locale.as_deref() }
 
-    let mut vec = Vec::new();
-    for pr in prs {
-      vec.push(pr_keyed_files(&self.repo, pr));
+  fn overlay(self, base: Defaults) -> Defaults {
+    Defaults {
+      vcs_level: self.vcs_level.or(base.vcs_level),
+      offline: self.offline.or(base.offline),
+      wide: self.wide.or(base.wide),
+      locale: self.locale.or(base.locale)
     }
-
-    Ok(vec.into_iter().flatten())
   }
+}
 
-  pub async fn build_plan(&self) -> Result<Plan> {
-    let mut plan = PlanBuilder::create(&self.repo, self.current.file(), self.user_prefs.auth());
+/// The projects' own `prev_tag`s that are still in effect. Once the repo-wide `prev_tag` contains a project's own
+/// `prev_tag` (that is, once there's been a release since), the project's changes are counted from the repo-wide
+/// `prev_tag` again.
+fn find_baselines(file: &ConfigFile, repo: &Repo) -> Result<HashMap<ProjectId, String>> {
+  let mut baselines = HashMap::new();
+  if repo.peek_commit_oid("HEAD").is_none() {
+    return Ok(baselines);
+  }
 
-    // Consider the grouped, unsquashed commits to determine project sizing and changelogs.
-    for pr in self.changes().await?.groups().values() {
-      plan.start_pr(pr)?;
-      for commit in pr.included_commits() {
-        plan.start_commit(commit.clone())?;
-        for file in commit.files() {
-          plan.start_file(file)?;
-          plan.finish_file()?;
-        }
-        plan.finish_commit()?;
+  let released = repo.peek_commit_oid(file.prev_tag()).is_some();
+  for proj in file.projects() {
+    if let Some(base) = proj.prev_tag() {
+      if repo.peek_commit_oid(base).is_none() {
+        bail!("Can't find prev_tag \"{}\" of project {}.", base, proj.id());
+      }
+      if !released || !repo.is_ancestor(base, file.prev_tag())? {
+        trace!("Project {} counts its changes from {}.", proj.id(), base);
+        baselines.insert(proj.id().clone(), base.to_string());
       }
-      plan.finish_pr()?;
     }
-
-    // Some projects might depend on other projects.
-    plan.handle_deps()?;
-
-    // Sort projects by earliest closed date, mark duplicate commits.
-    plan.sort_and_dedup()?;
-
-    Ok(plan.build())
   }
+  Ok(baselines)
+}
 
-  pub async fn changes(&self) -> Result<Changes> {
-    let base = FromTagBuf::new(self.current.prev_tag().to_string(), true);
-    changes(&self.user_prefs.auth, &self.repo, base, "HEAD".into()).await
+/// The projects that have their own `prev_tag`, grouped by it.
+fn baseline_groups(baselines: &HashMap<ProjectId, String>) -> Vec<(String, HashSet<ProjectId>)> {
+  let mut groups: Vec<(String, HashSet<ProjectId>)> = Vec::new();
+  for (id, base) in baselines {
+    match groups.iter_mut().find(|(b, _)| b == base) {
+      Some((_, ids)) => {
+        ids.insert(id.clone());
+      }
+      None => groups.push((base.clone(), once(id.clone()).collect()))
+    }
   }
+  groups.sort_by(|(b1, _), (b2, _)| b1.cmp(b2));
+  groups
 }
 
-/// Read the user preferences file, with some values override with environment variables.
-fn read_env_prefs() -> Result<UserPrefs> {
-  read_user_prefs().map(|mut prefs| {
-    if let Ok(token) = std::env::var("GITHUB_TOKEN") {
-      prefs.auth_mut().set_github_token(Some(token))
+/// A hash of the config, with its included files and the environment variables that it uses, which are part of the
+/// config too; or `None` if any of its files can't be read.
+fn config_key(current: &Config<CurrentState>, repo: &Repo) -> Result<Option<String>> {
+  let root = repo.working_dir()?;
+  let mut data = Vec::new();
+  for path in once(CONFIG_FILENAME).chain(current.file().included().iter().map(|p| p.as_str())) {
+    match std::fs::read(root.join(path)) {
+      Ok(file_data) => data.extend(file_data),
+      Err(_) => return Ok(None)
     }
-    prefs
-  })
+  }
+  for var in current.file().env() {
+    data.extend(var.as_bytes());
+  }
+  Ok(Some(git2::Oid::hash_object(git2::ObjectType::Blob, &data)?.to_string()))
 }
 
-fn read_user_prefs() -> Result<UserPrefs> {
-  let homefile = dirs::home_dir().map(|h| h.join(USER_PREFS_DIR).join(USER_PREFS_FILE));
-  let homefile = match homefile {
-    Some(f) => f,
-    None => return Ok(Default::default())
-  };
-  if !homefile.exists() {
-    return Ok(Default::default());
+/// Find the last covering commit ID, if any, for each current project.
+#[instrument(skip_all)]
+fn find_last_commits(
+  current: &Config<CurrentState>, baselines: &HashMap<ProjectId, String>, repo: &Repo
+) -> Result<HashMap<ProjectId, String>> {
+  let mut cache = LastCommitsCache::read(current, repo);
+  let mut result = cache.last_commits_from(current, current.prev_tag(), repo)?;
+  result.retain(|id, _| !baselines.contains_key(id));
+  for (base, ids) in baseline_groups(baselines) {
+    result.extend(cache.last_commits_from(current, &base, repo)?.into_iter().filter(|(id, _)| ids.contains(id)));
+  }
+  if let Err(e) = cache.write() {
+    warn!("Unable to cache last commits: {:#}", e);
   }
 
-  let user_prefs: UserPrefs = toml::from_str(&std::fs::read_to_string(homefile)?)?;
-  Ok(user_prefs)
+  trace!("Found last commits: {:?}", result);
+  Ok(result)
 }
 
-#[derive(Deserialize, Debug, Default)]
-struct UserPrefs {
-  auth: Auth
+/// The results of `find_last_commits`, saved in the repo's `.git/versio` directory, so that a later run only has to
+/// walk the commits made since then.
+struct LastCommitsCache {
+  path: Option<PathBuf>,
+  config: Option<String>,
+  entries: Vec<CachedLastCommits>
 }
 
-impl UserPrefs {
-  fn auth(&self) -> &Auth { &self.auth }
-  fn auth_mut(&mut self) -> &mut Auth { &mut self.auth }
+/// The last commit of each project since the `base` commit (if any), as of the `head` commit. The config matters,
+/// since it decides which files each project covers.
+#[derive(Serialize, Deserialize)]
+struct CachedLastCommits {
+  base: Option<String>,
+  head: String,
+  config: String,
+  last_commits: HashMap<ProjectId, String>
 }
 
-/// Find the last covering commit ID, if any, for each current project.
-fn find_last_commits(current: &Config<CurrentState>, repo: &Repo) -> Result<HashMap<ProjectId, String>> {
-  let prev_spec = current.prev_tag();
+impl LastCommitsCache {
+  fn read(current: &Config<CurrentState>, repo: &Repo) -> LastCommitsCache {
+    let path = repo.git_dir().ok().map(|dir| dir.join(GIT_VERSIO_DIR).join(LAST_COMMITS_CACHE_FILE));
+    let config = config_key(current, repo).ok().flatten();
+    let entries = match path.as_deref().filter(|p| p.exists()).map(read_cached_last_commits).transpose() {
+      Ok(entries) => entries.unwrap_or_default(),
+      Err(e) => {
+        warn!("Unable to read cached last commits: {:#}", e);
+        Vec::new()
+      }
+    };
+    LastCommitsCache { path, config, entries }
+  }
+
+  /// The last commits since `base`, walking only the commits since a cached result if there is one for the same
+  /// base and config, whose head is an ancestor of HEAD.
+  fn last_commits_from(
+    &mut self, current: &Config<CurrentState>, base: &str, repo: &Repo
+  ) -> Result<HashMap<ProjectId, String>> {
+    let (head, config) = match (repo.peek_commit_oid("HEAD"), &self.config) {
+      (Some(head), Some(config)) => (head, config.clone()),
+      _ => return last_commits_from(current, FromTag::new(base, true), repo)
+    };
+    let base_oid = repo.peek_commit_oid(base);
+
+    let cached = self.entries.iter().find(|e| e.base == base_oid && e.config == config);
+    let last_commits = match cached {
+      Some(cached) if cached.head == head => cached.last_commits.clone(),
+      Some(cached) if repo.is_ancestor(&cached.head, &head).unwrap_or(false) => {
+        trace!("Finding last commits since cached head {}.", cached.head);
+        let mut newer = last_commits_from(current, FromTag::new(&cached.head, false), repo)?;
+        for (id, commit) in &cached.last_commits {
+          newer.entry(id.clone()).or_insert_with(|| commit.clone());
+        }
+        newer
+      }
+      _ => last_commits_from(current, FromTag::new(base, true), repo)?
+    };
+
+    self.entries.retain(|e| e.base != base_oid);
+    self.entries.push(CachedLastCommits { base: base_oid, head, config, last_commits: last_commits.clone() });
+    Ok(last_commits)
+  }
+
+  fn write(&self) -> Result<()> {
+    if let Some(path) = self.path.as_ref().filter(|_| !self.entries.is_empty()) {
+      if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+      }
+      std::fs::write(path, serde_json::to_string(&self.entries)?)?;
+    }
+    Ok(())
+  }
+}
+
+fn read_cached_last_commits(path: &Path) -> Result<Vec<CachedLastCommits>> {
+  Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+}
+
+fn last_commits_from(current: &Config<CurrentState>, base: FromTag, repo: &Repo) -> Result<HashMap<ProjectId, String>> {
   let mut last_commits = LastCommitBuilder::create(repo, current);
+  let mut progress = Progress::new(tr!("progress.commits"));
 
   // Consider the in-line commits to determine the last commit (if any) for each project.
-  for commit in line_commits_head(repo, FromTag::new(prev_spec, true))? {
+  for commit in line_commits_head(repo, base)? {
+    progress.inc();
     last_commits.start_line_commit(&commit)?;
     for file in commit.files() {
       last_commits.start_line_file(file)?;
@@ -257,9 +1822,12 @@ fn find_last_commits(current: &Config<CurrentState>, repo: &Repo) -> Result<Hash
     last_commits.finish_line_commit()?;
   }
 
-  let result = last_commits.build();
-  trace!("Found last commits: {:?}", result);
-  result
+  last_commits.build()
+}
+
+/// Whether the project has any of the labels; or true if there are no labels.
+fn has_label(project: &Project, labels: &[String]) -> bool {
+  labels.is_empty() || project.labels().iter().any(|l| labels.contains(l))
 }
 
 fn pr_keyed_files(repo: &Repo, pr: FullPr) -> impl Iterator<Item = Result<(String, String)>> + '_ {
@@ -297,6 +1865,7 @@ fn pr_keyed_files(repo: &Repo, pr: FullPr) -> impl Iterator<Item = Result<(Strin
 
 /// Additional information about a plan that doesn't contribute to plan's execution, but may still be of
 /// interested to the user.
+#[derive(Serialize, Deserialize)]
 pub struct PlanInfo {
   failed_commits: BTreeSet<CommitInfoBuf>
 }
@@ -311,59 +1880,529 @@ impl PlanInfo {
   pub fn add_failed_commit(&mut self, failure: CommitInfoBuf) { self.failed_commits.insert(failure); }
 }
 
+/// What a release would do to the version of a single project of the plan.
+pub struct NextVersion<'p> {
+  pub id: ProjectId,
+  pub name: String,
+  pub size: Size,
+  /// The size that's applied to the project's version, which is smaller than `size` if its `zero_major_policy`
+  /// downgrades it.
+  pub applied: Size,
+  pub changelog: Cow<'p, Changelog>,
+  pub version: String,
+  pub change: VersionChange,
+  /// Whether the project is released at the version pinned by `versio pin`, instead of by its size.
+  pub pin: bool,
+  /// Why the version breaks the project's `constraints`, if it was forced anyway.
+  pub forced: Option<String>
+}
+
+impl NextVersion<'_> {
+  /// Release the project at the increment of `size` from its previous version, instead of at the planned version.
+  pub fn resize(&mut self, proj: &Project, size: Size, force: bool) -> Result<()> {
+    let prev = self.prev().ok_or_else(|| bad!("{}", tr!("review.no_previous", name = self.name)))?;
+    let applied = proj.applied_size(size, prev)?;
+    let target = proj.bump(applied, prev)?;
+    self.retarget(proj, target, force)?;
+    self.size = size;
+    self.applied = applied;
+    Ok(())
+  }
+
+  /// Release the project at `target` instead of at the planned version. If the current version is already past the
+  /// target, the current version is released instead, as with a planned version.
+  pub fn retarget(&mut self, proj: &Project, target: String, force: bool) -> Result<()> {
+    let (prev, curt) = match &self.change {
+      VersionChange::Changed { prev, curt, .. } | VersionChange::Forward { prev, curt, .. } => (prev, curt),
+      _ => bail!("{}", tr!("review.no_previous", name = self.name))
+    };
+    if !Size::less_than(prev, &target)? {
+      bail!("{}", tr!("review.not_after", version = target, previous = prev));
+    }
+
+    let (prev, curt) = (prev.clone(), curt.clone());
+    if Size::less_than(&curt, &target)? {
+      self.forced = verify_next(proj, &target, force)?;
+      self.change = VersionChange::Changed { prev, curt, target: target.clone() };
+    } else {
+      self.forced = verify_next(proj, &curt, force)?;
+      self.change = VersionChange::Forward { prev, curt, target: target.clone() };
+    }
+    self.version = target;
+    self.pin = false;
+    Ok(())
+  }
+
+  /// The previous version of the project, if it has one and is released.
+  pub fn prev(&self) -> Option<&str> {
+    match &self.change {
+      VersionChange::Changed { prev, .. } | VersionChange::Forward { prev, .. } => Some(prev),
+      _ => None
+    }
+  }
+}
+
+/// What a release does to the version of a project.
+pub enum VersionChange {
+  /// The project keeps its current version, because it has no changes or its tags are locked.
+  NoChange { prev: Option<String>, curt: String, locked: bool },
+  /// The project was already released on its own (by `release --id`, etc.) since the previous release.
+  Released { prev: String, curt: String },
+  /// The project's version is raised to the target.
+  Changed { prev: String, curt: String, target: String },
+  /// The project's current version already exceeds the target, so the current version is released instead.
+  Forward { prev: String, curt: String, target: String },
+  /// The project is new, and is released at its current version.
+  New { curt: String }
+}
+
+/// A version of a project that was tagged before versio was adopted.
+pub struct PastRelease {
+  version: String,
+  tag: String,
+  date: String
+}
+
+impl PastRelease {
+  pub fn version(&self) -> &str { &self.version }
+  pub fn tag(&self) -> &str { &self.tag }
+}
+
+/// The projects that cover a file, for `owners`.
+#[derive(Serialize)]
+pub struct FileOwners {
+  path: String,
+  owners: Vec<String>
+}
+
+impl FileOwners {
+  pub fn path(&self) -> &str { &self.path }
+  pub fn owners(&self) -> &[String] { &self.owners }
+}
+
+/// What `audit` found in the tag history of a project.
+#[derive(Serialize)]
+pub struct AuditReport {
+  id: String,
+  name: String,
+  versions: usize,
+  problems: Vec<String>
+}
+
+impl AuditReport {
+  pub fn name(&self) -> &str { &self.name }
+  pub fn versions(&self) -> usize { self.versions }
+  pub fn problems(&self) -> &[String] { &self.problems }
+}
+
+/// How far `adopt` has gotten: the latest past release of each project whose changelog entry has been rendered, and
+/// each changelog so far.
+#[derive(Serialize, Deserialize, Default)]
+pub struct AdoptProgress {
+  rendered: HashMap<ProjectId, String>,
+  logs: HashMap<ProjectId, (PathBuf, String)>
+}
+
+impl AdoptProgress {
+  pub fn new() -> AdoptProgress { AdoptProgress::default() }
+  pub fn rendered(&self, id: &ProjectId) -> Option<&str> { self.rendered.get(id).map(|v| v.as_str()) }
+  pub fn log(&self, id: &ProjectId) -> Option<&str> { self.logs.get(id).map(|(_, content)| content.as_str()) }
+
+  pub fn record(&mut self, id: &ProjectId, version: &str, log: Option<(PathBuf, String)>) {
+    self.rendered.insert(id.clone(), version.to_string());
+    if let Some(log) = log {
+      self.logs.insert(id.clone(), log);
+    }
+  }
+}
+
+pub struct Rollback {
+  prev_tag: String,
+  release_commit: Option<String>,
+  tags: Vec<String>,
+  prev_commit: String,
+  prev_versions: HashMap<ProjectId, String>
+}
+
+impl Rollback {
+  pub fn prev_tag(&self) -> &str { &self.prev_tag }
+  pub fn release_commit(&self) -> Option<&str> { self.release_commit.as_deref() }
+  pub fn tags(&self) -> &[String] { &self.tags }
+  pub fn prev_commit(&self) -> &str { &self.prev_commit }
+}
+
+/// The state that a plan was built from: if any of it changes, a cached plan can't be used.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+struct PlanKey {
+  head: String,
+  prev_tag: Option<String>,
+  config: String,
+  offline: bool
+}
+
+#[derive(Deserialize)]
+struct CachedPlan {
+  key: PlanKey,
+  plan: Plan
+}
+
+#[derive(Serialize)]
+struct CachedPlanRef<'a> {
+  key: PlanKey,
+  plan: &'a Plan
+}
+
+/// A record of a single release, written to the `releases_dir`.
+#[derive(Serialize)]
+struct ReleaseManifest {
+  date: String,
+  commit: Option<String>,
+  projects: Vec<ManifestProject>
+}
+
+#[derive(Serialize)]
+struct ManifestProject {
+  id: String,
+  name: String,
+  previous: Option<String>,
+  version: String,
+  tag: Option<String>,
+  changelog: Option<String>
+}
+
+impl ReleaseManifest {
+  /// The manifest as a CycloneDX BOM, where each released project is a component, and the rest of what's known
+  /// about it is in its `versio:` properties.
+  fn cyclonedx(&self) -> serde_json::Value {
+    let components: Vec<_> = self
+      .projects
+      .iter()
+      .map(|p| {
+        let known = [
+          ("versio:id", Some(&p.id)),
+          ("versio:previous", p.previous.as_ref()),
+          ("versio:tag", p.tag.as_ref()),
+          ("versio:changelog", p.changelog.as_ref())
+        ];
+        let properties: Vec<_> =
+          known.iter().filter_map(|(name, value)| value.map(|v| json!({ "name": name, "value": v }))).collect();
+        json!({
+          "type": "application",
+          "bom-ref": format!("versio:{}", p.id),
+          "name": p.name,
+          "version": p.version,
+          "properties": properties
+        })
+      })
+      .collect();
+
+    let mut metadata = json!({
+      "timestamp": self.date,
+      "tools": [{ "name": "versio", "version": env!("CARGO_PKG_VERSION") }]
+    });
+    if let Some(commit) = &self.commit {
+      metadata["properties"] = json!([{ "name": "versio:commit", "value": commit }]);
+    }
+    json!({
+      "bomFormat": "CycloneDX",
+      "specVersion": "1.5",
+      "version": 1,
+      "metadata": metadata,
+      "components": components
+    })
+  }
+}
+
+/// How `release --manifest` writes the manifest of a release.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ManifestFormat {
+  /// The same JSON as the manifests of the `releases_dir`.
+  Json,
+  /// A CycloneDX bill of materials.
+  CycloneDx
+}
+
+/// The outcome of running the `post_release` hooks: the projects that were published, the one that failed (if
+/// any), and the ones that weren't tried after it failed.
+pub struct Published {
+  ran: Vec<String>,
+  failed: Option<(String, Error)>,
+  skipped: Vec<String>
+}
+
+impl Published {
+  fn new() -> Published { Published { ran: Vec::new(), failed: None, skipped: Vec::new() } }
+
+  pub fn ran(&self) -> &[String] { &self.ran }
+  pub fn skipped(&self) -> &[String] { &self.skipped }
+  pub fn into_failed(self) -> Option<(String, Error)> { self.failed }
+}
+
+/// The hooks that a dry run ran or skipped, as (project name, hook) pairs.
+pub struct DryRunHooks {
+  ran: Vec<(String, String)>,
+  skipped: Vec<(String, String)>
+}
+
+impl DryRunHooks {
+  fn new() -> DryRunHooks { DryRunHooks { ran: Vec::new(), skipped: Vec::new() } }
+
+  pub fn ran(&self) -> &[(String, String)] { &self.ran }
+  pub fn skipped(&self) -> &[(String, String)] { &self.skipped }
+
+  fn run(&mut self, hooks: ProjectHooks, which: &str) -> Result<()> {
+    if !hooks.has(which) {
+      return Ok(());
+    }
+    let entry = (hooks.name().to_string(), which.to_string());
+    if hooks.is_safe(which) {
+      hooks.with_env("VERSIO_DRY_RUN", "true").execute(which)?;
+      self.ran.push(entry);
+    } else {
+      self.skipped.push(entry);
+    }
+    Ok(())
+  }
+}
+
+/// The environment of a project's hooks: which project it is, where it is, and its previous and new versions.
+fn hook_env(proj: &Project, branch: &str, previous: Option<&str>, version: &str) -> Vec<(&'static str, String)> {
+  vec![
+    ("VERSIO_BRANCH", branch.to_string()),
+    ("VERSIO_PROJECT_ID", proj.id().to_string()),
+    ("VERSIO_PROJECT_NAME", proj.name().to_string()),
+    ("VERSIO_ROOT", proj.root().map(|r| r.as_str()).unwrap_or(".").to_string()),
+    ("VERSIO_PREV_VERSION", previous.unwrap_or("").to_string()),
+    ("VERSIO_VERSION", version.to_string()),
+    ("VERSIO_TAG", proj.full_version(version).unwrap_or_default())
+  ]
+}
+
+/// A dependent's requirement on a project, rewritten by a release because of the project's new version.
+pub struct ChainWrite {
+  dependency: ProjectId,
+  dependent: ProjectId,
+  before: String,
+  after: String,
+  size: Size
+}
+
+impl ChainWrite {
+  pub fn dependency(&self) -> &ProjectId { &self.dependency }
+  pub fn dependent(&self) -> &ProjectId { &self.dependent }
+  pub fn before(&self) -> &str { &self.before }
+  pub fn after(&self) -> &str { &self.after }
+
+  /// The size of the dependency's release, which is why the requirement changes.
+  pub fn size(&self) -> Size { self.size }
+}
+
+/// A commit that didn't apply to any project, with the files it changed: if it should have, some project's `includes`
+/// or `excludes` are probably wrong.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct UncoveredCommit {
+  pr: u32,
+  oid: String,
+  summary: String,
+  files: Vec<String>
+}
+
+impl UncoveredCommit {
+  fn new(pr: u32, commit: &CommitInfoBuf) -> UncoveredCommit {
+    UncoveredCommit {
+      pr,
+      oid: commit.id().to_string(),
+      summary: commit.summary().to_string(),
+      files: commit.files().to_vec()
+    }
+  }
+
+  /// The number of the commit's PR, or 0 if it wasn't part of a PR.
+  pub fn pr(&self) -> u32 { self.pr }
+  pub fn oid(&self) -> &str { &self.oid }
+  pub fn summary(&self) -> &str { &self.summary }
+  pub fn files(&self) -> &[String] { &self.files }
+}
+
+#[derive(Serialize, Deserialize)]
 pub struct Plan {
   incrs: HashMap<ProjectId, (Size, Changelog)>, // proj ID, incr size, changelog
   ineffective: Vec<LoggedPr>,                   // PRs that didn't apply to any project
+  #[serde(default)]
+  uncovered: Vec<UncoveredCommit>,
   chain_writes: Vec<(ProjectId, ProjectId)>,
-  info: PlanInfo
+  #[serde(default)]
+  dep_order: Vec<ProjectId>,
+  info: PlanInfo,
+  offline: bool,
+  warnings: Vec<RangeWarning>
 }
 
 impl Plan {
   pub fn incrs(&self) -> &HashMap<ProjectId, (Size, Changelog)> { &self.incrs }
   pub fn ineffective(&self) -> &[LoggedPr] { &self.ineffective }
+
+  /// The commits that didn't apply to any project, in the order they were planned.
+  pub fn uncovered(&self) -> &[UncoveredCommit] { &self.uncovered }
+
   pub fn chain_writes(&self) -> &[(ProjectId, ProjectId)] { &self.chain_writes }
+
+  /// The order in which dependencies were resolved: each project comes after all the projects it depends on.
+  pub fn dep_order(&self) -> &[ProjectId] { &self.dep_order }
+
   pub fn info(&self) -> &PlanInfo { &self.info }
+  pub fn offline(&self) -> bool { self.offline }
+  pub fn warnings(&self) -> &[RangeWarning] { &self.warnings }
+
+  /// Record the branch being released from, which is available to changelog templates.
+  pub fn set_branch(&mut self, branch: Option<&str>) {
+    for (_, changelog) in self.incrs.values_mut() {
+      changelog.branch = branch.map(|b| b.to_string());
+    }
+  }
+
+  /// Record that this plan was built from local commits, without PR information.
+  fn mark_offline(&mut self) {
+    self.offline = true;
+    for (_, changelog) in self.incrs.values_mut() {
+      changelog.offline = true;
+    }
+  }
 }
 
+#[derive(Serialize, Deserialize, Clone)]
 pub struct Changelog {
-  entries: Vec<ChangelogEntry>
+  entries: Vec<ChangelogEntry>,
+  offline: bool,
+  #[serde(default)]
+  branch: Option<String>,
+  #[serde(default)]
+  date: Option<String>,
+  #[serde(default)]
+  compare: Option<String>
 }
 
+#[derive(Serialize, Deserialize, Clone)]
 pub enum ChangelogEntry {
   Pr(LoggedPr, Size),
   Dep(ProjectId, String)
 }
 
-impl Changelog {
-  pub fn empty() -> Changelog { Changelog { entries: Vec::new() } }
-  pub fn entries(&self) -> &[ChangelogEntry] { &self.entries }
-  pub fn add_entry(&mut self, pr: LoggedPr, size: Size) { self.entries.push(ChangelogEntry::Pr(pr, size)); }
+impl Changelog {
+  pub fn empty() -> Changelog {
+    Changelog { entries: Vec::new(), offline: false, branch: None, date: None, compare: None }
+  }
+
+  pub fn entries(&self) -> &[ChangelogEntry] { &self.entries }
+  pub fn offline(&self) -> bool { self.offline }
+  pub fn branch(&self) -> Option<&str> { self.branch.as_deref() }
+  pub fn date(&self) -> Option<&str> { self.date.as_deref() }
+
+  /// Where the release can be compared with the project's previous version on GitHub, without the new version's
+  /// tag: like `https://github.com/owner/repo/compare/v1.2.3...`.
+  pub fn compare(&self) -> Option<&str> { self.compare.as_deref() }
+  pub fn add_entry(&mut self, pr: LoggedPr, size: Size) { self.entries.push(ChangelogEntry::Pr(pr, size)); }
+
+  pub fn add_dep(&mut self, id: ProjectId, name: impl ToString) {
+    self.entries.push(ChangelogEntry::Dep(id, name.to_string()));
+  }
+
+  pub fn is_empty(&self) -> bool { self.entries.is_empty() }
+
+  /// The text of each PR entry that has included commits, by its index: the changelog from its description, if it
+  /// has one, or else its title (or the summaries of its commits, if it isn't a PR).
+  pub fn pr_texts(&self) -> Vec<(usize, String)> {
+    let mut texts = Vec::new();
+    for (i, entry) in self.entries.iter().enumerate() {
+      if let ChangelogEntry::Pr(pr, _) = entry {
+        let included: Vec<_> = pr.commits().iter().filter(|c| c.included()).map(|c| c.summary()).collect();
+        if included.is_empty() {
+          continue;
+        }
+        let text = match pr.changelog() {
+          Some(changelog) => changelog.to_string(),
+          None if pr.number() == 0 => included.join("; "),
+          None => pr.title().to_string()
+        };
+        texts.push((i, text));
+      }
+    }
+    texts
+  }
+
+  /// Replace the text of the PR entry at `index`, as if its description had a changelog of that text.
+  pub fn set_pr_text(&mut self, index: usize, text: String) {
+    if let Some(ChangelogEntry::Pr(pr, _)) = self.entries.get_mut(index) {
+      pr.changelog = Some(text);
+    }
+  }
 
-  pub fn add_dep(&mut self, id: ProjectId, name: impl ToString) {
-    self.entries.push(ChangelogEntry::Dep(id, name.to_string()));
+  /// A plain-text summary of the changes, one line each: the title of each PR, or the summary of each commit that
+  /// isn't part of a PR.
+  pub fn summary(&self) -> String {
+    let mut lines = Vec::new();
+    for entry in &self.entries {
+      if let ChangelogEntry::Pr(pr, _) = entry {
+        let included: Vec<_> = pr.commits().iter().filter(|c| c.included()).collect();
+        if included.is_empty() {
+          continue;
+        }
+        if pr.number() == 0 {
+          lines.extend(included.iter().map(|c| format!("- {}", c.summary())));
+        } else {
+          lines.push(format!("- {} (#{})", pr.title(), pr.number()));
+        }
+      }
+    }
+    lines.join("\n")
   }
 
-  pub fn is_empty(&self) -> bool { self.entries.is_empty() }
+  /// Add a member project's PR to an umbrella changelog, combining it with the same PR from other members.
+  fn merge_pr(&mut self, pr: &LoggedPr) {
+    let existing = self.entries.iter_mut().find_map(|entry| match entry {
+      ChangelogEntry::Pr(logged, _) if logged.number == pr.number => Some(logged),
+      _ => None
+    });
+
+    match existing {
+      Some(logged) => {
+        for commit in &pr.commits {
+          match logged.commits.iter_mut().find(|c| c.oid == commit.oid) {
+            Some(c) => c.applies |= commit.applies,
+            None => logged.commits.push(commit.clone())
+          }
+        }
+      }
+      None => self.add_entry(pr.clone(), Size::Empty)
+    }
+  }
 }
 
+#[derive(Serialize, Deserialize, Clone)]
 pub struct LoggedPr {
   number: u32,
   title: String,
   _closed_at: DateTime<FixedOffset>,
   discovery_order: usize,
   commits: Vec<LoggedCommit>,
-  url: Option<String>
+  url: Option<String>,
+  #[serde(default)]
+  changelog: Option<String>,
+  #[serde(default)]
+  issues: Vec<IssueLink>
 }
 
 impl LoggedPr {
-  pub fn capture(pr: &FullPr, url: Option<String>) -> LoggedPr {
+  pub fn capture(pr: &FullPr, url: Option<String>, issues: Vec<IssueLink>) -> LoggedPr {
     LoggedPr {
       number: pr.number(),
       title: pr.title().to_string(),
       _closed_at: *pr.closed_at(),
       discovery_order: pr.discovery_order(),
       commits: Vec::new(),
-      url
+      url,
+      changelog: pr.changelog().map(|c| c.to_string()),
+      issues
     }
   }
 
@@ -373,31 +2412,74 @@ impl LoggedPr {
   pub fn discovery_order(&self) -> usize { self.discovery_order }
   pub fn commits(&self) -> &[LoggedCommit] { &self.commits }
   pub fn url(&self) -> &Option<String> { &self.url }
+
+  /// The changelog entry from the PR description, which replaces the PR's commits in the changelog.
+  pub fn changelog(&self) -> Option<&str> { self.changelog.as_deref() }
+
+  /// The issues that the PR description refers to.
+  pub fn issues(&self) -> &[IssueLink] { &self.issues }
 }
 
+#[derive(Serialize, Deserialize, Clone)]
 pub struct LoggedCommit {
   oid: String,
   summary: String,
   message: String,
+  kind: String,
+  scope: Option<String>,
   size: Size,
   applies: bool,
   duplicate: bool,
-  url: Option<String>
+  url: Option<String>,
+  #[serde(default)]
+  reverted: bool,
+  #[serde(default)]
+  authors: Vec<String>,
+  #[serde(default)]
+  issues: Vec<IssueLink>
 }
 
 impl LoggedCommit {
-  pub fn new(oid: String, summary: String, message: String, size: Size, url: Option<String>) -> LoggedCommit {
-    LoggedCommit { oid, summary, message, size, applies: false, duplicate: false, url }
+  pub fn new(
+    oid: String, summary: String, message: String, kind: String, scope: Option<String>, size: Size, url: Option<String>
+  ) -> LoggedCommit {
+    LoggedCommit {
+      oid,
+      summary,
+      message,
+      kind,
+      scope,
+      size,
+      applies: false,
+      duplicate: false,
+      url,
+      reverted: false,
+      authors: Vec::new(),
+      issues: Vec::new()
+    }
   }
 
   pub fn applies(&self) -> bool { self.applies }
   pub fn duplicate(&self) -> bool { self.duplicate }
   pub fn included(&self) -> bool { self.applies && !self.duplicate }
+
+  /// Whether the commit reverts, or is reverted by, another commit in the same plan; such a pair doesn't count
+  /// toward the project's size.
+  pub fn reverted(&self) -> bool { self.reverted }
   pub fn oid(&self) -> &str { &self.oid }
   pub fn summary(&self) -> &str { &self.summary }
   pub fn message(&self) -> &str { &self.message }
+  pub fn kind(&self) -> &str { &self.kind }
+  pub fn breaking(&self) -> bool { self.kind == BREAKING_KIND }
+  pub fn scope(&self) -> Option<&str> { self.scope.as_deref() }
   pub fn size(&self) -> Size { self.size }
   pub fn url(&self) -> &Option<String> { &self.url }
+
+  /// The commit's author, followed by its co-authors, by name.
+  pub fn authors(&self) -> &[String] { &self.authors }
+
+  /// The issues that the commit message refers to.
+  pub fn issues(&self) -> &[IssueLink] { &self.issues }
 }
 
 struct PlanBuilder<'s> {
@@ -410,14 +2492,21 @@ struct PlanBuilder<'s> {
   // proj ID, incr size, changelog
   ineffective: Vec<LoggedPr>,
   // PRs that didn't apply to any project
+  uncovered: Vec<UncoveredCommit>,
   github_info: Option<GithubInfo>,
   chain_writes: Vec<(ProjectId, ProjectId)>,
-  info: PlanInfo
+  dep_order: Vec<ProjectId>,
+  info: PlanInfo,
+  labels: &'s [String],
+  // the projects planned from the PRs at hand, if not all of them; and whether to report ineffective PRs
+  only: Option<HashSet<ProjectId>>,
+  track_ineffective: bool,
+  issues: IssueFinder
 }
 
 impl<'s> PlanBuilder<'s> {
   fn create(repo: &'s Repo, current: &'s ConfigFile, auth: &Auth) -> PlanBuilder<'s> {
-    let prev = Slicer::init(repo);
+    let prev = Slicer::init(repo, current);
     let github_info = repo.github_info(auth).ok();
     PlanBuilder {
       on_pr_sizes: HashMap::new(),
@@ -427,25 +2516,127 @@ impl<'s> PlanBuilder<'s> {
       current,
       incrs: HashMap::new(),
       ineffective: Vec::new(),
+      uncovered: Vec::new(),
       github_info,
       chain_writes: Vec::new(),
-      info: PlanInfo::new()
+      dep_order: Vec::new(),
+      info: PlanInfo::new(),
+      labels: &[],
+      only: None,
+      track_ineffective: true,
+      issues: IssueFinder::new(current.links())
+    }
+  }
+
+  fn fixed(current: &'s ConfigFile) -> PlanBuilder<'s> {
+    PlanBuilder {
+      on_pr_sizes: HashMap::new(),
+      on_ineffective: None,
+      on_commit: None,
+      prev: Slicer::fixed(current),
+      current,
+      incrs: HashMap::new(),
+      ineffective: Vec::new(),
+      uncovered: Vec::new(),
+      github_info: None,
+      chain_writes: Vec::new(),
+      dep_order: Vec::new(),
+      info: PlanInfo::new(),
+      labels: &[],
+      only: None,
+      track_ineffective: true,
+      issues: IssueFinder::new(current.links())
+    }
+  }
+
+  /// Only report on the projects with any of the `labels`. The other projects are still planned, since the
+  /// reported projects might depend on them.
+  fn with_labels(mut self, labels: &'s [String]) -> PlanBuilder<'s> {
+    self.labels = labels;
+    self
+  }
+
+  #[instrument(skip_all)]
+  fn build_from<'p, I: Iterator<Item = &'p FullPr>>(mut self, prs: I) -> Result<Plan> {
+    self.plan_prs(prs)?;
+    self.finish()
+  }
+
+  /// Plan the PRs of a range for only the `only` projects, whose changes are counted from the start of that range.
+  /// Ineffective PRs are reported from only one range, since the ranges can overlap.
+  fn plan_range<'p, I: Iterator<Item = &'p FullPr>>(
+    &mut self, prs: I, only: HashSet<ProjectId>, track_ineffective: bool
+  ) -> Result<()> {
+    self.only = Some(only);
+    self.track_ineffective = track_ineffective;
+    let result = self.plan_prs(prs);
+    self.only = None;
+    self.track_ineffective = true;
+    result
+  }
+
+  fn plan_prs<'p, I: Iterator<Item = &'p FullPr>>(&mut self, prs: I) -> Result<()> {
+    // Consider the grouped, unsquashed commits to determine project sizing and changelogs.
+    for pr in prs {
+      if self.current.skips_pr(pr.number(), pr.labels()) {
+        trace!("skipping PR {}.", pr.number());
+        continue;
+      }
+      self.start_pr(pr)?;
+      for commit in pr.included_commits() {
+        self.start_commit(commit.clone())?;
+        for file in commit.files() {
+          self.start_file(file)?;
+          self.finish_file()?;
+        }
+        self.finish_commit()?;
+      }
+      self.finish_pr()?;
     }
+    Ok(())
+  }
+
+  fn finish(mut self) -> Result<Plan> {
+    // A commit and its revert cancel each other out.
+    self.cancel_reverts()?;
+
+    // Some projects might depend on other projects.
+    self.handle_deps()?;
+
+    // Umbrella projects collect everything from their members.
+    self.handle_umbrellas()?;
+
+    // Sort projects by earliest closed date, mark duplicate commits.
+    self.sort_and_dedup()?;
+
+    Ok(self.build())
+  }
+
+  fn build_bump(mut self, id: &ProjectId, size: Size) -> Result<Plan> {
+    self.incrs.insert(id.clone(), (size, Changelog::empty()));
+    self.handle_deps()?;
+    self.handle_umbrellas()?;
+    Ok(self.build())
   }
 
+  #[instrument(skip_all, fields(pr = pr.number()))]
   pub fn start_pr(&mut self, pr: &FullPr) -> Result<()> {
     trace!(
       "planning PR {} with {}.",
       pr.number(),
       self.github_info.as_ref().map(|gh| gh.repo_name()).unwrap_or("<no gh>")
     );
-    let url = self
-      .github_info
-      .as_ref()
-      .map(|gh| format!("https://github.com/{}/{}/pull/{}", gh.owner_name(), gh.repo_name(), pr.number()));
-    self.on_pr_sizes =
-      self.current.projects().iter().map(|p| (p.id().clone(), LoggedPr::capture(pr, url.clone()))).collect();
-    self.on_ineffective = Some(LoggedPr::capture(pr, url));
+    let url = self.github_info.as_ref().map(|gh| gh.pr_url(pr.number()));
+    let issues = self.issues.find(pr.body(), self.github_info.as_ref());
+    let only = self.only.as_ref();
+    self.on_pr_sizes = self
+      .current
+      .projects()
+      .iter()
+      .filter(|p| only.map(|only| only.contains(p.id())).unwrap_or(true))
+      .map(|p| (p.id().clone(), LoggedPr::capture(pr, url.clone(), issues.clone())))
+      .collect();
+    self.on_ineffective = if self.track_ineffective { Some(LoggedPr::capture(pr, url, issues)) } else { None };
     Ok(())
   }
 
@@ -453,18 +2644,20 @@ impl<'s> PlanBuilder<'s> {
     trace!("planning PR done.");
     let mut found = false;
     for (proj_id, logged_pr) in self.on_pr_sizes.drain() {
+      let labeled = self.current.get_project(&proj_id).map(|p| has_label(p, self.labels)).unwrap_or(false);
       let (size, changelog) = self.incrs.entry(proj_id).or_insert((Size::Empty, Changelog::empty()));
       let pr_size = logged_pr.commits.iter().filter(|c| c.applies).map(|c| c.size).max();
       if let Some(pr_size) = pr_size {
-        found = true;
+        found = found || labeled;
         *size = max(*size, pr_size);
         changelog.add_entry(logged_pr, pr_size);
       }
     }
 
-    let ineffective = self.on_ineffective.take().unwrap();
-    if !found {
-      self.ineffective.push(ineffective);
+    if let Some(ineffective) = self.on_ineffective.take() {
+      if !found {
+        self.ineffective.push(ineffective);
+      }
     }
 
     Ok(())
@@ -475,21 +2668,56 @@ impl<'s> PlanBuilder<'s> {
     let kind = commit.kind().to_string();
     let summary = commit.summary().to_string();
     let msg = commit.message().to_string();
+    let scope = commit.scope();
     self.prev.slice_to(FromTagBuf::new(id.clone(), false))?;
 
-    let url = self
-      .github_info
-      .as_ref()
-      .map(|gh| format!("https://github.com/{}/{}/commit/{}", gh.owner_name(), gh.repo_name(), id));
+    let url = self.github_info.as_ref().map(|gh| gh.commit_url(&id));
     trace!("  planning commit {} at {}.", id, url.as_deref().unwrap_or("<no url>"));
 
+    let set_size = trailer_size(&msg, self.current.size_trailer());
+    let skipped = trailer_skips(&msg, self.current.skip_trailer());
+    let authors = commit_authors(commit.author(), &msg);
+    let issues = self.issues.find(&msg, self.github_info.as_ref());
+
+    // A commit that isn't conventional can be sized from its diff, if one of the projects asks for it. Its lines
+    // aren't known if the commit isn't in the local repo (such as a squashed PR's commit).
+    let by_diff = kind == NO_KIND
+      && self.on_pr_sizes.keys().filter_map(|id| self.current.get_project(id)).any(|p| p.diff_sizes().is_some());
+    let lines = self.prev.repo().filter(|_| by_diff).and_then(|repo| repo.diff_lines(&id).ok());
+
     for (proj_id, logged_pr) in &mut self.on_pr_sizes {
       if let Some(cur_project) = self.current.get_project(proj_id) {
-        let size = cur_project.size(self.current.sizes(), &kind)?;
+        if skipped.iter().any(|s| s == cur_project.name() || s == &proj_id.to_string()) {
+          trace!("    skipped for project {} by trailer.", proj_id);
+          continue;
+        }
+        let derived = match cur_project.diff_sizes() {
+          Some(diff_sizes) if kind == NO_KIND => match cur_project.size(self.current.sizes(), &summary, &kind) {
+            Ok(derived) if !derived.is_failure() => derived,
+            _ => diff_sizes.size(commit.files().len(), lines)
+          },
+          _ => cur_project.size(self.current.sizes(), &summary, &kind)?
+        };
+        let size = match set_size {
+          Some(size) => size,
+          None => cur_project.cap_size(derived, self.current.max_size())
+        };
+        if size < derived && set_size.is_none() {
+          warn!("Capped commit {} from {} to {} for project {}.", id, derived, size, proj_id);
+        }
         if size.is_failure() {
           self.info.add_failed_commit(commit.clone());
         }
-        logged_pr.commits.push(LoggedCommit::new(id.clone(), summary.clone(), msg.clone(), size, url.clone()));
+        let scoped = scope.as_deref().map(|s| cur_project.does_cover_scope(s)).unwrap_or(false);
+        if scoped {
+          trace!("    scope routed to project {}.", proj_id);
+        }
+        let (summary, msg, kind, scope) = (summary.clone(), msg.clone(), kind.clone(), scope.clone());
+        let mut logged = LoggedCommit::new(id.clone(), summary, msg, kind, scope, size, url.clone());
+        logged.applies = scoped;
+        logged.authors = authors.clone();
+        logged.issues = issues.clone();
+        logged_pr.commits.push(logged);
       }
     }
 
@@ -499,6 +2727,13 @@ impl<'s> PlanBuilder<'s> {
 
   pub fn finish_commit(&mut self) -> Result<()> {
     trace!("  planning commit done.");
+    // Like ineffective PRs, uncovered commits are only tracked when ineffective PRs are.
+    if let (Some(pr), Some(commit)) = (&self.on_ineffective, &self.on_commit) {
+      let applied = |logged: &LoggedPr| logged.commits.iter().any(|c| c.oid == commit.id() && c.applies);
+      if !self.on_pr_sizes.values().any(applied) {
+        self.uncovered.push(UncoveredCommit::new(pr.number(), commit));
+      }
+    }
     Ok(())
   }
 
@@ -511,9 +2746,11 @@ impl<'s> PlanBuilder<'s> {
       if let Some(logged_pr) = self.on_pr_sizes.get_mut(prev_project.id()) {
         trace!("      vs current project {}.", prev_project.id());
         if prev_project.does_cover(path)? {
-          let LoggedCommit { applies, .. } = logged_pr.commits.iter_mut().find(|c| c.oid == commit_id).unwrap();
-          *applies = true;
-          trace!("        covered.");
+          // The commit isn't logged for a project that its trailer skips.
+          if let Some(LoggedCommit { applies, .. }) = logged_pr.commits.iter_mut().find(|c| c.oid == commit_id) {
+            *applies = true;
+            trace!("        covered.");
+          }
         } else {
           trace!("        not covered.");
         }
@@ -521,12 +2758,26 @@ impl<'s> PlanBuilder<'s> {
         trace!("      project {} doesn't currently exist.", prev_project.id());
       }
     }
+
+    // A project that has moved still covers the files under its old roots, whatever the config said at the time.
+    for (proj_id, logged_pr) in &mut self.on_pr_sizes {
+      if self.current.get_project(proj_id).map(|p| p.did_cover(path)).transpose()?.unwrap_or(false) {
+        if let Some(LoggedCommit { applies, .. }) = logged_pr.commits.iter_mut().find(|c| c.oid == commit_id) {
+          *applies = true;
+          trace!("      covered by a previous root of {}.", proj_id);
+        }
+      }
+    }
     Ok(())
   }
 
   pub fn finish_file(&mut self) -> Result<()> { Ok(()) }
 
+  #[instrument(skip_all)]
   pub fn handle_deps(&mut self) -> Result<()> {
+    // A project in a cycle would never be queued, so its increments and chain writes would be silently dropped.
+    self.current.verify_acyclic()?;
+
     // Use a modified Kahn's algorithm to traverse deps in order.
     let mut queue: VecDeque<ProjectId> = VecDeque::new();
 
@@ -542,6 +2793,7 @@ impl<'s> PlanBuilder<'s> {
     }
 
     while let Some(id) = queue.pop_front() {
+      self.dep_order.push(id.clone());
       let size = self.incrs.get(&id).map(|s| s.0).unwrap_or(Size::Empty);
       let depds: Option<HashMap<ProjectId, Depends>> = dependents.get(&id).cloned();
       if let Some(depds) = depds {
@@ -567,6 +2819,75 @@ impl<'s> PlanBuilder<'s> {
     Ok(())
   }
 
+  pub fn handle_umbrellas(&mut self) -> Result<()> {
+    for umbrella in self.current.projects().iter().filter(|p| p.is_umbrella()) {
+      let (mut size, mut changelog) = self.incrs.remove(umbrella.id()).unwrap_or((Size::Empty, Changelog::empty()));
+      for member in umbrella.members() {
+        if let Some((member_size, member_log)) = self.incrs.get(member) {
+          size = max(size, *member_size);
+          for entry in member_log.entries() {
+            if let ChangelogEntry::Pr(pr, _) = entry {
+              changelog.merge_pr(pr);
+            }
+          }
+        }
+      }
+
+      if size > Size::Empty {
+        self.incrs.insert(umbrella.id().clone(), (size, changelog));
+      }
+    }
+    Ok(())
+  }
+
+  /// Mark each commit that reverts an earlier commit of the same project's plan, along with that commit, and size
+  /// the project and its PRs without them.
+  pub fn cancel_reverts(&mut self) -> Result<()> {
+    for (size, changelog) in self.incrs.values_mut() {
+      let commits: Vec<_> = changelog
+        .entries
+        .iter()
+        .filter_map(|entry| match entry {
+          ChangelogEntry::Pr(pr, _) => Some(pr.commits.iter().filter(|c| c.applies)),
+          _ => None
+        })
+        .flatten()
+        .map(|c| (c.oid.as_str(), reverted_oid(&c.message)))
+        .collect();
+
+      let mut reverted = HashSet::new();
+      for (oid, target) in &commits {
+        let target = match target {
+          Some(target) if !reverted.contains(oid) => target,
+          _ => continue
+        };
+        let orig = commits.iter().find(|(o, _)| o != oid && o.starts_with(target) && !reverted.contains(o));
+        if let Some((orig, _)) = orig {
+          trace!("Commit {} reverts commit {}.", oid, orig);
+          reverted.insert(*oid);
+          reverted.insert(*orig);
+        }
+      }
+      if reverted.is_empty() {
+        continue;
+      }
+
+      let reverted: HashSet<String> = reverted.into_iter().map(|oid| oid.to_string()).collect();
+      *size = Size::Empty;
+      for entry in &mut changelog.entries {
+        if let ChangelogEntry::Pr(pr, pr_size) = entry {
+          for commit in &mut pr.commits {
+            commit.reverted = reverted.contains(&commit.oid);
+          }
+          let counted = pr.commits.iter().filter(|c| c.applies && !c.reverted);
+          *pr_size = counted.map(|c| c.size).max().unwrap_or(Size::Empty);
+          *size = max(*size, *pr_size);
+        }
+      }
+    }
+    Ok(())
+  }
+
   pub fn sort_and_dedup(&mut self) -> Result<()> {
     for (.., changelog) in self.incrs.values_mut() {
       changelog.entries.sort_by(|entry1, entry2| match entry1 {
@@ -589,15 +2910,30 @@ impl<'s> PlanBuilder<'s> {
             }
             seen_commits.insert(oid.clone());
           }
-          *size = pr.commits().iter().filter(|c| c.included()).map(|c| c.size).max().unwrap_or(Size::Empty);
+          let counted = pr.commits().iter().filter(|c| c.included() && !c.reverted);
+          *size = counted.map(|c| c.size).max().unwrap_or(Size::Empty);
         }
       }
     }
     Ok(())
   }
 
-  pub fn build(self) -> Plan {
-    Plan { incrs: self.incrs, ineffective: self.ineffective, chain_writes: self.chain_writes, info: self.info }
+  pub fn build(mut self) -> Plan {
+    let (current, labels) = (self.current, self.labels);
+    let labeled = |id: &ProjectId| current.get_project(id).map(|p| has_label(p, labels)).unwrap_or(false);
+    self.incrs.retain(|id, _| labeled(id));
+    self.chain_writes.retain(|(_, depd)| labeled(depd));
+
+    Plan {
+      incrs: self.incrs,
+      ineffective: self.ineffective,
+      uncovered: self.uncovered,
+      chain_writes: self.chain_writes,
+      dep_order: self.dep_order,
+      info: self.info,
+      offline: false,
+      warnings: Vec::new()
+    }
   }
 }
 
@@ -610,7 +2946,7 @@ struct LastCommitBuilder<'s, C: StateRead> {
 
 impl<'s, C: StateRead> LastCommitBuilder<'s, C> {
   fn create(repo: &'s Repo, current: &'s Config<C>) -> LastCommitBuilder<'s, C> {
-    let prev = Slicer::init(repo);
+    let prev = Slicer::init(repo, current.file());
     LastCommitBuilder { on_line_commit: None, last_commits: HashMap::new(), prev, current }
   }
 
@@ -635,6 +2971,11 @@ impl<'s, C: StateRead> LastCommitBuilder<'s, C> {
         self.last_commits.insert(proj_id.clone(), commit_id.clone());
       }
     }
+    for project in self.current.projects() {
+      if !self.last_commits.contains_key(project.id()) && project.did_cover(path)? {
+        self.last_commits.insert(project.id().clone(), commit_id.clone());
+      }
+    }
     Ok(())
   }
 
@@ -643,66 +2984,110 @@ impl<'s, C: StateRead> LastCommitBuilder<'s, C> {
   pub fn build(self) -> Result<HashMap<ProjectId, String>> { Ok(self.last_commits) }
 }
 
-#[allow(clippy::large_enum_variant)]
-enum Slicer<'r> {
-  Orig(&'r Repo),
-  Slice(FsConfig<PrevFiles<'r>>)
+/// Reads the config file as of various commits. Each commit's config is remembered, since the same commit is
+/// often sliced more than once (for example, when it appears in more than one PR). Its projects have the IDs of the
+/// latest config, even if they've been renumbered since.
+struct Slicer<'r> {
+  source: SliceSource<'r>,
+  current: Option<String>,
+  cache: HashMap<String, ConfigFile>
+}
+
+enum SliceSource<'r> {
+  Repo(&'r Repo, &'r ConfigFile),
+  Fixed(&'r ConfigFile)
 }
 
 impl<'r> Slicer<'r> {
-  pub fn init(repo: &'r Repo) -> Slicer<'r> { Slicer::Orig(repo) }
+  pub fn init(repo: &'r Repo, latest: &'r ConfigFile) -> Slicer<'r> { Slicer::new(SliceSource::Repo(repo, latest)) }
+  pub fn fixed(file: &'r ConfigFile) -> Slicer<'r> { Slicer::new(SliceSource::Fixed(file)) }
+
+  fn new(source: SliceSource<'r>) -> Slicer<'r> { Slicer { source, current: None, cache: HashMap::new() } }
+
+  /// The repo that's sliced, unless the config is fixed.
+  pub fn repo(&self) -> Option<&'r Repo> {
+    match &self.source {
+      SliceSource::Repo(repo, _) => Some(repo),
+      SliceSource::Fixed(_) => None
+    }
+  }
 
   pub fn file(&self) -> Result<&ConfigFile> {
-    match self {
-      Slicer::Slice(fsc) => Ok(fsc.file()),
+    match (&self.source, &self.current) {
+      (SliceSource::Fixed(file), _) => Ok(file),
+      (SliceSource::Repo(..), Some(key)) => Ok(&self.cache[key]),
       _ => err!("Slicer not sliced")
     }
   }
 
   pub fn slice_to(&mut self, id: FromTagBuf) -> Result<()> {
-    *self = Slicer::Slice(match self {
-      Slicer::Orig(repo) => FsConfig::from_slice(repo.slice(id))?,
-      Slicer::Slice(fsc) => fsc.slice_to(id)?
-    });
+    let (repo, latest) = match self.source {
+      SliceSource::Repo(repo, latest) => (repo, latest),
+      SliceSource::Fixed(_) => return Ok(())
+    };
+
+    let key = id.tag().to_string();
+    if !self.cache.contains_key(&key) {
+      let mut file = ConfigFile::from_read(&PrevFiles::new(repo.slice(id)))?;
+      file.adopt_aliases(latest);
+      self.cache.insert(key.clone(), file);
+    }
+    self.current = Some(key);
     Ok(())
   }
 }
 
-fn find_old_tags<'s, I: Iterator<Item = &'s Project>>(projects: I, prev_tag: &str, repo: &Repo) -> Result<OldTags> {
-  let mut by_proj_oid = HashMap::new(); // Map<proj_id, Map<oid, Vec<tag>>>
+#[instrument(skip_all)]
+fn find_old_tags<'s, I: Iterator<Item = &'s Project>>(
+  projects: I, prev_tag: &str, baselines: &HashMap<ProjectId, String>, repo: &Repo
+) -> Result<OldTags> {
+  let mut by_proj_oid = HashMap::new(); // Map<proj_id, Map<oid, Vec<version>>>
   let mut proj_ids = HashSet::new();
+  let projects: Vec<_> = projects.collect();
+  let mut progress = Progress::of(tr!("progress.tags"), projects.len());
 
   // TODO(performance): tags requests on the server can take a while. We should combine all tag patterns for all
   // projects into a single list and make only one `repo.tag_names()` request. Then, we can collate the results
   // into their respective projects using the patterns.
 
   for proj in projects {
+    progress.inc();
     proj_ids.insert(proj.id().clone());
-    for fnmatch in tag_fnmatches(proj) {
-      trace!("Searching tags for proj {} matching \"{}\".", proj.id(), fnmatch);
-      for tag in repo.tag_names(Some(fnmatch.as_str()))?.iter().flatten() {
-        let oid = repo.revparse_oid(FromTag::new(&format!("{}^{{}}", tag), false))?;
-        trace!("Found proj {} tag {} at {}.", proj.id(), tag, oid);
-        let by_id = by_proj_oid.entry(proj.id().clone()).or_insert_with(HashMap::new);
-        by_id.entry(oid).or_insert_with(Vec::new).push(tag.to_string());
-      }
+    for (version, tags) in find_version_tags(proj, repo)? {
+      // Only the first tag of each version counts: see `find_version_tags`.
+      let tag = &tags[0];
+      let oid = repo.revparse_oid(FromTag::new(&format!("{}^{{}}", tag), false))?;
+      trace!("Found proj {} tag {} at {}.", proj.id(), tag, oid);
+      let by_id = by_proj_oid.entry(proj.id().clone()).or_insert_with(HashMap::new);
+      by_id.entry(oid).or_insert_with(Vec::new).push(version);
     }
   }
+  drop(progress);
 
+  // Each project's latest tag is found among the commits since its own `prev_tag`, if it has one.
   let mut current = HashMap::new();
-  for commit_oid in repo.commits_to_head(FromTag::new(prev_tag, true), false)?.map(|c| c.map(|c| c.id())) {
-    let commit_oid = commit_oid?;
-    by_proj_oid.retain(|proj_id, by_id| {
-      if let Some(tags) = by_id.remove(&commit_oid) {
-        let mut versions = tags_to_versions(&tags);
-        versions.sort_unstable_by(version_sort);
-        current.insert(proj_id.clone(), versions[0].clone());
-        false
-      } else {
-        true
-      }
-    });
+  let mut progress = Progress::new(tr!("progress.tag_commits"));
+  let bases = once(prev_tag.to_string()).chain(baseline_groups(baselines).into_iter().map(|(base, _)| base));
+  for base in bases {
+    let counts = |proj_id: &ProjectId| baselines.get(proj_id).map(|b| b.as_str()).unwrap_or(prev_tag) == base;
+    for commit_oid in repo.commits_to_head(FromTag::new(&base, true), false)?.map(|c| c.map(|c| c.id())) {
+      let commit_oid = commit_oid?;
+      progress.inc();
+      by_proj_oid.retain(|proj_id, by_id| {
+        if !counts(proj_id) {
+          return true;
+        }
+        if let Some(mut versions) = by_id.remove(&commit_oid) {
+          versions.sort_unstable_by(version_sort);
+          current.insert(proj_id.clone(), versions[0].clone());
+          false
+        } else {
+          true
+        }
+      });
+    }
   }
+  drop(progress);
 
   let prev = pull_from_annotation(repo, prev_tag)?;
   fill_from_prev(&prev, &proj_ids, &mut current);
@@ -714,15 +3099,10 @@ fn find_old_tags<'s, I: Iterator<Item = &'s Project>>(projects: I, prev_tag: &st
 
 fn pull_from_annotation(repo: &Repo, prev_tag: &str) -> Result<HashMap<ProjectId, String>> {
   repo
-    .annotation_of(prev_tag)
-    .map(|anno| {
-      // search for and exclude trailing signature
-      let clip = if let Some(p) = anno.find("\n-----BEGIN PGP SIGNATURE-----") { &anno[.. p] } else { anno.as_str() };
-      serde_json::from_str::<PrevTagMessage>(clip)
-    })
+    .prev_message(prev_tag)
+    .map(|anno| PrevTagMessage::parse(&anno))
     .transpose()
-    .map_err(|e| e.into())
-    .map(|o| o.unwrap_or_default().into_versions())
+    .map(|o| o.flatten().unwrap_or_default().into_versions())
 }
 
 fn fill_from_prev(
@@ -742,56 +3122,61 @@ fn fill_from_prev(
 /// This will return an empty iterator if the project doesn't have a tag_prefix. The resulting patterns are
 /// usable by both `Repository::tag_names` and as a git fetch refspec `refs/tags/{pattern}`.
 fn tag_fnmatches(proj: &Project) -> impl Iterator<Item = String> + '_ {
-  let majors = proj.tag_majors();
-
-  let majors_v = if let Some(majors) = majors {
-    E2::A(majors.iter().map(|major| format!("v{}.*", major)))
-  } else {
-    E2::B(once("v*".to_string()))
+  let majors: Vec<Option<u32>> = match proj.tag_majors() {
+    Some(majors) => majors.iter().map(|major| Some(*major)).collect(),
+    None => vec![None]
   };
 
-  let sep = proj.tag_prefix_separator();
-  let tag_prefix = proj.tag_prefix().as_deref();
-  match tag_prefix {
-    None => E3::A(empty()),
-    Some("") => E3::B(majors_v),
-    Some(pref) => E3::C(majors_v.map(move |major_v| format!("{}{}{}", pref, sep, major_v)))
+  proj.tag_templates().flat_map(move |tmpl| {
+    majors.clone().into_iter().map(move |major| {
+      let (version, major) = match major {
+        Some(major) => (format!("{}.*", major), major.to_string()),
+        None => ("*".to_string(), "*".to_string())
+      };
+      tmpl.replace("{version}", &version).replace("{major}", &major).replace("{minor}", "*").replace("{patch}", "*")
+    })
+  })
+}
+
+/// Find the version tags of a project, grouped by version. A version might be tagged more than once: for example,
+/// both `v1.2.3` and `proj-v1.2.3` after the project's `tag_prefix` was changed. The tags of each version are in
+/// order of precedence: `tag_prefix` first, and then each of `tag_prefix_aliases` in order.
+fn find_version_tags(proj: &Project, repo: &Repo) -> Result<Vec<(String, Vec<String>)>> {
+  let mut by_version: Vec<(String, Vec<String>)> = Vec::new();
+  for fnmatch in tag_fnmatches(proj) {
+    trace!("Searching tags for proj {} matching \"{}\".", proj.id(), fnmatch);
+    for tag in repo.tag_names(Some(fnmatch.as_str()))?.iter().flatten() {
+      if let Some(version) = proj.tag_version(tag) {
+        match by_version.iter_mut().find(|(v, _)| v == &version) {
+          Some((_, tags)) => tags.push(tag.to_string()),
+          None => by_version.push((version, vec![tag.to_string()]))
+        }
+      }
+    }
   }
+  Ok(by_version)
 }
 
-fn tags_to_versions(tags: &[String]) -> Vec<String> {
-  tags
-    .iter()
-    .map(|tag| {
-      let v = tag.rfind('-').map(|d| d + 1).unwrap_or(0);
-      tag[v + 1 ..].to_string()
-    })
-    .filter(|v| Size::parts(v).is_ok())
-    .collect()
+/// The versions that can directly follow `prev`: its major, minor, and patch increments. There are none if either
+/// version isn't a plain `major.minor.patch`, since then there's no telling what was skipped.
+fn next_versions(prev: &str, next: &str) -> Option<[String; 3]> {
+  let [major, minor, patch] = Size::parts(prev).ok()?;
+  Size::parts(next).ok()?;
+  Some([
+    format!("{}.0.0", major + 1),
+    format!("{}.{}.0", major, minor + 1),
+    format!("{}.{}.{}", major, minor, patch + 1)
+  ])
 }
 
 #[allow(clippy::ptr_arg)]
 fn version_sort(a: &String, b: &String) -> Ordering {
-  let p1 = Size::parts(a);
-  let p2 = Size::parts(b);
+  let p1 = Size::all_parts(a);
+  let p2 = Size::all_parts(b);
 
   if let Ok(p1) = p1 {
     if let Ok(p2) = p2 {
-      if p1[0] < p2[0] {
-        Ordering::Greater
-      } else if p1[0] > p2[0] {
-        Ordering::Less
-      } else if p1[1] < p2[1] {
-        Ordering::Greater
-      } else if p1[1] > p2[1] {
-        Ordering::Less
-      } else if p1[2] < p2[2] {
-        Ordering::Greater
-      } else if p1[2] > p2[2] {
-        Ordering::Less
-      } else {
-        Ordering::Equal
-      }
+      compare_parts(&p2, &p1)
     } else {
       Ordering::Greater
     }
@@ -801,3 +3186,776 @@ fn version_sort(a: &String, b: &String) -> Ordering {
     Ordering::Equal
   }
 }
+
+/// Check that the project can be released at `vers`. With `force`, a broken constraint doesn't fail: instead, the
+/// reason it's broken is returned.
+fn verify_next(proj: &Project, vers: &str, force: bool) -> Result<Option<String>> {
+  if !force {
+    proj.verify_restrictions(vers)?;
+    return Ok(None);
+  }
+  proj.verify_majors(vers)?;
+  proj.constraint_violation(vers)
+}
+
+/// The short hashes of (up to five of) the commits whose size couldn't be found.
+pub fn failed_hashes(plan: &Plan) -> String {
+  let mut commits =
+    plan.info().failed_commits().iter().rev().take(5).map(|c| c.id()[.. 7].to_string()).collect::<Vec<_>>().join(",");
+  if plan.info().failed_commits().len() > 5 {
+    commits.push_str(",...");
+  }
+  if commits.is_empty() {
+    // This shouldn't happen.
+    commits.push_str("<unfound>");
+  }
+
+  commits
+}
+
+/// The `released` projects (each with its previous and new version) in the dependency `order` of a plan, followed by
+/// any that aren't in it.
+fn release_order<'r>(
+  order: &'r [ProjectId], released: &'r [(ProjectId, Option<String>, String)]
+) -> impl Iterator<Item = &'r (ProjectId, Option<String>, String)> + 'r {
+  let in_order = order.iter().filter_map(move |id| released.iter().find(|(r, ..)| r == id));
+  let unordered = released.iter().filter(move |(id, ..)| !order.contains(id));
+  in_order.chain(unordered)
+}
+
+/// Find the values of a trailer in the last paragraph of a commit message (never in its subject line).
+fn find_trailers<'m>(msg: &'m str, key: &'m str) -> impl Iterator<Item = &'m str> + 'm {
+  let trailers = msg.trim_end().rsplit_once("\n\n").map(|(_, t)| t).unwrap_or("");
+  trailers
+    .lines()
+    .filter_map(|line| line.split_once(':'))
+    .filter(move |(k, _)| k.trim().eq_ignore_ascii_case(key))
+    .map(|(_, v)| v.trim())
+}
+
+/// The commit that a commit reverts, from the "This reverts commit <oid>." line that `git revert` writes.
+fn reverted_oid(msg: &str) -> Option<&str> {
+  msg.lines().find_map(|line| {
+    let rest = line.trim().strip_prefix("This reverts commit ")?;
+    let oid = rest.split(|c: char| !c.is_ascii_hexdigit()).next()?;
+    Some(oid).filter(|oid| oid.len() >= 7)
+  })
+}
+
+/// The size set by a commit's size trailer (`Versio-Size` by default), if it has one.
+fn trailer_size(msg: &str, key: &str) -> Option<Size> {
+  find_trailers(msg, key).next().and_then(|v| Size::from_str(&v.to_lowercase()).ok())
+}
+
+/// The projects (by name or ID) that a commit's skip trailers (`Versio-Skip` by default) leave it out of.
+fn trailer_skips(msg: &str, key: &str) -> Vec<String> {
+  find_trailers(msg, key)
+    .flat_map(|v| v.split(','))
+    .map(|p| p.trim().to_string())
+    .filter(|p| !p.is_empty())
+    .collect()
+}
+
+/// The author of a commit, and then the co-authors from its `Co-authored-by: Name <email>` trailers, without
+/// repeats.
+fn commit_authors(author: &str, msg: &str) -> Vec<String> {
+  let co_authors = find_trailers(msg, "Co-authored-by").map(|v| v.split('<').next().unwrap_or("").trim());
+  let mut authors: Vec<String> = Vec::new();
+  for name in once(author.trim()).chain(co_authors) {
+    if !name.is_empty() && !authors.iter().any(|a| a == name) {
+      authors.push(name.to_string());
+    }
+  }
+  authors
+}
+
+#[cfg(test)]
+mod test {
+  use super::{commit_authors, hook_env, next_versions, trailer_size, trailer_skips, CachedLastCommits, CachedPlan,
+              CachedPlanRef, ChangelogEntry, ManifestProject, PlanBuilder, PlanKey, ReleaseManifest, RepoPrefs,
+              UserPrefs};
+  use super::{AdoptProgress, Changelog, Mono, VersionChange};
+  use crate::config::{ConfigFile, ProjectHooks, ProjectId, Size, PINS_FILENAME};
+  use crate::git::{CommitInfoBuf, FullPr};
+  use crate::testing::{block_on, TestRepo};
+  use crate::vcs::{VcsLevel, VcsState};
+  use chrono::DateTime;
+
+  /// The violations that `check --strict` finds in a repo with a single commit of the `files` and `config`, tagged
+  /// with `tags`.
+  fn strict_violations(config: &str, files: &[(&str, &str)], tags: &[&str]) -> Vec<String> {
+    let repo = TestRepo::new(config);
+    for (path, content) in files {
+      repo.write(path, content);
+    }
+    repo.commit("chore: start");
+    for tag in tags {
+      repo.tag(tag);
+    }
+    Mono::open(repo.path(), VcsState::new(VcsLevel::Local, false)).unwrap().check_strict().unwrap()
+  }
+
+  #[test]
+  fn test_strict_tag_mismatch() {
+    let config = "projects:\n  - { name: a, id: 1, tag_prefix: a, version: { file: v.txt } }\n";
+    let violations = strict_violations(config, &[("v.txt", "1.1.0")], &["a-v1.0.0"]);
+    assert_eq!(1, violations.len());
+    assert!(violations[0].contains("1.1.0") && violations[0].contains("1.0.0"));
+    assert!(strict_violations(config, &[("v.txt", "1.1.0")], &["a-v1.1.0"]).is_empty());
+  }
+
+  #[test]
+  fn test_strict_tag_collision() {
+    let config = r#"
+projects:
+  - { name: a, id: 1, tag_prefix: lib, includes: [ "a/**" ], version: { file: a/v.txt } }
+  - { name: b, id: 2, tag_prefix: Lib, includes: [ "b/**" ], version: { file: b/v.txt } }
+"#;
+    let violations = strict_violations(config, &[("a/v.txt", "1.0.0"), ("b/v.txt", "1.0.0")], &[]);
+    assert_eq!(1, violations.len());
+    assert!(violations[0].starts_with("Projects a and b"));
+  }
+
+  #[test]
+  fn test_strict_overlap() {
+    let config = |allow: bool| {
+      format!(
+        r#"
+projects:
+  - {{ name: all, id: 1, tag_prefix: all, includes: [ "**" ], version: {{ file: v.txt }} }}
+  - {{ name: sub, id: 2, tag_prefix: sub, includes: [ "sub/**" ], allow_overlap: {}, version: {{ file: sub/v.txt }} }}
+  - {{ name: both, id: 3, tag_prefix: both, members: [ 1, 2 ], version: {{ tags: {{ default: "1.0.0" }} }} }}
+"#,
+        allow
+      )
+    };
+    let files = [("v.txt", "1.0.0"), ("sub/v.txt", "1.0.0"), ("sub/code.txt", "")];
+    let violations = strict_violations(&config(false), &files, &[]);
+    assert_eq!(1, violations.len());
+    assert!(violations[0].contains("all") && violations[0].contains("sub") && violations[0].contains("2 file(s)"));
+    assert!(strict_violations(&config(true), &files, &[]).is_empty());
+  }
+
+  #[test]
+  fn test_strict_unknown_dep() {
+    let config = r#"
+projects:
+  - { name: a, id: 1, version: { file: v.txt }, depends: { 9: { size: match } } }
+"#;
+    let violations = strict_violations(config, &[("v.txt", "1.0.0")], &[]);
+    assert_eq!(vec!["Project a depends on unknown project 9.".to_string()], violations);
+  }
+
+  #[test]
+  fn test_check_release_only() {
+    let repo = TestRepo::new(
+      r#"
+projects:
+  - { name: a, id: 1, includes: [ "a/**" ], version: { file: a/version.txt }, changelog: a/CHANGELOG.md }
+  - { name: b, id: 2, includes: [ "b/**" ], version: { file: b/version.txt }, changelog: b/CHANGELOG.md }
+"#
+    );
+    for proj in ["a", "b"] {
+      repo.write(&format!("{}/version.txt", proj), "1.0.0");
+      repo.write(&format!("{}/CHANGELOG.md", proj), "# 1.0.0\n");
+    }
+    repo.commit("chore: start");
+    repo.tag("base");
+    repo.write("b/code.txt", "new");
+    repo.commit("feat: add code to b");
+    let mono = Mono::open(repo.path(), VcsState::new(VcsLevel::Local, false)).unwrap();
+    assert!(mono.check_release_only("base").unwrap().is_empty());
+
+    repo.write("a/version.txt", "1.1.0");
+    repo.write("a/CHANGELOG.md", "# 1.1.0\n");
+    repo.commit("chore: release a by hand");
+    let mono = Mono::open(repo.path(), VcsState::new(VcsLevel::Local, false)).unwrap();
+    let violations = mono.check_release_only("base").unwrap();
+    assert_eq!(2, violations.len());
+    assert!(violations[0].contains("1.0.0") && violations[0].contains("1.1.0"));
+    assert!(violations[1].contains("a/CHANGELOG.md"));
+  }
+
+  #[test]
+  fn test_cyclonedx() {
+    let project = ManifestProject {
+      id: "1".into(),
+      name: "core".into(),
+      previous: Some("1.0.2".into()),
+      version: "1.1.0".into(),
+      tag: Some("core-v1.1.0".into()),
+      changelog: None
+    };
+    let manifest =
+      ReleaseManifest { date: "2024-03-01T12:00:00Z".into(), commit: Some("1a2b3c".into()), projects: vec![project] };
+    let bom = manifest.cyclonedx();
+
+    assert_eq!("CycloneDX", bom["bomFormat"]);
+    assert_eq!("versio:commit", bom["metadata"]["properties"][0]["name"]);
+    let component = &bom["components"][0];
+    assert_eq!("core", component["name"]);
+    assert_eq!("1.1.0", component["version"]);
+    let names: Vec<_> = component["properties"].as_array().unwrap().iter().map(|p| p["name"].clone()).collect();
+    assert_eq!(vec!["versio:id", "versio:previous", "versio:tag"], names);
+  }
+
+  #[test]
+  fn test_defaults_overlay() {
+    let repo: RepoPrefs = toml::from_str("[defaults]\nvcs_level = \"local\"\noffline = true\n").unwrap();
+    let user: UserPrefs = toml::from_str("[defaults]\noffline = false\nwide = true\n").unwrap();
+    let defaults = user.defaults.overlay(repo.defaults);
+
+    let vcs = defaults.vcs_range().unwrap().unwrap();
+    assert_eq!((VcsLevel::Local, VcsLevel::Local), (vcs.min(), vcs.max()));
+    assert!(!defaults.offline());
+    assert!(defaults.wide());
+  }
+
+  #[test]
+  fn test_proxy_prefs() {
+    let repo: RepoPrefs =
+      toml::from_str("[proxy]\nhttps = \"http://repo:3128\"\nno_proxy = [\"localhost\"]\n").unwrap();
+    let user: UserPrefs = toml::from_str("[proxy]\nhttps = \"http://mine:3128\"\n").unwrap();
+    let vars = user.proxy.overlay(repo.proxy).vars();
+    assert_eq!(vec![("HTTPS_PROXY", "http://mine:3128".to_string()), ("NO_PROXY", "localhost".to_string())], vars);
+    assert!(toml::from_str::<RepoPrefs>("[proxy]\nftp = \"http://proxy:3128\"\n").is_err());
+  }
+
+  #[test]
+  fn test_repo_prefs_no_auth() {
+    assert!(toml::from_str::<RepoPrefs>("[auth]\ngithub_token = \"secret\"\n").is_err());
+    assert!(toml::from_str::<RepoPrefs>("[defaults]\ngithub_token = \"secret\"\n").is_err());
+  }
+
+  #[test]
+  fn test_trailer_size() {
+    let msg = "feat!: drop v1\n\nIt's gone.\n\nversio-size: Major\n";
+    assert_eq!(Some(Size::Major), trailer_size(msg, "Versio-Size"));
+    assert_eq!(None, trailer_size("feat!: drop v1\n\nVersio-Size: major\n\nIt's gone.", "Versio-Size"));
+    assert_eq!(None, trailer_size("feat!: drop v1\n\nVersio-Size: huge", "Versio-Size"));
+    assert_eq!(Some(Size::Patch), trailer_size("fix: x\n\nBump: patch", "Bump"));
+  }
+
+  #[test]
+  fn test_trailer_skips() {
+    let msg = "fix: x\n\nVersio-Skip: app, lib\nversio-skip: 3\n";
+    assert_eq!(vec!["app", "lib", "3"], trailer_skips(msg, "Versio-Skip"));
+    assert!(trailer_skips("fix: x\n\nVersio-Skip: app\n\nMore.", "Versio-Skip").is_empty());
+  }
+
+  #[test]
+  fn test_commit_authors() {
+    let msg = "feat: x\n\nCo-authored-by: Bo <bo@example.com>\nco-authored-by: Al <al@example.com>\n";
+    assert_eq!(vec!["Al", "Bo"], commit_authors("Al", msg));
+    assert!(commit_authors("", "feat: x").is_empty());
+  }
+
+  #[test]
+  fn test_plan_cache_roundtrip() {
+    let file: ConfigFile = serde_yaml::from_str(
+      "projects:\n  - name: p\n    id: 1\n    version: { file: v.txt }\nsizes:\n  minor: [ feat ]\n"
+    )
+    .unwrap();
+    let time = DateTime::parse_from_rfc3339("2024-01-01T00:00:00Z").unwrap();
+    let commit =
+      CommitInfoBuf::new("a".repeat(40), "feat".into(), "feat: x".into(), "feat: x".into(), vec!["f".into()], time);
+    let pr = FullPr::synthetic(0, "direct".into(), vec![commit], 0);
+    let plan = PlanBuilder::fixed(&file).build_from([pr].iter()).unwrap();
+
+    let key = || PlanKey { head: "h".into(), prev_tag: None, config: "c".into(), offline: false };
+    let json = serde_json::to_string(&CachedPlanRef { key: key(), plan: &plan }).unwrap();
+    let cached: CachedPlan = serde_json::from_str(&json).unwrap();
+
+    assert_eq!(key(), cached.key);
+    let (size, changelog) = cached.plan.incrs().get(&ProjectId::from_id(1)).unwrap();
+    assert_eq!(&Size::Minor, size);
+    assert_eq!(1, changelog.entries().len());
+  }
+
+  #[test]
+  fn test_last_commits_cache_roundtrip() {
+    let last_commits = [(ProjectId::from_id(1), "a".repeat(40)), (ProjectId::new(2, vec![1, 2]), "b".repeat(40))];
+    let entry = CachedLastCommits {
+      base: None,
+      head: "c".repeat(40),
+      config: "d".repeat(40),
+      last_commits: last_commits.iter().cloned().collect()
+    };
+    let json = serde_json::to_string(&[entry]).unwrap();
+    let cached: Vec<CachedLastCommits> = serde_json::from_str(&json).unwrap();
+
+    assert_eq!(1, cached.len());
+    assert_eq!(None, cached[0].base);
+    assert_eq!(Some(&"b".repeat(40)), cached[0].last_commits.get(&ProjectId::new(2, vec![1, 2])));
+    assert_eq!(2, cached[0].last_commits.len());
+  }
+
+  #[test]
+  fn test_umbrella_plan() {
+    let file: ConfigFile = serde_yaml::from_str(
+      r#"
+projects:
+  - { name: a, id: 1, includes: [ "a/**" ], version: { file: a.txt } }
+  - { name: b, id: 2, includes: [ "b/**" ], version: { file: b.txt } }
+  - { name: c, id: 3, includes: [ "c/**" ], version: { file: c.txt } }
+  - { name: all, id: 4, tag_prefix: release, version: { tags: { default: "1.0.0" } }, members: [ 1, 2 ] }
+sizes:
+  minor: [ feat ]
+  patch: [ fix ]
+"#
+    )
+    .unwrap();
+    let time = DateTime::parse_from_rfc3339("2024-01-01T00:00:00Z").unwrap();
+    let commit = |id: char, kind: &str, file: &str| {
+      let summary = format!("{}: x", kind);
+      CommitInfoBuf::new(id.to_string().repeat(40), kind.into(), summary.clone(), summary, vec![file.into()], time)
+    };
+    let pr1 = FullPr::synthetic(1, "one".into(), vec![commit('a', "fix", "a/x"), commit('b', "feat", "b/x")], 0);
+    let pr2 = FullPr::synthetic(2, "two".into(), vec![commit('c', "feat", "c/x")], 1);
+    let plan = PlanBuilder::fixed(&file).build_from([pr1, pr2].iter()).unwrap();
+
+    let (size, changelog) = plan.incrs().get(&ProjectId::from_id(4)).unwrap();
+    assert_eq!(&Size::Minor, size);
+    assert_eq!(1, changelog.entries().len());
+    match &changelog.entries()[0] {
+      ChangelogEntry::Pr(pr, size) => {
+        assert_eq!(1, pr.number());
+        assert_eq!(&Size::Minor, size);
+        assert_eq!(2, pr.commits().iter().filter(|c| c.included()).count());
+      }
+      _ => panic!("Expected a PR entry.")
+    }
+  }
+
+  #[test]
+  fn test_diff_sizes_plan() {
+    let file: ConfigFile = serde_yaml::from_str(
+      r#"
+projects:
+  - name: p
+    id: 1
+    version: { file: v.txt }
+    diff_sizes: { minor: { files: 3 }, major: { files: 10, lines: 1000 } }
+  - { name: q, id: 2, version: { file: q.txt } }
+sizes:
+  minor: [ feat ]
+  fail: [ "*" ]
+"#
+    )
+    .unwrap();
+    let time = DateTime::parse_from_rfc3339("2024-01-01T00:00:00Z").unwrap();
+    let files: Vec<String> = vec!["a".into(), "b".into(), "c".into()];
+    let commit = CommitInfoBuf::new("a".repeat(40), "-".into(), "did stuff".into(), "did stuff".into(), files, time);
+    let pr = FullPr::synthetic(1, "one".into(), vec![commit], 0);
+    let plan = PlanBuilder::fixed(&file).build_from([pr].iter()).unwrap();
+
+    assert_eq!(&Size::Minor, &plan.incrs().get(&ProjectId::from_id(1)).unwrap().0);
+    assert_eq!(&Size::Fail, &plan.incrs().get(&ProjectId::from_id(2)).unwrap().0);
+  }
+
+  #[test]
+  fn test_revert_plan() {
+    let file: ConfigFile = serde_yaml::from_str(
+      r#"
+projects:
+  - { name: p, id: 1, version: { file: v.txt } }
+sizes:
+  minor: [ feat ]
+  patch: [ revert ]
+"#
+    )
+    .unwrap();
+    let time = DateTime::parse_from_rfc3339("2024-01-01T00:00:00Z").unwrap();
+    let commit = |id: char, kind: &str, msg: String| {
+      let summary = msg.lines().next().unwrap().to_string();
+      CommitInfoBuf::new(id.to_string().repeat(40), kind.into(), summary, msg, vec!["f".into()], time)
+    };
+    let feat = commit('a', "feat", "feat: x".into());
+    let revert = commit('b', "revert", format!("revert: x\n\nThis reverts commit {}.", "a".repeat(40)));
+    let pr1 = FullPr::synthetic(1, "one".into(), vec![feat], 0);
+    let pr2 = FullPr::synthetic(2, "two".into(), vec![revert], 1);
+    let plan = PlanBuilder::fixed(&file).build_from([pr1, pr2].iter()).unwrap();
+
+    let (size, changelog) = plan.incrs().get(&ProjectId::from_id(1)).unwrap();
+    assert_eq!(&Size::Empty, size);
+    for entry in changelog.entries() {
+      match entry {
+        ChangelogEntry::Pr(pr, size) => {
+          assert_eq!(&Size::Empty, size);
+          assert!(pr.commits().iter().all(|c| c.reverted()));
+        }
+        _ => panic!("Expected a PR entry.")
+      }
+    }
+  }
+
+  #[test]
+  fn test_dep_order() {
+    let file: ConfigFile = serde_yaml::from_str(
+      r#"
+projects:
+  - { name: app, id: 1, version: { file: a.txt }, depends: { 2: { size: match } } }
+  - { name: lib, id: 2, version: { file: b.txt }, depends: { 3: { size: match } } }
+  - { name: core, id: 3, version: { file: c.txt } }
+"#
+    )
+    .unwrap();
+    let plan = PlanBuilder::fixed(&file).build_bump(&ProjectId::from_id(3), Size::Minor).unwrap();
+    let order: Vec<_> = plan.dep_order().iter().map(|id| id.to_string()).collect();
+    assert_eq!(vec!["3", "2", "1"], order);
+  }
+
+  #[test]
+  fn test_next_versions() {
+    let expected = next_versions("1.4.2", "1.6.0").unwrap();
+    assert_eq!(["2.0.0", "1.5.0", "1.4.3"], expected);
+    assert!(!expected.contains(&"1.6.0".to_string()));
+    assert!(next_versions("1.4.2", "1.5.0-rc.1").is_none());
+    assert!(next_versions("2024.03", "2024.04").is_none());
+  }
+
+  /// A repo whose project was released as 1.0.0 and 1.1.0 before versio was adopted. Its changelog is at an
+  /// absolute path in the repo, since files are written relative to the current directory.
+  fn pre_adopt_repo() -> TestRepo {
+    let repo = TestRepo::new("");
+    let changelog = repo.path().join("a/CHANGELOG.html");
+    let config = format!(
+      r#"
+projects:
+  - name: a
+    id: 1
+    tag_prefix: ""
+    includes: [ "a/**" ]
+    version: {{ file: a/version.txt }}
+    changelog: "{}"
+sizes:
+  use_angular: true
+"#,
+      changelog.display()
+    );
+    repo.write(".versio.yaml", &config);
+    repo.write("a/version.txt", "1.0.0");
+    repo.commit("feat: start a");
+    repo.tag("v1.0.0");
+    repo.write("a/code.txt", "new");
+    repo.write("a/version.txt", "1.1.0");
+    repo.commit("feat: add code");
+    repo.tag("v1.1.0");
+    repo
+  }
+
+  fn open_offline(repo: &TestRepo) -> Mono {
+    let mut mono = Mono::open(repo.path(), VcsState::new(VcsLevel::Local, false)).unwrap();
+    mono.set_offline(true);
+    mono
+  }
+
+  #[test]
+  fn test_adopt_backfills_changelog() {
+    let repo = pre_adopt_repo();
+    let mono = open_offline(&repo);
+    let id = ProjectId::from_id(1);
+    let releases = mono.past_releases(&id).unwrap();
+    assert_eq!(vec!["1.0.0", "1.1.0"], releases.iter().map(|r| r.version()).collect::<Vec<_>>());
+
+    let first = block_on(mono.render_past_changelog(&id, None, &releases[0], None)).unwrap().unwrap();
+    assert!(first.1.contains("1.0.0"));
+    let (path, log) =
+      block_on(mono.render_past_changelog(&id, Some(&releases[0]), &releases[1], Some(&first.1))).unwrap().unwrap();
+    assert_eq!(repo.path().join("a/CHANGELOG.html"), path);
+    assert!(log.contains("1.0.0") && log.contains("1.1.0"));
+  }
+
+  #[test]
+  fn test_adopt_resumes() {
+    let repo = pre_adopt_repo();
+    let id = ProjectId::from_id(1);
+
+    // The first run renders the first release, and is interrupted.
+    {
+      let mono = open_offline(&repo);
+      let releases = mono.past_releases(&id).unwrap();
+      let log = block_on(mono.render_past_changelog(&id, None, &releases[0], None)).unwrap();
+      let mut progress = AdoptProgress::new();
+      progress.record(&id, releases[0].version(), log);
+      mono.save_adopt_progress(&progress).unwrap();
+    }
+
+    let mut mono = open_offline(&repo);
+    let mut progress = mono.adopt_progress().unwrap().expect("saved progress");
+    assert_eq!(Some("1.0.0"), progress.rendered(&id));
+    let releases = mono.past_releases(&id).unwrap();
+    let log = block_on(mono.render_past_changelog(&id, Some(&releases[0]), &releases[1], progress.log(&id))).unwrap();
+    progress.record(&id, releases[1].version(), log);
+    mono.finish_adopt(progress).unwrap();
+
+    let changelog = repo.read("a/CHANGELOG.html");
+    assert!(changelog.contains("1.0.0") && changelog.contains("1.1.0"));
+    assert!(mono.adopt_progress().unwrap().is_none());
+    assert_eq!(Some(repo.head()), repo.tag_target("versio-prev"));
+  }
+
+  #[test]
+  fn test_changelogs_keep_order() {
+    // More projects than are rendered at once, so that later renders can finish first.
+    let repo = TestRepo::new("");
+    let mut config = "projects:\n".to_string();
+    for i in 1 ..= 12 {
+      let changelog = repo.path().join(format!("p{}/CHANGELOG.html", i));
+      config.push_str(&format!(
+        "  - {{ name: p{0}, id: {0}, includes: [ \"p{0}/**\" ], version: {{ file: p{0}/version.txt }}, \
+         changelog: \"{1}\" }}\n",
+        i,
+        changelog.display()
+      ));
+      repo.write(&format!("p{}/version.txt", i), "1.0.0");
+    }
+    repo.write(".versio.yaml", &config);
+    repo.commit("chore: start");
+
+    let mut mono = open_offline(&repo);
+    let mut changelog = Changelog::empty();
+    changelog.add_dep(ProjectId::from_id(1), "p1");
+    let ids = [7, 3, 12, 1, 9, 5, 11, 2, 8, 4, 10, 6];
+    let logs: Vec<_> = ids.iter().map(|i| (ProjectId::from_id(*i), &changelog, "1.1.0".to_string())).collect();
+    let wrote = block_on(mono.write_changelogs_for(&logs)).unwrap();
+
+    let expected: Vec<_> = ids.iter().map(|i| repo.path().join(format!("p{}/CHANGELOG.html", i))).collect();
+    assert_eq!(expected, wrote);
+  }
+
+  #[test]
+  fn test_plan_cache() {
+    let repo = TestRepo::new(
+      r#"
+projects:
+  - { name: a, id: 1, includes: [ "a/**" ], version: { file: a/version.txt } }
+sizes: { use_angular: true }
+"#
+    );
+    repo.write("a/version.txt", "1.0.0");
+    repo.commit("feat: start a");
+    let old = repo.path().join(".git/versio-plan.json");
+    std::fs::write(&old, "{}").unwrap();
+
+    let mono = open_offline(&repo);
+    assert_eq!(1, block_on(mono.build_and_cache_plan()).unwrap().incrs().len());
+    assert!(!old.exists());
+
+    // The cached plan is used while the repo is in the same state, so blank its increments to tell it apart.
+    let cache = repo.path().join(".git/versio/plan.json");
+    let mut cached: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&cache).unwrap()).unwrap();
+    cached["plan"]["incrs"] = serde_json::json!({});
+    std::fs::write(&cache, cached.to_string()).unwrap();
+    assert!(block_on(mono.cached_plan()).unwrap().incrs().is_empty());
+
+    // `release --no-cache` builds the plan instead.
+    assert_eq!(1, block_on(mono.build_plan()).unwrap().incrs().len());
+  }
+
+  #[test]
+  fn test_project_prev_tag() {
+    let repo = TestRepo::new(
+      r#"
+projects:
+  - { name: a, id: 1, includes: [ "a/**" ], version: { file: a/version.txt } }
+  - { name: b, id: 2, includes: [ "b/**" ], version: { file: b/version.txt }, prev_tag: b-start }
+sizes: { use_angular: true }
+"#
+    );
+    repo.write("a/version.txt", "1.0.0");
+    repo.write("b/version.txt", "1.0.0");
+    repo.commit("chore: start");
+    repo.tag("versio-prev");
+    repo.write("b/code.txt", "old");
+    repo.commit("feat: b before it was adopted");
+    repo.tag("b-start");
+    repo.write("a/code.txt", "new");
+    repo.commit("feat: a");
+    repo.write("b/code.txt", "fixed");
+    repo.commit("fix: b");
+
+    let sizes = |mono: &Mono| {
+      let plan = block_on(mono.build_plan()).unwrap();
+      let size = |id: u32| plan.incrs().get(&ProjectId::from_id(id)).unwrap().0;
+      (size(1), size(2))
+    };
+    assert_eq!((Size::Minor, Size::Patch), sizes(&open_offline(&repo)));
+
+    // Once there's been a release since, b counts its changes from the repo-wide prev tag again.
+    repo.tag("versio-prev");
+    repo.write("b/code.txt", "more");
+    repo.commit("feat: b");
+    assert_eq!((Size::Empty, Size::Minor), sizes(&open_offline(&repo)));
+  }
+
+  #[test]
+  fn test_preview() {
+    let repo = TestRepo::new(
+      r#"
+projects:
+  - { name: a, id: 1, includes: [ "a/**" ], version: { file: a/version.txt } }
+  - { name: b, id: 2, includes: [ "b/**" ], version: { file: b/version.txt } }
+sizes: { use_angular: true }
+"#
+    );
+    repo.write("a/version.txt", "1.0.0");
+    repo.write("b/version.txt", "2.0.0");
+    repo.commit("chore: start");
+    repo.tag("versio-prev");
+    repo.write("a/code.txt", "new");
+    repo.commit("feat: add code");
+    let head = repo.head();
+
+    let mono = open_offline(&repo);
+    let plan = block_on(mono.cached_plan()).unwrap();
+    let nexts = mono.next_versions(&plan, None, false, false).unwrap();
+    let next = |id: u32| nexts.iter().find(|n| n.id == ProjectId::from_id(id)).unwrap();
+    assert_eq!("1.1.0", next(1).version);
+    assert!(matches!(&next(1).change, VersionChange::Changed { curt, .. } if curt == "1.0.0"));
+    assert_eq!("2.0.0", next(2).version);
+    assert!(matches!(next(2).change, VersionChange::NoChange { locked: false, .. }));
+
+    // Nothing is written or committed.
+    assert_eq!("1.0.0", repo.read("a/version.txt"));
+    assert_eq!(head, repo.head());
+  }
+
+  #[test]
+  fn test_pin() {
+    let repo = TestRepo::new(
+      r#"
+projects:
+  - { name: a, id: 1, includes: [ "a/**" ], version: { file: a/version.txt } }
+sizes: { use_angular: true }
+"#
+    );
+    repo.write("a/version.txt", "1.0.0");
+    repo.commit("chore: start");
+    repo.tag("versio-prev");
+    repo.write("a/code.txt", "new");
+    repo.write(PINS_FILENAME, "\"1\": 2.0.0\n");
+    repo.commit("feat: add code, pinned to 2.0.0");
+
+    let mut mono = open_offline(&repo);
+    let id = ProjectId::from_id(1);
+    assert_eq!(Some(&"2.0.0".to_string()), mono.pins().unwrap().get(&id));
+    let plan = block_on(mono.build_plan()).unwrap();
+    let nexts = mono.next_versions(&plan, None, false, false).unwrap();
+    assert_eq!("2.0.0", nexts[0].version);
+    assert!(nexts[0].pin);
+
+    assert!(mono.pin(&id, Some("1.0.0")).is_err());
+    assert!(mono.pin(&id, Some("3.0.0")).is_ok());
+  }
+
+  /// Release version `version` of the project of `ROLLBACK_CONFIG`, as a release would: commit its changelog, tag
+  /// it, and move the prev tag.
+  fn release_at(repo: &TestRepo, version: &str) {
+    let mut mono = open_offline(repo);
+    let id = ProjectId::from_id(1);
+    mono.next.write_file(repo.path().join("a/CHANGELOG.md"), format!("# {}\n", version), &id, true).unwrap();
+    mono.set_by_id(&id, version).unwrap();
+    mono.commit(true, false).unwrap();
+  }
+
+  const ROLLBACK_CONFIG: &str = r#"
+projects:
+  - { name: a, id: 1, tag_prefix: a, includes: [ "a/**" ], version: { tags: { default: "1.0.0" } } }
+"#;
+
+  #[test]
+  fn test_rollback() {
+    let repo = TestRepo::new(ROLLBACK_CONFIG);
+    repo.write("a/code.txt", "start");
+    repo.commit("feat: start");
+    release_at(&repo, "1.0.0");
+    let first = repo.head();
+    repo.write("a/code.txt", "more");
+    repo.commit("feat: more");
+    release_at(&repo, "1.1.0");
+    let second = repo.head();
+
+    let mono = open_offline(&repo);
+    let rollback = mono.plan_rollback().unwrap();
+    assert_eq!(vec!["a-v1.1.0".to_string()], rollback.tags());
+    assert_eq!(Some(second.to_string().as_str()), rollback.release_commit());
+    assert_eq!(first.to_string(), rollback.prev_commit());
+    mono.rollback(&rollback).unwrap();
+
+    assert_eq!(None, repo.tag_target("a-v1.1.0"));
+    assert_eq!(Some(first), repo.tag_target("a-v1.0.0"));
+    assert_eq!(Some(first), repo.tag_target("versio-prev"));
+    let head = repo.repo().find_commit(repo.head()).unwrap();
+    assert_eq!(vec![second], head.parent_ids().collect::<Vec<_>>());
+    assert!(head.summary().unwrap().starts_with("Revert"));
+    assert_eq!("# 1.0.0\n", repo.read("a/CHANGELOG.md"));
+  }
+
+  #[test]
+  fn test_set_cascade() {
+    let repo = TestRepo::new("");
+    let config = format!(
+      r#"
+projects:
+  - {{ name: a, id: 1, tag_prefix: a, includes: [ "a/**" ], version: {{ tags: {{ default: "1.0.0" }} }} }}
+  - name: b
+    id: 2
+    tag_prefix: b
+    includes: [ "b/**" ]
+    version: {{ tags: {{ default: "2.0.0" }} }}
+    depends: {{ 1: {{ files: [ {{ file: "{}", json: dependencies.a }} ] }} }}
+  - {{ name: c, id: 3, tag_prefix: c, includes: [ "c/**" ], version: {{ tags: {{ default: "3.0.0" }} }} }}
+"#,
+      repo.path().join("b/package.json").display()
+    );
+    repo.write(".versio.yaml", &config);
+    repo.write("b/package.json", r#"{ "dependencies": { "a": "1.0.0" } }"#);
+    repo.commit("chore: start");
+
+    let mut mono = open_offline(&repo);
+    let id = ProjectId::from_id(1);
+    mono.set_by_id(&id, "1.1.0").unwrap();
+    mono.write_dependents(&id, "1.1.0").unwrap();
+    mono.commit(false, false).unwrap();
+
+    assert_eq!(r#"{ "dependencies": { "a": "1.1.0" } }"#, repo.read("b/package.json"));
+    assert!(repo.tag_target("a-v1.1.0").is_some());
+    assert!(repo.tag_target("b-v2.0.1").is_none());
+  }
+
+  #[cfg(not(windows))]
+  #[test]
+  fn test_hook_env() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let out = dir.path().join("env.txt");
+    let config = format!(
+      r#"
+projects:
+  - name: p1
+    id: 1
+    tag_prefix: p1
+    version: {{ file: f1 }}
+    hooks: {{ pre_release: "env > '{}'" }}
+"#,
+      out.display()
+    );
+    let file: ConfigFile = serde_yaml::from_str(&config).unwrap();
+    let proj = &file.projects()[0];
+    ProjectHooks::new(proj, hook_env(proj, "main", Some("1.0.0"), "1.1.0")).execute("pre_release").unwrap();
+
+    let env = std::fs::read_to_string(out).unwrap();
+    let versio: Vec<_> = env.lines().filter(|line| line.starts_with("VERSIO_")).collect();
+    for expected in [
+      "VERSIO_BRANCH=main",
+      "VERSIO_PROJECT_ID=1",
+      "VERSIO_PROJECT_NAME=p1",
+      "VERSIO_ROOT=.",
+      "VERSIO_PREV_VERSION=1.0.0",
+      "VERSIO_VERSION=1.1.0",
+      "VERSIO_TAG=p1-v1.1.0"
+    ] {
+      assert!(versio.contains(&expected), "{} not in {:?}", expected, versio);
+    }
+  }
+}
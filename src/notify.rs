@@ -0,0 +1,286 @@
+//! Notifications posted to webhooks (such as a Slack incoming webhook) after a release, and the release notes that
+//! are published to HTTP APIs (such as Confluence or Notion) along with them.
+
+use crate::errors::{Error, Result};
+use crate::template::DEFAULT_NOTES_TEMPLATE;
+use hyper::header::CONTENT_TYPE;
+use hyper::{Body, Client, Method, Request, Uri};
+use hyper_tls::HttpsConnector;
+use liquid::ParserBuilder;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+const DEFAULT_CONTENT_TYPE: &str = "application/json";
+
+/// A webhook to notify after each release, from the `notifications` list of the config.
+#[derive(Deserialize, Debug, Clone)]
+pub struct Notification {
+  url: Option<String>,
+  url_env: Option<String>,
+  body: Option<String>,
+  content_type: Option<String>
+}
+
+impl Notification {
+  /// Check that exactly one of `url` or `url_env` is set.
+  pub fn validate(&self) -> Result<()> { validate_url("notification", &self.url, &self.url_env) }
+
+  /// A description of the webhook which doesn't expose its URL, since webhook URLs are often secret.
+  pub fn describe(&self) -> String { describe_url(&self.url, &self.url_env) }
+
+  fn url(&self) -> Result<String> { find_url(&self.url, &self.url_env) }
+
+  /// The body to post: the summary as JSON, or else the `body` template rendered with the summary.
+  fn render(&self, summary: &ReleaseSummary) -> Result<String> {
+    match &self.body {
+      Some(body) => {
+        let template = ParserBuilder::with_stdlib().build()?.parse(body)?;
+        Ok(template.render(&liquid::to_object(summary)?)?)
+      }
+      None => Ok(serde_json::to_string(summary)?)
+    }
+  }
+
+  async fn send(&self, summary: &ReleaseSummary) -> Result<()> {
+    let content_type = self.content_type.as_deref().unwrap_or(DEFAULT_CONTENT_TYPE);
+    post(&self.url()?, content_type, self.render(summary)?).await
+  }
+}
+
+/// An HTTP API to publish the release notes to after each release, from the `notes_publishers` list of the config.
+#[derive(Deserialize, Debug, Clone)]
+pub struct NotesPublisher {
+  url: Option<String>,
+  url_env: Option<String>,
+  #[serde(default)]
+  method: PublishMethod,
+  #[serde(default)]
+  headers: BTreeMap<String, String>,
+  template: Option<String>,
+  body: Option<String>,
+  content_type: Option<String>
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum PublishMethod {
+  #[default]
+  Post,
+  Put
+}
+
+impl NotesPublisher {
+  /// Check that exactly one of `url` or `url_env` is set.
+  pub fn validate(&self) -> Result<()> { validate_url("notes publisher", &self.url, &self.url_env) }
+
+  /// A description of the API which doesn't expose its URL, which may have a secret in it.
+  pub fn describe(&self) -> String { describe_url(&self.url, &self.url_env) }
+
+  /// The template that renders the release notes.
+  pub fn template(&self) -> &str { self.template.as_deref().unwrap_or(DEFAULT_NOTES_TEMPLATE) }
+
+  /// The headers and body to send: the summary with its `notes` as JSON, or else the `body` template rendered with
+  /// them. The header values are templates too, rendered the same way.
+  fn render(&self, summary: &ReleaseSummary, notes: &str) -> Result<(Vec<(String, String)>, String)> {
+    let mut data = serde_json::to_value(summary)?;
+    data["notes"] = notes.into();
+    let default_body = serde_json::to_string(&data)?;
+
+    // A JSON string of the notes, with its quotes, for a `body` that's JSON itself.
+    data["notes_json"] = serde_json::to_string(notes)?.into();
+    let globals = liquid::to_object(&data)?;
+    let parser = ParserBuilder::with_stdlib().build()?;
+    let headers = self
+      .headers
+      .iter()
+      .map(|(name, value)| Ok((name.clone(), parser.parse(value)?.render(&globals)?)))
+      .collect::<Result<_>>()?;
+    let body = match &self.body {
+      Some(body) => parser.parse(body)?.render(&globals)?,
+      None => default_body
+    };
+    Ok((headers, body))
+  }
+
+  /// Send the `notes`, unless this is a `dry` run, which only checks that they can be rendered.
+  pub async fn publish(&self, summary: &ReleaseSummary, notes: &str, dry: bool) -> Result<()> {
+    let (headers, body) = self.render(summary, notes)?;
+    if dry {
+      return Ok(());
+    }
+    let content_type = self.content_type.as_deref().unwrap_or(DEFAULT_CONTENT_TYPE);
+    let method = match self.method {
+      PublishMethod::Post => Method::POST,
+      PublishMethod::Put => Method::PUT
+    };
+    send(method, &find_url(&self.url, &self.url_env)?, content_type, &headers, body).await
+  }
+}
+
+fn validate_url(kind: &str, url: &Option<String>, url_env: &Option<String>) -> Result<()> {
+  match (url, url_env) {
+    (Some(_), Some(_)) => bail!("{} has both url and url_env", kind),
+    (None, None) => bail!("{} has neither url nor url_env", kind),
+    _ => Ok(())
+  }
+}
+
+fn describe_url(url: &Option<String>, url_env: &Option<String>) -> String {
+  match (url, url_env) {
+    (_, Some(var)) => format!("${}", var),
+    (Some(url), None) => url.parse::<Uri>().ok().and_then(|u| u.host().map(|h| h.to_string())).unwrap_or_default(),
+    (None, None) => String::new()
+  }
+}
+
+fn find_url(url: &Option<String>, url_env: &Option<String>) -> Result<String> {
+  match (url, url_env) {
+    (_, Some(var)) => std::env::var(var).map_err(|_| bad!("Environment variable {} isn't set.", var)),
+    (Some(url), None) => Ok(url.clone()),
+    (None, None) => bail!("No URL.")
+  }
+}
+
+/// Post a body to a URL, failing unless the response is a success.
+pub async fn post(url: &str, content_type: &str, body: String) -> Result<()> {
+  send(Method::POST, url, content_type, &[], body).await
+}
+
+/// Send a body to a URL with the `method` and extra `headers`, failing unless the response is a success.
+async fn send(method: Method, url: &str, content_type: &str, headers: &[(String, String)], body: String) -> Result<()> {
+  let mut request = Request::builder().method(method).uri(url.parse::<Uri>()?).header(CONTENT_TYPE, content_type);
+  for (name, value) in headers {
+    request = request.header(name.as_str(), value.as_str());
+  }
+  let request = request.body(Body::from(body))?;
+
+  let client = Client::builder().build::<_, Body>(HttpsConnector::new());
+  let resp = client.request(request).await?;
+  if !resp.status().is_success() {
+    bail!("Unsuccessful request: {}", resp.status().as_u16());
+  }
+  Ok(())
+}
+
+/// What was released: this is posted as JSON, or is available to a `body` template.
+#[derive(Serialize, Debug)]
+pub struct ReleaseSummary {
+  repository: Option<String>,
+  commit: Option<String>,
+  projects: Vec<NotifiedProject>
+}
+
+impl ReleaseSummary {
+  pub fn new(repository: Option<String>, commit: Option<String>, projects: Vec<NotifiedProject>) -> ReleaseSummary {
+    ReleaseSummary { repository, commit, projects }
+  }
+}
+
+/// A single project of a release.
+#[derive(Serialize, Debug)]
+pub struct NotifiedProject {
+  id: String,
+  name: String,
+  previous: Option<String>,
+  version: String,
+  tag: Option<String>,
+  changelog: Option<String>
+}
+
+impl NotifiedProject {
+  pub fn new(
+    id: String, name: String, previous: Option<String>, version: String, tag: Option<String>, changelog: Option<String>
+  ) -> NotifiedProject {
+    NotifiedProject { id, name, previous, version, tag, changelog }
+  }
+}
+
+/// Post the summary to each of the webhooks, returning the description of those that failed, with why. A
+/// failure doesn't stop the others from being notified.
+pub async fn notify_all(notifications: &[Notification], summary: &ReleaseSummary) -> Vec<(String, Error)> {
+  let mut failed = Vec::new();
+  for notification in notifications {
+    if let Err(e) = notification.send(summary).await {
+      failed.push((notification.describe(), e));
+    }
+  }
+  failed
+}
+
+#[cfg(test)]
+mod test {
+  use super::{NotesPublisher, Notification, NotifiedProject, ReleaseSummary};
+
+  fn summary() -> ReleaseSummary {
+    let proj = NotifiedProject::new(
+      "1".into(),
+      "proj".into(),
+      Some("1.0.0".into()),
+      "1.1.0".into(),
+      Some("proj-v1.1.0".into()),
+      None
+    );
+    ReleaseSummary::new(Some("owner/repo".into()), None, vec![proj])
+  }
+
+  #[test]
+  fn test_render_default() {
+    let notification: Notification = serde_yaml::from_str("url: https://hooks.example.com/abc").unwrap();
+    let body: serde_json::Value = serde_json::from_str(&notification.render(&summary()).unwrap()).unwrap();
+    assert_eq!("1.1.0", body["projects"][0]["version"]);
+    assert_eq!("proj-v1.1.0", body["projects"][0]["tag"]);
+    assert_eq!("hooks.example.com", notification.describe());
+  }
+
+  #[test]
+  fn test_render_template() {
+    let notification: Notification = serde_yaml::from_str(
+      r#"
+url_env: SLACK_URL
+body: '{"text": "{% for p in projects %}{{ p.name }} {{ p.previous }} -> {{ p.version }}{% endfor %}"}'
+"#
+    )
+    .unwrap();
+    assert_eq!(r#"{"text": "proj 1.0.0 -> 1.1.0"}"#, notification.render(&summary()).unwrap());
+    assert_eq!("$SLACK_URL", notification.describe());
+  }
+
+  #[test]
+  fn test_render_notes() {
+    let publisher: NotesPublisher = serde_yaml::from_str(
+      r#"
+url: https://wiki.example.com/rest/api/content
+method: PUT
+headers:
+  X-Release: "{{ projects[0].tag }}"
+body: '{"title": "{{ repository }}", "body": {{ notes_json }}}'
+"#
+    )
+    .unwrap();
+    let (headers, body) = publisher.render(&summary(), "# Notes\n\n\"Quoted\"").unwrap();
+    assert_eq!(vec![("X-Release".to_string(), "proj-v1.1.0".to_string())], headers);
+    let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!("owner/repo", body["title"]);
+    assert_eq!("# Notes\n\n\"Quoted\"", body["body"]);
+    assert_eq!("builtin:release-notes", publisher.template());
+  }
+
+  #[test]
+  fn test_render_notes_default() {
+    let publisher: NotesPublisher = serde_yaml::from_str("url_env: NOTES_URL").unwrap();
+    let (headers, body) = publisher.render(&summary(), "notes").unwrap();
+    assert!(headers.is_empty());
+    let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!("notes", body["notes"]);
+    assert_eq!("1.1.0", body["projects"][0]["version"]);
+    assert!(body.get("notes_json").is_none());
+  }
+
+  #[test]
+  fn test_validate() {
+    let both: Notification = serde_yaml::from_str("url: https://a\nurl_env: A").unwrap();
+    assert!(both.validate().is_err());
+    let neither: Notification = serde_yaml::from_str("body: hi").unwrap();
+    assert!(neither.validate().is_err());
+  }
+}
@@ -1,16 +1,26 @@
 //! The way we output things to the user.
 
 use crate::analyze::Analysis;
-use crate::commands::{failed_hashes, InfoShow};
+use crate::commands::{EarlyInfo, InfoShow};
 use crate::config::{Project, ProjectId, Size};
-use crate::errors::{Context as _, Result};
+use crate::deprecate::Fixed;
+use crate::drift::Drift;
+use crate::errors::{Context as _, Error, Result};
+use crate::git::{GithubInfo, RangeWarning};
 use crate::github::Changes;
-use crate::mono::ChangelogEntry;
-use crate::mono::{Mono, Plan};
+use crate::mono::{ChainWrite, ChangelogEntry, LoggedCommit, LoggedPr, UncoveredCommit};
+use crate::mono::{failed_hashes, AuditReport, DryRunHooks, FileOwners, Mono, NextVersion, PastRelease, Plan, Published,
+                  Rollback, VersionChange};
 use crate::state::StateRead;
 use crate::template::{construct_changelog_html, read_template};
+use chrono::{SecondsFormat, Utc};
+use liquid::{ParserBuilder, Template};
+use serde::Serialize;
 use serde_json::json;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
 
 pub struct Output {}
 
@@ -21,63 +31,169 @@ impl Default for Output {
 impl Output {
   pub fn new() -> Output { Output {} }
   pub fn check(&self) -> CheckOutput { CheckOutput::new() }
-  pub fn projects(&self, wide: bool, vers_only: bool) -> ProjOutput { ProjOutput::new(wide, vers_only) }
+  pub fn projects(&self, wide: bool) -> ProjOutput { ProjOutput::new(wide) }
   pub fn info(&self, show: InfoShow) -> ProjOutput { ProjOutput::info(show) }
   pub fn diff(&self) -> DiffOutput { DiffOutput::new() }
   pub fn files(&self) -> FilesOutput { FilesOutput::new() }
+  pub fn env(&self) -> EnvOutput { EnvOutput::new() }
+  pub fn bump(&self) -> BumpOutput { BumpOutput::new() }
   pub fn changes(&self) -> ChangesOutput { ChangesOutput::new() }
   pub fn plan(&self) -> PlanOutput { PlanOutput::new() }
   pub fn release(&self) -> ReleaseOutput { ReleaseOutput::new() }
+  pub fn preview(&self) -> PreviewOutput { PreviewOutput::new() }
   pub fn resume(&self) -> ResumeOutput { ResumeOutput::new() }
+  pub fn rollback(&self) -> RollbackOutput { RollbackOutput::new() }
+  pub fn adopt(&self) -> AdoptOutput { AdoptOutput::new() }
+  pub fn config_fix(&self) -> ConfigFixOutput { ConfigFixOutput::new() }
+  pub fn changelog(&self) -> ChangelogOutput { ChangelogOutput::new() }
+  pub fn notes(&self) -> NotesOutput { NotesOutput::new() }
+  pub fn verify(&self) -> VerifyOutput { VerifyOutput::new() }
+  pub fn audit(&self, json: bool) -> AuditOutput { AuditOutput::new(json) }
+  pub fn owners(&self, json: bool) -> OwnersOutput { OwnersOutput::new(json) }
+  pub fn pin(&self) -> PinOutput { PinOutput::new() }
 }
 
-pub struct CheckOutput {}
+/// Warnings found outside of a command's output, such as deprecated config keys, or problems found while the repo
+/// is opened. The command's `Warnings` gathers these along with its own.
+static PENDING_WARNINGS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// Report a warning that isn't found through a command's output, to be printed (or included in JSON output) with
+/// the command's other warnings.
+pub fn warn_later<M: ToString>(message: M) { PENDING_WARNINGS.lock().unwrap().push(message.to_string()); }
+
+/// Print the warnings that no command output gathered, such as when the command has no warnings of its own.
+pub fn eprintln_pending_warnings() { Warnings::new().eprintln(); }
+
+/// Whether the command writes JSON (or another format that a program reads) to stdout.
+static JSON_STDOUT: AtomicBool = AtomicBool::new(false);
+
+/// Set whether the command writes JSON to stdout, which output from its hooks mustn't be mixed into.
+pub fn set_json_stdout(json: bool) { JSON_STDOUT.store(json, Ordering::Relaxed); }
+
+/// Non-fatal problems found while a command runs. These are gathered instead of printed as they're found, so that
+/// they aren't mixed in with the command's results: they're printed to stderr all together after the results, or
+/// are included in JSON output. Any that are left when this is dropped (because the command failed) are printed
+/// then. The same warning is only kept once.
+#[derive(Default)]
+pub struct Warnings {
+  messages: Vec<String>
+}
+
+impl Warnings {
+  pub fn new() -> Warnings { Warnings { messages: Vec::new() } }
+
+  pub fn push<M: ToString>(&mut self, message: M) {
+    let message = message.to_string();
+    if !self.messages.contains(&message) {
+      self.messages.push(message);
+    }
+  }
+
+  pub fn extend<M: ToString>(&mut self, messages: &[M]) {
+    for message in messages {
+      self.push(message.to_string());
+    }
+  }
+
+  /// Add the warnings that were found outside of the command's output.
+  fn gather(&mut self) {
+    let pending = std::mem::take(&mut *PENDING_WARNINGS.lock().unwrap());
+    self.extend(&pending);
+  }
+
+  /// Take the warnings, to include them in JSON output instead of printing them.
+  fn take(&mut self) -> Vec<String> {
+    self.gather();
+    std::mem::take(&mut self.messages)
+  }
+
+  fn eprintln(&mut self) {
+    for message in self.take() {
+      eprintln!("{}", tr!("warning", message = message));
+    }
+  }
+}
+
+impl Drop for Warnings {
+  fn drop(&mut self) { self.eprintln(); }
+}
+
+pub struct CheckOutput {
+  warnings: Warnings
+}
 
 impl Default for CheckOutput {
   fn default() -> CheckOutput { CheckOutput::new() }
 }
 
 impl CheckOutput {
-  pub fn new() -> CheckOutput { CheckOutput {} }
+  pub fn new() -> CheckOutput { CheckOutput { warnings: Warnings::new() } }
   pub fn write_done(&mut self) -> Result<()> { Ok(()) }
 
+  pub fn write_violations(&mut self, violations: &[String]) {
+    for violation in violations {
+      eprintln!("{}", tr!("check.violation", violation = violation));
+    }
+  }
+
+  pub fn write_ambiguous_tags(&mut self, ambiguous: &[(String, String, Vec<String>)]) {
+    for (name, version, tags) in ambiguous {
+      let message = tr!("check.ambiguous_tags", name = name, version = version, tags = tags.join(", "), tag = tags[0]);
+      self.warnings.push(message);
+    }
+  }
+
   pub fn commit(&mut self) -> Result<()> {
-    println!("Check complete.");
+    println!("{}", tr!("check.complete"));
+    self.warnings.eprintln();
     Ok(())
   }
 }
 
-pub struct ResumeOutput {}
+pub struct ResumeOutput {
+  warnings: Warnings
+}
 
 impl Default for ResumeOutput {
   fn default() -> ResumeOutput { ResumeOutput::new() }
 }
 
 impl ResumeOutput {
-  pub fn new() -> ResumeOutput { ResumeOutput {} }
+  pub fn new() -> ResumeOutput { ResumeOutput { warnings: Warnings::new() } }
   pub fn write_done(&mut self) -> Result<()> { Ok(()) }
+  pub fn write_mirrored(&mut self, failed: Vec<(String, Error)>) { self.warnings.extend(&mirror_failures(failed)); }
 
   pub fn commit(&mut self) -> Result<()> {
-    println!("Release complete.");
+    println!("{}", tr!("release.complete"));
+    self.warnings.eprintln();
     Ok(())
   }
 }
 
 pub struct ProjOutput {
   wide: bool,
-  vers_only: bool,
   proj_lines: Vec<ProjLine>,
   info_only: bool,
-  show: InfoShow
+  show: InfoShow,
+  template: Option<Template>
 }
 
 impl ProjOutput {
-  pub fn new(wide: bool, vers_only: bool) -> ProjOutput {
-    ProjOutput { show: InfoShow::new(), info_only: false, wide, vers_only, proj_lines: Vec::new() }
+  pub fn new(wide: bool) -> ProjOutput {
+    ProjOutput { show: InfoShow::new(), info_only: false, wide, proj_lines: Vec::new(), template: None }
   }
 
   pub fn info(show: InfoShow) -> ProjOutput {
-    ProjOutput { info_only: true, show, wide: false, vers_only: false, proj_lines: Vec::new() }
+    ProjOutput { info_only: true, show, wide: false, proj_lines: Vec::new(), template: None }
+  }
+
+  /// Render each project with this Liquid template, instead of the usual table or JSON.
+  pub fn with_template(mut self, template: Option<&str>) -> Result<ProjOutput> {
+    if let Some(template) = template {
+      let parsed = ParserBuilder::with_stdlib().build()?.parse(template);
+      self.template = Some(parsed.with_context(|| format!("Illegal template \"{}\".", template))?);
+    }
+    Ok(self)
   }
 
   pub fn write_projects<I: Iterator<Item = Result<ProjLine>>>(&mut self, lines: I) -> Result<()> {
@@ -92,7 +208,11 @@ impl ProjOutput {
 
   pub fn commit(&mut self) -> Result<()> {
     let name_width = self.proj_lines.iter().map(|l| l.name.len()).max().unwrap_or(0);
-    if self.info_only {
+    if let Some(template) = &self.template {
+      for line in &self.proj_lines {
+        println!("{}", template.render(&line.to_liquid())?);
+      }
+    } else if self.info_only {
       let val = json!(self
         .proj_lines
         .iter()
@@ -123,9 +243,7 @@ impl ProjOutput {
       println!("{}", serde_json::to_string(&val)?);
     } else {
       for line in &self.proj_lines {
-        if self.vers_only {
-          println!("{}", line.version);
-        } else if self.wide {
+        if self.wide {
           println!("{:>6}. {:width$} : {}", line.id, line.name, line.version, width = name_width);
         } else {
           println!("{:width$} : {}", line.name, line.version, width = name_width);
@@ -168,6 +286,19 @@ impl ProjLine {
     let root = p.root().cloned();
     Ok(ProjLine { id: id.clone(), name, tag_prefix, tag_prefix_separator, version, full_version, root })
   }
+
+  /// The project as a template object, as `project` in a changelog, or each project of `get --template`.
+  pub fn to_liquid(&self) -> liquid::Object {
+    liquid::object!({
+      "id": self.id.to_string(),
+      "name": self.name,
+      "tag_prefix": self.tag_prefix.as_deref().unwrap_or_default(),
+      "tag_prefix_separator": self.tag_prefix_separator,
+      "version": self.version,
+      "full_version": self.full_version.as_deref().unwrap_or_default(),
+      "root": self.root.as_deref().unwrap_or_default(),
+    })
+  }
 }
 
 pub struct DiffOutput {
@@ -196,26 +327,26 @@ impl DiffOutput {
 
 fn println_analysis(analysis: &Analysis) {
   if !analysis.older().is_empty() {
-    println!("Removed projects:");
+    println!("{}", tr!("diff.removed"));
     for mark in analysis.older() {
       println!("  {} : {}", mark.name(), mark.mark());
     }
   }
 
   if !analysis.newer().is_empty() {
-    println!("New projects:");
+    println!("{}", tr!("diff.new"));
     for mark in analysis.newer() {
       println!("  {} : {}", mark.name(), mark.mark());
     }
   }
 
   if analysis.changes().iter().any(|c| c.value().is_some()) {
-    println!("Changed versions:");
+    println!("{}", tr!("diff.changed"));
     for change in analysis.changes().iter().filter(|c| c.value().is_some()) {
       print!("  {}", change.new_mark().name());
 
       if let Some((o, _)) = change.name().as_ref() {
-        print!(" (was \"{}\")", o);
+        print!("{}", tr!("diff.was", name = o));
       }
       if let Some((o, n)) = change.value().as_ref() {
         print!(" : {} -> {}", o, n);
@@ -227,12 +358,12 @@ fn println_analysis(analysis: &Analysis) {
   }
 
   if analysis.changes().iter().any(|c| c.value().is_none()) {
-    println!("Unchanged versions:");
+    println!("{}", tr!("diff.unchanged"));
     for change in analysis.changes().iter().filter(|c| c.value().is_none()) {
       print!("  {}", change.new_mark().name());
 
       if let Some((o, _)) = change.name().as_ref() {
-        print!(" (was \"{}\")", o);
+        print!("{}", tr!("diff.was", name = o));
       }
       print!(" : {}", change.new_mark().mark());
       println!();
@@ -240,6 +371,54 @@ fn println_analysis(analysis: &Analysis) {
   }
 }
 
+pub struct BumpOutput {
+  bumped: Vec<(String, String, String)>
+}
+
+impl Default for BumpOutput {
+  fn default() -> BumpOutput { BumpOutput::new() }
+}
+
+impl BumpOutput {
+  pub fn new() -> BumpOutput { BumpOutput { bumped: Vec::new() } }
+  pub fn write_bumped(&mut self, name: String, curt: String, targ: String) { self.bumped.push((name, curt, targ)); }
+
+  pub fn commit(&mut self) {
+    self.bumped.sort();
+    for (name, curt, targ) in &self.bumped {
+      println!("  {} : {} -> {}", name, curt, targ);
+    }
+  }
+}
+
+pub struct EnvOutput {
+  env: serde_json::Value
+}
+
+impl Default for EnvOutput {
+  fn default() -> EnvOutput { EnvOutput::new() }
+}
+
+impl EnvOutput {
+  pub fn new() -> EnvOutput { EnvOutput { env: json!({}) } }
+
+  pub fn write_env(&mut self, info: &EarlyInfo, branch: Option<&str>, github: Option<&GithubInfo>) -> Result<()> {
+    self.env = json!({
+      "working_dir": info.working_dir(),
+      "project_count": info.project_count(),
+      "vcs": { "min": info.vcs().min().to_string(), "max": info.vcs().max().to_string() },
+      "branch": branch,
+      "github": github.map(|g| json!({ "host": g.host(), "owner": g.owner_name(), "repo": g.repo_name() }))
+    });
+    Ok(())
+  }
+
+  pub fn commit(&mut self) -> Result<()> {
+    println!("{}", serde_json::to_string(&self.env)?);
+    Ok(())
+  }
+}
+
 pub struct FilesOutput {
   files: Vec<(String, String)>
 }
@@ -284,7 +463,7 @@ impl ChangesOutput {
     if let Some(changes) = &self.changes {
       println_changes(changes)
     } else {
-      println!("No changes.");
+      println!("{}", tr!("changes.none"));
     }
   }
 }
@@ -314,7 +493,10 @@ pub struct PlanOutput {
   plan: Option<Plan>,
   id: Option<ProjectId>,
   template: Option<String>,
-  orig_dir: Option<PathBuf>
+  orig_dir: Option<PathBuf>,
+  json: bool,
+  show_ineffective: bool,
+  warnings: Warnings
 }
 
 impl Default for PlanOutput {
@@ -322,11 +504,34 @@ impl Default for PlanOutput {
 }
 
 impl PlanOutput {
-  pub fn new() -> PlanOutput { PlanOutput { plan: None, id: None, template: None, orig_dir: None } }
+  pub fn new() -> PlanOutput {
+    PlanOutput {
+      plan: None,
+      id: None,
+      template: None,
+      orig_dir: None,
+      json: false,
+      show_ineffective: false,
+      warnings: Warnings::new()
+    }
+  }
+
+  /// Print the plan as JSON (see `JsonPlan`), instead of as text.
+  pub fn with_json(mut self, json: bool) -> PlanOutput {
+    self.json = json;
+    self
+  }
+
+  /// Also list the commits that didn't apply to any project, and those that couldn't be sized.
+  pub fn with_ineffective(mut self, show: bool) -> PlanOutput {
+    self.show_ineffective = show;
+    self
+  }
 
   pub fn write_plan(
     &mut self, plan: Plan, id: Option<ProjectId>, template: Option<&str>, orig_dir: &Path
   ) -> Result<()> {
+    self.warnings.extend(plan.warnings());
     self.plan = Some(plan);
     self.id = id;
     self.template = template.map(|s| s.to_string());
@@ -337,16 +542,67 @@ impl PlanOutput {
 
   pub async fn commit(&mut self, mono: &Mono) -> Result<()> {
     if let Some(plan) = &self.plan {
-      self.println_plan(plan, mono).await
+      if self.json {
+        self.warnings.gather();
+        let json = self.json_plan(plan, mono, self.warnings.messages.clone())?;
+        println!("{}", serde_json::to_string_pretty(&json)?);
+        self.warnings.take();
+        return Ok(());
+      }
+      self.println_plan(plan, mono).await?;
     } else {
-      println!("No plan.");
-      Ok(())
+      println!("{}", tr!("plan.none"));
     }
+    self.warnings.eprintln();
+    Ok(())
   }
 
   async fn println_plan(&self, plan: &Plan, mono: &Mono) -> Result<()> {
+    if plan.offline() && self.template.is_none() {
+      println!("{}", tr!("plan.offline"));
+    }
     self.println_plan_incrs(plan, mono).await?;
+    if self.template.is_none() {
+      self.println_plan_chains(plan, mono)?;
+    }
     self.println_plan_ineff(plan);
+    if self.show_ineffective && self.template.is_none() {
+      self.println_plan_uncovered(plan);
+    }
+    Ok(())
+  }
+
+  /// The chain writes of the plan that involve the `--id` project, or all of them.
+  fn chain_writes(&self, plan: &Plan, mono: &Mono) -> Result<Vec<ChainWrite>> {
+    let mut writes = mono.planned_chain_writes(plan)?;
+    if let Some(id) = &self.id {
+      writes.retain(|w| w.dependency() == id || w.dependent() == id);
+    }
+    Ok(writes)
+  }
+
+  fn println_plan_chains(&self, plan: &Plan, mono: &Mono) -> Result<()> {
+    let writes = self.chain_writes(plan, mono)?;
+    if writes.is_empty() {
+      return Ok(());
+    }
+
+    println!("{}", tr!("plan.chains"));
+    for write in writes {
+      let dependent = mono.get_project(write.dependent())?.name();
+      let dependency = mono.get_project(write.dependency())?.name();
+      println!(
+        "{}",
+        tr!(
+          "plan.chain",
+          dependent = dependent,
+          dependency = dependency,
+          before = write.before(),
+          after = write.after(),
+          size = write.size()
+        )
+      );
+    }
     Ok(())
   }
 
@@ -356,7 +612,7 @@ impl PlanOutput {
     }
 
     if plan.incrs().is_empty() {
-      println!("(No projects)");
+      println!("{}", tr!("plan.no_projects"));
       return Ok(());
     }
 
@@ -380,15 +636,19 @@ impl PlanOutput {
 
       if let Some(prev_vers) = prev_vers {
         if size.is_failure() {
-          println!("  ! Non-parseable conventional commits: {}", failed_hashes(plan));
+          println!("{}", tr!("plan.failed", hashes = failed_hashes(plan)));
         } else if size != &Size::Empty {
-          let target = size.apply(&prev_vers)?;
+          let applied = curt_proj.applied_size(*size, &prev_vers)?;
+          if &applied != size {
+            println!("{}", tr!("plan.zero_major", applied = applied));
+          }
+          let target = curt_proj.bump(applied, &prev_vers)?;
           if Size::less_than(&curt_vers, &target)? {
             if curt_proj.verify_restrictions(&target).is_err() {
-              println!("  ! Illegal size change for restricted project {}.", curt_proj.id());
+              println!("{}", tr!("plan.restricted", id = curt_proj.id()));
             }
           } else if curt_proj.verify_restrictions(&curt_vers).is_err() {
-            println!("  ! Illegal size change for restricted project {}.", curt_proj.id());
+            println!("{}", tr!("plan.restricted", id = curt_proj.id()));
           }
         }
       }
@@ -401,9 +661,10 @@ impl PlanOutput {
             }
             if pr.number() == 0 {
               // "PR zero" is the top-level set of commits.
-              println!("  Other commits : {}", size);
+              let name = if plan.offline() { tr!("plan.local_commits") } else { tr!("plan.other_commits") };
+              println!("  {} : {}", name, size);
             } else {
-              println!("  PR {} : {}", pr.number(), size);
+              println!("{}", tr!("plan.pr", number = pr.number(), size = size));
             }
             for c in pr.commits().iter().filter(|c| c.included()) {
               let symbol = if c.duplicate() {
@@ -413,11 +674,14 @@ impl PlanOutput {
               } else {
                 " "
               };
-              println!("    {} commit {} ({}) : {}", symbol, &c.oid()[.. 7], c.size(), c.message().trim());
+              let breaking = if c.breaking() { tr!("plan.breaking") } else { String::new() };
+              let reverted = if c.reverted() { tr!("plan.reverted") } else { String::new() };
+              let oid = &c.oid()[.. 7];
+              println!("    {} commit {} ({}{}{}) : {}", symbol, oid, c.size(), breaking, reverted, c.message().trim());
             }
           }
           ChangelogEntry::Dep(proj_id, proj_name) => {
-            println!("  Depends on: {} ({})", proj_name, proj_id);
+            println!("{}", tr!("plan.depends", name = proj_name, id = proj_id));
           }
         }
       }
@@ -432,9 +696,9 @@ impl PlanOutput {
         continue;
       }
       if pr.number() == 0 {
-        println!("  Unapplied commits");
+        println!("{}", tr!("plan.unapplied_commits"));
       } else {
-        println!("  Unapplied PR {}", pr.number());
+        println!("{}", tr!("plan.unapplied_pr", number = pr.number()));
       }
       for c in pr.commits().iter().filter(|c| c.included()) {
         let symbol = if c.duplicate() {
@@ -449,6 +713,116 @@ impl PlanOutput {
     }
   }
 
+  /// The commits that didn't apply to any project, with the files they changed, under their PRs.
+  fn println_plan_uncovered(&self, plan: &Plan) {
+    if !plan.uncovered().is_empty() {
+      println!("{}", tr!("plan.uncovered"));
+    }
+    let mut on_pr = None;
+    for commit in plan.uncovered() {
+      if on_pr != Some(commit.pr()) {
+        on_pr = Some(commit.pr());
+        let ineffective = plan.ineffective().iter().find(|pr| pr.number() == commit.pr());
+        match ineffective {
+          _ if commit.pr() == 0 => println!("{}", tr!("plan.uncovered_local")),
+          Some(pr) => println!("{}", tr!("plan.ineffective_pr", number = pr.number(), title = pr.title())),
+          None => println!("{}", tr!("plan.uncovered_pr", number = commit.pr()))
+        }
+      }
+      println!("{}", tr!("plan.uncovered_commit", oid = &commit.oid()[.. 7], summary = commit.summary()));
+      for file in commit.files() {
+        println!("      {}", file);
+      }
+    }
+
+    if !plan.info().failed_commits().is_empty() {
+      println!("{}", tr!("plan.unsized"));
+      for commit in plan.info().failed_commits() {
+        println!("{}", tr!("plan.uncovered_commit", oid = &commit.id()[.. 7], summary = commit.summary()));
+      }
+    }
+  }
+
+  fn json_plan(&self, plan: &Plan, mono: &Mono, warnings: Vec<String>) -> Result<JsonPlan> {
+    let curt_config = mono.config();
+    let prev_config = curt_config.slice_to_prev(mono.repo())?;
+
+    let mut projects = Vec::new();
+    for proj in curt_config.projects() {
+      let id = proj.id();
+      let (size, changelog) = match plan.incrs().get(id) {
+        Some(incr) if self.id.as_ref().map(|self_id| self_id == id).unwrap_or(true) => incr,
+        _ => continue
+      };
+
+      let previous = prev_config.get_value(id).with_context(|| format!("Unable to find prev {} value.", id))?;
+      let current = proj.get_value(curt_config.state_read())?;
+      let applied = match &previous {
+        Some(prev) => proj.applied_size(*size, prev)?,
+        None => *size
+      };
+      let target = match &previous {
+        Some(prev) if !size.is_failure() && size != &Size::Empty => Some(proj.bump(applied, prev)?),
+        _ => None
+      };
+      let restricted = match &target {
+        Some(target) if Size::less_than(&current, target)? => proj.verify_restrictions(target).is_err(),
+        Some(_) => proj.verify_restrictions(&current).is_err(),
+        None => false
+      };
+
+      let mut prs = Vec::new();
+      let mut deps = Vec::new();
+      for entry in changelog.entries() {
+        match entry {
+          ChangelogEntry::Pr(pr, size) => prs.push(JsonPr::from(pr, Some(size))),
+          ChangelogEntry::Dep(id, name) => deps.push(JsonDep { id: id.to_string(), name: name.clone() })
+        }
+      }
+
+      projects.push(JsonProject {
+        id: id.to_string(),
+        name: proj.name().to_string(),
+        size: size.to_string(),
+        applied: Some(applied).filter(|a| a != size).map(|a| a.to_string()),
+        previous,
+        current,
+        target,
+        restricted,
+        prs,
+        deps
+      });
+    }
+
+    Ok(JsonPlan {
+      schema: PLAN_SCHEMA_VERSION,
+      created: Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+      commit: mono.repo().peek_commit_oid("HEAD"),
+      offline: plan.offline(),
+      projects,
+      chain_writes: self
+        .chain_writes(plan, mono)?
+        .iter()
+        .map(|w| JsonChainWrite {
+          dependency: w.dependency().to_string(),
+          dependent: w.dependent().to_string(),
+          before: w.before().to_string(),
+          after: w.after().to_string(),
+          size: w.size().to_string()
+        })
+        .collect(),
+      ineffective: plan.ineffective().iter().map(|pr| JsonPr::from(pr, None)).collect(),
+      uncovered: plan.uncovered().to_vec(),
+      failed_commits: plan
+        .info()
+        .failed_commits()
+        .iter()
+        .map(|c| JsonFailedCommit { oid: c.id().to_string(), summary: c.summary().to_string() })
+        .collect(),
+      warnings
+    })
+  }
+
   async fn println_template_plan(&self, plan: &Plan, mono: &Mono) -> Result<()> {
     let orig_dir = self.orig_dir.as_ref().ok_or_else(|| bad!("No orig dir for template format."))?;
     let tmpl = self.template.as_ref().ok_or_else(|| bad!("No template for template format."))?;
@@ -469,9 +843,11 @@ impl PlanOutput {
         .unwrap_or_else(|| panic!("No such project {}.", id));
 
       let proj = curt_config.get_project(id).ok_or_else(|| bad!("No such project ID {}", id))?;
+      let blocks = proj.changelog_blocks()?;
+      let sections = proj.changelog_sections();
       let proj = ProjLine::from_version(proj, curt_vers.clone())?;
 
-      let html = construct_changelog_html(changelog, proj, &curt_vers, "".to_string(), template)?;
+      let html = construct_changelog_html(changelog, proj, &curt_vers, "".to_string(), blocks, &sections, template)?;
       println!("{}", html);
       break;
     }
@@ -480,8 +856,474 @@ impl PlanOutput {
   }
 }
 
+/// The version of the `plan --format json` schema. This is increased whenever a field is removed, renamed, or
+/// changes its meaning; new fields may be added without changing it.
+const PLAN_SCHEMA_VERSION: u32 = 1;
+
+/// The plan, as printed by `plan --format json`. This is documented in the reference doc: keep the two in sync.
+#[derive(Serialize)]
+struct JsonPlan {
+  schema: u32,
+  created: String,
+  commit: Option<String>,
+  offline: bool,
+  projects: Vec<JsonProject>,
+  chain_writes: Vec<JsonChainWrite>,
+  ineffective: Vec<JsonPr>,
+  uncovered: Vec<UncoveredCommit>,
+  failed_commits: Vec<JsonFailedCommit>,
+  warnings: Vec<String>
+}
+
+#[derive(Serialize)]
+struct JsonProject {
+  id: String,
+  name: String,
+  size: String,
+  applied: Option<String>,
+  previous: Option<String>,
+  current: String,
+  target: Option<String>,
+  restricted: bool,
+  prs: Vec<JsonPr>,
+  deps: Vec<JsonDep>
+}
+
+#[derive(Serialize)]
+struct JsonPr {
+  number: u32,
+  title: String,
+  size: Option<String>,
+  url: Option<String>,
+  changelog: Option<String>,
+  commits: Vec<JsonCommit>
+}
+
+impl JsonPr {
+  fn from(pr: &LoggedPr, size: Option<&Size>) -> JsonPr {
+    JsonPr {
+      number: pr.number(),
+      title: pr.title().to_string(),
+      size: size.map(|s| s.to_string()),
+      url: pr.url().clone(),
+      changelog: pr.changelog().map(|c| c.to_string()),
+      commits: pr.commits().iter().map(JsonCommit::from).collect()
+    }
+  }
+}
+
+#[derive(Serialize)]
+struct JsonCommit {
+  oid: String,
+  summary: String,
+  kind: String,
+  scope: Option<String>,
+  size: String,
+  breaking: bool,
+  applies: bool,
+  duplicate: bool,
+  reverted: bool,
+  url: Option<String>
+}
+
+impl JsonCommit {
+  fn from(c: &LoggedCommit) -> JsonCommit {
+    JsonCommit {
+      oid: c.oid().to_string(),
+      summary: c.summary().to_string(),
+      kind: c.kind().to_string(),
+      scope: c.scope().map(|s| s.to_string()),
+      size: c.size().to_string(),
+      breaking: c.breaking(),
+      applies: c.applies(),
+      duplicate: c.duplicate(),
+      reverted: c.reverted(),
+      url: c.url().clone()
+    }
+  }
+}
+
+#[derive(Serialize)]
+struct JsonDep {
+  id: String,
+  name: String
+}
+
+#[derive(Serialize)]
+struct JsonChainWrite {
+  dependency: String,
+  dependent: String,
+  before: String,
+  after: String,
+  size: String
+}
+
+#[derive(Serialize)]
+struct JsonFailedCommit {
+  oid: String,
+  summary: String
+}
+
+pub struct PreviewOutput {
+  lines: Vec<String>,
+  warnings: Warnings
+}
+
+impl Default for PreviewOutput {
+  fn default() -> PreviewOutput { PreviewOutput::new() }
+}
+
+impl PreviewOutput {
+  pub fn new() -> PreviewOutput { PreviewOutput { lines: Vec::new(), warnings: Warnings::new() } }
+  pub fn write_warnings(&mut self, warnings: &[RangeWarning]) { self.warnings.extend(warnings); }
+
+  pub fn write_next(&mut self, next: &NextVersion) {
+    let name = &next.name;
+    let size = if next.applied == next.size {
+      next.size.to_string()
+    } else {
+      tr!("preview.zero_major", size = next.size, applied = next.applied)
+    };
+    let line = match &next.change {
+      VersionChange::NoChange { curt, locked, .. } => {
+        let locked = if *locked { tr!("release.locked") } else { String::new() };
+        tr!("preview.no_change", name = name, current = curt, locked = locked)
+      }
+      VersionChange::Released { curt, .. } => tr!("preview.released", name = name, current = curt),
+      VersionChange::Changed { curt, target, .. } if next.pin => {
+        tr!("preview.pinned", name = name, current = curt, next = target)
+      }
+      VersionChange::Changed { curt, target, .. } => {
+        tr!("preview.changed", name = name, current = curt, next = target, size = size)
+      }
+      VersionChange::Forward { curt, target, .. } => {
+        tr!("preview.forward", name = name, current = curt, next = target, size = size)
+      }
+      VersionChange::New { curt } => tr!("preview.new", name = name, current = curt)
+    };
+    self.lines.push(line);
+  }
+
+  pub fn commit(&mut self) {
+    if self.lines.is_empty() {
+      println!("{}", tr!("preview.none"));
+    }
+    for line in &self.lines {
+      println!("{}", line);
+    }
+    self.warnings.eprintln();
+  }
+}
+
+pub struct RollbackOutput {
+  steps: Vec<String>,
+  dry: bool
+}
+
+impl Default for RollbackOutput {
+  fn default() -> RollbackOutput { RollbackOutput::new() }
+}
+
+impl RollbackOutput {
+  pub fn new() -> RollbackOutput { RollbackOutput { steps: Vec::new(), dry: false } }
+  pub fn write_dry(&mut self) { self.dry = true; }
+
+  pub fn write_rollback(&mut self, rollback: &Rollback) {
+    if let Some(commit) = rollback.release_commit() {
+      self.steps.push(tr!("rollback.revert", commit = &commit[.. 7]));
+    }
+    for tag in rollback.tags() {
+      self.steps.push(tr!("rollback.delete_tag", tag = tag));
+    }
+    self.steps.push(tr!("rollback.move_prev", tag = rollback.prev_tag(), commit = &rollback.prev_commit()[.. 7]));
+  }
+
+  pub fn commit(&mut self) {
+    for step in &self.steps {
+      println!("  {}", step);
+    }
+    if self.dry {
+      println!("{}", tr!("dry_run"));
+    } else {
+      println!("{}", tr!("rollback.complete"));
+    }
+  }
+}
+
+pub struct AdoptOutput {
+  done: Option<String>,
+  warnings: Warnings
+}
+
+impl Default for AdoptOutput {
+  fn default() -> AdoptOutput { AdoptOutput::new() }
+}
+
+impl AdoptOutput {
+  pub fn new() -> AdoptOutput { AdoptOutput { done: None, warnings: Warnings::new() } }
+  pub fn write_resuming(&mut self) {
+    println!("{}", tr!("adopt.resuming"));
+  }
+  pub fn write_dry(&mut self) { self.done = None; }
+  pub fn write_done(&mut self, prev_tag: &str) { self.done = Some(prev_tag.to_string()); }
+
+  pub fn write_project(&mut self, name: &str, releases: &[PastRelease]) {
+    match releases.last() {
+      Some(latest) => println!(
+        "{}",
+        tr!("adopt.project", name = name, count = releases.len(), version = latest.version(), tag = latest.tag())
+      ),
+      None => println!("{}", tr!("adopt.untagged", name = name))
+    }
+  }
+
+  pub fn write_unreleased(&mut self, name: &str, tag: &str) {
+    self.warnings.push(tr!("adopt.unreleased", name = name, tag = tag));
+  }
+
+  pub fn write_logged(&mut self, name: &str, version: &str) {
+    println!("  {}", tr!("adopt.logged", name = name, version = version));
+  }
+
+  pub fn commit(&mut self) {
+    match &self.done {
+      Some(prev_tag) => println!("{}", tr!("adopt.complete", tag = prev_tag)),
+      None => println!("{}", tr!("dry_run"))
+    }
+    self.warnings.eprintln();
+  }
+}
+
+pub struct ConfigFixOutput {
+  fixed: Vec<String>,
+  remaining: Vec<String>,
+  dry: bool
+}
+
+impl Default for ConfigFixOutput {
+  fn default() -> ConfigFixOutput { ConfigFixOutput::new() }
+}
+
+impl ConfigFixOutput {
+  pub fn new() -> ConfigFixOutput { ConfigFixOutput { fixed: Vec::new(), remaining: Vec::new(), dry: false } }
+  pub fn write_dry(&mut self) { self.dry = true; }
+
+  pub fn write_fixed(&mut self, fixed: &Fixed) {
+    for dep in fixed.fixed() {
+      let line = dep.line().unwrap_or_default();
+      self.fixed.push(tr!("deprecated.fixed", location = dep.location(), replacement = dep.replacement(), line = line));
+    }
+    for dep in fixed.remaining() {
+      self.remaining.push(tr!("deprecated.remaining", key = dep));
+    }
+  }
+
+  pub fn commit(&mut self) {
+    if self.fixed.is_empty() && self.remaining.is_empty() {
+      println!("{}", tr!("deprecated.none"));
+      return;
+    }
+    for line in &self.fixed {
+      println!("{}", line);
+    }
+    for line in &self.remaining {
+      eprintln!("{}", line);
+    }
+    if self.dry {
+      println!("{}", tr!("dry_run"));
+    } else if !self.fixed.is_empty() {
+      println!("{}", tr!("deprecated.complete"));
+    }
+  }
+}
+
+pub struct ChangelogOutput {
+  regenerated: Option<String>
+}
+
+impl Default for ChangelogOutput {
+  fn default() -> ChangelogOutput { ChangelogOutput::new() }
+}
+
+impl ChangelogOutput {
+  pub fn new() -> ChangelogOutput { ChangelogOutput { regenerated: None } }
+
+  pub fn write_regenerated(&mut self, name: &str, version: &str, path: &Path) {
+    self.regenerated = Some(tr!("changelog.regenerated", name = name, version = version, path = path.display()));
+  }
+
+  pub fn commit(&mut self) {
+    if let Some(line) = &self.regenerated {
+      println!("{}", line);
+    }
+  }
+}
+
+pub struct NotesOutput {
+  text: Option<String>
+}
+
+impl Default for NotesOutput {
+  fn default() -> NotesOutput { NotesOutput::new() }
+}
+
+impl NotesOutput {
+  pub fn new() -> NotesOutput { NotesOutput { text: None } }
+
+  pub fn write_empty(&mut self) { self.text = Some(tr!("notes.none")); }
+  pub fn write_notes(&mut self, notes: String) { self.text = Some(notes.trim_end().to_string()); }
+  pub fn write_wrote(&mut self, path: PathBuf) { self.text = Some(tr!("notes.wrote", path = path.display())); }
+
+  pub fn commit(&mut self) {
+    if let Some(text) = &self.text {
+      println!("{}", text);
+    }
+  }
+}
+
+pub struct VerifyOutput {
+  verified: Option<String>
+}
+
+impl Default for VerifyOutput {
+  fn default() -> VerifyOutput { VerifyOutput::new() }
+}
+
+impl VerifyOutput {
+  pub fn new() -> VerifyOutput { VerifyOutput { verified: None } }
+
+  pub fn write_discrepancies(&mut self, discrepancies: &[String]) {
+    for discrepancy in discrepancies {
+      eprintln!("{}", tr!("check.violation", violation = discrepancy));
+    }
+  }
+
+  pub fn write_verified(&mut self, name: &str, tag: &str) {
+    self.verified = Some(tr!("verify.verified", name = name, tag = tag));
+  }
+
+  pub fn commit(&mut self) {
+    if let Some(line) = &self.verified {
+      println!("{}", line);
+    }
+  }
+}
+
+pub struct AuditOutput {
+  json: bool,
+  reports: Vec<AuditReport>
+}
+
+impl AuditOutput {
+  pub fn new(json: bool) -> AuditOutput { AuditOutput { json, reports: Vec::new() } }
+
+  pub fn write_reports(&mut self, reports: Vec<AuditReport>) { self.reports = reports; }
+
+  pub fn commit(&mut self) -> Result<()> {
+    if self.json {
+      println!("{}", serde_json::to_string_pretty(&self.reports)?);
+      return Ok(());
+    }
+
+    for report in &self.reports {
+      let (name, count) = (report.name(), report.versions());
+      if report.problems().is_empty() {
+        println!("{}", tr!("audit.clean", name = name, count = count));
+      } else {
+        println!("{}", tr!("audit.problems", name = name, count = count, problems = report.problems().len()));
+        for problem in report.problems() {
+          println!("{}", tr!("check.violation", violation = problem));
+        }
+      }
+    }
+    Ok(())
+  }
+}
+
+pub struct OwnersOutput {
+  json: bool,
+  owners: Vec<FileOwners>
+}
+
+impl OwnersOutput {
+  pub fn new(json: bool) -> OwnersOutput { OwnersOutput { json, owners: Vec::new() } }
+
+  pub fn write_owners(&mut self, owners: Vec<FileOwners>) { self.owners = owners; }
+
+  pub fn commit(&mut self) -> Result<()> {
+    if self.json {
+      println!("{}", serde_json::to_string_pretty(&self.owners)?);
+      return Ok(());
+    }
+
+    for file in &self.owners {
+      let (path, names) = (file.path(), file.owners().join(", "));
+      match file.owners().len() {
+        0 => println!("{}", tr!("owners.uncovered", path = path)),
+        1 => println!("{}", tr!("owners.owned", path = path, owners = names)),
+        _ => println!("{}", tr!("owners.overlap", path = path, owners = names))
+      }
+    }
+    Ok(())
+  }
+}
+
+pub struct PinOutput {
+  line: Option<String>
+}
+
+impl Default for PinOutput {
+  fn default() -> PinOutput { PinOutput::new() }
+}
+
+impl PinOutput {
+  pub fn new() -> PinOutput { PinOutput { line: None } }
+
+  pub fn write_pinned(&mut self, name: &str, version: Option<&str>) {
+    self.line = Some(match version {
+      Some(version) => tr!("pin.pinned", name = name, version = version),
+      None => tr!("pin.unpinned", name = name)
+    });
+  }
+
+  pub fn commit(&mut self) {
+    if let Some(line) = &self.line {
+      println!("{}", line);
+    }
+  }
+}
+
+/// Print a line of a hook's output (or error output, if `err`), marked with the hook that it came from.
+pub fn println_hook_output(prefix: &str, line: &str, err: bool) {
+  let line = tr!("hook_output", hook = prefix, line = line);
+  if hook_output_to_stderr(err) {
+    eprintln!("{}", line);
+  } else {
+    println!("{}", line);
+  }
+}
+
+/// Whether a line of a hook's output goes to stderr: its error output always does, and all of it does when stdout
+/// is JSON.
+fn hook_output_to_stderr(err: bool) -> bool { err || JSON_STDOUT.load(Ordering::Relaxed) }
+
+/// Warn about each mirror remote that a release couldn't be pushed to.
+pub fn eprintln_mirror_failures(failed: Vec<(String, Error)>) {
+  let mut warnings = Warnings::new();
+  warnings.extend(&mirror_failures(failed));
+  warnings.eprintln();
+}
+
+fn mirror_failures(failed: Vec<(String, Error)>) -> Vec<String> {
+  failed
+    .into_iter()
+    .map(|(target, e)| tr!("release.mirror_failed", target = target, error = format!("{:#}", e)))
+    .collect()
+}
+
 pub struct ReleaseOutput {
-  result: ReleaseResult
+  result: ReleaseResult,
+  warnings: Warnings
 }
 
 impl Default for ReleaseOutput {
@@ -489,19 +1331,117 @@ impl Default for ReleaseOutput {
 }
 
 impl ReleaseOutput {
-  pub fn new() -> ReleaseOutput { ReleaseOutput { result: ReleaseResult::Empty } }
+  pub fn new() -> ReleaseOutput { ReleaseOutput { result: ReleaseResult::Empty, warnings: Warnings::new() } }
 
   pub fn write_empty(&mut self) -> Result<()> {
     self.result = ReleaseResult::Empty;
     Ok(())
   }
 
+  pub fn write_warnings(&mut self, warnings: &[RangeWarning]) { self.warnings.extend(warnings); }
+
   pub fn write_logged(&mut self, path: PathBuf) { self.result.append_logged(path); }
+  pub fn write_manifest(&mut self, path: PathBuf) { self.result.append(ReleaseEvent::Manifest(path)); }
+  pub fn write_manifest_json(&mut self, json: String) { self.result.append(ReleaseEvent::ManifestJson(json)); }
+  pub fn write_notes(&mut self, path: PathBuf) { self.result.append(ReleaseEvent::Notes(path)); }
+  pub fn write_receipt(&mut self, receipt: String, hash: String) {
+    self.result.append(ReleaseEvent::Receipt(receipt, hash));
+  }
   pub fn write_done(&mut self) { self.result.append_done(); }
   pub fn write_commit(&mut self) { self.result.append_commit(); }
   pub fn write_pause(&mut self) { self.result.append_pause(); }
   pub fn write_dry(&mut self) { self.result.append_dry(); }
   pub fn write_wrote_changelogs(&mut self) { self.result.append_wrote_channgelogs(); }
+  pub fn write_tag_only(&mut self, target: Option<String>) { self.result.append_tag_only(target); }
+
+  pub fn write_published(&mut self, published: &Published) {
+    for name in published.ran() {
+      self.result.append(ReleaseEvent::Published(name.clone()));
+    }
+    if !published.skipped().is_empty() {
+      self.result.append(ReleaseEvent::Unpublished(published.skipped().join(", ")));
+    }
+  }
+
+  /// The release is a dry run, since these projects are set to `dry_run_default`.
+  pub fn write_dry_by_default(&mut self, names: Vec<String>) {
+    self.result.append(ReleaseEvent::DryByDefault(names.join(", ")));
+  }
+
+  /// The unified diff of each file that the release would change.
+  pub fn write_diffs(&mut self, diffs: Vec<String>) {
+    if diffs.is_empty() {
+      self.result.append(ReleaseEvent::NoDiff);
+    }
+    for diff in diffs {
+      self.result.append(ReleaseEvent::Diff(diff));
+    }
+  }
+
+  pub fn write_dry_run_hooks(&mut self, hooks: &DryRunHooks) {
+    for (name, hook) in hooks.ran() {
+      self.result.append(ReleaseEvent::DryRunHook(name.clone(), hook.clone()));
+    }
+    for (name, hook) in hooks.skipped() {
+      self.result.append(ReleaseEvent::SkippedHook(name.clone(), hook.clone()));
+    }
+  }
+
+  /// Dependents left out of a partial release.
+  pub fn write_unpicked(&mut self, names: Vec<String>) {
+    if !names.is_empty() {
+      self.warnings.push(tr!("release.unpicked", names = names.join(", ")));
+    }
+  }
+
+  /// A project released at a version that breaks its constraints, because of `--force`.
+  pub fn write_forced(&mut self, name: &str, version: &str, reason: &str) {
+    self.warnings.push(tr!("release.forced", name = name, version = version, reason = reason));
+  }
+
+  /// A project whose increment is downgraded by its `zero_major_policy`.
+  pub fn write_zero_major(&mut self, name: &str, size: Size, applied: Size) {
+    self.result.append(ReleaseEvent::ZeroMajor(name.to_string(), size, applied));
+  }
+
+  /// Drift from the saved plan: if the release stops because of it, it's still printed.
+  pub fn write_drift(&mut self, drift: &[Drift]) {
+    if !drift.is_empty() {
+      let lines: Vec<_> = drift.iter().map(|d| d.to_string()).collect();
+      self.warnings.push(format!("{}\n{}", tr!("release.drift"), lines.join("\n")));
+    }
+  }
+
+  /// Webhook failures don't fail the release, which has already succeeded.
+  pub fn write_notified(&mut self, failed: Vec<(String, Error)>) {
+    for (target, e) in failed {
+      self.warnings.push(tr!("release.notify_failed", target = target, error = format!("{:#}", e)));
+    }
+  }
+
+  /// Like webhooks, notes publishers that fail don't fail the release.
+  pub fn write_notes_published(&mut self, published: Vec<(String, Result<()>)>, dry: bool) {
+    for (target, result) in published {
+      match result {
+        Ok(()) => self.result.append(ReleaseEvent::NotesPublished(dry, target)),
+        Err(e) => self.warnings.push(tr!("release.notes_failed", target = target, error = format!("{:#}", e)))
+      }
+    }
+  }
+
+  pub fn write_mirrored(&mut self, failed: Vec<(String, Error)>) { self.warnings.extend(&mirror_failures(failed)); }
+
+  pub fn write_metrics(&mut self, failed: Vec<(String, Error)>) {
+    for (target, e) in failed {
+      self.warnings.push(tr!("release.metrics_failed", target = target, error = format!("{:#}", e)));
+    }
+  }
+
+  pub fn write_logged_timing(&mut self, count: usize, elapsed: Duration) {
+    if count > 0 {
+      self.result.append_logged_timing(count, elapsed);
+    }
+  }
 
   pub fn write_changed(&mut self, name: String, prev: String, curt: String, targ: String) {
     self.result.append_changed(name, prev, curt, targ);
@@ -517,7 +1457,12 @@ impl ReleaseOutput {
 
   pub fn write_new(&mut self, all: bool, name: String, curt: String) { self.result.append_new(all, name, curt); }
 
-  pub fn commit(&mut self) { self.result.commit(); }
+  pub fn write_deferred(&mut self, name: String, targ: String) { self.result.append_deferred(name, targ); }
+
+  pub fn commit(&mut self) {
+    self.result.commit();
+    self.warnings.eprintln();
+  }
 }
 
 enum ReleaseResult {
@@ -532,6 +1477,11 @@ impl ReleaseResult {
   fn append_pause(&mut self) { self.append(ReleaseEvent::Pause); }
   fn append_dry(&mut self) { self.append(ReleaseEvent::Dry); }
   fn append_wrote_channgelogs(&mut self) { self.append(ReleaseEvent::WroteChangelogs); }
+  fn append_tag_only(&mut self, target: Option<String>) { self.append(ReleaseEvent::TagOnly(target)); }
+
+  fn append_logged_timing(&mut self, count: usize, elapsed: Duration) {
+    self.append(ReleaseEvent::LoggedTiming(count, elapsed));
+  }
 
   fn append_changed(&mut self, name: String, prev: String, curt: String, targ: String) {
     self.append(ReleaseEvent::Changed(name, prev, curt, targ));
@@ -546,6 +1496,7 @@ impl ReleaseResult {
   }
 
   fn append_new(&mut self, all: bool, name: String, curt: String) { self.append(ReleaseEvent::New(all, name, curt)); }
+  fn append_deferred(&mut self, name: String, targ: String) { self.append(ReleaseEvent::Deferred(name, targ)); }
 
   fn append(&mut self, ev: ReleaseEvent) {
     match self {
@@ -562,7 +1513,7 @@ impl ReleaseResult {
 
   fn commit(&mut self) {
     match self {
-      ReleaseResult::Empty => println!("No release: no projects."),
+      ReleaseResult::Empty => println!("{}", tr!("release.none")),
       ReleaseResult::Wrote(w) => w.commit()
     }
   }
@@ -585,63 +1536,184 @@ impl WroteReleases {
 
 enum ReleaseEvent {
   Logged(PathBuf),
+  Manifest(PathBuf),
+  ManifestJson(String),
+  Notes(PathBuf),
+  Receipt(String, String),
+  LoggedTiming(usize, Duration),
   Changed(String, String, String, String),
   Forward(bool, String, String, String, String),
   NoChange(bool, bool, String, Option<String>, String),
   New(bool, String, String),
+  Deferred(String, String),
+  ZeroMajor(String, Size, Size),
   Commit,
   Pause,
   Dry,
   WroteChangelogs,
+  TagOnly(Option<String>),
+  Published(String),
+  Unpublished(String),
+  NotesPublished(bool, String),
+  DryRunHook(String, String),
+  Diff(String),
+  NoDiff,
+  DryByDefault(String),
+  SkippedHook(String, String),
   Done
 }
 
 impl ReleaseEvent {
   fn commit(&mut self) {
     match self {
-      ReleaseEvent::Logged(p) => println!("Wrote changelog at {}.", p.to_string_lossy()),
-      ReleaseEvent::Done => println!("Release complete."),
-      ReleaseEvent::Commit => println!("Changes committed."),
-      ReleaseEvent::Pause => println!("Paused for commit: use --resume to continue."),
-      ReleaseEvent::Dry => println!("Dry run: no actual changes."),
-      ReleaseEvent::WroteChangelogs => println!("Changelogs only: only changelogs written."),
+      ReleaseEvent::Logged(p) => println!("{}", tr!("release.logged", path = p.to_string_lossy())),
+      ReleaseEvent::Manifest(p) => println!("{}", tr!("release.manifest", path = p.to_string_lossy())),
+      ReleaseEvent::ManifestJson(json) => print!("{}", json),
+      ReleaseEvent::Notes(p) => println!("{}", tr!("release.notes", path = p.to_string_lossy())),
+      ReleaseEvent::Receipt(receipt, hash) => {
+        println!("{}", receipt);
+        println!("{}", tr!("release.receipt", hash = hash))
+      }
+      ReleaseEvent::LoggedTiming(n, d) => {
+        let seconds = format!("{:.2}", d.as_secs_f64());
+        println!("{}", tr!("release.logged_timing", count = n, seconds = seconds))
+      }
+      ReleaseEvent::Done => println!("{}", tr!("release.complete")),
+      ReleaseEvent::Commit => println!("{}", tr!("release.committed")),
+      ReleaseEvent::Pause => println!("{}", tr!("release.paused")),
+      ReleaseEvent::Dry => println!("{}", tr!("dry_run")),
+      ReleaseEvent::WroteChangelogs => println!("{}", tr!("release.changelogs_only")),
+      ReleaseEvent::TagOnly(None) => println!("{}", tr!("release.tag_only")),
+      ReleaseEvent::TagOnly(Some(rev)) => println!("{}", tr!("release.tag_only_at", rev = rev)),
+      ReleaseEvent::Published(name) => println!("{}", tr!("release.published", name = name)),
+      ReleaseEvent::Unpublished(names) => println!("{}", tr!("release.unpublished", names = names)),
+      ReleaseEvent::NotesPublished(false, target) => println!("{}", tr!("release.notes_published", target = target)),
+      ReleaseEvent::NotesPublished(true, target) => println!("{}", tr!("release.notes_would_publish", target = target)),
+      ReleaseEvent::DryRunHook(name, hook) => println!("{}", tr!("release.dry_run_hook", name = name, hook = hook)),
+      ReleaseEvent::Diff(diff) => print!("{}", diff),
+      ReleaseEvent::NoDiff => println!("{}", tr!("release.no_diff")),
+      ReleaseEvent::DryByDefault(names) => println!("{}", tr!("release.dry_by_default", names = names)),
+      ReleaseEvent::SkippedHook(name, hook) => println!("{}", tr!("release.skipped_hook", name = name, hook = hook)),
       ReleaseEvent::Changed(name, prev, curt, targ) => {
         if prev == curt {
-          println!("  {} : {} -> {}", name, prev, targ);
+          println!("{}", tr!("release.changed", name = name, prev = prev, target = targ));
         } else {
-          println!("  {} : {} -> {} instead of {}", name, prev, targ, curt);
+          println!("{}", tr!("release.changed_instead", name = name, prev = prev, target = targ, current = curt));
         }
       }
       ReleaseEvent::NoChange(all, locked, name, prev, curt) => {
         if *all {
-          let lockmsg = if *locked { " (locked)" } else { "" };
+          let locked = if *locked { tr!("release.locked") } else { String::new() };
           if let Some(prev) = prev {
             if prev == curt {
-              println!("  {} : untouched at {}{}", name, curt, lockmsg);
+              println!("{}", tr!("release.untouched", name = name, current = curt, locked = locked));
             } else {
-              println!("  {} : untouched: {} -> {}{}", name, prev, curt, lockmsg);
+              println!(
+                "{}",
+                tr!("release.untouched_changed", name = name, prev = prev, current = curt, locked = locked)
+              );
             }
           } else {
-            println!("  {} : untouched non-existent at {}{}", name, curt, lockmsg);
+            println!("{}", tr!("release.untouched_new", name = name, current = curt, locked = locked));
           }
         }
       }
       ReleaseEvent::Forward(all, name, prev, curt, targ) => {
         if *all {
           if prev == curt {
-            println!("  {} : no change to {}", name, curt);
+            println!("{}", tr!("release.no_change", name = name, current = curt));
           } else if curt == targ {
-            println!("  {} : no change: already {} -> {}", name, prev, curt);
+            println!("{}", tr!("release.no_change_already", name = name, prev = prev, current = curt));
           } else {
-            println!("  {} : no change: {} -> {} exceeds {}", name, prev, curt, targ);
+            println!("{}", tr!("release.no_change_exceeds", name = name, prev = prev, current = curt, target = targ));
           }
         }
       }
       ReleaseEvent::New(all, name, curt) => {
         if *all {
-          println!("  {} : no change: {} is new", name, curt);
+          println!("{}", tr!("release.no_change_new", name = name, current = curt));
         }
       }
+      ReleaseEvent::Deferred(name, targ) => println!("{}", tr!("release.deferred", name = name, target = targ)),
+      ReleaseEvent::ZeroMajor(name, size, applied) => {
+        println!("{}", tr!("release.zero_major", name = name, size = size, applied = applied))
+      }
     }
   }
 }
+
+#[cfg(test)]
+mod test {
+  use super::{EnvOutput, PlanOutput};
+  use crate::commands::EarlyInfo;
+  use crate::git::{Auth, GithubInfo};
+  use crate::mono::{Defaults, Mono};
+  use crate::testing::{block_on, TestRepo};
+  use crate::vcs::{VcsLevel, VcsRange, VcsState};
+  use serde_json::json;
+
+  #[test]
+  fn test_env() {
+    let info = EarlyInfo::new(
+      2,
+      "/work".into(),
+      "/work/a".into(),
+      VcsRange::new(VcsLevel::Local, VcsLevel::Smart),
+      Defaults::default()
+    );
+    let github = GithubInfo::new("github.com".into(), "me".into(), "proj".into(), Auth::default());
+    let mut output = EnvOutput::new();
+    output.write_env(&info, Some("main"), Some(&github)).unwrap();
+    let expected = json!({
+      "working_dir": "/work",
+      "project_count": 2,
+      "vcs": { "min": "local", "max": "smart" },
+      "branch": "main",
+      "github": { "host": "github.com", "owner": "me", "repo": "proj" }
+    });
+    assert_eq!(expected, output.env);
+
+    let info = EarlyInfo::new(0, "/work".into(), "/work".into(), VcsRange::full(), Defaults::default());
+    output.write_env(&info, None, None).unwrap();
+    assert_eq!(json!({ "min": "none", "max": "smart" }), output.env["vcs"]);
+    assert!(output.env["branch"].is_null() && output.env["github"].is_null());
+  }
+
+  #[test]
+  fn test_json_plan() {
+    let repo = TestRepo::new(
+      r#"
+projects:
+  - { name: a, id: 1, includes: [ "a/**" ], version: { file: a/version.txt } }
+sizes: { use_angular: true }
+"#
+    );
+    repo.write("a/version.txt", "1.0.0");
+    repo.commit("chore: start");
+    repo.tag("versio-prev");
+    repo.write("a/code.txt", "new");
+    repo.commit("feat: add code");
+
+    let mut mono = Mono::open(repo.path(), VcsState::new(VcsLevel::Local, false)).unwrap();
+    mono.set_offline(true);
+    let plan = block_on(mono.build_plan()).unwrap();
+    let json = PlanOutput::new().json_plan(&plan, &mono, vec!["careful".into()]).unwrap();
+    let json = serde_json::to_value(json).unwrap();
+
+    assert_eq!(1, json["schema"]);
+    assert_eq!(true, json["offline"]);
+    assert_eq!(repo.head().to_string(), json["commit"]);
+    assert_eq!(serde_json::json!(["careful"]), json["warnings"]);
+    let projects = json["projects"].as_array().unwrap();
+    assert_eq!(1, projects.len());
+    let project = &projects[0];
+    assert_eq!("1", project["id"]);
+    assert_eq!("minor", project["size"]);
+    assert!(project["applied"].is_null());
+    assert_eq!("1.0.0", project["previous"]);
+    assert_eq!("1.0.0", project["current"]);
+    assert_eq!("1.1.0", project["target"]);
+    assert_eq!(false, project["restricted"]);
+    assert!(!project["prs"].as_array().unwrap().is_empty());
+  }
+}
@@ -0,0 +1,110 @@
+//! Feedback on the long steps of a command, so that it doesn't sit silent for minutes on a large repo.
+//!
+//! A `Progress` draws a spinner with a count on stderr while a step runs, and erases it when the step is done. It's
+//! only drawn at a terminal, and not for JSON output or `--verbose`. With `--verbose`, the `StepTimer` instead
+//! prints how long each of versio's traced steps took, as each one finishes.
+
+use std::io::{stderr, IsTerminal as _, Write as _};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use tracing::span::{Attributes, Id};
+use tracing::Subscriber;
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+const SPINNER: &[char] = &['|', '/', '-', '\\'];
+const DRAW_INTERVAL: Duration = Duration::from_millis(100);
+
+static ENABLED: AtomicBool = AtomicBool::new(true);
+
+/// Whether to draw progress at all: it's not, for output that's read by another program.
+pub fn set_progress(enabled: bool) { ENABLED.store(enabled, Ordering::Relaxed); }
+
+/// The progress of a step, which is erased when it's dropped.
+pub struct Progress {
+  label: String,
+  count: usize,
+  total: Option<usize>,
+  frame: usize,
+  drawn: Option<Instant>
+}
+
+impl Progress {
+  /// Start a step, which is drawn right away if progress is shown at all.
+  pub fn new(label: String) -> Progress { Progress::start(label, None) }
+
+  /// Start a step of `total` parts, which is drawn as a count of them.
+  pub fn of(label: String, total: usize) -> Progress { Progress::start(label, Some(total)) }
+
+  fn start(label: String, total: Option<usize>) -> Progress {
+    let mut progress = Progress { label, count: 0, total, frame: 0, drawn: None };
+    progress.draw();
+    progress
+  }
+
+  /// Count one more part of the step done. This is cheap enough to call for every commit: the spinner is only
+  /// redrawn every so often.
+  pub fn inc(&mut self) {
+    self.count += 1;
+    if self.drawn.map(|d| d.elapsed() >= DRAW_INTERVAL).unwrap_or(false) {
+      self.draw();
+    }
+  }
+
+  fn draw(&mut self) {
+    if !shown() {
+      return;
+    }
+    self.frame = (self.frame + 1) % SPINNER.len();
+    let count = match self.total {
+      Some(total) => format!("{}/{}", self.count, total),
+      None => self.count.to_string()
+    };
+    let mut err = stderr().lock();
+    let _ = write!(err, "\r\x1b[2K{} {} {}", SPINNER[self.frame], self.label, count);
+    let _ = err.flush();
+    self.drawn = Some(Instant::now());
+  }
+}
+
+impl Drop for Progress {
+  fn drop(&mut self) {
+    if self.drawn.is_some() {
+      let mut err = stderr().lock();
+      let _ = write!(err, "\r\x1b[2K");
+      let _ = err.flush();
+    }
+  }
+}
+
+fn shown() -> bool { ENABLED.load(Ordering::Relaxed) && stderr().is_terminal() }
+
+/// A tracing layer for `--verbose`, which prints each of versio's spans (the steps that are also written by
+/// `--profile`) as it closes, indented under the step that it's part of, with how long it took.
+pub struct StepTimer;
+
+/// When a span was created, kept with the span.
+struct Started(Instant);
+
+impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for StepTimer {
+  fn on_new_span(&self, _attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+    if let Some(span) = ctx.span(id) {
+      span.extensions_mut().insert(Started(Instant::now()));
+    }
+  }
+
+  fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+    let span = match ctx.span(&id) {
+      Some(span) if span.metadata().target().starts_with("versio") => span,
+      _ => return
+    };
+    let elapsed = match span.extensions().get::<Started>() {
+      Some(Started(start)) => start.elapsed(),
+      None => return
+    };
+    let depth = span.scope().skip(1).filter(|s| s.metadata().target().starts_with("versio")).count();
+    let step = span.name().replace('_', " ");
+    let line = tr!("progress.step", step = step, elapsed = format!("{:.2?}", elapsed));
+    eprintln!("{}{}", "  ".repeat(depth), line);
+  }
+}
@@ -0,0 +1,146 @@
+//! A release receipt: a canonical record of what a release contains, and its hash.
+//!
+//! The receipt leaves out anything that changes from run to run (such as the date), and is written as compact JSON
+//! with sorted keys, so two runs of the same release on the same commit produce the same bytes. Its SHA-256 can
+//! be approved after a dry run, and then checked by the real release with `--verify-receipt`.
+
+use crate::errors::Result;
+use crate::notify::post;
+use ring::digest::{digest, SHA256};
+use serde::Serialize;
+use serde_json::Value;
+
+/// What a release contains.
+#[derive(Serialize)]
+pub struct Receipt {
+  commit: Option<String>,
+  projects: Vec<ReceiptProject>
+}
+
+/// A single project of a release, with the commits (by ID) that it includes.
+#[derive(Serialize)]
+pub struct ReceiptProject {
+  id: String,
+  name: String,
+  previous: Option<String>,
+  version: String,
+  tag: Option<String>,
+  commits: Vec<String>
+}
+
+impl ReceiptProject {
+  pub fn new(
+    id: String, name: String, previous: Option<String>, version: String, tag: Option<String>, mut commits: Vec<String>
+  ) -> ReceiptProject {
+    commits.sort();
+    commits.dedup();
+    ReceiptProject { id, name, previous, version, tag, commits }
+  }
+}
+
+impl Receipt {
+  pub fn new(commit: Option<String>, mut projects: Vec<ReceiptProject>) -> Receipt {
+    projects.sort_by(|p1, p2| p1.id.cmp(&p2.id));
+    Receipt { commit, projects }
+  }
+
+  /// The receipt as canonical JSON: compact, with the keys of every object sorted.
+  pub fn canonical(&self) -> Result<String> {
+    let mut out = String::new();
+    write_canonical(&serde_json::to_value(self)?, &mut out)?;
+    Ok(out)
+  }
+
+  /// The lowercase hex SHA-256 of the canonical JSON.
+  pub fn hash(&self) -> Result<String> { Ok(sha256_hex(&self.canonical()?)) }
+
+  /// Fail unless the receipt's hash is `expected`.
+  pub fn verify(&self, expected: &str) -> Result<()> {
+    let hash = self.hash()?;
+    if !hash.eq_ignore_ascii_case(expected.trim()) {
+      bail!("{}", tr!("release.receipt_mismatch", hash = hash, expected = expected.trim()));
+    }
+    Ok(())
+  }
+
+  /// Post the canonical JSON to `url`.
+  pub async fn upload(&self, url: &str) -> Result<()> { post(url, "application/json", self.canonical()?).await }
+}
+
+fn sha256_hex(data: &str) -> String {
+  digest(&SHA256, data.as_bytes()).as_ref().iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn write_canonical(value: &Value, out: &mut String) -> Result<()> {
+  match value {
+    Value::Array(items) => {
+      out.push('[');
+      for (i, item) in items.iter().enumerate() {
+        if i > 0 {
+          out.push(',');
+        }
+        write_canonical(item, out)?;
+      }
+      out.push(']');
+    }
+    Value::Object(map) => {
+      let mut keys: Vec<_> = map.keys().collect();
+      keys.sort();
+      out.push('{');
+      for (i, key) in keys.into_iter().enumerate() {
+        if i > 0 {
+          out.push(',');
+        }
+        out.push_str(&serde_json::to_string(key)?);
+        out.push(':');
+        write_canonical(&map[key], out)?;
+      }
+      out.push('}');
+    }
+    other => out.push_str(&serde_json::to_string(other)?)
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod test {
+  use super::{sha256_hex, Receipt, ReceiptProject};
+
+  fn receipt(commits: Vec<&str>) -> Receipt {
+    let commits = commits.into_iter().map(|c| c.to_string()).collect();
+    let other = ReceiptProject::new("2".into(), "other".into(), None, "0.1.0".into(), None, Vec::new());
+    let proj = ReceiptProject::new(
+      "1".into(),
+      "proj".into(),
+      Some("1.0.0".into()),
+      "1.1.0".into(),
+      Some("proj-v1.1.0".into()),
+      commits
+    );
+    Receipt::new(Some("abc123".into()), vec![other, proj])
+  }
+
+  #[test]
+  fn test_sha256() {
+    assert_eq!("e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855", sha256_hex(""));
+  }
+
+  #[test]
+  fn test_canonical() {
+    assert_eq!(
+      concat!(
+        r#"{"commit":"abc123","projects":[{"commits":["a1","b2"],"id":"1","name":"proj","previous":"1.0.0","#,
+        r#""tag":"proj-v1.1.0","version":"1.1.0"},{"commits":[],"id":"2","name":"other","previous":null,"#,
+        r#""tag":null,"version":"0.1.0"}]}"#
+      ),
+      receipt(vec!["b2", "a1", "b2"]).canonical().unwrap()
+    );
+  }
+
+  #[test]
+  fn test_verify() {
+    let hash = receipt(vec!["a1", "b2"]).hash().unwrap();
+    assert!(receipt(vec!["b2", "a1"]).verify(&hash.to_uppercase()).is_ok());
+    assert!(receipt(vec!["a1"]).verify(&hash).is_err());
+  }
+}
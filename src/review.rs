@@ -0,0 +1,99 @@
+//! The prompts of `release --interactive`, which shows each project that the plan would release, and lets the user
+//! accept, skip, or change its release before anything is written.
+
+use crate::config::Size;
+use crate::errors::{Error, Result};
+use std::io::{stdin, stdout, BufRead as _, IsTerminal as _, Write as _};
+
+/// What the user decided to do with the release of a project.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Decision {
+  Accept,
+  Skip,
+  Edit,
+  Size(Size),
+  Version(String)
+}
+
+pub struct Reviewer {}
+
+impl Reviewer {
+  /// Start a review, which can only be done at a terminal.
+  pub fn new() -> Result<Reviewer> {
+    if !stdin().is_terminal() {
+      bail!("{}", tr!("review.no_tty"));
+    }
+    Ok(Reviewer {})
+  }
+
+  /// Show the planned release of a project: its version, and a summary of its changelog.
+  pub fn show(&self, name: &str, prev: Option<&str>, version: &str, size: Size, summary: &str) {
+    println!();
+    match prev {
+      Some(prev) => println!("{}", tr!("review.project", name = name, prev = prev, version = version, size = size)),
+      None => println!("{}", tr!("review.new_project", name = name, version = version))
+    }
+    for line in summary.lines() {
+      println!("  {}", line);
+    }
+  }
+
+  /// Ask what to do with the project that was last shown, until there's an answer that makes sense.
+  pub fn decide(&mut self) -> Result<Decision> {
+    loop {
+      match parse_decision(&self.ask(&tr!("review.prompt"))?) {
+        Some(decision) => return Ok(decision),
+        None => println!("{}", tr!("review.unknown"))
+      }
+    }
+  }
+
+  /// Ask for new text for a changelog entry, showing its current `text`. Returns `None` to keep the current text.
+  pub fn edit(&mut self, text: &str) -> Result<Option<String>> {
+    println!("{}", tr!("review.entry", text = text));
+    let answer = self.ask(&tr!("review.edit_prompt"))?;
+    Ok(Some(answer).filter(|a| !a.is_empty()))
+  }
+
+  /// Explain why a change that the user asked for can't be made.
+  pub fn reject(&self, e: &Error) { println!("{}", tr!("review.rejected", reason = format!("{:#}", e))); }
+
+  fn ask(&mut self, question: &str) -> Result<String> {
+    print!("{} ", question);
+    stdout().flush()?;
+    let mut line = String::new();
+    if stdin().lock().read_line(&mut line)? == 0 {
+      bail!("{}", tr!("review.closed"));
+    }
+    Ok(line.trim().to_string())
+  }
+}
+
+/// Read an answer to the review prompt: a size or a version releases the project at that instead of the plan.
+fn parse_decision(answer: &str) -> Option<Decision> {
+  match answer.to_lowercase().as_str() {
+    "" | "a" | "accept" => Some(Decision::Accept),
+    "s" | "skip" => Some(Decision::Skip),
+    "e" | "edit" => Some(Decision::Edit),
+    size @ ("major" | "minor" | "patch") => Size::from_str(size).ok().map(Decision::Size),
+    _ if answer.starts_with(|c: char| c.is_ascii_digit()) => Some(Decision::Version(answer.to_string())),
+    _ => None
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::{parse_decision, Decision};
+  use crate::config::Size;
+
+  #[test]
+  fn test_parse_decision() {
+    assert_eq!(Some(Decision::Accept), parse_decision(""));
+    assert_eq!(Some(Decision::Skip), parse_decision("S"));
+    assert_eq!(Some(Decision::Edit), parse_decision("edit"));
+    assert_eq!(Some(Decision::Size(Size::Minor)), parse_decision("minor"));
+    assert_eq!(Some(Decision::Version("2.0.0-rc.1".into())), parse_decision("2.0.0-rc.1"));
+    assert_eq!(None, parse_decision("none"));
+    assert_eq!(None, parse_decision("later"));
+  }
+}
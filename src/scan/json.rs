@@ -31,6 +31,7 @@ fn scan_json<P: IntoPartVec>(data: &str, loc: P) -> Result<Mark> {
   let mut parts = loc.into_part_vec();
   parts.reverse();
 
+  let data = blank_comments(data)?;
   let trace = Arc::new(Mutex::new(Trace::new()));
   let reader = MeteredReader::new(data.as_bytes(), trace.clone());
 
@@ -41,6 +42,66 @@ fn scan_json<P: IntoPartVec>(data: &str, loc: P) -> Result<Mark> {
   Ok(mark)
 }
 
+/// Blank out any comments (`// ...` and `/* ... */`) and trailing commas, so that JSON with comments can be
+/// scanned. Each blanked byte becomes a space (newlines are kept), so everything else stays at the same position.
+fn blank_comments(data: &str) -> Result<String> {
+  let bytes = data.as_bytes();
+  let mut blanked = bytes.to_vec();
+  let mut blank = |from: usize, to: usize| {
+    for b in &mut blanked[from .. to] {
+      if *b != b'\n' {
+        *b = b' ';
+      }
+    }
+  };
+
+  let mut in_string = false;
+  let mut comma = None;
+  let mut i = 0;
+  while i < bytes.len() {
+    let b = bytes[i];
+    if in_string {
+      match b {
+        b'\\' => i += 1,
+        b'"' => in_string = false,
+        _ => ()
+      }
+      i += 1;
+      continue;
+    }
+
+    match (b, bytes.get(i + 1)) {
+      (b'/', Some(b'/')) => {
+        let end = data[i ..].find('\n').map(|e| i + e).unwrap_or(bytes.len());
+        blank(i, end);
+        i = end;
+        continue;
+      }
+      (b'/', Some(b'*')) => {
+        let end = data[i + 2 ..].find("*/").map(|e| i + e + 4).unwrap_or(bytes.len());
+        blank(i, end);
+        i = end;
+        continue;
+      }
+      (b',', _) => comma = Some(i),
+      (b'}', _) | (b']', _) => {
+        if let Some(c) = comma.take() {
+          blank(c, c + 1);
+        }
+      }
+      (b'"', _) => {
+        in_string = true;
+        comma = None;
+      }
+      _ if b.is_ascii_whitespace() => (),
+      _ => comma = None
+    }
+    i += 1;
+  }
+
+  Ok(String::from_utf8(blanked)?)
+}
+
 fn pop(mut parts: Vec<Part>, trace: TraceRef) -> NthElement {
   let part = parts.pop().unwrap();
   NthElement::new(part, parts, trace)
@@ -271,4 +332,17 @@ mod test {
     assert_eq!("1.2.3", mark.value());
     assert_eq!(39, mark.start());
   }
+  #[test]
+  fn test_json_comments() {
+    let doc = r#"
+{
+  // The release version.
+  "version": "1.2.3", /* not "2.0.0" */
+  "tags": ["a", "b",],
+}"#;
+
+    let mark = JsonScanner::new("version").find(doc).unwrap();
+    assert_eq!("1.2.3", mark.value());
+    assert_eq!(43, mark.start());
+  }
 }
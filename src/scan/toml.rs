@@ -175,6 +175,35 @@ thing = [ "thing2", "1.2.3" ]"#;
     assert_eq!(24, mark.start());
   }
 
+  #[test]
+  fn test_toml_pyproject() {
+    let doc = r#"
+[build-system]
+requires = ["poetry-core"]
+
+[tool.poetry]
+name = "thing"
+version = "1.2.3""#;
+
+    let mark = TomlScanner::new("tool.poetry.version").find(doc).unwrap();
+    assert_eq!("1.2.3", mark.value());
+    assert_eq!(84, mark.start());
+  }
+
+  #[test]
+  fn test_toml_workspace() {
+    let doc = r#"
+[workspace]
+members = ["a", "b"]
+
+[workspace.package]
+version = "1.2.3""#;
+
+    let mark = TomlScanner::new("workspace.package.version").find(doc).unwrap();
+    assert_eq!("1.2.3", mark.value());
+    assert_eq!(66, mark.start());
+  }
+
   #[test]
   fn test_toml_utf8() {
     let doc = r#"
@@ -35,12 +35,13 @@ fn scan_xml<P: IntoPartVec>(data: &str, loc: P) -> Result<Mark> {
 
   let mut extra_depth = 0;
   let mut on_target = false;
+  let mut matched = Vec::new();
 
   for token in Tokenizer::from(data) {
     match token? {
       Token::ElementStart { local, .. } => {
         if extra_depth == 0 && is_match_str(local.as_str(), parts.last()) {
-          parts.pop();
+          matched.push(parts.pop().unwrap());
           if parts.is_empty() {
             on_target = true;
           }
@@ -51,6 +52,11 @@ fn scan_xml<P: IntoPartVec>(data: &str, loc: P) -> Result<Mark> {
       Token::ElementEnd { end, .. } if is_ending(&end) => {
         if extra_depth > 0 {
           extra_depth -= 1;
+        } else if let Some(part) = matched.pop() {
+          // A matching element closed without containing the target: keep looking in its later siblings (e.g.
+          // multiple `<PropertyGroup>`s in a .csproj).
+          parts.push(part);
+          on_target = false;
         } else {
           bail!("Couldn't find version in XML: still expecting {:?}", parts);
         }
@@ -110,6 +116,37 @@ mod test {
     assert_eq!(21, mark.start());
   }
 
+  #[test]
+  fn test_xml_pom() {
+    let doc = r#"<?xml version="1.0" encoding="UTF-8"?>
+<project xmlns="http://maven.apache.org/POM/4.0.0">
+  <parent>
+    <version>2.4.6</version>
+  </parent>
+  <version>1.2.3</version>
+</project>"#;
+
+    let mark = XmlScanner::new("project.version").find(doc).unwrap();
+    assert_eq!("1.2.3", mark.value());
+    assert_eq!(154, mark.start());
+  }
+
+  #[test]
+  fn test_xml_csproj() {
+    let doc = r#"<Project Sdk="Microsoft.NET.Sdk">
+  <PropertyGroup>
+    <TargetFramework>net6.0</TargetFramework>
+  </PropertyGroup>
+  <PropertyGroup>
+    <Version>1.2.3</Version>
+  </PropertyGroup>
+</Project>"#;
+
+    let mark = XmlScanner::new("Project.PropertyGroup.Version").find(doc).unwrap();
+    assert_eq!("1.2.3", mark.value());
+    assert_eq!(148, mark.start());
+  }
+
   #[test]
   fn test_xml_utf8() {
     let doc = r#"
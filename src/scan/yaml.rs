@@ -6,6 +6,7 @@ use crate::mark::{CharMark, Mark};
 use crate::scan::parts::ToPart;
 use crate::scan::parts::{IntoPartVec, Part};
 use crate::scan::Scanner;
+use std::collections::HashMap;
 use yaml_rust::parser::{Event, MarkedEventReceiver, Parser};
 use yaml_rust::scanner::{Marker, TScalarStyle};
 
@@ -14,7 +15,6 @@ pub struct YamlScanner {
 }
 
 impl YamlScanner {
-  #[cfg(test)]
   pub fn new(target: &str) -> YamlScanner { YamlScanner { target: target.into_part_vec() } }
 
   #[cfg(test)]
@@ -35,18 +35,22 @@ fn scan_yaml<P: IntoPartVec>(data: &str, loc: P) -> Result<CharMark> {
 
   parser.load(&mut rcvr, false)?;
 
-  Ok(rcvr.result.unwrap())
+  rcvr.result.ok_or_else(|| bad!("No scalar value found in YAML."))
 }
 
 struct Receiver {
   parts: Vec<Part>,
   stack: Vec<Loc>,
   depth: usize,
-  result: Option<CharMark>
+  result: Option<CharMark>,
+  /// The value and position of each anchored scalar, so that an alias can be traced back to its anchor.
+  anchors: HashMap<usize, (String, usize)>
 }
 
 impl Receiver {
-  pub fn new(parts: Vec<Part>) -> Receiver { Receiver { parts, stack: Vec::new(), depth: 0, result: None } }
+  pub fn new(parts: Vec<Part>) -> Receiver {
+    Receiver { parts, stack: Vec::new(), depth: 0, result: None, anchors: HashMap::new() }
+  }
 
   fn found_key(&mut self) { self.depth += 1; }
 
@@ -148,17 +152,20 @@ impl MarkedEventReceiver for Receiver {
         self.stack.pop();
         self.after_object(true);
       }
-      Event::Scalar(val, style, _anchor, _tag) => {
+      Event::Scalar(val, style, anchor, _tag) => {
         self.before_object();
+        let mut index = mark.index();
+        match style {
+          TScalarStyle::SingleQuoted | TScalarStyle::DoubleQuoted => {
+            index += 1;
+          }
+          _ => ()
+        }
+        if anchor > 0 {
+          self.anchors.insert(anchor, (val.clone(), index));
+        }
         match self.expecting() {
           Expect::Scalar => {
-            let mut index = mark.index();
-            match style {
-              TScalarStyle::SingleQuoted | TScalarStyle::DoubleQuoted => {
-                index += 1;
-              }
-              _ => ()
-            }
             self.result = Some(CharMark::new(val, index));
           }
           Expect::Key(key) => {
@@ -171,6 +178,16 @@ impl MarkedEventReceiver for Receiver {
         }
         self.after_object(false);
       }
+      Event::Alias(anchor) => {
+        // An aliased value is written at its anchor, which also changes every other alias of it.
+        self.before_object();
+        if matches!(self.expecting(), Expect::Scalar) {
+          if let Some((val, index)) = self.anchors.get(&anchor) {
+            self.result = Some(CharMark::new(val.clone(), *index));
+          }
+        }
+        self.after_object(false);
+      }
       _ => ()
     }
   }
@@ -271,4 +288,22 @@ package:
     assert_eq!("0.0.6", mark.value());
     assert_eq!(31, mark.start());
   }
+  #[test]
+  fn test_yaml_anchors() {
+    let doc = r#"
+defaults: &defaults
+  replicas: 2 # how many
+image:
+  <<: *defaults
+  tag: &tag 1.2.3 # the release
+other: *tag"#;
+
+    let mark = YamlScanner::new("image.tag").find(doc).unwrap();
+    assert_eq!("1.2.3", mark.value());
+    assert_eq!(81, mark.start());
+
+    let mark = YamlScanner::new("other").find(doc).unwrap();
+    assert_eq!("1.2.3", mark.value());
+    assert_eq!(81, mark.start());
+  }
 }
@@ -0,0 +1,781 @@
+//! A JSON Schema of the config file, and validation of a config against it.
+//!
+//! The schema follows the serde types of `config`, including the shapes accepted by their hand-written
+//! deserializers. Validating against it finds the same mistakes that serde does, but it can report where they
+//! are, and it also catches unknown (probably misspelled) keys, which serde ignores.
+
+use crate::errors::{Error, Result};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::fmt;
+use yaml_rust::parser::{Event, MarkedEventReceiver, Parser};
+use yaml_rust::scanner::{Marker, TScalarStyle};
+use yaml_rust::Yaml;
+
+/// The definition that an included config file is checked against.
+pub const FRAGMENT: &str = "fragment";
+
+/// A JSON Schema (draft 7) of `.versio.yaml`.
+pub fn config_schema() -> Value {
+  let size = json!({ "enum": ["major", "minor", "patch", "none", "fail", "empty"] });
+  let strings = json!({ "type": "array", "items": { "type": "string" } });
+  let string_or_list = json!({ "anyOf": [{ "type": "string" }, strings] });
+
+  json!({
+    "$schema": "http://json-schema.org/draft-07/schema#",
+    "title": "Versio config",
+    "description": "The .versio.yaml config of a Versio repo.",
+    "type": "object",
+    "properties": {
+      "options": {
+        "type": "object",
+        "properties": {
+          "prev_tag": { "type": "string", "description": "The tag that marks the latest release." },
+          "branch": {
+            "description": "The branch (or branch patterns) that releases must be made from.",
+            "anyOf": [{ "type": "string" }, strings]
+          },
+          "maintenance": {
+            "type": "array",
+            "description": "Branches on which older major versions are maintained.",
+            "items": {
+              "type": "object",
+              "properties": {
+                "branch": { "type": "string", "description": "The branch (or branch pattern)." },
+                "majors": { "type": "array", "items": { "type": "integer", "minimum": 0 } },
+                "prev_tag": { "type": "string", "description": "The tag that marks the latest release of the branch." }
+              },
+              "required": ["branch", "majors"],
+              "additionalProperties": false
+            }
+          },
+          "tag_remote": { "type": "string", "description": "The remote from which tags are fetched." },
+          "mirrors": { "type": "array", "items": { "type": "string" }, "description": "Other remotes to push to." },
+          "parallel_push": { "type": "boolean", "description": "Push to all the mirrors at the same time." },
+          "allowed_paths": {
+            "type": "array",
+            "items": { "type": "string" },
+            "description": "The path prefixes that a release may write."
+          },
+          "max_size": { "$ref": "#/definitions/size", "description": "The largest automatic increment." },
+          "dry_run_default": { "type": "boolean", "description": "Make releases dry runs unless confirmed." },
+          "stale_months": { "type": "integer", "minimum": 0, "description": "Warn when prev_tag is this old." },
+          "plan_max_hours": { "type": "integer", "minimum": 0, "description": "Reject older saved plans." },
+          "plan_max_commits": {
+            "type": "integer",
+            "minimum": 0,
+            "description": "Reject saved plans that are this many commits behind."
+          },
+          "releases_dir": { "type": "string", "description": "Where to write a manifest of each release." },
+          "skip_prs": { "type": "array", "items": { "type": "integer" }, "description": "PRs to leave out." },
+          "size_trailer": { "type": "string", "description": "The commit trailer that sets a commit's size." },
+          "skip_trailer": { "type": "string", "description": "The commit trailer that skips a commit in projects." }
+        },
+        "additionalProperties": false
+      },
+      "projects": { "type": "array", "items": { "$ref": "#/definitions/project" } },
+      "commit": {
+        "type": "object",
+        "properties": {
+          "message": { "type": "string" },
+          "author": { "type": "string" },
+          "email": { "type": "string" },
+          "skip_ci": { "type": "string" },
+          "tag_skip_ci": { "type": "string" },
+          "sign": {
+            "type": "object",
+            "properties": {
+              "commits": { "type": "boolean" },
+              "tags": { "type": "boolean" },
+              "key": { "type": "string" },
+              "format": { "enum": ["openpgp", "gpg", "ssh"] }
+            },
+            "additionalProperties": false
+          },
+          "state_storage": { "enum": ["annotation", "notes"] }
+        },
+        "additionalProperties": false
+      },
+      "sizes": {
+        "type": "object",
+        "description": "The size of each conventional commit type.",
+        "properties": {
+          "use_angular": { "type": "boolean" },
+          "rules": {
+            "type": "array",
+            "description": "Regexes of commit summaries, and their sizes, tried in order before the commit type.",
+            "items": { "type": "object", "additionalProperties": { "$ref": "#/definitions/size" } }
+          },
+          "major": strings,
+          "minor": strings,
+          "patch": strings,
+          "none": strings,
+          "fail": strings,
+          "empty": strings
+        },
+        "additionalProperties": false
+      },
+      "notifications": {
+        "type": "array",
+        "items": {
+          "type": "object",
+          "properties": {
+            "url": { "type": "string" },
+            "url_env": { "type": "string" },
+            "body": { "type": "string" },
+            "content_type": { "type": "string" }
+          },
+          "additionalProperties": false
+        }
+      },
+      "notes_publishers": {
+        "type": "array",
+        "description": "HTTP APIs to publish the release notes to after each release.",
+        "items": {
+          "type": "object",
+          "properties": {
+            "url": { "type": "string" },
+            "url_env": { "type": "string" },
+            "method": { "enum": ["POST", "PUT"] },
+            "headers": { "type": "object", "additionalProperties": { "type": "string" } },
+            "template": { "type": "string" },
+            "body": { "type": "string" },
+            "content_type": { "type": "string" }
+          },
+          "additionalProperties": false
+        }
+      },
+      "links": {
+        "type": "array",
+        "description": "Issue trackers whose issues are linked in changelogs.",
+        "items": {
+          "type": "object",
+          "properties": {
+            "keys": { "type": "array", "items": { "type": "string", "pattern": "^\\w+$" } },
+            "url": { "type": "string" }
+          },
+          "required": ["keys", "url"],
+          "additionalProperties": false
+        }
+      },
+      "include": {
+        "type": "array",
+        "description": "Config files of more projects.",
+        "items": {
+          "anyOf": [
+            { "type": "string" },
+            {
+              "type": "object",
+              "properties": { "file": { "type": "string" }, "id_base": { "type": "integer", "minimum": 0 } },
+              "required": ["file"],
+              "additionalProperties": false
+            }
+          ]
+        }
+      }
+    },
+    "additionalProperties": false,
+    "definitions": {
+      "fragment": {
+        "type": "object",
+        "properties": { "projects": { "type": "array", "items": { "$ref": "#/definitions/project" } } },
+        "additionalProperties": false
+      },
+      "size": size,
+      "project_id": { "anyOf": [{ "type": "integer", "minimum": 0 }, { "type": "string" }] },
+      "project": {
+        "type": "object",
+        "properties": {
+          "name": { "type": "string" },
+          "id": { "$ref": "#/definitions/project_id" },
+          "root": { "type": "string", "description": "The project's directory, relative to the repo." },
+          "aliases": {
+            "type": "array",
+            "items": { "type": "integer", "minimum": 0 },
+            "description": "The IDs that the project had before it was renumbered."
+          },
+          "previous_roots": {
+            "type": "array",
+            "items": { "type": "string" },
+            "description": "The directories that the project had before it was moved."
+          },
+          "workspace": {
+            "type": "string",
+            "description": "The project's package in the repo's Cargo or npm workspace, whose directory is its root."
+          },
+          "includes": strings,
+          "excludes": strings,
+          "case_insensitive": {
+            "type": "boolean",
+            "description": "Whether `includes` and `excludes` match file paths regardless of case."
+          },
+          "allow_overlap": {
+            "type": "boolean",
+            "description": "Whether the project is meant to cover files that other projects also cover."
+          },
+          "depends": {
+            "type": "object",
+            "description": "The projects that this one depends on, by ID.",
+            "additionalProperties": { "$ref": "#/definitions/depends" }
+          },
+          "changelog": {
+            "anyOf": [
+              { "$ref": "#/definitions/changelog_target" },
+              { "type": "boolean", "description": "Only `false`, for a project with no changelog." },
+              {
+                "type": "array",
+                "description": "Several changelogs, the first being the primary one.",
+                "items": { "$ref": "#/definitions/changelog_target" },
+                "minItems": 1
+              }
+            ]
+          },
+          "version": { "$ref": "#/definitions/location" },
+          "also": { "type": "array", "items": { "$ref": "#/definitions/location" } },
+          "labels": string_or_list,
+          "scopes": string_or_list,
+          "tag_prefix": { "type": "string" },
+          "tag_prefix_separator": { "type": "string" },
+          "tag_prefix_aliases": strings,
+          "tag_template": {
+            "type": "string",
+            "description": "The form of this project's version tags, like \"{prefix}_{major}_{minor}_{patch}\"."
+          },
+          "tag_style": { "enum": ["lightweight", "annotated"] },
+          "tag_message": {
+            "type": "string",
+            "description": "The message of this project's annotated version tags, as a Liquid template."
+          },
+          "floating_tags": {
+            "type": "array",
+            "items": { "enum": ["major", "minor"] },
+            "description": "Tags like v1 and v1.4 that are moved to each new version of this project."
+          },
+          "prev_tag": {
+            "type": "string",
+            "description": "The tag or commit to count this project's changes from, until the next release."
+          },
+          "subs": {
+            "type": "object",
+            "properties": {
+              "dirs": { "type": "string" },
+              "tops": { "type": "array", "items": { "type": "integer", "minimum": 0 } }
+            },
+            "additionalProperties": false
+          },
+          "hooks": {
+            "type": "object",
+            "additionalProperties": {
+              "anyOf": [
+                { "type": "string" },
+                {
+                  "type": "object",
+                  "properties": {
+                    "cmd": { "type": "string" },
+                    "safety": { "enum": ["safe", "unsafe"] },
+                    "timeout": { "type": "integer", "minimum": 1 }
+                  },
+                  "required": ["cmd"],
+                  "additionalProperties": false
+                }
+              ]
+            }
+          },
+          "cargo_lock": { "type": "string" },
+          "max_size": { "$ref": "#/definitions/size" },
+          "dry_run_default": { "type": "boolean" },
+          "go_module": { "type": "boolean" },
+          "diff_sizes": {
+            "type": "object",
+            "description": "Size the commits that aren't conventional from their diffs.",
+            "properties": {
+              "minor": { "$ref": "#/definitions/diff_threshold" },
+              "major": { "$ref": "#/definitions/diff_threshold" }
+            },
+            "additionalProperties": false
+          },
+          "zero_major_policy": { "enum": ["keep", "minor", "shift"] },
+          "version_format": {
+            "type": "object",
+            "properties": {
+              "parts": { "type": "integer", "minimum": 2, "description": "2, 3, or 4 numbers." },
+              "build": { "enum": ["counter", "timestamp"] }
+            },
+            "additionalProperties": false
+          },
+          "build_metadata": {
+            "type": "string",
+            "description": "A liquid template of the build metadata to add to the version in the project's files."
+          },
+          "constraints": {
+            "type": "object",
+            "description": "Versions that this project may never be released at.",
+            "properties": {
+              "minimum": { "type": "string" },
+              "skip": strings,
+              "frozen": strings
+            },
+            "additionalProperties": false
+          },
+          "members": { "type": "array", "items": { "$ref": "#/definitions/project_id" } }
+        },
+        "required": ["name", "id", "version"],
+        "additionalProperties": false
+      },
+      "diff_threshold": {
+        "type": "object",
+        "properties": {
+          "files": { "type": "integer", "minimum": 0 },
+          "lines": { "type": "integer", "minimum": 0 }
+        },
+        "additionalProperties": false
+      },
+      "depends": {
+        "type": "object",
+        "properties": {
+          "files": { "type": "array", "items": { "$ref": "#/definitions/location" } },
+          "size": { "enum": ["match", "major", "minor", "patch", "none"] },
+          "style": { "enum": ["exact", "caret", "tilde", "range"] },
+          "npm": {
+            "type": "object",
+            "description": "Rewrite the requirements on a package of the same JS workspace.",
+            "properties": {
+              "package": { "type": "string" },
+              "lockfile": { "enum": ["npm", "pnpm", "yarn"] }
+            },
+            "required": ["package"],
+            "additionalProperties": false
+          }
+        },
+        "additionalProperties": false
+      },
+      "changelog_target": {
+        "anyOf": [
+          { "type": "string" },
+          {
+            "type": "object",
+            "properties": {
+              "file": { "type": "string" },
+              "template": { "type": "string" },
+              "format": { "enum": ["template", "keep-a-changelog"] },
+              "preamble": { "$ref": "#/definitions/text_block" },
+              "footer": { "$ref": "#/definitions/text_block" },
+              "group_by_type": { "type": "boolean" },
+              "stats": { "type": "boolean" },
+              "sections": {
+                "type": "array",
+                "items": {
+                  "type": "object",
+                  "properties": {
+                    "title": { "type": "string" },
+                    "types": strings,
+                    "sizes": { "type": "array", "items": { "$ref": "#/definitions/size" } }
+                  },
+                  "required": ["title"],
+                  "additionalProperties": false
+                }
+              }
+            },
+            "required": ["file"],
+            "additionalProperties": false
+          }
+        ]
+      },
+      "text_block": {
+        "anyOf": [
+          { "type": "string" },
+          {
+            "type": "object",
+            "properties": { "file": { "type": "string" } },
+            "required": ["file"],
+            "additionalProperties": false
+          }
+        ]
+      },
+      "location": {
+        "anyOf": [
+          {
+            "type": "object",
+            "properties": {
+              "file": { "type": "string" },
+              "pattern": { "type": "string" },
+              "json": { "$ref": "#/definitions/parts" },
+              "yaml": { "$ref": "#/definitions/parts" },
+              "toml": { "$ref": "#/definitions/parts" },
+              "xml": { "$ref": "#/definitions/parts" },
+              "format": { "type": "string" }
+            },
+            "required": ["file"],
+            "additionalProperties": false
+          },
+          {
+            "type": "object",
+            "properties": {
+              "tags": {
+                "anyOf": [
+                  {
+                    "type": "object",
+                    "properties": { "default": { "type": "string" } },
+                    "additionalProperties": false
+                  },
+                  {
+                    "type": "object",
+                    "properties": { "majors": { "type": "array", "items": { "type": "integer", "minimum": 0 } } },
+                    "required": ["majors"],
+                    "additionalProperties": false
+                  }
+                ]
+              }
+            },
+            "required": ["tags"],
+            "additionalProperties": false
+          },
+          {
+            "type": "object",
+            "properties": {
+              "command": {
+                "type": "object",
+                "properties": { "read": { "type": "string" }, "write": { "type": "string" } },
+                "required": ["read", "write"],
+                "additionalProperties": false
+              }
+            },
+            "required": ["command"],
+            "additionalProperties": false
+          }
+        ]
+      },
+      "parts": {
+        "anyOf": [
+          { "type": "string" },
+          { "type": "array", "items": { "anyOf": [{ "type": "string" }, { "type": "integer", "minimum": 0 }] } }
+        ]
+      }
+    }
+  })
+}
+
+/// A place where a config doesn't match the schema.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+  path: String,
+  line: usize,
+  column: usize,
+  message: String
+}
+
+impl fmt::Display for Violation {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let path = if self.path.is_empty() { "(top)" } else { &self.path };
+    write!(
+      f,
+      "{}",
+      tr!("schema.violation", line = self.line, column = self.column, path = path, message = self.message)
+    )
+  }
+}
+
+/// Check the YAML text of a config against the schema, or against one of its `definitions` (such as `FRAGMENT`)
+/// if given.
+pub fn validate(data: &str, definition: Option<&str>) -> Result<Vec<Violation>> {
+  let mut builder = TreeBuilder::default();
+  Parser::new(data.chars()).load(&mut builder, false)?;
+  let tree = match builder.root {
+    Some(tree) => tree,
+    None => return Ok(Vec::new())
+  };
+
+  let schema = config_schema();
+  let start = match definition {
+    Some(name) => schema["definitions"].get(name).ok_or_else(|| bad!("No schema definition \"{}\".", name))?,
+    None => &schema
+  };
+  let mut validator = Validator { root: &schema, violations: Vec::new() };
+  validator.check(start, &tree, "");
+  Ok(validator.violations)
+}
+
+/// Add the schema violations of a config to an error found while parsing it, since they explain where the
+/// problem is.
+pub fn explain(data: &str, definition: Option<&str>, error: Error) -> Error {
+  match validate(data, definition) {
+    Ok(violations) if !violations.is_empty() => {
+      let list = violations.iter().map(|v| format!("  {}", v)).collect::<Vec<_>>().join("\n");
+      error.context(tr!("schema.invalid", violations = list))
+    }
+    _ => error
+  }
+}
+
+/// A YAML node, with the place where it starts.
+#[derive(Clone, Debug)]
+struct Node {
+  kind: NodeKind,
+  mark: Marker
+}
+
+#[derive(Clone, Debug)]
+enum NodeKind {
+  Scalar(String, TScalarStyle),
+  Seq(Vec<Node>),
+  Map(Vec<(Node, Node)>)
+}
+
+impl Node {
+  /// The JSON type of this node.
+  fn type_name(&self) -> &'static str {
+    match &self.kind {
+      NodeKind::Seq(_) => "array",
+      NodeKind::Map(_) => "object",
+      NodeKind::Scalar(_, TScalarStyle::Plain) => match Yaml::from_str(self.text()) {
+        Yaml::Null => "null",
+        Yaml::Boolean(_) => "boolean",
+        Yaml::Integer(_) => "integer",
+        Yaml::Real(_) => "number",
+        _ => "string"
+      },
+      NodeKind::Scalar(..) => "string"
+    }
+  }
+
+  fn text(&self) -> &str {
+    match &self.kind {
+      NodeKind::Scalar(text, _) => text,
+      _ => ""
+    }
+  }
+
+  fn is_type(&self, expected: &str) -> bool {
+    let actual = self.type_name();
+    if actual == expected || (expected == "number" && actual == "integer") {
+      return true;
+    }
+    // An environment variable could be any kind of scalar, once it's interpolated.
+    matches!(self.kind, NodeKind::Scalar(..))
+      && self.text().contains("${")
+      && matches!(expected, "string" | "integer" | "number" | "boolean")
+  }
+}
+
+#[derive(Default)]
+struct TreeBuilder {
+  open: Vec<(NodeKind, Marker, Option<Node>, usize)>,
+  anchors: HashMap<usize, Node>,
+  root: Option<Node>
+}
+
+impl TreeBuilder {
+  fn add(&mut self, node: Node, anchor: usize) {
+    if anchor > 0 {
+      self.anchors.insert(anchor, node.clone());
+    }
+    match self.open.last_mut() {
+      Some((NodeKind::Seq(items), ..)) => items.push(node),
+      Some((NodeKind::Map(pairs), _, key, _)) => match key.take() {
+        Some(key) => pairs.push((key, node)),
+        None => *key = Some(node)
+      },
+      Some((NodeKind::Scalar(..), ..)) => unreachable!("A scalar isn't a container."),
+      None => self.root = Some(node)
+    }
+  }
+
+  fn close(&mut self) {
+    if let Some((kind, mark, _, anchor)) = self.open.pop() {
+      self.add(Node { kind, mark }, anchor);
+    }
+  }
+}
+
+impl MarkedEventReceiver for TreeBuilder {
+  fn on_event(&mut self, ev: Event, mark: Marker) {
+    match ev {
+      Event::Scalar(text, style, anchor, _) => self.add(Node { kind: NodeKind::Scalar(text, style), mark }, anchor),
+      Event::SequenceStart(anchor) => self.open.push((NodeKind::Seq(Vec::new()), mark, None, anchor)),
+      Event::MappingStart(anchor) => self.open.push((NodeKind::Map(Vec::new()), mark, None, anchor)),
+      Event::SequenceEnd | Event::MappingEnd => self.close(),
+      Event::Alias(anchor) => {
+        if let Some(node) = self.anchors.get(&anchor).cloned() {
+          self.add(node, 0);
+        }
+      }
+      _ => ()
+    }
+  }
+}
+
+struct Validator<'s> {
+  root: &'s Value,
+  violations: Vec<Violation>
+}
+
+impl<'s> Validator<'s> {
+  fn violation(&mut self, node: &Node, path: &str, message: String) {
+    let (line, column) = (node.mark.line(), node.mark.col() + 1);
+    self.violations.push(Violation { path: path.to_string(), line, column, message });
+  }
+
+  fn resolve(&self, schema: &'s Value) -> &'s Value {
+    match schema.get("$ref").and_then(|r| r.as_str()).and_then(|r| r.strip_prefix("#/definitions/")) {
+      Some(name) => self.resolve(&self.root["definitions"][name]),
+      None => schema
+    }
+  }
+
+  fn check(&mut self, schema: &'s Value, node: &Node, path: &str) {
+    let schema = self.resolve(schema);
+
+    if let Some(alts) = schema.get("anyOf").and_then(|a| a.as_array()) {
+      let mut tries = Vec::new();
+      for alt in alts {
+        let mut sub = Validator { root: self.root, violations: Vec::new() };
+        sub.check(alt, node, path);
+        if sub.violations.is_empty() {
+          return;
+        }
+        tries.push((self.resolve(alt), sub.violations));
+      }
+      // Explain the first alternative of the right type, or else what types would have been right.
+      match tries
+        .into_iter()
+        .find(|(alt, _)| alt.get("type").and_then(|t| t.as_str()).map(|t| node.is_type(t)) == Some(true))
+      {
+        Some((_, violations)) => self.violations.extend(violations),
+        None => {
+          let types: Vec<_> =
+            alts.iter().filter_map(|alt| self.resolve(alt).get("type").and_then(|t| t.as_str())).collect();
+          let message = tr!("schema.wrong_type", expected = types.join(" or "), found = node.type_name());
+          self.violation(node, path, message);
+        }
+      }
+      return;
+    }
+
+    if let Some(expected) = schema.get("type").and_then(|t| t.as_str()) {
+      if !node.is_type(expected) {
+        let message = tr!("schema.wrong_type", expected = expected, found = node.type_name());
+        self.violation(node, path, message);
+        return;
+      }
+    }
+
+    if let Some(options) = schema.get("enum").and_then(|e| e.as_array()) {
+      if !options.iter().any(|o| o.as_str() == Some(node.text())) {
+        let expected = options.iter().filter_map(|o| o.as_str()).collect::<Vec<_>>().join(", ");
+        self.violation(node, path, tr!("schema.not_enum", expected = expected, found = node.text()));
+      }
+    }
+
+    if let Some(minimum) = schema.get("minimum").and_then(|m| m.as_i64()) {
+      if let Yaml::Integer(value) = Yaml::from_str(node.text()) {
+        if value < minimum {
+          self.violation(node, path, tr!("schema.below_minimum", minimum = minimum, found = value));
+        }
+      }
+    }
+
+    match &node.kind {
+      NodeKind::Seq(items) => {
+        if let Some(item_schema) = schema.get("items") {
+          for (i, item) in items.iter().enumerate() {
+            self.check(item_schema, item, &format!("{}[{}]", path, i));
+          }
+        }
+      }
+      NodeKind::Map(pairs) => self.check_map(schema, node, pairs, path),
+      NodeKind::Scalar(..) => ()
+    }
+  }
+
+  fn check_map(&mut self, schema: &'s Value, node: &Node, pairs: &[(Node, Node)], path: &str) {
+    let properties = schema.get("properties").and_then(|p| p.as_object());
+    for (key, value) in pairs {
+      let name = key.text();
+      let inner = if path.is_empty() { name.to_string() } else { format!("{}.{}", path, name) };
+      match (properties.and_then(|p| p.get(name)), schema.get("additionalProperties")) {
+        (Some(prop), _) => self.check(prop, value, &inner),
+        (None, Some(Value::Bool(false))) => self.violation(key, path, tr!("schema.unknown_key", key = name)),
+        (None, Some(additional)) => self.check(additional, value, &inner),
+        (None, None) => ()
+      }
+    }
+
+    for required in schema.get("required").and_then(|r| r.as_array()).into_iter().flatten() {
+      let required = required.as_str().unwrap_or_default();
+      if !pairs.iter().any(|(key, _)| key.text() == required) {
+        self.violation(node, path, tr!("schema.missing_key", key = required));
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::{validate, FRAGMENT};
+
+  fn violations(data: &str) -> Vec<String> {
+    validate(data, None).unwrap().iter().map(|v| format!("{}:{} {}", v.line, v.column, v.path)).collect()
+  }
+
+  #[test]
+  fn test_validate_good() {
+    let data = r#"
+options:
+  prev_tag: "versio-prev"
+projects:
+  - name: everything
+    id: 1
+    tag_prefix: ""
+    labels: cli
+    changelog: CHANGELOG.html
+    depends:
+      2:
+        size: match
+        files:
+          - file: Cargo.toml
+            toml: [dependencies, other, version]
+    version:
+      tags:
+        default: "0.0.0"
+  - name: other
+    id: "${OTHER_ID}"
+    version:
+      file: "toplevel.json"
+      json: "version"
+sizes:
+  use_angular: true
+  rules:
+    - '^JIRA-\d+ fix': patch
+  fail: ["*"]
+"#;
+    assert!(violations(data).is_empty(), "{:?}", violations(data));
+  }
+
+  #[test]
+  fn test_validate_bad() {
+    let data = r#"
+options:
+  prev_tg: "versio-prev"
+projects:
+  - name: everything
+    id: one
+    version:
+      tags:
+        default: 1
+      file: "toplevel.json"
+    depends:
+      2:
+        size: huge
+"#;
+    let found = violations(data);
+    assert!(found.contains(&"3:3 options".to_string()), "{:?}", found);
+    assert!(found.contains(&"13:15 projects[0].depends.2.size".to_string()), "{:?}", found);
+    assert!(found.iter().any(|v| v.ends_with("projects[0].version")), "{:?}", found);
+  }
+
+  #[test]
+  fn test_validate_fragment() {
+    assert!(validate("projects: []\n", Some(FRAGMENT)).unwrap().is_empty());
+    assert_eq!(1, validate("options: {}\n", Some(FRAGMENT)).unwrap().len());
+  }
+}
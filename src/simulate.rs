@@ -0,0 +1,148 @@
+//! Hypothetical commits, which can be planned without touching the repository.
+//!
+//! A simulation file looks like this:
+//!
+//! ```yaml
+//! commits:
+//!   - type: feat
+//!     scope: api
+//!     summary: add the thing
+//!     paths: [ "api/src/thing.rs" ]
+//!     pr: 12
+//!   - type: fix
+//!     breaking: true
+//!     paths: [ "lib/src/lib.rs" ]
+//!
+//! expect:
+//!   api: minor
+//!   lib: major
+//! ```
+//!
+//! Commits with the same `pr` number are grouped together; commits without one are treated like commits
+//! that were pushed directly to the branch.
+
+use crate::config::{Config, Size};
+use crate::errors::Result;
+use crate::git::{CommitInfoBuf, FullPr};
+use crate::mono::Plan;
+use crate::state::StateRead;
+use chrono::{FixedOffset, TimeZone};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+#[derive(Deserialize, Debug)]
+pub struct Simulation {
+  commits: Vec<SimCommit>,
+  #[serde(default)]
+  expect: BTreeMap<String, Size>
+}
+
+#[derive(Deserialize, Debug)]
+struct SimCommit {
+  #[serde(rename = "type")]
+  kind: String,
+  scope: Option<String>,
+  summary: Option<String>,
+  #[serde(default)]
+  breaking: bool,
+  #[serde(default)]
+  paths: Vec<String>,
+  #[serde(default)]
+  pr: u32
+}
+
+impl SimCommit {
+  fn header(&self) -> String {
+    let scope = self.scope.as_ref().map(|s| format!("({})", s)).unwrap_or_default();
+    let bang = if self.breaking { "!" } else { "" };
+    let summary = self.summary.as_deref().unwrap_or("simulated commit");
+    format!("{}{}{}: {}", self.kind, scope, bang, summary)
+  }
+
+  fn to_info(&self, index: usize) -> CommitInfoBuf {
+    let id = format!("{:07x}{:033}", index + 1, 0);
+    let kind = if self.breaking { "!".to_string() } else { self.kind.trim().to_lowercase() };
+    let header = self.header();
+    let offset = FixedOffset::west_opt(0).expect("0 should be in bounds");
+    let time = offset.timestamp_opt(index as i64, 0).single().expect("small timestamps in bounds");
+    CommitInfoBuf::new(id, kind, header.clone(), header, self.paths.clone(), time)
+  }
+}
+
+impl Simulation {
+  pub fn read<P: AsRef<Path>>(path: P) -> Result<Simulation> {
+    Ok(serde_yaml::from_str(&std::fs::read_to_string(path)?)?)
+  }
+
+  /// Group the simulated commits into PRs, in the order that their PR numbers first appear.
+  pub fn prs(&self) -> Vec<FullPr> {
+    let mut order = Vec::new();
+    let mut grouped: BTreeMap<u32, Vec<CommitInfoBuf>> = BTreeMap::new();
+    for (i, commit) in self.commits.iter().enumerate() {
+      if !grouped.contains_key(&commit.pr) {
+        order.push(commit.pr);
+      }
+      grouped.entry(commit.pr).or_default().push(commit.to_info(i));
+    }
+
+    order
+      .into_iter()
+      .enumerate()
+      .map(|(discovery_order, number)| {
+        let commits = grouped.remove(&number).unwrap_or_default();
+        let title = if number == 0 { "<direct commits>".to_string() } else { format!("Simulated PR {}", number) };
+        FullPr::synthetic(number, title, commits, discovery_order)
+      })
+      .collect()
+  }
+
+  /// Compare the plan against the `expect` sizes, returning a description of each mismatch.
+  pub fn mismatches<S: StateRead>(&self, plan: &Plan, cfg: &Config<S>) -> Result<Vec<String>> {
+    let mut mismatches = Vec::new();
+    for (name, expected) in &self.expect {
+      let id = cfg.find_exact(name)?;
+      let actual = plan.incrs().get(id).map(|(size, _)| *size).unwrap_or(Size::Empty);
+      if &actual != expected {
+        mismatches.push(format!("{}: expected {}, but planned {}", name, expected, actual));
+      }
+    }
+    Ok(mismatches)
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::Simulation;
+
+  #[test]
+  fn test_group_prs() {
+    let sim: Simulation = serde_yaml::from_str(
+      r#"
+commits:
+  - type: feat
+    paths: [ "a/x" ]
+    pr: 3
+  - type: fix
+    paths: [ "b/y" ]
+  - type: Fix
+    scope: a
+    paths: [ "a/y" ]
+    pr: 3
+  - type: chore
+    breaking: true
+"#
+    )
+    .unwrap();
+
+    let prs = sim.prs();
+    assert_eq!(2, prs.len());
+    assert_eq!(3, prs[0].number());
+    assert_eq!(0, prs[1].number());
+
+    let kinds: Vec<_> = prs[0].commits().iter().map(|c| c.kind()).collect();
+    assert_eq!(vec!["feat", "fix"], kinds);
+    assert_eq!("Fix(a): simulated commit", prs[0].commits()[1].summary());
+    assert_eq!("!", prs[1].commits()[1].kind());
+  }
+}
@@ -1,24 +1,33 @@
 //! The mechanisms used to read and write state, both current and historical.
 
-use crate::config::{CommitConfig, HookSet, ProjectId};
-use crate::errors::{Context as _, Result};
-use crate::git::{FromTagBuf, Repo, Slice};
+use crate::config::{CommitConfig, ProjectHooks, ProjectId};
+use crate::errors::{Context as _, Error, Result};
+use crate::git::{Mirrors, Repo, Slice};
 use crate::mark::{NamedData, Picker};
+use crate::scan::{Scanner, TomlScanner};
+use crate::template::splice_keep_a_changelog;
 use path_slash::{PathBufExt as _, PathExt as _};
 use regex::Regex;
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
-use std::fs::OpenOptions;
 use std::mem::take;
 use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
 use tracing::{trace, warn};
 
 pub trait StateRead: FilesRead {
   fn latest_tag(&self, proj: &ProjectId) -> Option<&String>;
+
+  /// The version printed by a version `command`, run in the `root` directory; or `None` if commands can't be run
+  /// against this state, since its files aren't checked out.
+  fn command_version(&self, cmd: &str, root: Option<&String>) -> Result<Option<String>>;
 }
 
 impl<S: StateRead> StateRead for &S {
   fn latest_tag(&self, proj: &ProjectId) -> Option<&String> { <S as StateRead>::latest_tag(*self, proj) }
+  fn command_version(&self, cmd: &str, root: Option<&String>) -> Result<Option<String>> {
+    <S as StateRead>::command_version(*self, cmd, root)
+  }
 }
 
 pub trait FilesRead {
@@ -48,6 +57,14 @@ impl FilesRead for CurrentState {
 
 impl StateRead for CurrentState {
   fn latest_tag(&self, proj: &ProjectId) -> Option<&String> { self.tags.latest(proj) }
+
+  fn command_version(&self, cmd: &str, root: Option<&String>) -> Result<Option<String>> {
+    let dir = match root {
+      Some(root) => self.files.root.join(PathBuf::from_slash(root)),
+      None => self.files.root.clone()
+    };
+    run_version_command(cmd, &dir).map(Some)
+  }
 }
 
 impl CurrentState {
@@ -92,6 +109,7 @@ impl<'r> FilesRead for PrevState<'r> {
 
 impl<'r> StateRead for PrevState<'r> {
   fn latest_tag(&self, proj: &ProjectId) -> Option<&String> { self.tags.latest(proj) }
+  fn command_version(&self, _cmd: &str, _root: Option<&String>) -> Result<Option<String>> { Ok(None) }
 }
 
 impl<'r> PrevState<'r> {
@@ -110,10 +128,44 @@ impl<'r> FilesRead for PrevFiles<'r> {
 }
 
 impl<'r> PrevFiles<'r> {
-  pub fn from_slice(slice: Slice<'r>) -> Result<PrevFiles> { Ok(PrevFiles::new(slice)) }
-
   pub fn new(slice: Slice<'r>) -> PrevFiles { PrevFiles { slice } }
-  pub fn slice_to(&self, spec: FromTagBuf) -> Result<PrevFiles<'r>> { PrevFiles::from_slice(self.slice.slice(spec)) }
+}
+
+/// A command that runs `script` with the platform's shell: `bash -e` everywhere but Windows, which runs it with
+/// `cmd /C`. The `args` are the script's positional arguments, which `cmd` doesn't have, so scripts that need to
+/// run on Windows should read the environment instead.
+pub fn shell(script: &str, args: &[&str]) -> Command {
+  #[cfg(windows)]
+  {
+    use std::os::windows::process::CommandExt as _;
+    let _ = args;
+    let mut command = Command::new("cmd");
+    command.args(["/D", "/C"]).raw_arg(script);
+    command
+  }
+  #[cfg(not(windows))]
+  {
+    let mut command = Command::new("bash");
+    command.args(["-e", "-c", script]).args(args);
+    command
+  }
+}
+
+/// Run a version `command` in `dir`, and return what it prints, trimmed.
+pub fn run_version_command(cmd: &str, dir: &Path) -> Result<String> {
+  let output = shell(cmd, &[])
+    .current_dir(dir)
+    .stderr(Stdio::inherit())
+    .output()
+    .with_context(|| format!("Can't run version command {}.", cmd))?;
+  if !output.status.success() {
+    bail!("Unable to read version with command {}.", cmd);
+  }
+  let vers = String::from_utf8(output.stdout)?.trim().to_string();
+  if vers.is_empty() {
+    bail!("Version command {} printed no version.", cmd);
+  }
+  Ok(vers)
 }
 
 #[derive(Debug)]
@@ -140,7 +192,13 @@ pub struct StateWrite {
   tag_head: Vec<String>,
   tag_commit: HashMap<String, String>,
   tag_head_or_last: Vec<(String, ProjectId)>,
-  new_tags: HashMap<ProjectId, String>
+  new_tags: HashMap<ProjectId, String>,
+  #[serde(default)]
+  tag_messages: HashMap<String, String>,
+  #[serde(default)]
+  versions: HashMap<ProjectId, String>,
+  #[serde(default)]
+  target: Option<String>
 }
 
 impl Default for StateWrite {
@@ -155,7 +213,10 @@ impl StateWrite {
       tag_commit: HashMap::new(),
       tag_head_or_last: Vec::new(),
       proj_writes: HashSet::new(),
-      new_tags: HashMap::new()
+      new_tags: HashMap::new(),
+      tag_messages: HashMap::new(),
+      versions: HashMap::new(),
+      target: None
     }
   }
 
@@ -167,12 +228,58 @@ impl StateWrite {
     Ok(())
   }
 
+  /// Whether any file of the project will be written.
+  pub fn writes_project(&self, proj_id: &ProjectId) -> bool { self.proj_writes.contains(proj_id) }
+
+  /// Remember the new version of the project, for its hooks.
+  pub fn set_version(&mut self, proj_id: &ProjectId, vers: &str) { self.versions.insert(proj_id.clone(), vers.into()); }
+  pub fn version(&self, proj_id: &ProjectId) -> Option<&String> { self.versions.get(proj_id) }
+
+  /// Splice a release's changelog `section` into a Keep-a-Changelog file, instead of overwriting the file. The file
+  /// is read when it's written, so any hand-written changes made to it before then are kept.
+  pub fn splice_changelog(
+    &mut self, file: PathBuf, section: String, version: String, tag: Option<String>, proj_id: &ProjectId
+  ) {
+    self.writes.push(FileWrite::Splice { path: file, section, version, tag });
+    self.proj_writes.insert(proj_id.clone());
+  }
+
+  /// Write a file that isn't part of any one project, such as a release manifest.
+  pub fn write_other_file<C: ToString>(&mut self, file: PathBuf, content: C) {
+    self.writes.push(FileWrite::Write { path: file, val: content.to_string(), changelog: false });
+  }
+
+  /// Remove a file that isn't part of any one project, if it exists.
+  pub fn remove_other_file(&mut self, file: PathBuf) { self.writes.push(FileWrite::Remove { path: file }); }
+
   pub fn update_mark<C: ToString>(&mut self, pick: PickPath, content: C, proj_id: &ProjectId) -> Result<()> {
     self.writes.push(FileWrite::Update { pick, val: content.to_string() });
     self.proj_writes.insert(proj_id.clone());
     Ok(())
   }
 
+  /// Run a version `command` in `dir` to write the new version, which is passed to it as both its first argument
+  /// and `$VERSIO_VERSION`.
+  pub fn write_by_command(&mut self, cmd: String, dir: PathBuf, vers: &str, proj_id: &ProjectId) {
+    self.writes.push(FileWrite::Command { cmd, dir, val: vers.to_string() });
+    self.proj_writes.insert(proj_id.clone());
+  }
+
+  /// Run a `cmd` in `dir` that updates a lockfile, after the files are written. The same command is only run once.
+  pub fn update_lockfile(&mut self, cmd: String, dir: PathBuf, proj_id: &ProjectId) {
+    let pending = |w: &FileWrite| matches!(w, FileWrite::Command { cmd: c, dir: d, .. } if *c == cmd && *d == dir);
+    if !self.writes.iter().any(pending) {
+      self.writes.push(FileWrite::Command { cmd, dir, val: String::new() });
+    }
+    self.proj_writes.insert(proj_id.clone());
+  }
+
+  pub fn update_cargo_lock(&mut self, lock: PathBuf, manifest: PathBuf, vers: &str, proj_id: &ProjectId) -> Result<()> {
+    self.writes.push(FileWrite::CargoLock { lock, manifest, val: vers.to_string() });
+    self.proj_writes.insert(proj_id.clone());
+    Ok(())
+  }
+
   pub fn tag_head_or_last<T: ToString>(&mut self, vers: &str, tag: T, proj: &ProjectId) -> Result<()> {
     let tag = tag.to_string();
     trace!("head_or_last on {} tagged with {}.", proj, tag);
@@ -181,6 +288,45 @@ impl StateWrite {
     Ok(())
   }
 
+  /// Move a floating `tag` (like `v1`) to wherever the project's new version tag goes. It's always lightweight, and
+  /// it's force-pushed, since it's expected to move.
+  pub fn float_tag(&mut self, tag: String, proj: &ProjectId) {
+    trace!("head_or_last on {} floated {}.", proj, tag);
+    self.tag_head_or_last.push((tag, proj.clone()));
+  }
+
+  /// Tag the commit `oid` instead of HEAD: nothing is committed, so this is only for tag-only releases.
+  pub fn tag_at(&mut self, oid: String) { self.target = Some(oid); }
+
+  /// Create `tag` as an annotated tag with this message, instead of a lightweight tag.
+  pub fn annotate_tag(&mut self, tag: String, msg: String) { self.tag_messages.insert(tag, msg); }
+
+  /// The files that will be written. Version commands aren't included, since they can write anything.
+  pub fn written_paths(&self) -> impl Iterator<Item = &Path> + '_ { self.writes.iter().filter_map(|w| w.path()) }
+
+  /// Each file that would be written, as it is and as it would be after every write to it, in the order they're
+  /// first written. Nothing is changed on disk, and version commands are skipped, since they can write anything.
+  pub fn preview(&self) -> Result<Vec<FilePreview>> {
+    let mut previews: Vec<FilePreview> = Vec::new();
+    for write in &self.writes {
+      let path = match write.path() {
+        Some(path) => path,
+        None => continue
+      };
+      let i = match previews.iter().position(|p| p.path == path) {
+        Some(i) => i,
+        None => {
+          let old = read_if_exists(path)?;
+          previews.push(FilePreview { path: path.to_path_buf(), new: old.clone(), old });
+          previews.len() - 1
+        }
+      };
+      let new = write.preview(previews[i].new.as_deref(), &previews)?;
+      previews[i].new = new;
+    }
+    Ok(previews)
+  }
+
   pub fn write_changelogs(&mut self) -> Result<()> {
     // TODO(later): we're probably not going to do anything else after this, but should we remove the changelogs
     // from `self.writes`, just in case?
@@ -190,7 +336,8 @@ impl StateWrite {
     Ok(())
   }
 
-  pub fn commit(&mut self, repo: &Repo, data: CommitArgs) -> Result<()> {
+  /// Write, commit, tag, and push everything (or pause first), returning the mirrors that couldn't be pushed to.
+  pub fn commit(&mut self, repo: &Repo, data: CommitArgs) -> Result<Vec<(String, Error)>> {
     for write in &self.writes {
       write.write()?;
     }
@@ -198,8 +345,8 @@ impl StateWrite {
     self.writes.clear();
 
     for proj_id in &self.proj_writes {
-      if let Some((root, hooks)) = data.hooks.get(proj_id) {
-        hooks.execute_post_write(root)?;
+      if let Some(hooks) = data.hooks.get(proj_id) {
+        hooks.execute("post_write")?;
       }
     }
 
@@ -214,12 +361,13 @@ impl StateWrite {
       last_commits,
       old_tags,
       data.advance_prev,
-      repo.commit_config().clone()
+      repo.commit_config().clone(),
+      repo.mirrors().clone()
     );
 
     if data.pause {
-      let file = OpenOptions::new().create(true).write(true).truncate(true).open(".versio-paused")?;
-      Ok(serde_json::to_writer(file, &commit_state)?)
+      repo.save_paused(&serde_json::to_string(&commit_state)?)?;
+      Ok(Vec::new())
     } else {
       commit_state.resume(repo)
     }
@@ -231,14 +379,14 @@ pub struct CommitArgs<'a> {
   last_commits: &'a HashMap<ProjectId, String>,
   old_tags: &'a HashMap<ProjectId, String>,
   advance_prev: bool,
-  hooks: &'a HashMap<ProjectId, (Option<&'a String>, &'a HookSet)>,
+  hooks: &'a HashMap<ProjectId, ProjectHooks<'a>>,
   pause: bool
 }
 
 impl<'a> CommitArgs<'a> {
   pub fn new(
     prev_tag: &'a str, last_commits: &'a HashMap<ProjectId, String>, old_tags: &'a HashMap<ProjectId, String>,
-    advance_prev: bool, hooks: &'a HashMap<ProjectId, (Option<&'a String>, &'a HookSet)>, pause: bool
+    advance_prev: bool, hooks: &'a HashMap<ProjectId, ProjectHooks<'a>>, pause: bool
   ) -> CommitArgs<'a> {
     CommitArgs { prev_tag, last_commits, old_tags, advance_prev, hooks, pause }
   }
@@ -261,20 +409,23 @@ pub struct CommitState {
   last_commits: HashMap<ProjectId, String>,
   old_tags: HashMap<ProjectId, String>,
   advance_prev: bool,
-  commit_config: CommitConfig
+  commit_config: CommitConfig,
+  #[serde(default)]
+  mirrors: Mirrors
 }
 
 impl CommitState {
   pub fn new(
     write: StateWrite, did_write: bool, prev_tag: String, last_commits: HashMap<ProjectId, String>,
-    old_tags: HashMap<ProjectId, String>, advance_prev: bool, commit_config: CommitConfig
+    old_tags: HashMap<ProjectId, String>, advance_prev: bool, commit_config: CommitConfig, mirrors: Mirrors
   ) -> CommitState {
-    CommitState { write, did_write, prev_tag, last_commits, old_tags, advance_prev, commit_config }
+    CommitState { write, did_write, prev_tag, last_commits, old_tags, advance_prev, commit_config, mirrors }
   }
 
   pub fn commit_config(&self) -> &CommitConfig { &self.commit_config }
 
-  pub fn resume(&mut self, repo: &Repo) -> Result<()> {
+  /// Commit, tag, and push, and then push everything to the mirrors: returns the mirrors that failed, with why.
+  pub fn resume(&mut self, repo: &Repo) -> Result<Vec<(String, Error)>> {
     if self.did_write {
       trace!("Wrote files, so committing.");
       repo.commit()?;
@@ -282,75 +433,270 @@ impl CommitState {
       trace!("No files written, so not committing.");
     }
 
+    let head = self.write.target.clone().unwrap_or_else(|| "HEAD".to_string());
+    let messages = take(&mut self.write.tag_messages);
+    let mut tagged = Vec::new();
+    let mut update_tag = |tag: &str, spec: &str| {
+      tagged.push(tag.to_string());
+      match messages.get(tag) {
+        Some(msg) => repo.update_tag_anno(tag, spec, msg),
+        None => repo.update_tag(tag, spec)
+      }
+    };
+
     for tag in &self.write.tag_head {
-      repo.update_tag_head(tag)?;
+      update_tag(tag, &head)?;
     }
     self.write.tag_head.clear();
 
     for (tag, proj_id) in &self.write.tag_head_or_last {
       if self.write.proj_writes.contains(proj_id) {
-        repo.update_tag_head(tag)?;
+        update_tag(tag, &head)?;
       } else if let Some(oid) = self.last_commits.get(proj_id) {
-        repo.update_tag(tag, oid)?;
+        update_tag(tag, oid)?;
       } else {
-        warn!("Latest commit for project {} unknown: tagging head.", proj_id);
-        repo.update_tag_head(tag)?;
+        warn!("Latest commit for project {} unknown: tagging {}.", proj_id, head);
+        update_tag(tag, &head)?;
       }
     }
     self.write.tag_head_or_last.clear();
     self.write.proj_writes.clear();
 
     for (tag, oid) in &self.write.tag_commit {
-      repo.update_tag(tag, oid)?;
+      update_tag(tag, oid)?;
     }
     self.write.tag_commit.clear();
 
     if self.advance_prev {
       fill_from_old(&self.old_tags, &mut self.write.new_tags);
-      let msg = serde_json::to_string(&PrevTagMessage::new(std::mem::take(&mut self.write.new_tags)))?;
-      repo.update_tag_head_anno(&self.prev_tag, &msg)?;
+      let previous = PrevRelease::find(repo, &self.prev_tag)?;
+      let msg = PrevTagMessage::new(std::mem::take(&mut self.write.new_tags), previous);
+      let msg = serde_json::to_string(&msg)?;
+      let msg = self.commit_config.tag_message(&msg);
+      repo.write_prev(&self.prev_tag, &head, &msg)?;
+      tagged.push(self.prev_tag.clone());
     }
 
-    Ok(())
+    if self.mirrors.is_empty() {
+      return Ok(Vec::new());
+    }
+    Ok(repo.push_mirrors(&self.mirrors, &tagged))
   }
 }
 
 #[derive(Deserialize, Serialize, Default)]
 pub struct PrevTagMessage {
-  versions: HashMap<ProjectId, String>
+  versions: HashMap<ProjectId, String>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  previous: Option<PrevRelease>
 }
 
 impl PrevTagMessage {
-  pub fn new(versions: HashMap<ProjectId, String>) -> PrevTagMessage { PrevTagMessage { versions } }
+  pub fn new(versions: HashMap<ProjectId, String>, previous: Option<PrevRelease>) -> PrevTagMessage {
+    PrevTagMessage { versions, previous }
+  }
+
+  /// Read the JSON at the start of a prev tag annotation, ignoring any trailing CI-skip marker or signature.
+  pub fn parse(anno: &str) -> Result<Option<PrevTagMessage>> {
+    Ok(serde_json::Deserializer::from_str(anno).into_iter().next().transpose()?)
+  }
+
+  pub fn versions(&self) -> &HashMap<ProjectId, String> { &self.versions }
+  pub fn previous(&self) -> Option<&PrevRelease> { self.previous.as_ref() }
   pub fn into_versions(self) -> HashMap<ProjectId, String> { self.versions }
 }
 
+/// Where the prev tag pointed before the latest release (and what it recorded), so the release can be rolled back.
+#[derive(Deserialize, Serialize, Debug)]
+pub struct PrevRelease {
+  commit: String,
+  versions: HashMap<ProjectId, String>
+}
+
+impl PrevRelease {
+  fn find(repo: &Repo, prev_tag: &str) -> Result<Option<PrevRelease>> {
+    let commit = match repo.tag_commit_oid(prev_tag)? {
+      Some(commit) => commit,
+      None => return Ok(None)
+    };
+    let anno = repo.prev_message(prev_tag).map(|anno| PrevTagMessage::parse(&anno)).transpose()?.flatten();
+    Ok(Some(PrevRelease { commit, versions: anno.map(|a| a.into_versions()).unwrap_or_default() }))
+  }
+
+  pub fn commit(&self) -> &str { &self.commit }
+  pub fn versions(&self) -> &HashMap<ProjectId, String> { &self.versions }
+}
+
+/// A file as it is, and as it would be after a release: a file that doesn't exist has no content.
+pub struct FilePreview {
+  path: PathBuf,
+  old: Option<String>,
+  new: Option<String>
+}
+
+impl FilePreview {
+  pub fn path(&self) -> &Path { &self.path }
+  pub fn old(&self) -> Option<&str> { self.old.as_deref() }
+  pub fn new(&self) -> Option<&str> { self.new.as_deref() }
+}
+
+fn read_if_exists(path: &Path) -> Result<Option<String>> {
+  if !path.exists() {
+    return Ok(None);
+  }
+  Ok(Some(std::fs::read_to_string(path).with_context(|| format!("Can't read {}", path.to_string_lossy()))?))
+}
+
 #[derive(Deserialize, Serialize)]
 enum FileWrite {
   Write { path: PathBuf, val: String, changelog: bool },
-  Update { pick: PickPath, val: String }
+  Splice { path: PathBuf, section: String, version: String, tag: Option<String> },
+  Update { pick: PickPath, val: String },
+  CargoLock { lock: PathBuf, manifest: PathBuf, val: String },
+  Command { cmd: String, dir: PathBuf, val: String },
+  Remove { path: PathBuf }
 }
 
 impl FileWrite {
   pub fn is_changelog(&self) -> bool {
     match self {
       FileWrite::Write { changelog, .. } => *changelog,
-      FileWrite::Update { .. } => false
+      FileWrite::Splice { .. } => true,
+      FileWrite::Update { .. }
+      | FileWrite::CargoLock { .. }
+      | FileWrite::Command { .. }
+      | FileWrite::Remove { .. } => false
+    }
+  }
+
+  fn path(&self) -> Option<&Path> {
+    match self {
+      FileWrite::Write { path, .. } | FileWrite::Splice { path, .. } | FileWrite::Remove { path } => Some(path),
+      FileWrite::Update { pick, .. } => Some(&pick.file),
+      FileWrite::CargoLock { lock, .. } => Some(lock),
+      FileWrite::Command { .. } => None
+    }
+  }
+
+  /// The content of this write's file after it's written, given its `current` content, and the content that the
+  /// `pending` writes will leave in other files.
+  fn preview(&self, current: Option<&str>, pending: &[FilePreview]) -> Result<Option<String>> {
+    match self {
+      FileWrite::Write { val, .. } => Ok(Some(val.clone())),
+      FileWrite::Splice { section, version, tag, .. } => {
+        Ok(Some(splice_keep_a_changelog(current.unwrap_or(""), section, version, tag.as_deref())))
+      }
+      FileWrite::Update { pick, val } => {
+        let data = current.ok_or_else(|| bad!("Can't read file {}.", pick.file.to_string_lossy()))?;
+        pick.new_data(data.to_string(), val).map(Some)
+      }
+      FileWrite::CargoLock { lock, manifest, val } => {
+        let lock_data = current.ok_or_else(|| bad!("Can't read lockfile {}.", lock.to_string_lossy()))?;
+        let manifest_data = match pending.iter().find(|p| p.path == *manifest) {
+          Some(p) => p.new.clone(),
+          None => read_if_exists(manifest)?
+        };
+        let manifest_data = manifest_data.ok_or_else(|| bad!("Can't read manifest {}.", manifest.to_string_lossy()))?;
+        let name = TomlScanner::new("package.name").find(&manifest_data)?.value().to_string();
+        let lock_data = rewrite_cargo_lock(lock_data, &name, val)
+          .with_context(|| format!("Can't update {} in {}.", name, lock.to_string_lossy()))?;
+        Ok(Some(lock_data))
+      }
+      FileWrite::Command { .. } => Ok(current.map(|c| c.to_string())),
+      FileWrite::Remove { .. } => Ok(None)
     }
   }
 
   pub fn write(&self) -> Result<()> {
     match self {
       FileWrite::Write { path, val, .. } => {
+        if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+          std::fs::create_dir_all(dir).with_context(|| format!("Can't create {}", dir.to_string_lossy()))?;
+        }
         Ok(std::fs::write(path, val).with_context(|| format!("Can't write to {}", path.to_string_lossy()))?)
       }
+      FileWrite::Splice { path, section, version, tag } => {
+        let old = if path.exists() {
+          std::fs::read_to_string(path).with_context(|| format!("Can't read {}", path.to_string_lossy()))?
+        } else {
+          String::new()
+        };
+        let val = splice_keep_a_changelog(&old, section, version, tag.as_deref());
+        FileWrite::Write { path: path.clone(), val, changelog: true }.write()
+      }
       // FileWrite::Append { path, val } => {
       //   let mut file = OpenOptions::new().append(true).open(path)?;
       //   Ok(file.write_all(val.as_bytes())?)
       // }
-      FileWrite::Update { pick, val } => pick.write_value(val)
+      FileWrite::Update { pick, val } => pick.write_value(val),
+      FileWrite::CargoLock { lock, manifest, val } => write_cargo_lock(lock, manifest, val),
+      FileWrite::Command { cmd, dir, val } => {
+        let status = shell(cmd, &["versio", val])
+          .current_dir(dir)
+          .env("VERSIO_VERSION", val)
+          .status()
+          .with_context(|| format!("Can't run version command {}.", cmd))?;
+        if !status.success() {
+          bail!("Unable to write version {} with command {}.", val, cmd);
+        }
+        Ok(())
+      }
+      FileWrite::Remove { path } => {
+        if path.exists() {
+          std::fs::remove_file(path).with_context(|| format!("Can't remove {}", path.to_string_lossy()))?;
+        }
+        Ok(())
+      }
+    }
+  }
+}
+
+/// Update the version of the manifest's package in a `Cargo.lock`, so it agrees with the new manifest version.
+fn write_cargo_lock(lock: &Path, manifest: &Path, val: &str) -> Result<()> {
+  let manifest_data = std::fs::read_to_string(manifest)
+    .with_context(|| format!("Can't read manifest {}.", manifest.to_string_lossy()))?;
+  let name = TomlScanner::new("package.name").find(&manifest_data)?.value().to_string();
+  let lock_data =
+    std::fs::read_to_string(lock).with_context(|| format!("Can't read lockfile {}.", lock.to_string_lossy()))?;
+  let lock_data = rewrite_cargo_lock(&lock_data, &name, val)
+    .with_context(|| format!("Can't update {} in {}.", name, lock.to_string_lossy()))?;
+  std::fs::write(lock, lock_data).with_context(|| format!("Can't write to {}", lock.to_string_lossy()))?;
+  Ok(())
+}
+
+/// Rewrite the version of the local (source-less) `[[package]]` named `name`.
+fn rewrite_cargo_lock(data: &str, name: &str, val: &str) -> Result<String> {
+  let name_line = format!("name = \"{}\"", name);
+  let mut found = false;
+  let mut output = Vec::new();
+  let mut block: Vec<&str> = Vec::new();
+
+  let mut flush = |block: &mut Vec<&str>, output: &mut Vec<String>| {
+    let is_target = block.first().map(|l| l.trim() == "[[package]]").unwrap_or(false)
+      && block.iter().any(|l| l.trim() == name_line)
+      && !block.iter().any(|l| l.trim().starts_with("source = "));
+    for line in block.drain(..) {
+      if is_target && line.trim().starts_with("version = ") {
+        output.push(format!("version = \"{}\"", val));
+        found = true;
+      } else {
+        output.push(line.to_string());
+      }
+    }
+  };
+
+  for line in data.split('\n') {
+    if line.trim().starts_with('[') {
+      flush(&mut block, &mut output);
     }
+    block.push(line);
+  }
+  flush(&mut block, &mut output);
+
+  if !found {
+    bail!("No local package \"{}\" found in lockfile.", name);
   }
+  Ok(output.join("\n"))
 }
 
 #[derive(Deserialize, Serialize)]
@@ -370,11 +716,68 @@ impl PickPath {
     mark.write_new_value(val)?;
     Ok(())
   }
+
+  /// The file's `data` with the new value, without writing it.
+  fn new_data(&self, data: String, val: &str) -> Result<String> {
+    Ok(self.picker.scan(NamedData::new(self.file.clone(), data))?.into_new_data(val))
+  }
 }
 
 pub fn read_from_slice<P: AsRef<Path>>(slice: &Slice, path: P) -> Result<String> {
   let path = path.as_ref().to_slash_lossy();
   let blob = slice.blob(&path)?;
-  let cont: &str = std::str::from_utf8(blob.content()).with_context(|| format!("Not UTF8 content: {}", path))?;
+  let cont: &str = std::str::from_utf8(&blob).with_context(|| format!("Not UTF8 content: {}", path))?;
   Ok(cont.to_string())
 }
+
+#[cfg(test)]
+mod test {
+  use super::{rewrite_cargo_lock, PickPath};
+  use crate::mark::{Picker, ScanningPicker};
+  use crate::scan::parts::Part;
+  use std::path::PathBuf;
+
+  #[test]
+  fn test_rewrite_cargo_lock() {
+    let lock = r#"version = 3
+
+[[package]]
+name = "other"
+version = "0.1.0"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+
+[[package]]
+name = "thing"
+version = "1.2.3"
+dependencies = [
+ "other",
+]
+
+[[package]]
+name = "other"
+version = "0.1.0"
+"#;
+
+    let rewritten = rewrite_cargo_lock(lock, "other", "0.2.0").unwrap();
+    assert!(rewritten.contains("name = \"other\"\nversion = \"0.1.0\"\nsource"));
+    assert!(rewritten.ends_with("name = \"other\"\nversion = \"0.2.0\"\n"));
+    assert!(rewritten.contains("name = \"thing\"\nversion = \"1.2.3\""));
+  }
+
+  #[test]
+  fn test_rewrite_cargo_lock_missing() {
+    assert!(rewrite_cargo_lock("[[package]]\nname = \"thing\"\nversion = \"1.2.3\"\n", "other", "0.2.0").is_err());
+  }
+
+  #[test]
+  fn test_two_fields() {
+    // A Helm chart's own version, and the version of the app that it deploys.
+    let pick = |field: &str| {
+      PickPath::new(PathBuf::from("Chart.yaml"), Picker::Yaml(ScanningPicker::new(vec![Part::Map(field.into())])))
+    };
+    let chart = "apiVersion: v2\nname: widgets\nversion: 0.3.1\nappVersion: \"1.16.0\"\n";
+    let chart = pick("version").new_data(chart.into(), "0.3.2").unwrap();
+    let chart = pick("appVersion").new_data(chart, "1.17.0").unwrap();
+    assert_eq!("apiVersion: v2\nname: widgets\nversion: 0.3.2\nappVersion: \"1.17.0\"\n", chart);
+  }
+}
@@ -1,13 +1,17 @@
 //! Template and changelog management for Versio.
 
 use crate::bail;
+use crate::config::ChangelogSection;
 use crate::errors::Result;
+use crate::links::IssueLink;
 use crate::mono::{Changelog, ChangelogEntry};
 use crate::output::ProjLine;
 use chrono::prelude::Utc;
 use hyper::Client;
+use liquid::model::Value;
 use liquid::ParserBuilder;
 use path_slash::PathBufExt;
+use std::iter::once;
 use std::path::{Path, PathBuf};
 
 /// Extract everything in an old changelog between the `BEGIN CONTENT` and `END CONTENT` lines.
@@ -16,22 +20,209 @@ pub fn extract_old_content(path: &Path) -> Result<String> {
     return Ok("".into());
   }
 
-  let full_content = std::fs::read_to_string(path)?;
-  let content = full_content
+  Ok(extract_content(&std::fs::read_to_string(path)?))
+}
+
+/// Extract everything in a rendered changelog between the `BEGIN CONTENT` and `END CONTENT` lines.
+pub fn extract_content(full_content: &str) -> String {
+  full_content
     .split('\n')
     .skip_while(|l| !l.contains("### VERSIO BEGIN CONTENT ###"))
     .skip(1)
     .take_while(|l| !l.contains("### VERSIO END CONTENT ###"))
     .collect::<Vec<_>>()
-    .join("\n");
-  Ok(content)
+    .join("\n")
+}
+
+/// The marker in the first line of each release's section of a changelog, followed by the release's date and
+/// version: e.g. `### VERSIO CONTENT 2024-03-01 1.2.0 ###`.
+const SECTION_MARKER: &str = "### VERSIO CONTENT ";
+
+/// Replace the section of a release in a rendered changelog with a new `section`. A changelog rendered before its
+/// markers had versions can still have a section replaced if no other section has the same date.
+pub fn replace_section(full_content: &str, version: &str, date: &str, section: &str) -> Result<String> {
+  let lines: Vec<_> = full_content.split('\n').collect();
+  let begin = match lines.iter().position(|l| l.contains("### VERSIO BEGIN CONTENT ###")) {
+    Some(begin) => begin + 1,
+    None => bail!("The changelog has no \"### VERSIO BEGIN CONTENT ###\" marker.")
+  };
+  let end = lines[begin ..].iter().position(|l| l.contains("### VERSIO END CONTENT ###")).map(|e| begin + e);
+  let end = end.unwrap_or(lines.len());
+
+  let starts: Vec<_> = (begin .. end).filter(|i| lines[*i].contains(SECTION_MARKER)).collect();
+  // The date and version (if any) in the marker of the section that starts at line `i`.
+  let marked = |i: usize| {
+    let line = lines[i];
+    let rest = &line[line.find(SECTION_MARKER).unwrap() + SECTION_MARKER.len() ..];
+    rest.split_whitespace().take_while(|w| *w != "###").collect::<Vec<_>>()
+  };
+  let found = match starts.iter().position(|i| marked(*i).get(1) == Some(&version)) {
+    Some(found) => found,
+    None => {
+      let dated: Vec<_> = starts.iter().enumerate().filter(|(_, i)| marked(**i) == [date]).map(|(n, _)| n).collect();
+      match dated.as_slice() {
+        [found] => *found,
+        _ => bail!("Can't find the section of version {} in the changelog.", version)
+      }
+    }
+  };
+
+  let start = starts[found];
+  let stop = starts.get(found + 1).copied().unwrap_or(end);
+  let blanks = lines[start .. stop].iter().rev().take_while(|l| l.trim().is_empty()).count();
+  let mut section: Vec<_> = section.split('\n').collect();
+  while section.last().map(|l| l.trim().is_empty()).unwrap_or(false) {
+    section.pop();
+  }
+
+  let mut result = lines[.. start].to_vec();
+  result.extend(section);
+  result.extend(vec![""; blanks]);
+  result.extend(&lines[stop ..]);
+  Ok(result.join("\n"))
+}
+
+/// A release's rendered changelog: either the whole changelog file, or the release's section to splice into it.
+pub enum RenderedLog {
+  Full(String),
+  Section { section: String, version: String, tag: Option<String> }
+}
+
+/// The start of a new Keep-a-Changelog file, before any release is spliced into it.
+const KEEP_A_CHANGELOG_HEADER: &str = "# Changelog
+
+All notable changes to this project will be documented in this file.
+
+The format is based on [Keep a Changelog](https://keepachangelog.com/en/1.1.0/).
+
+## [Unreleased]
+";
+
+/// Splice a release's rendered `section` into a Keep-a-Changelog file, just under its `## [Unreleased]` heading.
+/// Notes written by hand under that heading are moved into the release; everything else in the file is kept. If
+/// the file has an `[unreleased]: <url>/compare/<old>...HEAD` link and the release has a `tag`, that link is moved
+/// up to the new tag, and a comparison link for the release is added beneath it.
+pub fn splice_keep_a_changelog(old: &str, section: &str, version: &str, tag: Option<&str>) -> String {
+  let old = if old.trim().is_empty() { KEEP_A_CHANGELOG_HEADER } else { old };
+  let mut lines: Vec<String> = old.lines().map(|l| l.to_string()).collect();
+  let is_release = |l: &str| l.starts_with("## ");
+
+  let (at, notes) = match lines.iter().position(|l| is_unreleased(l)) {
+    Some(i) => {
+      let end = lines[i + 1 ..].iter().position(|l| is_release(l) || is_link(l)).map(|e| i + 1 + e);
+      let end = end.unwrap_or(lines.len());
+      let notes: Vec<_> = lines.drain(i + 1 .. end).collect();
+      (i + 1, trim_blank(notes))
+    }
+    None => {
+      let i = lines.iter().position(|l| is_release(l) || is_link(l)).unwrap_or(lines.len());
+      lines.insert(i, "## [Unreleased]".into());
+      (i + 1, Vec::new())
+    }
+  };
+
+  let mut section = tidy_section(section);
+  if !notes.is_empty() {
+    let rest = section.split_off(1.min(section.len()));
+    section.push(String::new());
+    section.extend(notes);
+    section.extend(rest);
+  }
+  let mut block = vec![String::new()];
+  block.extend(section);
+  block.push(String::new());
+  let trailing = at + block.len();
+  lines.splice(at .. at, block);
+  while lines.get(trailing).map(|l| l.trim().is_empty()).unwrap_or(false) {
+    lines.remove(trailing);
+  }
+  if trailing >= lines.len() {
+    lines.truncate(trailing - 1);
+  }
+
+  if let Some(tag) = tag {
+    update_links(&mut lines, version, tag);
+  }
+
+  let mut spliced = lines.join("\n");
+  spliced.push('\n');
+  spliced
+}
+
+fn is_unreleased(line: &str) -> bool {
+  let line = line.trim().to_lowercase();
+  line.starts_with("## [unreleased]") || line == "## unreleased"
 }
 
+/// Whether a line is a link reference definition, like the comparison links at the bottom of a changelog.
+fn is_link(line: &str) -> bool { line.starts_with('[') && line.contains("]: ") }
+
+fn trim_blank(mut lines: Vec<String>) -> Vec<String> {
+  while lines.last().map(|l| l.trim().is_empty()).unwrap_or(false) {
+    lines.pop();
+  }
+  let lead = lines.iter().take_while(|l| l.trim().is_empty()).count();
+  lines.split_off(lead)
+}
+
+/// Tidy a rendered section: runs of blank lines are collapsed, and list items aren't separated by blank lines.
+fn tidy_section(section: &str) -> Vec<String> {
+  let lines = trim_blank(section.lines().map(|l| l.trim_end().to_string()).collect());
+  let is_item = |l: &str| l.starts_with("- ") || l.starts_with("* ");
+  let mut tidy: Vec<String> = Vec::new();
+  for (i, line) in lines.iter().enumerate() {
+    if line.is_empty() {
+      let prev_item = tidy.last().map(|l| is_item(l)).unwrap_or(false);
+      let next = lines[i ..].iter().find(|l| !l.is_empty());
+      let next_item = next.map(|l| is_item(l)).unwrap_or(false);
+      if tidy.last().map(|l| l.is_empty()).unwrap_or(true) || (prev_item && next_item) {
+        continue;
+      }
+    }
+    tidy.push(line.clone());
+  }
+  tidy
+}
+
+/// Move the `[unreleased]` comparison link up to `tag`, and add a link comparing the previous tag with `tag`.
+fn update_links(lines: &mut Vec<String>, version: &str, tag: &str) {
+  let i = match lines.iter().position(|l| l.to_lowercase().starts_with("[unreleased]: ")) {
+    Some(i) => i,
+    None => return
+  };
+  let line = lines[i].clone();
+  let (label, url) = line.split_at(line.find("]: ").unwrap() + 3);
+  let (base, prev) = match url.trim().rsplit_once("/compare/") {
+    Some((base, range)) => match range.strip_suffix("...HEAD") {
+      Some(prev) => (base, prev),
+      None => return
+    },
+    None => return
+  };
+  lines[i] = format!("{}{}/compare/{}...HEAD", label, base, tag);
+  lines.insert(i + 1, format!("[{}]: {}/compare/{}...{}", version, base, prev, tag));
+}
+
+/// The heading for commits that don't match any configured changelog section.
+const OTHER_SECTION: &str = "Other Changes";
+
+/// Configured text that surrounds the generated content of a changelog, and whether each release shows its
+/// statistics.
+#[derive(Default)]
+pub struct ChangelogBlocks {
+  pub preamble: String,
+  pub footer: String,
+  pub stats: bool
+}
+
+/// Render a changelog. If `sections` is not empty, the commits are also grouped under those headings (plus a
+/// final "Other Changes" heading) by their type and size.
 pub fn construct_changelog_html(
-  cl: &Changelog, proj: ProjLine, new_vers: &str, old_content: String, tmpl: String
+  cl: &Changelog, proj: ProjLine, new_vers: &str, old_content: String, blocks: ChangelogBlocks,
+  sections: &[ChangelogSection], tmpl: String
 ) -> Result<String> {
   let tmpl = ParserBuilder::with_stdlib().build()?.parse(&tmpl)?;
-  let nowymd = Utc::now().format("%Y-%m-%d").to_string();
+  let nowymd = cl.date().map(|d| d.to_string()).unwrap_or_else(|| Utc::now().format("%Y-%m-%d").to_string());
 
   let pr_count = cl
     .entries()
@@ -44,6 +235,9 @@ pub fn construct_changelog_html(
 
   let mut prs = Vec::new();
   let mut dps = Vec::new();
+  let mut contributors: Vec<&str> = Vec::new();
+  let mut stats = ReleaseStats::default();
+  let mut grouped = vec![Vec::new(); sections.len() + 1];
 
   for entry in cl.entries() {
     match entry {
@@ -52,34 +246,64 @@ pub fn construct_changelog_html(
           continue;
         }
 
+        let pr_name = if pr.number() == 0 {
+          if cl.offline() {
+            "Local commits".to_string()
+          } else if pr_count == 1 {
+            "Commits".to_string()
+          } else {
+            "Other commits".to_string()
+          }
+        } else {
+          format!("PR {}", pr.number())
+        };
+        let pr_link = pr.number() > 0 && pr.url().is_some();
+
+        if pr.number() > 0 {
+          stats.prs += 1;
+        }
         let mut commits = Vec::new();
         for c in pr.commits().iter().filter(|c| c.included()) {
-          commits.push(liquid::object!({
+          stats.count(c.kind(), c.breaking());
+          for author in c.authors() {
+            if !contributors.contains(&author.as_str()) {
+              contributors.push(author);
+            }
+          }
+          let commit = liquid::object!({
             "href": c.url().as_deref().unwrap_or(""),
             "link": c.url().is_some(),
             "shorthash": c.oid()[.. 7].to_string(),
             "size": c.size().to_string(),
+            "kind": c.kind(),
+            "breaking": c.breaking(),
+            "reverted": c.reverted(),
+            "scope": c.scope().unwrap_or(""),
             "summary": c.summary(),
-            "message": c.message().trim()
-          }));
-        }
+            "message": c.message().trim(),
+            "authors": c.authors(),
+            "issues": issue_objects(c.issues())
+          });
 
-        let pr_name = if pr.number() == 0 {
-          if pr_count == 1 {
-            "Commits".to_string()
-          } else {
-            "Other commits".to_string()
+          if !sections.is_empty() {
+            let index = ChangelogSection::find(sections, c.kind(), c.size()).unwrap_or(sections.len());
+            let mut entry = commit.clone();
+            entry.insert("pr_name".into(), Value::scalar(pr_name.clone()));
+            entry.insert("pr_href".into(), Value::scalar(pr.url().clone().unwrap_or_default()));
+            entry.insert("pr_link".into(), Value::scalar(pr_link));
+            grouped[index].push(entry);
           }
-        } else {
-          format!("PR {}", pr.number())
-        };
+          commits.push(commit);
+        }
 
         prs.push(liquid::object!({
           "title": pr.title(),
           "name": pr_name,
           "size": size.to_string(),
           "href": pr.url().as_deref().unwrap_or(""),
-          "link": pr.number() > 0 && pr.url().is_some(),
+          "link": pr_link,
+          "changelog": pr.changelog().unwrap_or(""),
+          "issues": issue_objects(pr.issues()),
           "commits": commits
         }));
       }
@@ -92,29 +316,133 @@ pub fn construct_changelog_html(
     }
   }
 
+  let titles = sections.iter().map(|s| s.title()).chain(once(OTHER_SECTION));
+  let sections: Vec<_> = titles
+    .zip(grouped)
+    .filter(|(_, commits)| !commits.is_empty())
+    .map(|(title, commits)| liquid::object!({ "title": title, "commits": commits }))
+    .collect();
+
+  let compare = match (cl.compare(), proj.full_version.as_deref()) {
+    (Some(base), Some(tag)) => format!("{}{}", base, tag),
+    _ => String::new()
+  };
+  let stats = liquid::object!({
+    "show": blocks.stats,
+    "features": stats.features,
+    "fixes": stats.fixes,
+    "breaking": stats.breaking,
+    "commits": stats.commits,
+    "prs": stats.prs,
+    "contributors": contributors.len(),
+    "compare": compare,
+    "compare_link": !compare.is_empty()
+  });
+
   let globals = liquid::object!({
-    "project": {
-      "id": proj.id.to_string(),
-      "name": proj.name,
-      "tag_prefix": proj.tag_prefix.unwrap_or_default(),
-      "tag_prefix_separator": proj.tag_prefix_separator,
-      "version": proj.version,
-      "full_version": proj.full_version.unwrap_or_default(),
-      "root": proj.root.unwrap_or_default(),
-    },
+    "project": proj.to_liquid(),
     "release": {
       "date": nowymd,
       "prs": prs,
       "deps": dps,
-      "version": new_vers
+      "grouped": !sections.is_empty(),
+      "sections": sections,
+      "contributors": contributors,
+      "stats": stats,
+      "version": new_vers,
+      "offline": cl.offline(),
+      "branch": cl.branch().unwrap_or_default()
     },
     "old_content": old_content,
-    "content_marker": format!("CONTENT {}", nowymd)
+    "preamble": blocks.preamble,
+    "footer": blocks.footer,
+    "content_marker": format!("CONTENT {} {}", nowymd, new_vers)
   });
 
   Ok(tmpl.render(&globals)?)
 }
 
+/// A released project in the release notes, with its changelog `section` rendered in markdown.
+pub struct NotesProject {
+  pub proj: ProjLine,
+  pub previous: Option<String>,
+  pub size: String,
+  pub section: String
+}
+
+/// Render the release notes of a whole release, which has every released project with its new version and the
+/// body of its changelog section. Runs of blank lines in the result are collapsed.
+pub fn construct_release_notes(projects: &[NotesProject], tmpl: String) -> Result<String> {
+  let tmpl = ParserBuilder::with_stdlib().build()?.parse(&tmpl)?;
+  let projects: Vec<_> = projects
+    .iter()
+    .map(|p| {
+      liquid::object!({
+        "id": p.proj.id.to_string(),
+        "name": p.proj.name,
+        "previous": p.previous.as_deref().unwrap_or_default(),
+        "version": p.proj.version,
+        "tag": p.proj.full_version.as_deref().unwrap_or_default(),
+        "size": p.size,
+        "section": section_body(&p.section)
+      })
+    })
+    .collect();
+
+  let globals = liquid::object!({
+    "release": {
+      "date": Utc::now().format("%Y-%m-%d").to_string(),
+      "projects": projects
+    }
+  });
+  let mut notes = tidy_section(&tmpl.render(&globals)?).join("\n");
+  notes.push('\n');
+  Ok(notes)
+}
+
+/// A rendered changelog section without its `## ` heading, which the release notes replace with their own.
+fn section_body(section: &str) -> String {
+  let mut lines = tidy_section(section);
+  if lines.first().map(|l| l.starts_with("## ")).unwrap_or(false) {
+    lines = trim_blank(lines.split_off(1));
+  }
+  lines.join("\n")
+}
+
+/// Counts of what went into a release, for its statistics.
+#[derive(Default)]
+struct ReleaseStats {
+  features: usize,
+  fixes: usize,
+  breaking: usize,
+  commits: usize,
+  prs: usize
+}
+
+impl ReleaseStats {
+  fn count(&mut self, kind: &str, breaking: bool) {
+    self.commits += 1;
+    match kind {
+      "feat" => self.features += 1,
+      "fix" => self.fixes += 1,
+      _ => ()
+    }
+    if breaking {
+      self.breaking += 1;
+    }
+  }
+}
+
+fn issue_objects(issues: &[IssueLink]) -> Vec<liquid::Object> {
+  issues
+    .iter()
+    .map(|i| liquid::object!({ "name": i.name(), "href": i.url().unwrap_or(""), "link": i.url().is_some() }))
+    .collect()
+}
+
+/// The template of the release notes, unless another is given.
+pub const DEFAULT_NOTES_TEMPLATE: &str = "builtin:release-notes";
+
 pub async fn read_template(tmpl_url: &str, base_path: Option<&Path>, forward_slash: bool) -> Result<String> {
   let parts: Vec<_> = tmpl_url.splitn(2, ':').collect();
   if parts.len() > 1 {
@@ -122,6 +450,8 @@ pub async fn read_template(tmpl_url: &str, base_path: Option<&Path>, forward_sla
       "builtin" => match parts[1] {
         "html" => Ok(include_str!("tmpl/changelog.liquid").to_string()),
         "json" => Ok(include_str!("tmpl/json.liquid").to_string()),
+        "keep-a-changelog" => Ok(include_str!("tmpl/keep_a_changelog.liquid").to_string()),
+        "release-notes" => Ok(include_str!("tmpl/release_notes.liquid").to_string()),
         _ => bail!("Unknown builtin template: {}", parts[1])
       },
       "file" => {
@@ -146,3 +476,116 @@ pub async fn read_template(tmpl_url: &str, base_path: Option<&Path>, forward_sla
     bail!("Template URL has no protocol: {}", tmpl_url);
   }
 }
+
+#[cfg(test)]
+mod test {
+  use super::{construct_changelog_html, construct_release_notes, replace_section, splice_keep_a_changelog,
+              ChangelogBlocks, NotesProject};
+  use crate::config::ProjectId;
+  use crate::mono::Changelog;
+  use crate::output::ProjLine;
+
+  fn proj_line() -> ProjLine {
+    ProjLine {
+      id: ProjectId::from_id(1),
+      name: "proj".into(),
+      tag_prefix: None,
+      tag_prefix_separator: "-".into(),
+      version: "1.0.0".into(),
+      full_version: None,
+      root: None
+    }
+  }
+
+  #[test]
+  fn test_blocks() {
+    let tmpl = include_str!("tmpl/changelog.liquid").to_string();
+    let blocks = ChangelogBlocks { preamble: "<p>PREAMBLE</p>".into(), footer: "<p>FOOTER</p>".into(), stats: false };
+    let html =
+      construct_changelog_html(&Changelog::empty(), proj_line(), "1.0.0", "".into(), blocks, &[], tmpl).unwrap();
+
+    let preamble = html.find("<p>PREAMBLE</p>").unwrap();
+    let begin = html.find("### VERSIO BEGIN CONTENT ###").unwrap();
+    let end = html.find("### VERSIO END CONTENT ###").unwrap();
+    let footer = html.find("<p>FOOTER</p>").unwrap();
+    assert!(preamble < begin && end < footer);
+  }
+
+  #[test]
+  fn test_stats() {
+    let tmpl = include_str!("tmpl/keep_a_changelog.liquid").to_string();
+    let stats = "Features: 0, fixes: 0, contributors: 0, commits: 0\n";
+    let blocks = ChangelogBlocks { stats: true, ..Default::default() };
+    let md = construct_changelog_html(&Changelog::empty(), proj_line(), "1.0.0", "".into(), blocks, &[], tmpl.clone());
+    assert!(md.unwrap().contains(stats));
+
+    let blocks = ChangelogBlocks::default();
+    let md = construct_changelog_html(&Changelog::empty(), proj_line(), "1.0.0", "".into(), blocks, &[], tmpl);
+    assert!(!md.unwrap().contains("Features:"));
+  }
+
+  #[test]
+  fn test_no_blocks() {
+    let tmpl = include_str!("tmpl/changelog.liquid").to_string();
+    let blocks = ChangelogBlocks::default();
+    let html =
+      construct_changelog_html(&Changelog::empty(), proj_line(), "1.0.0", "".into(), blocks, &[], tmpl).unwrap();
+    assert!(html.contains("</p>\n\n<!-- ### VERSIO BEGIN CONTENT ### -->"));
+    assert!(html.contains("<!-- ### VERSIO END CONTENT ### -->\n\n<script>"));
+  }
+
+  #[test]
+  fn test_replace_section() {
+    let full = "<h1>Log</h1>\n<!-- ### VERSIO BEGIN CONTENT ### -->\n\
+                <!-- ### VERSIO CONTENT 2024-02-01 1.1.0 ### -->\n<p>new</p>\n\
+                <!-- ### VERSIO CONTENT 2024-01-01 ### -->\n<p>old</p>\n\n\
+                <!-- ### VERSIO END CONTENT ### -->";
+
+    let section = "<!-- ### VERSIO CONTENT 2024-02-01 1.1.0 ### -->\n<p>newer</p>\n";
+    let replaced = replace_section(full, "1.1.0", "2024-02-01", section).unwrap();
+    assert!(replaced.contains("1.1.0 ### -->\n<p>newer</p>\n<!-- ### VERSIO CONTENT 2024-01-01 ### -->"));
+
+    let section = "<!-- ### VERSIO CONTENT 2024-01-01 1.0.0 ### -->\n<p>older</p>\n";
+    let replaced = replace_section(full, "1.0.0", "2024-01-01", section).unwrap();
+    assert!(replaced.ends_with("1.0.0 ### -->\n<p>older</p>\n\n<!-- ### VERSIO END CONTENT ### -->"));
+
+    assert!(replace_section(full, "0.9.0", "2023-12-01", section).is_err());
+  }
+
+  #[test]
+  fn test_release_notes() {
+    let tmpl = include_str!("tmpl/release_notes.liquid").to_string();
+    let mut proj = proj_line();
+    proj.version = "1.1.0".into();
+    let section = "## [1.1.0] - 2024-02-01\n\n\n### Changed\n\n- Second. (abcdef0)\n\n".to_string();
+    let projects = [NotesProject { proj, previous: Some("1.0.0".into()), size: "minor".into(), section }];
+
+    let notes = construct_release_notes(&projects, tmpl).unwrap();
+    assert!(notes.starts_with("# Release "));
+    assert!(notes.ends_with("## proj 1.1.0\n\nUpdated from 1.0.0 (minor).\n\n### Changed\n\n- Second. (abcdef0)\n"));
+  }
+
+  #[test]
+  fn test_splice_keep_a_changelog() {
+    let old = "# Changelog\n\nHand-written intro.\n\n## [Unreleased]\n\n- A note.\n\n\
+               ## [1.0.0] - 2024-01-01\n\n### Added\n\n- First.\n\n\
+               [unreleased]: https://github.com/o/r/compare/v1.0.0...HEAD\n\
+               [1.0.0]: https://github.com/o/r/releases/tag/v1.0.0\n";
+    let section = "## [1.1.0] - 2024-02-01\n\n\n### Changed\n\n- Second. (abcdef0)\n\n- Third. (bcdef01)\n\n";
+
+    let spliced = splice_keep_a_changelog(old, section, "1.1.0", Some("v1.1.0"));
+    assert_eq!(
+      spliced,
+      "# Changelog\n\nHand-written intro.\n\n## [Unreleased]\n\n## [1.1.0] - 2024-02-01\n\n- A note.\n\n\
+       ### Changed\n\n- Second. (abcdef0)\n- Third. (bcdef01)\n\n## [1.0.0] - 2024-01-01\n\n### Added\n\n\
+       - First.\n\n[unreleased]: https://github.com/o/r/compare/v1.1.0...HEAD\n\
+       [1.1.0]: https://github.com/o/r/compare/v1.0.0...v1.1.0\n\
+       [1.0.0]: https://github.com/o/r/releases/tag/v1.0.0\n"
+    );
+
+    let fresh = splice_keep_a_changelog("", section, "1.1.0", None);
+    assert!(fresh.starts_with("# Changelog\n"));
+    assert!(fresh.ends_with("## [Unreleased]\n\n## [1.1.0] - 2024-02-01\n\n### Changed\n\n- Second. (abcdef0)\n\
+                             - Third. (bcdef01)\n"));
+  }
+}
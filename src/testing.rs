@@ -0,0 +1,78 @@
+//! Scratch git repositories for tests that need a real working tree.
+
+use git2::{IndexAddOption, Oid, Repository, Signature};
+use std::future::Future;
+use std::path::Path;
+use tempfile::TempDir;
+
+/// A git repository in a temporary directory, which is deleted when it's dropped.
+pub struct TestRepo {
+  dir: TempDir,
+  repo: Repository
+}
+
+impl TestRepo {
+  /// Create a repo on a `main` branch, with a `.versio.yaml` of `config`, but nothing committed yet.
+  pub fn new(config: &str) -> TestRepo {
+    let dir = TempDir::new().unwrap();
+    let repo = Repository::init(dir.path()).unwrap();
+    repo.set_head("refs/heads/main").unwrap();
+    let mut git_config = repo.config().unwrap();
+    git_config.set_str("user.name", "Test").unwrap();
+    git_config.set_str("user.email", "test@example.com").unwrap();
+    let test_repo = TestRepo { dir, repo };
+    test_repo.write(".versio.yaml", config);
+    test_repo
+  }
+
+  /// Clone the repo at `origin` into a new temporary directory, as a CI machine would.
+  pub fn clone_from(origin: &TestRepo) -> TestRepo {
+    let dir = TempDir::new().unwrap();
+    let repo = Repository::clone(origin.path().to_str().unwrap(), dir.path()).unwrap();
+    let mut git_config = repo.config().unwrap();
+    git_config.set_str("user.name", "Test").unwrap();
+    git_config.set_str("user.email", "test@example.com").unwrap();
+    TestRepo { dir, repo }
+  }
+
+  pub fn path(&self) -> &Path { self.dir.path() }
+  pub fn repo(&self) -> &Repository { &self.repo }
+
+  /// Write a file (relative to the root of the repo), creating its directories.
+  pub fn write(&self, path: &str, content: &str) {
+    let path = self.path().join(path);
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(path, content).unwrap();
+  }
+
+  pub fn read(&self, path: &str) -> String { std::fs::read_to_string(self.path().join(path)).unwrap() }
+
+  /// Commit every file in the working tree to the current branch.
+  pub fn commit(&self, message: &str) -> Oid {
+    let mut index = self.repo.index().unwrap();
+    index.add_all(["*"].iter(), IndexAddOption::DEFAULT, None).unwrap();
+    index.write().unwrap();
+    let tree = self.repo.find_tree(index.write_tree().unwrap()).unwrap();
+    let sig = Signature::now("Test", "test@example.com").unwrap();
+    let parent = self.repo.head().ok().map(|head| head.peel_to_commit().unwrap());
+    let parents: Vec<_> = parent.iter().collect();
+    self.repo.commit(Some("HEAD"), &sig, &sig, message, &tree, &parents).unwrap()
+  }
+
+  /// Point the lightweight tag `name` at the current commit.
+  pub fn tag(&self, name: &str) {
+    let head = self.repo.head().unwrap().peel_to_commit().unwrap();
+    self.repo.tag_lightweight(name, head.as_object(), true).unwrap();
+  }
+
+  /// The commit that the tag `name` points to, if it exists.
+  pub fn tag_target(&self, name: &str) -> Option<Oid> {
+    let tag = self.repo.revparse_single(&format!("refs/tags/{}", name)).ok()?;
+    Some(tag.peel_to_commit().unwrap().id())
+  }
+
+  pub fn head(&self) -> Oid { self.repo.head().unwrap().peel_to_commit().unwrap().id() }
+}
+
+/// Run a future to completion, for testing async code.
+pub fn block_on<F: Future>(future: F) -> F::Output { tokio::runtime::Runtime::new().unwrap().block_on(future) }
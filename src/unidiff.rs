@@ -0,0 +1,168 @@
+//! Unified diffs of the files that a release would write, for `release --dry-run --show-diff`; and the files that a
+//! unified diff touches, for `owners`.
+//!
+//! The lines common to the start and end of both files are set aside first, so only the part that changed (usually
+//! a version line or a new changelog section) is compared line-by-line.
+
+/// How many unchanged lines are shown around each change.
+const CONTEXT: usize = 3;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Op {
+  Same,
+  Removed,
+  Added
+}
+
+/// A unified diff of `path` from its `old` content to its `new` content, or an empty string if they have the same
+/// lines. A file that doesn't exist has no content.
+pub fn unified_diff(path: &str, old: Option<&str>, new: Option<&str>) -> String {
+  let old_lines: Vec<&str> = old.map(|o| o.lines().collect()).unwrap_or_default();
+  let new_lines: Vec<&str> = new.map(|n| n.lines().collect()).unwrap_or_default();
+  let ops = edits(&old_lines, &new_lines);
+  if ops.iter().all(|(op, _)| *op == Op::Same) {
+    return String::new();
+  }
+
+  let header = |side: &str, content: Option<&str>| match content {
+    Some(_) => format!("{}/{}", side, path),
+    None => "/dev/null".to_string()
+  };
+  let mut out = format!("--- {}\n+++ {}\n", header("a", old), header("b", new));
+  for (lo, hi) in hunks(&ops) {
+    let before = &ops[.. lo];
+    let within = &ops[lo .. hi];
+    let old_start = before.iter().filter(|(op, _)| *op != Op::Added).count();
+    let new_start = before.iter().filter(|(op, _)| *op != Op::Removed).count();
+    let old_len = within.iter().filter(|(op, _)| *op != Op::Added).count();
+    let new_len = within.iter().filter(|(op, _)| *op != Op::Removed).count();
+    out.push_str(&format!("@@ -{} +{} @@\n", range(old_start, old_len), range(new_start, new_len)));
+    for (op, line) in within {
+      let sign = match op {
+        Op::Same => ' ',
+        Op::Removed => '-',
+        Op::Added => '+'
+      };
+      out.push(sign);
+      out.push_str(line);
+      out.push('\n');
+    }
+  }
+  out
+}
+
+/// A hunk's range of lines, which starts after the line before it: an empty range names that line instead.
+/// The paths of the files that a unified diff (such as the output of `git diff`) touches, in order: the new path of
+/// each file, or the old path of a file that the diff deletes.
+pub fn diff_paths(diff: &str) -> Vec<String> {
+  let mut paths = Vec::new();
+  let mut old = None;
+  for line in diff.lines() {
+    if let Some(path) = line.strip_prefix("--- ") {
+      old = Some(strip_side(path, "a/"));
+    } else if let Some(path) = line.strip_prefix("+++ ") {
+      let path = if path.trim_end() == "/dev/null" { old.take() } else { Some(strip_side(path, "b/")) };
+      if let Some(path) = path.filter(|p| p != "/dev/null" && !paths.contains(p)) {
+        paths.push(path);
+      }
+    }
+  }
+  paths
+}
+
+/// Whether `text` looks like a unified diff, rather than a list of paths.
+pub fn is_diff(text: &str) -> bool {
+  text.lines().any(|line| line.starts_with("+++ ") || line.starts_with("diff --git "))
+}
+
+/// A path from a diff header, without its trailing timestamp (if any) or the `a/` or `b/` of its side.
+fn strip_side(path: &str, side: &str) -> String {
+  let path = path.split('\t').next().unwrap_or(path).trim_end();
+  path.strip_prefix(side).unwrap_or(path).to_string()
+}
+
+fn range(before: usize, len: usize) -> String {
+  let start = if len == 0 { before } else { before + 1 };
+  format!("{},{}", start, len)
+}
+
+/// The shortest edit from the `old` lines to the `new` lines.
+fn edits<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<(Op, &'a str)> {
+  let prefix = old.iter().zip(new).take_while(|(o, n)| o == n).count();
+  let suffix = old[prefix ..].iter().rev().zip(new[prefix ..].iter().rev()).take_while(|(o, n)| o == n).count();
+  let (a, b) = (&old[prefix .. old.len() - suffix], &new[prefix .. new.len() - suffix]);
+
+  // The length of the longest common subsequence of every pair of suffixes of `a` and `b`.
+  let mut lcs = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+  for i in (0 .. a.len()).rev() {
+    for j in (0 .. b.len()).rev() {
+      lcs[i][j] = if a[i] == b[j] { lcs[i + 1][j + 1] + 1 } else { lcs[i + 1][j].max(lcs[i][j + 1]) };
+    }
+  }
+
+  let mut ops: Vec<_> = old[.. prefix].iter().map(|l| (Op::Same, *l)).collect();
+  let (mut i, mut j) = (0, 0);
+  while i < a.len() || j < b.len() {
+    if i < a.len() && j < b.len() && a[i] == b[j] {
+      ops.push((Op::Same, a[i]));
+      i += 1;
+      j += 1;
+    } else if i < a.len() && (j == b.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+      ops.push((Op::Removed, a[i]));
+      i += 1;
+    } else {
+      ops.push((Op::Added, b[j]));
+      j += 1;
+    }
+  }
+  ops.extend(old[old.len() - suffix ..].iter().map(|l| (Op::Same, *l)));
+  ops
+}
+
+/// The ranges of `ops` that make up each hunk: every change with the unchanged lines around it, where changes that
+/// are close enough together share a hunk.
+fn hunks(ops: &[(Op, &str)]) -> Vec<(usize, usize)> {
+  let mut hunks: Vec<(usize, usize)> = Vec::new();
+  for (i, _) in ops.iter().enumerate().filter(|(_, (op, _))| *op != Op::Same) {
+    let lo = i.saturating_sub(CONTEXT);
+    let hi = (i + 1 + CONTEXT).min(ops.len());
+    match hunks.last_mut() {
+      Some(last) if last.1 >= lo => last.1 = hi,
+      _ => hunks.push((lo, hi))
+    }
+  }
+  hunks
+}
+
+#[cfg(test)]
+mod test {
+  use super::{diff_paths, is_diff, unified_diff};
+
+  #[test]
+  fn test_unified_diff() {
+    let old = "[package]\nname = \"widgets\"\nversion = \"1.0.0\"\nedition = \"2021\"\n\n\
+               [dependencies]\nserde = \"1\"\n";
+    let new = old.replace("1.0.0", "1.1.0");
+    assert_eq!(
+      "--- a/Cargo.toml\n+++ b/Cargo.toml\n@@ -1,6 +1,6 @@\n [package]\n name = \"widgets\"\n-version = \"1.0.0\"\n\
+       +version = \"1.1.0\"\n edition = \"2021\"\n \n [dependencies]\n",
+      unified_diff("Cargo.toml", Some(old), Some(&new))
+    );
+
+    assert_eq!(
+      "--- /dev/null\n+++ b/CHANGELOG.md\n@@ -0,0 +1,2 @@\n+# Changelog\n+\n",
+      unified_diff("CHANGELOG.md", None, Some("# Changelog\n\n"))
+    );
+    assert_eq!("", unified_diff("Cargo.toml", Some(old), Some(old)));
+  }
+
+  #[test]
+  fn test_diff_paths() {
+    let diff = "diff --git a/core/lib.rs b/core/lib.rs\n--- a/core/lib.rs\n+++ b/core/lib.rs\n@@ -1 +1 @@\n-a\n+b\n\
+                --- a/old.txt\n+++ /dev/null\n@@ -1 +0,0 @@\n-gone\n\
+                --- /dev/null\n+++ b/docs/new.md\t2024-01-01 00:00:00\n@@ -0,0 +1 @@\n+new\n";
+    assert!(is_diff(diff));
+    assert_eq!(vec!["core/lib.rs", "old.txt", "docs/new.md"], diff_paths(diff));
+    assert!(!is_diff("core/lib.rs\ndocs/new.md\n"));
+  }
+}
@@ -4,19 +4,30 @@ use crate::bail;
 use crate::errors::Result;
 use crate::git::Repo;
 use std::cmp::{max, min};
+use std::fmt;
+use std::path::Path;
 use std::str::FromStr;
 use tracing::debug;
 
 #[derive(PartialEq, PartialOrd, Eq, Ord, Clone, Copy, Debug)]
 pub struct VcsState {
   level: VcsLevel,
-  ignore_current: bool
+  ignore_current: bool,
+  lock: bool
 }
 
 impl VcsState {
-  pub fn new(level: VcsLevel, ignore_current: bool) -> VcsState { VcsState { level, ignore_current } }
+  pub fn new(level: VcsLevel, ignore_current: bool) -> VcsState { VcsState { level, ignore_current, lock: false } }
   pub fn level(&self) -> &VcsLevel { &self.level }
   pub fn ignore_current(&self) -> bool { self.ignore_current }
+
+  /// Lock the repo while it's open, since the command will change it.
+  pub fn with_lock(mut self, lock: bool) -> VcsState {
+    self.lock = lock;
+    self
+  }
+
+  pub fn locks(&self) -> bool { self.lock }
 }
 
 #[derive(Debug)]
@@ -38,9 +49,15 @@ impl VcsRange {
     VcsRange::new(max(self.min(), other.min()), min(self.max(), other.max()))
   }
 
-  pub fn detect() -> Result<VcsRange> { Ok(VcsRange::new(VcsLevel::None, Repo::detect(".")?)) }
+  pub fn detect() -> Result<VcsRange> { VcsRange::detect_at(".") }
+
+  pub fn detect_at<P: AsRef<Path>>(dir: P) -> Result<VcsRange> { Ok(VcsRange::new(VcsLevel::None, Repo::detect(dir)?)) }
 
   pub fn detect_and_combine(pref: &VcsRange, reqd: &VcsRange) -> Result<VcsRange> {
+    VcsRange::detect_and_combine_at(".", pref, reqd)
+  }
+
+  pub fn detect_and_combine_at<P: AsRef<Path>>(dir: P, pref: &VcsRange, reqd: &VcsRange) -> Result<VcsRange> {
     if pref.is_empty() {
       bail!("Preferred VCS {:?} is empty.", pref);
     } else if reqd.is_empty() {
@@ -56,7 +73,7 @@ impl VcsRange {
       }
     }
 
-    let dctd = VcsRange::detect()?;
+    let dctd = VcsRange::detect_at(dir)?;
     let i2 = i1.intersect(&dctd);
     if i2.is_empty() {
       bail!("Couldn't detect {:?} with preferred {:?} required {:?}", dctd, pref, reqd);
@@ -95,3 +112,14 @@ impl FromStr for VcsLevel {
     }
   }
 }
+
+impl fmt::Display for VcsLevel {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      VcsLevel::None => write!(f, "none"),
+      VcsLevel::Local => write!(f, "local"),
+      VcsLevel::Remote => write!(f, "remote"),
+      VcsLevel::Smart => write!(f, "smart")
+    }
+  }
+}
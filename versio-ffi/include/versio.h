@@ -0,0 +1,39 @@
+/*
+ * C interface to the versio planning engine.
+ *
+ * Every function that returns `char *` returns a JSON envelope, either
+ * `{"ok": <value>}` or `{"error": "<message>"}`, which must be released
+ * with `versio_string_free`.
+ */
+
+#ifndef VERSIO_H
+#define VERSIO_H
+
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct VersioRepo VersioRepo;
+
+/*
+ * Open a monorepo. `options_json` may be NULL, or a JSON object with
+ * optional "path" and "vcs" ("none", "local", "remote", "smart") keys.
+ * Returns NULL on failure, and sets `*error` if `error` is not NULL.
+ */
+VersioRepo *versio_open(const char *options_json, char **error);
+void versio_close(VersioRepo *repo);
+
+char *versio_versions(const VersioRepo *repo);
+char *versio_get(const VersioRepo *repo, uint32_t id);
+char *versio_plan(const VersioRepo *repo);
+char *versio_release_dry_run(const VersioRepo *repo);
+
+void versio_string_free(char *s);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* VERSIO_H */
@@ -0,0 +1,206 @@
+//! A C ABI for the versio planning engine, so that non-Rust build systems can link versio directly.
+//!
+//! All data crosses the boundary as JSON. Every call that returns a string returns a newly-allocated
+//! envelope: either `{"ok": <value>}` or `{"error": "<message>"}`. Such strings must be released with
+//! `versio_string_free`. See `include/versio.h` for the C declarations.
+
+use serde::{Deserialize, Serialize};
+use std::ffi::{CStr, CString};
+use std::os::raw::c_char;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::ptr::null_mut;
+use versio::api::Versio;
+use versio::errors::Result;
+use versio::vcs::{VcsLevel, VcsRange};
+
+/// An opened monorepo. Opaque to C callers.
+pub struct VersioRepo {
+  versio: Versio,
+  runtime: tokio::runtime::Runtime
+}
+
+#[derive(Deserialize, Default)]
+struct OpenOptions {
+  path: Option<String>,
+  vcs: Option<String>
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Envelope<T> {
+  Ok(T),
+  Error(String)
+}
+
+/// Open the monorepo described by `options_json` (`{"path": ".", "vcs": "local"}`; both keys are optional,
+/// and `options_json` may be null). Returns null on failure, in which case `*error` (if `error` is not null)
+/// is set to a string that must be released with `versio_string_free`.
+///
+/// # Safety
+///
+/// `options_json` must be null or a valid NUL-terminated string; `error` must be null or writable.
+#[no_mangle]
+pub unsafe extern "C" fn versio_open(options_json: *const c_char, error: *mut *mut c_char) -> *mut VersioRepo {
+  let result = guard(|| {
+    let options: OpenOptions = match read_str(options_json)? {
+      Some(json) => serde_json::from_str(&json)?,
+      None => OpenOptions::default()
+    };
+    let vcs = options.vcs.map(|v| v.parse::<VcsLevel>().map(VcsRange::exact)).transpose()?;
+    let versio = Versio::open(options.path.as_deref().unwrap_or("."), vcs)?;
+    let runtime = tokio::runtime::Runtime::new()?;
+    Ok(VersioRepo { versio, runtime })
+  });
+
+  match result {
+    Ok(repo) => Box::into_raw(Box::new(repo)),
+    Err(e) => {
+      if !error.is_null() {
+        *error = to_c_string(format!("{:#}", e));
+      }
+      null_mut()
+    }
+  }
+}
+
+/// Release a repo returned by `versio_open`. Passing null is a no-op.
+///
+/// # Safety
+///
+/// `repo` must be null or a pointer returned by `versio_open` that hasn't yet been closed.
+#[no_mangle]
+pub unsafe extern "C" fn versio_close(repo: *mut VersioRepo) {
+  if !repo.is_null() {
+    drop(Box::from_raw(repo));
+  }
+}
+
+/// The current versions of all projects.
+///
+/// # Safety
+///
+/// `repo` must be a live pointer returned by `versio_open`.
+#[no_mangle]
+pub unsafe extern "C" fn versio_versions(repo: *const VersioRepo) -> *mut c_char {
+  respond(repo, |repo| repo.versio.versions())
+}
+
+/// The current version of the project with the given ID.
+///
+/// # Safety
+///
+/// `repo` must be a live pointer returned by `versio_open`.
+#[no_mangle]
+pub unsafe extern "C" fn versio_get(repo: *const VersioRepo, id: u32) -> *mut c_char {
+  respond(repo, |repo| repo.versio.get(id))
+}
+
+/// The release plan.
+///
+/// # Safety
+///
+/// `repo` must be a live pointer returned by `versio_open`.
+#[no_mangle]
+pub unsafe extern "C" fn versio_plan(repo: *const VersioRepo) -> *mut c_char {
+  respond(repo, |repo| repo.runtime.block_on(repo.versio.plan()))
+}
+
+/// What `versio release` would do, without writing anything.
+///
+/// # Safety
+///
+/// `repo` must be a live pointer returned by `versio_open`.
+#[no_mangle]
+pub unsafe extern "C" fn versio_release_dry_run(repo: *const VersioRepo) -> *mut c_char {
+  respond(repo, |repo| repo.runtime.block_on(repo.versio.release_dry()))
+}
+
+/// Release a string returned by any other `versio_` function. Passing null is a no-op.
+///
+/// # Safety
+///
+/// `s` must be null or a string returned by this library that hasn't yet been freed.
+#[no_mangle]
+pub unsafe extern "C" fn versio_string_free(s: *mut c_char) {
+  if !s.is_null() {
+    drop(CString::from_raw(s));
+  }
+}
+
+unsafe fn respond<T, F>(repo: *const VersioRepo, f: F) -> *mut c_char
+where
+  T: Serialize,
+  F: FnOnce(&VersioRepo) -> Result<T>
+{
+  let result = if repo.is_null() { Err(versio::bad!("Null versio repo.")) } else { guard(|| f(&*repo)) };
+  let envelope = match result {
+    Ok(v) => Envelope::Ok(v),
+    Err(e) => Envelope::Error(format!("{:#}", e))
+  };
+  let json = serde_json::to_string(&envelope)
+    .unwrap_or_else(|e| serde_json::to_string(&Envelope::<()>::Error(e.to_string())).unwrap());
+  to_c_string(json)
+}
+
+/// Don't let a panic unwind across the C boundary.
+fn guard<T, F: FnOnce() -> Result<T>>(f: F) -> Result<T> {
+  catch_unwind(AssertUnwindSafe(f)).unwrap_or_else(|_| versio::err!("Panic in versio."))
+}
+
+unsafe fn read_str(s: *const c_char) -> Result<Option<String>> {
+  if s.is_null() {
+    Ok(None)
+  } else {
+    Ok(Some(CStr::from_ptr(s).to_str()?.to_string()))
+  }
+}
+
+fn to_c_string(s: String) -> *mut c_char {
+  // JSON never contains a raw NUL, but an error message might.
+  CString::new(s.replace('\0', "\\u0000")).map(CString::into_raw).unwrap_or(null_mut())
+}
+
+#[cfg(test)]
+mod test {
+  use super::{versio_close, versio_get, versio_open, versio_string_free};
+  use serde_json::{json, Value};
+  use std::ffi::{CStr, CString};
+  use std::ptr::{null, null_mut};
+
+  /// Take ownership of a string returned by the library, and parse its envelope.
+  unsafe fn take_json(s: *mut std::os::raw::c_char) -> Value {
+    assert!(!s.is_null());
+    let value = serde_json::from_str(CStr::from_ptr(s).to_str().unwrap()).unwrap();
+    versio_string_free(s);
+    value
+  }
+
+  #[test]
+  fn test_get_envelopes() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(
+      dir.path().join(".versio.yaml"),
+      "projects:\n  - { name: a, id: 1, version: { file: version.txt } }\n"
+    )
+    .unwrap();
+    std::fs::write(dir.path().join("version.txt"), "1.2.3").unwrap();
+    let options = json!({ "path": dir.path(), "vcs": "none" }).to_string();
+    let options = CString::new(options).unwrap();
+
+    unsafe {
+      let mut error = null_mut();
+      let repo = versio_open(options.as_ptr(), &mut error);
+      assert!(!repo.is_null());
+      assert!(error.is_null());
+
+      let ok = take_json(versio_get(repo, 1));
+      assert_eq!(json!({ "ok": { "id": "1", "name": "a", "version": "1.2.3" } }), ok);
+      let missing = take_json(versio_get(repo, 2));
+      assert_eq!(json!({ "error": "No such project 2." }), missing);
+      let closed = take_json(versio_get(null(), 1));
+      assert_eq!(json!({ "error": "Null versio repo." }), closed);
+
+      versio_close(repo);
+    }
+  }
+}
@@ -0,0 +1,95 @@
+//! Python bindings for the versio planning engine.
+//!
+//! ```python
+//! import versio
+//!
+//! repo = versio.open(".")
+//! print(repo.versions())
+//! print(repo.plan())
+//! ```
+
+use pyo3::exceptions::PyRuntimeError;
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList};
+use serde_json::Value;
+use versio::api::Versio;
+use versio::vcs::{VcsLevel, VcsRange};
+
+/// An opened monorepo. This wraps a live git repository, so it can't be shared between threads.
+#[pyclass(name = "Repo", unsendable)]
+struct PyRepo {
+  versio: Versio
+}
+
+#[pymethods]
+impl PyRepo {
+  /// The current versions of all projects, as a list of dicts.
+  fn versions(&self, py: Python) -> PyResult<PyObject> {
+    let versions = self.versio.versions().map_err(to_py_err)?;
+    to_py(py, versions)
+  }
+
+  /// The current version of a single project, as a dict.
+  fn get(&self, py: Python, id: u32) -> PyResult<PyObject> {
+    let version = self.versio.get(id).map_err(to_py_err)?;
+    to_py(py, version)
+  }
+
+  /// The release plan, as a dict of `projects`, `ineffective` PRs, and `warnings`.
+  fn plan(&self, py: Python) -> PyResult<PyObject> {
+    let runtime = tokio::runtime::Runtime::new().map_err(|e| PyRuntimeError::new_err(e.to_string()))?;
+    let plan = runtime.block_on(self.versio.plan()).map_err(to_py_err)?;
+    to_py(py, plan)
+  }
+}
+
+/// Open the monorepo at `path`. `vcs` may be one of "none", "local", "remote", or "smart"; if omitted, the
+/// highest detectable level is used.
+#[pyfunction]
+#[pyo3(signature = (path = ".", vcs = None))]
+fn open(path: &str, vcs: Option<&str>) -> PyResult<PyRepo> {
+  let vcs = vcs.map(|v| v.parse::<VcsLevel>().map(VcsRange::exact)).transpose().map_err(to_py_err)?;
+  let versio = Versio::open(path, vcs).map_err(to_py_err)?;
+  Ok(PyRepo { versio })
+}
+
+#[pymodule]
+#[pyo3(name = "versio")]
+fn versio_py(_py: Python, m: &PyModule) -> PyResult<()> {
+  m.add_class::<PyRepo>()?;
+  m.add_function(wrap_pyfunction!(open, m)?)?;
+  Ok(())
+}
+
+fn to_py_err(e: versio::errors::Error) -> PyErr { PyRuntimeError::new_err(format!("{:#}", e)) }
+
+fn to_py<T: serde::Serialize>(py: Python, val: T) -> PyResult<PyObject> {
+  let val = serde_json::to_value(val).map_err(|e| PyRuntimeError::new_err(e.to_string()))?;
+  value_to_py(py, &val)
+}
+
+fn value_to_py(py: Python, val: &Value) -> PyResult<PyObject> {
+  Ok(match val {
+    Value::Null => py.None(),
+    Value::Bool(b) => b.into_py(py),
+    Value::Number(n) => match n.as_i64() {
+      Some(i) => i.into_py(py),
+      None => n.as_f64().unwrap_or_default().into_py(py)
+    },
+    Value::String(s) => s.into_py(py),
+    Value::Array(vs) => {
+      let list = PyList::empty(py);
+      for v in vs {
+        list.append(value_to_py(py, v)?)?;
+      }
+      list.into_py(py)
+    }
+    Value::Object(map) => {
+      let dict = PyDict::new(py);
+      for (k, v) in map {
+        dict.set_item(k, value_to_py(py, v)?)?;
+      }
+      dict.into_py(py)
+    }
+  })
+}